@@ -0,0 +1,123 @@
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+mod corpus;
+use corpus::CorpusEntry;
+
+// A minimal harness: `cargo bench [FILTER]` runs every benchmark whose name
+// contains FILTER, and reports the median and fastest of repeated runs.
+fn main() {
+    let filter: Option<String> = std::env::args().skip(1).find(|a| !a.starts_with("--"));
+    let run = |name: &str| filter.as_ref().is_none_or(|f| name.contains(f.as_str()));
+
+    let large_text = corpus::text(16 << 20, 1);
+    let large_noise = corpus::noise(16 << 20, 2);
+
+    let small: Vec<CorpusEntry> = (0..10_000)
+        .map(|i| CorpusEntry {
+            name: format!("small/{i:05}.txt"),
+            data: corpus::text(128, i),
+            deflate: i % 2 == 0,
+        })
+        .collect();
+    let small_zip = corpus::write_zip("small", &small, b"");
+    let small_bytes: u64 = small.iter().map(|e| e.data.len() as u64).sum();
+
+    let stored_zip = corpus::write_zip(
+        "stored",
+        &[CorpusEntry {
+            name: "large.bin".into(),
+            data: large_noise,
+            deflate: false,
+        }],
+        b"",
+    );
+    let deflate_zip = corpus::write_zip(
+        "deflate",
+        &[CorpusEntry {
+            name: "large.txt".into(),
+            data: large_text,
+            deflate: true,
+        }],
+        b"",
+    );
+
+    // the end record sits behind the longest possible comment
+    let comment_zip = corpus::write_zip(
+        "comment",
+        &[CorpusEntry {
+            name: "a".into(),
+            data: b"a".to_vec(),
+            deflate: false,
+        }],
+        &[b'#'; 65535],
+    );
+
+    if run("eocd_search") {
+        bench("eocd_search", 0, || {
+            let mut input = File::open(&comment_zip).unwrap();
+            munzip::IterableArchive::new(&mut input).unwrap();
+        });
+    }
+    if run("cd_parse") {
+        bench("cd_parse/10k_entries", 0, || {
+            let mut input = File::open(&small_zip).unwrap();
+            munzip::SearchableArchive::new(&mut input).unwrap();
+        });
+    }
+    if run("extract") {
+        bench("extract/stored_16m", 16 << 20, || extract_all(&stored_zip));
+        bench("extract/deflate_16m", 16 << 20, || {
+            extract_all(&deflate_zip)
+        });
+        bench("extract/small_10k", small_bytes, || extract_all(&small_zip));
+    }
+    if run("by_name") {
+        let mut input = File::open(&small_zip).unwrap();
+        let mut zi = munzip::SearchableArchive::new(&mut input).unwrap();
+        bench("by_name/small", 0, || {
+            zi.by_name("small/05000.txt").unwrap().unwrap();
+        });
+    }
+
+    for path in [&small_zip, &stored_zip, &deflate_zip, &comment_zip] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+fn extract_all(path: &Path) {
+    let mut input = File::open(path).unwrap();
+    for entry in munzip::IterableArchive::new(&mut input).unwrap() {
+        entry.unwrap().buffer().unwrap();
+    }
+}
+
+fn bench(name: &str, bytes: u64, mut f: impl FnMut()) {
+    f(); // warm up caches
+
+    let mut samples: Vec<Duration> = Vec::new();
+    let start = Instant::now();
+    while samples.len() < 5 || (start.elapsed() < Duration::from_secs(2) && samples.len() < 1000) {
+        let t = Instant::now();
+        f();
+        samples.push(t.elapsed());
+    }
+    samples.sort();
+
+    let median = samples[samples.len() / 2];
+    let fastest = samples[0];
+    let throughput = if bytes > 0 {
+        format!(
+            "{:>9.1} MiB/s",
+            bytes as f64 / median.as_secs_f64() / (1 << 20) as f64
+        )
+    } else {
+        String::new()
+    };
+
+    println!(
+        "{name:<24} median {median:>12.3?}  fastest {fastest:>12.3?}  runs {:>4} {throughput}",
+        samples.len()
+    );
+}
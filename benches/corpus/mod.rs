@@ -0,0 +1,253 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// An entry to be written into a generated corpus archive.
+pub struct CorpusEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+    pub deflate: bool,
+}
+
+/// Writes a zip containing `entries` (and an optional trailing comment) to the
+/// temp dir, returning its path.
+pub fn write_zip(name: &str, entries: &[CorpusEntry], comment: &[u8]) -> PathBuf {
+    let mut out: Vec<u8> = Vec::new();
+    let mut central: Vec<u8> = Vec::new();
+
+    for entry in entries {
+        let offset = out.len() as u32;
+        let crc = crc32(&entry.data);
+        let (method, payload) = if entry.deflate {
+            (8u16, deflate_fixed(&entry.data))
+        } else {
+            (0u16, entry.data.clone())
+        };
+
+        out.extend_from_slice(&0x04034B50u32.to_le_bytes());
+        out.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        out.extend_from_slice(&0u16.to_le_bytes()); // flags
+        out.extend_from_slice(&method.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes()); // time
+        out.extend_from_slice(&0x21u16.to_le_bytes()); // date, 1980-01-01
+        out.extend_from_slice(&crc.to_le_bytes());
+        out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(entry.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes()); // extra
+        out.extend_from_slice(entry.name.as_bytes());
+        out.extend_from_slice(&payload);
+
+        central.extend_from_slice(&0x02014B50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        central.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        central.extend_from_slice(&0u16.to_le_bytes()); // flags
+        central.extend_from_slice(&method.to_le_bytes());
+        central.extend_from_slice(&0u16.to_le_bytes()); // time
+        central.extend_from_slice(&0x21u16.to_le_bytes()); // date
+        central.extend_from_slice(&crc.to_le_bytes());
+        central.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        central.extend_from_slice(&(entry.data.len() as u32).to_le_bytes());
+        central.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+        central.extend_from_slice(&0u16.to_le_bytes()); // extra
+        central.extend_from_slice(&0u16.to_le_bytes()); // comment
+        central.extend_from_slice(&0u16.to_le_bytes()); // disk number
+        central.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+        central.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(entry.name.as_bytes());
+    }
+
+    let cd_offset = out.len() as u32;
+    out.extend_from_slice(&central);
+
+    out.extend_from_slice(&0x06054B50u32.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&cd_offset.to_le_bytes());
+    out.extend_from_slice(&(comment.len() as u16).to_le_bytes());
+    out.extend_from_slice(comment);
+
+    let path = std::env::temp_dir().join(format!("munzip-bench-{name}.zip"));
+    File::create(&path).unwrap().write_all(&out).unwrap();
+    path
+}
+
+/// Deterministic, compressible text-like data.
+pub fn text(len: usize, seed: u32) -> Vec<u8> {
+    const WORDS: &[&str] = &[
+        "the",
+        "archive",
+        "central",
+        "directory",
+        "entry",
+        "inflate",
+        "header",
+        "local",
+        "stored",
+        "deflate",
+        "record",
+        "signature",
+        "offset",
+        "buffer",
+        "\n",
+    ];
+    let mut rng = Lcg(seed);
+    let mut out = Vec::with_capacity(len + 16);
+    while out.len() < len {
+        out.extend_from_slice(WORDS[rng.next() as usize % WORDS.len()].as_bytes());
+        out.push(b' ');
+    }
+    out.truncate(len);
+    out
+}
+
+/// Deterministic, incompressible data.
+pub fn noise(len: usize, seed: u32) -> Vec<u8> {
+    let mut rng = Lcg(seed);
+    (0..len).map(|_| (rng.next() >> 16) as u8).collect()
+}
+
+struct Lcg(u32);
+
+impl Lcg {
+    fn next(&mut self) -> u32 {
+        self.0 = self.0.wrapping_mul(1103515245).wrapping_add(12345);
+        self.0 >> 1
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, count: u32) {
+        self.acc |= value << self.bits;
+        self.bits += count;
+        while self.bits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    // huffman codes are packed starting from their most significant bit
+    fn put_code(&mut self, code: u32, count: u32) {
+        let reversed = code.reverse_bits() >> (32 - count);
+        self.put(reversed, count);
+    }
+
+    fn put_literal(&mut self, sym: u32) {
+        match sym {
+            0..=143 => self.put_code(0x30 + sym, 8),
+            144..=255 => self.put_code(0x190 + sym - 144, 9),
+            256..=279 => self.put_code(sym - 256, 7),
+            _ => self.put_code(0xC0 + sym - 280, 8),
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+/// A single fixed-Huffman deflate block with greedy LZ77 matching. Not
+/// competitive with real encoders, but it exercises every inflate code path.
+pub fn deflate_fixed(data: &[u8]) -> Vec<u8> {
+    const WINDOW: usize = 32768;
+    let mut w = BitWriter {
+        out: Vec::new(),
+        acc: 0,
+        bits: 0,
+    };
+    w.put(1, 1); // final block
+    w.put(1, 2); // fixed huffman
+
+    let mut head = vec![usize::MAX; 1 << 15];
+    let hash = |i: usize| {
+        ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & 0x7FFF
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = 0;
+        let mut dist = 0;
+        if i + 3 <= data.len() {
+            let h = hash(i);
+            let cand = head[h];
+            head[h] = i;
+            if cand != usize::MAX && i - cand <= WINDOW {
+                let max = (data.len() - i).min(258);
+                while best < max && data[cand + best] == data[i + best] {
+                    best += 1;
+                }
+                dist = i - cand;
+            }
+        }
+
+        if best >= 3 {
+            let li = LENGTH_BASE
+                .iter()
+                .rposition(|&b| b as usize <= best)
+                .unwrap();
+            w.put_literal(257 + li as u32);
+            w.put(
+                (best - LENGTH_BASE[li] as usize) as u32,
+                LENGTH_EXTRA[li] as u32,
+            );
+            let di = DIST_BASE.iter().rposition(|&b| b as usize <= dist).unwrap();
+            w.put_code(di as u32, 5);
+            w.put(
+                (dist - DIST_BASE[di] as usize) as u32,
+                DIST_EXTRA[di] as u32,
+            );
+            i += best;
+        } else {
+            w.put_literal(data[i] as u32);
+            i += 1;
+        }
+    }
+
+    w.put_literal(256);
+    w.finish()
+}
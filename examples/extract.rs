@@ -0,0 +1,17 @@
+use std::fs::File;
+
+fn main() {
+    let mut args = std::env::args();
+    if args.len() != 3 {
+        eprintln!("{} <FILE> <DEST>", args.next().unwrap());
+        return;
+    }
+
+    let mut input = File::open(args.nth(1).unwrap()).unwrap();
+    let dest = args.next().unwrap();
+
+    let zi = munzip::SearchableArchive::new(&mut input).unwrap();
+
+    let options = munzip::ExtractOptions::new().preserve_mtime(true);
+    zi.extract_all(dest.as_ref(), &options).unwrap();
+}
@@ -1,12 +1,11 @@
 use std::fs::File;
 
-use munzip;
 mod write;
 
 fn main() {
     let mut args = std::env::args();
     if args.len() != 2 {
-        eprintln!("{} <FILE>", args.nth(0).unwrap());
+        eprintln!("{} <FILE>", args.next().unwrap());
         return;
     }
 
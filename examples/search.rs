@@ -1,12 +1,11 @@
 use std::fs::File;
 
-use munzip;
 mod write;
 
 fn main() {
     let mut args = std::env::args();
     if args.len() != 2 {
-        eprintln!("{} <FILE>", args.nth(0).unwrap());
+        eprintln!("{} <FILE>", args.next().unwrap());
         return;
     }
 
@@ -15,5 +14,5 @@ fn main() {
 
     let filename = "munzip/Cargo.toml";
     let cargo_toml = zi.by_name(filename).unwrap().unwrap();
-    write::write_file(&"Cargo.toml".to_owned(), &cargo_toml).unwrap();
+    write::write_file("Cargo.toml", &cargo_toml).unwrap();
 }
@@ -3,13 +3,13 @@ use std::path::Path;
 
 use munzip::MuError;
 
-pub fn write_file(filename: &String, data: &Vec<u8>) -> Result<(), MuError> {
-    let path = Path::new(&filename);
+pub fn write_file(filename: &str, data: &[u8]) -> Result<(), MuError> {
+    let path = Path::new(filename);
 
     if filename.ends_with("/") {
         if !path.exists() {
             std::fs::create_dir_all(path)
-                .map_err(|_| MuError(format!("failed to create dir '{:?}'", path).to_string()))?;
+                .map_err(|_| MuError::from(format!("failed to create dir '{:?}'", path)))?;
         }
         return Ok(());
     }
@@ -20,7 +20,7 @@ pub fn write_file(filename: &String, data: &Vec<u8>) -> Result<(), MuError> {
     }
 
     let mut file = std::fs::File::create(path).unwrap();
-    file.write_all(&data).unwrap();
+    file.write_all(data).unwrap();
 
     Ok(())
 }
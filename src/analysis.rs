@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hasher};
+use std::io::{Read, Seek, Write};
+
+use crate::crc32::Crc32;
+use crate::searchable::SearchableArchive;
+use crate::shared::*;
+use crate::types::*;
+
+/// Entries whose decompressed contents are identical.
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    /// Names of the entries sharing these contents, in central directory order
+    pub filenames: Vec<String>,
+    /// Decompressed size of each copy
+    pub size: u64,
+    /// Compressed size of each copy, in the same order as `filenames`
+    pub compressed_sizes: Vec<u64>,
+}
+
+impl DuplicateGroup {
+    /// Returns the decompressed bytes that storing one copy would save
+    pub fn savings(&self) -> u64 {
+        self.size * (self.filenames.len() as u64 - 1)
+    }
+
+    /// Returns the archive bytes that storing only the smallest compressed copy
+    /// would save
+    pub fn compressed_savings(&self) -> u64 {
+        let total: u64 = self.compressed_sizes.iter().sum();
+        total - self.compressed_sizes.iter().min().unwrap_or(&0)
+    }
+}
+
+/// The result of `SearchableArchive::duplicate_report()`.
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateReport {
+    /// Every set of two or more entries with the same contents, largest savings first
+    pub groups: Vec<DuplicateGroup>,
+}
+
+impl DuplicateReport {
+    /// Returns the decompressed bytes that deduplicating every group would save
+    pub fn savings(&self) -> u64 {
+        self.groups.iter().map(|g| g.savings()).sum()
+    }
+
+    /// Returns the archive bytes that deduplicating every group would save
+    pub fn compressed_savings(&self) -> u64 {
+        self.groups.iter().map(|g| g.compressed_savings()).sum()
+    }
+}
+
+// Limits of Linux, macOS and Windows alike, in bytes and UTF-16 units respectively.
+const MAX_COMPONENT_BYTES: usize = 255;
+// Windows' MAX_PATH, less the drive and terminating NUL
+const MAX_PATH_UNITS: usize = 256;
+
+/// Something about an entry's name that may cause trouble when extracting it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NameIssue {
+    /// A NUL or other control character, which most filesystems refuse or which
+    /// can disguise the name when it's displayed
+    ControlCharacter,
+    /// A component longer than the 255 bytes filesystems allow. Holds its length.
+    ComponentTooLong(usize),
+    /// A path longer than Windows allows without long path support, 260 UTF-16 units
+    /// with the drive and `\`. Holds its length in UTF-16 units.
+    PathTooLong(usize),
+    /// Both `/` and `\\` are used as separators in the name as stored
+    MixedSeparators,
+    /// A component ending in a space or a dot, which Windows silently drops
+    TrailingSpaceOrDot,
+}
+
+/// An entry whose name has one or more `NameIssue`s.
+#[derive(Debug, Clone)]
+pub struct NameProblem {
+    /// The entry's name, with separators normalized
+    pub filename: String,
+    /// Every issue found with it
+    pub issues: Vec<NameIssue>,
+}
+
+/// The result of `SearchableArchive::name_report()`.
+#[derive(Debug, Clone, Default)]
+pub struct NameReport {
+    /// Entries with problem names, in central directory order
+    pub problems: Vec<NameProblem>,
+}
+
+impl NameReport {
+    /// True when every name is safe to extract anywhere
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+// Feeds everything written to it into a CRC-32 and a SipHash, which together with
+// the size make a 160 bit fingerprint of the contents.
+struct ContentHasher {
+    crc: Crc32,
+    sip: DefaultHasher,
+    len: u64,
+}
+
+impl Write for ContentHasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.crc.update(buf);
+        self.sip.write(buf);
+        self.len += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    /// Decompresses every file entry, streaming it through a hash rather than holding
+    /// it in memory, and groups those with identical contents. Empty files and
+    /// directories are left out.
+    ///
+    /// Contents are compared by fingerprint (size, CRC-32 and a 64 bit SipHash), not
+    /// byte for byte, so this is meant for reporting rather than for deciding to
+    /// delete data unseen.
+    pub fn duplicate_report(&self) -> Result<DuplicateReport, MuError> {
+        let mut file = self.file.borrow_mut();
+        let mut by_content: HashMap<(u64, u32, u64), DuplicateGroup> = HashMap::new();
+        let mut order = Vec::new();
+
+        for (filename, header) in &self.entries {
+            if filename.ends_with('/') || header.uncompressed_size == 0 {
+                continue;
+            }
+
+            let mut hasher = ContentHasher {
+                crc: Crc32::new(),
+                sip: DefaultHasher::new(),
+                len: 0,
+            };
+            std::io::copy(&mut data_reader(&mut *file, header)?, &mut hasher)?;
+
+            let key = (hasher.len, hasher.crc.finish(), hasher.sip.finish());
+            let group = by_content.entry(key).or_insert_with(|| {
+                order.push(key);
+                DuplicateGroup {
+                    filenames: Vec::new(),
+                    size: hasher.len,
+                    compressed_sizes: Vec::new(),
+                }
+            });
+            group.filenames.push(filename.clone());
+            group.compressed_sizes.push(header.compressed_size);
+        }
+
+        let mut groups: Vec<DuplicateGroup> = order
+            .iter()
+            .filter_map(|key| by_content.remove(key))
+            .filter(|g| g.filenames.len() > 1)
+            .collect();
+        groups.sort_by_key(|g| std::cmp::Reverse(g.savings()));
+
+        Ok(DuplicateReport { groups })
+    }
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    /// Checks every entry's name for things that extract badly, or differently, on
+    /// some platform: control characters, names too long for common filesystems,
+    /// mixed `/` and `\\` separators, and components ending in a space or dot. No data
+    /// is read.
+    ///
+    /// This doesn't repeat the checks `extract_all()` makes anyway, such as refusing
+    /// `..` components.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut input = std::fs::File::open("upload.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut input).unwrap();
+    ///
+    /// let report = zi.name_report();
+    /// for problem in &report.problems {
+    ///     eprintln!("{}: {:?}", problem.filename, problem.issues);
+    /// }
+    /// ```
+    pub fn name_report(&self) -> NameReport {
+        let mut problems = Vec::new();
+
+        for (filename, header) in &self.entries {
+            let mut issues = Vec::new();
+
+            if filename.chars().any(char::is_control) {
+                issues.push(NameIssue::ControlCharacter);
+            }
+
+            let components = filename.split('/').filter(|c| !c.is_empty());
+            if let Some(longest) = components.clone().map(str::len).max() {
+                if longest > MAX_COMPONENT_BYTES {
+                    issues.push(NameIssue::ComponentTooLong(longest));
+                }
+            }
+
+            let units = filename.encode_utf16().count();
+            if units > MAX_PATH_UNITS {
+                issues.push(NameIssue::PathTooLong(units));
+            }
+
+            let raw = &header.filename_raw;
+            if raw.contains(&b'/') && raw.contains(&b'\\') {
+                issues.push(NameIssue::MixedSeparators);
+            }
+
+            let trailing = |c: &str| c != "." && c != ".." && c.ends_with([' ', '.']);
+            if components.clone().any(trailing) {
+                issues.push(NameIssue::TrailingSpaceOrDot);
+            }
+
+            if !issues.is_empty() {
+                problems.push(NameProblem {
+                    filename: filename.clone(),
+                    issues,
+                });
+            }
+        }
+
+        NameReport { problems }
+    }
+}
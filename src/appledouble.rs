@@ -0,0 +1,93 @@
+// AppleDouble files, as Finder writes them to `__MACOSX/` when making an archive.
+// Each describes one real entry: its Finder info, resource fork and extended
+// attributes. See Apple's "AppleSingle/AppleDouble Formats" and xnu's vfs_xattr.c.
+
+const MAGIC: u32 = 0x00051607;
+const RESOURCE_FORK: u32 = 2;
+const FINDER_INFO: u32 = 9;
+const FINDER_INFO_LEN: usize = 32;
+const ATTR_MAGIC: &[u8; 4] = b"ATTR";
+
+// The name of the entry an AppleDouble entry describes: `__MACOSX/dir/._name`
+// becomes `dir/name`.
+pub fn target(name: &str) -> Option<String> {
+    let rest = name.strip_prefix("__MACOSX/")?;
+    let (dir, file) = match rest.rsplit_once('/') {
+        Some((dir, file)) => (Some(dir), file),
+        None => (None, rest),
+    };
+    let file = file.strip_prefix("._").filter(|f| !f.is_empty())?;
+
+    Some(match dir {
+        Some(dir) => format!("{dir}/{file}"),
+        None => file.to_string(),
+    })
+}
+
+// The extended attributes held by an AppleDouble file, with the Finder info and
+// resource fork as the attributes macOS exposes them as.
+pub fn xattrs(data: &[u8]) -> Option<Vec<(String, Vec<u8>)>> {
+    if be32(data, 0)? != MAGIC {
+        return None;
+    }
+
+    let mut attrs = Vec::new();
+    let count = be16(data, 24)? as usize;
+    for i in 0..count {
+        let at = 26 + i * 12;
+        let id = be32(data, at)?;
+        let offset = be32(data, at + 4)? as usize;
+        let len = be32(data, at + 8)? as usize;
+        let body = data.get(offset..offset.checked_add(len)?)?;
+
+        match id {
+            FINDER_INFO if body.len() >= FINDER_INFO_LEN => {
+                let info = &body[..FINDER_INFO_LEN];
+                if info.iter().any(|&b| b != 0) {
+                    attrs.push(("com.apple.FinderInfo".to_string(), info.to_vec()));
+                }
+                // extended attributes follow the Finder info, after two bytes of padding
+                if body.get(FINDER_INFO_LEN + 2..FINDER_INFO_LEN + 6) == Some(ATTR_MAGIC) {
+                    extended(data, offset + FINDER_INFO_LEN + 2, &mut attrs)?;
+                }
+            }
+            RESOURCE_FORK if len > 0 => {
+                attrs.push(("com.apple.ResourceFork".to_string(), body.to_vec()));
+            }
+            _ => {}
+        }
+    }
+
+    Some(attrs)
+}
+
+// Parse an attribute header, whose offsets are from the start of the file.
+fn extended(data: &[u8], header: usize, attrs: &mut Vec<(String, Vec<u8>)>) -> Option<()> {
+    let count = be16(data, header + 34)? as usize;
+
+    let mut at = header + 36;
+    for _ in 0..count {
+        let offset = be32(data, at)? as usize;
+        let len = be32(data, at + 4)? as usize;
+        let name_len = *data.get(at + 10)? as usize;
+        let name = data.get(at + 11..at + 11 + name_len)?;
+        // the name is NUL terminated
+        let name = String::from_utf8_lossy(name.strip_suffix(&[0]).unwrap_or(name));
+
+        let value = data.get(offset..offset.checked_add(len)?)?;
+        attrs.push((name.into_owned(), value.to_vec()));
+
+        // entries are padded to four bytes
+        at = (at + 11 + name_len + 3) & !3;
+    }
+
+    Some(())
+}
+
+fn be16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
@@ -0,0 +1,161 @@
+use std::io::{Read, Seek};
+
+use crate::iterable::{Entry, EntryMeta};
+use crate::shared::*;
+use crate::types::*;
+
+/// The system an entry was made on, from the high byte of the central directory's
+/// version made by. It says how to read the entry's external attributes: entries
+/// from Unix (and macOS) hold a Unix mode, and everything else DOS attributes only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HostSystem {
+    /// MS-DOS, and anything writing plain DOS attributes, as most Windows tools do
+    Dos,
+    /// Unix, with a Unix mode in the high 16 bits of the external attributes
+    Unix,
+    /// Windows NTFS
+    Ntfs,
+    /// Windows VFAT
+    Vfat,
+    /// macOS, which writes a Unix mode as Unix does
+    Osx,
+    /// Any other host the spec lists, by its number
+    Other(u8),
+}
+
+impl HostSystem {
+    pub(crate) fn from_version_made_by(version_made_by: u16) -> HostSystem {
+        match (version_made_by >> 8) as u8 {
+            HOST_DOS => HostSystem::Dos,
+            HOST_UNIX => HostSystem::Unix,
+            HOST_NTFS => HostSystem::Ntfs,
+            HOST_VFAT => HostSystem::Vfat,
+            HOST_OSX => HostSystem::Osx,
+            host => HostSystem::Other(host),
+        }
+    }
+}
+
+/// What an entry is, from its name, its mode if it was made on Unix, and its DOS
+/// attributes, in that order: a name ending in `/` is always a directory, as every
+/// extractor treats it, and then the mode's file type settles it if there is one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EntryKind {
+    /// A regular file
+    File,
+    /// A directory
+    Directory,
+    /// A symlink, whose data is the target path
+    Symlink,
+    /// A Unix special file, which has no data to extract
+    Special(SpecialKind),
+    /// A Unix mode with a file type that isn't one of Unix's
+    Unknown,
+}
+
+/// The kind of an `EntryKind::Special` entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialKind {
+    /// A named pipe
+    Fifo,
+    /// A character device
+    CharDevice,
+    /// A block device
+    BlockDevice,
+    /// A Unix domain socket
+    Socket,
+}
+
+impl<'a, R: Read + Seek> Entry<'a, R> {
+    /// Returns what the entry is: a file, directory, symlink or something else
+    pub fn kind(&self) -> EntryKind {
+        entry_kind(&self.filename, &self.header)
+    }
+    /// Returns the system the entry was made on, which says how its attributes
+    /// are read
+    pub fn host_system(&self) -> HostSystem {
+        HostSystem::from_version_made_by(self.header.version_made_by)
+    }
+    /// Returns the Unix mode (as in `st_mode`, file type bits included), if the
+    /// entry was made on Unix or macOS and has one
+    pub fn unix_mode(&self) -> Option<u32> {
+        unix_mode(&self.header)
+    }
+    /// Returns the DOS attribute byte: `0x01` readonly, `0x02` hidden, `0x04`
+    /// system, `0x10` directory and `0x20` archive. For entries made on Unix, the
+    /// readonly bit is also set when the mode has no write permission.
+    pub fn dos_attributes(&self) -> u8 {
+        dos_attributes(&self.header)
+    }
+    /// Returns true if the entry is readonly, by its Unix mode if it has one, or
+    /// else its DOS attributes
+    pub fn is_readonly(&self) -> bool {
+        is_readonly(&self.header)
+    }
+}
+
+impl EntryMeta {
+    /// Returns what the entry is, as `Entry::kind()` does
+    pub fn kind(&self) -> EntryKind {
+        entry_kind(&self.filename, &self.header)
+    }
+    /// Returns the system the entry was made on, as `Entry::host_system()` does
+    pub fn host_system(&self) -> HostSystem {
+        HostSystem::from_version_made_by(self.header.version_made_by)
+    }
+    /// Returns the Unix mode, as `Entry::unix_mode()` does
+    pub fn unix_mode(&self) -> Option<u32> {
+        unix_mode(&self.header)
+    }
+    /// Returns the DOS attribute byte, as `Entry::dos_attributes()` does
+    pub fn dos_attributes(&self) -> u8 {
+        dos_attributes(&self.header)
+    }
+    /// Returns true if the entry is readonly, as `Entry::is_readonly()` does
+    pub fn is_readonly(&self) -> bool {
+        is_readonly(&self.header)
+    }
+}
+
+pub(crate) fn entry_kind(filename: &str, header: &InternalHeader) -> EntryKind {
+    if filename.ends_with('/') {
+        return EntryKind::Directory;
+    }
+
+    match unix_mode(header).map(|mode| mode & S_IFMT) {
+        // a mode of just permissions says nothing about the type
+        None | Some(0) => {}
+        Some(S_IFREG) => return EntryKind::File,
+        Some(S_IFDIR) => return EntryKind::Directory,
+        Some(S_IFLNK) => return EntryKind::Symlink,
+        Some(S_IFIFO) => return EntryKind::Special(SpecialKind::Fifo),
+        Some(S_IFCHR) => return EntryKind::Special(SpecialKind::CharDevice),
+        Some(S_IFBLK) => return EntryKind::Special(SpecialKind::BlockDevice),
+        Some(S_IFSOCK) => return EntryKind::Special(SpecialKind::Socket),
+        Some(_) => return EntryKind::Unknown,
+    }
+
+    match header.external_file_attributes & DOS_DIRECTORY {
+        0 => EntryKind::File,
+        _ => EntryKind::Directory,
+    }
+}
+
+// The DOS attribute byte, with readonly worked out from the mode for Unix entries,
+// whose tools don't always set it.
+pub(crate) fn dos_attributes(header: &InternalHeader) -> u8 {
+    let attributes = header.external_file_attributes as u8;
+    match unix_mode(header) {
+        Some(mode) if mode & 0o222 == 0 => attributes | DOS_READONLY as u8,
+        _ => attributes,
+    }
+}
+
+pub(crate) fn is_readonly(header: &InternalHeader) -> bool {
+    match unix_mode(header) {
+        Some(mode) => mode & 0o222 == 0,
+        None => header.external_file_attributes & DOS_READONLY != 0,
+    }
+}
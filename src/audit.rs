@@ -0,0 +1,203 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::searchable::SearchableArchive;
+use crate::shared::*;
+use crate::types::*;
+
+/// What a `Region` of the archive holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegionKind {
+    /// An entry's local header, data, and data descriptor if it has one. Holds the
+    /// entry's index in the central directory.
+    Entry(usize),
+    CentralDirectory,
+    /// The ZIP64 end record and its locator, which come just before the end record
+    Zip64EndRecord,
+    EndRecord,
+}
+
+/// A byte range of the archive, `start` inclusive and `end` exclusive.
+#[derive(Debug, Clone)]
+pub struct Region {
+    pub kind: RegionKind,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Where an entry's parts sit in the archive.
+#[derive(Debug, Clone)]
+pub struct EntryLayout {
+    pub filename: String,
+    /// The entry's index in the central directory, as `RegionKind::Entry` holds
+    pub central_index: usize,
+    pub header_start: u64,
+    pub data_start: u64,
+    pub data_end: u64,
+}
+
+/// The result of `SearchableArchive::audit_layout()`.
+#[derive(Debug, Clone)]
+pub struct LayoutReport {
+    /// Per-entry offsets, in central directory order.
+    pub entries: Vec<EntryLayout>,
+    /// Every region, sorted by `start`.
+    pub regions: Vec<Region>,
+    /// Pairs of indices into `regions` that share bytes.
+    pub overlaps: Vec<(usize, usize)>,
+    /// Byte ranges no region accounts for, such as a self-extractor stub.
+    pub gaps: Vec<(u64, u64)>,
+}
+
+impl LayoutReport {
+    /// True when the regions tile the file exactly: nothing overlaps, and nothing is
+    /// left over.
+    pub fn is_clean(&self) -> bool {
+        self.overlaps.is_empty() && self.gaps.is_empty()
+    }
+
+    /// Returns the total size of the gaps
+    pub fn gap_bytes(&self) -> u64 {
+        self.gaps.iter().map(|(start, end)| end - start).sum()
+    }
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    /// Maps out which bytes of the file, or of the window it was opened within,
+    /// belong to which entry, the central directory, any ZIP64 end record and locator,
+    /// and the end record, and checks that they don't overlap or leave gaps. Entries
+    /// left out under `ErrorMode::Continue` aren't accounted for, so their bytes show
+    /// up as gaps. `__MACOSX/` entries are, whether they're listed or not.
+    pub fn audit_layout(&self) -> Result<LayoutReport, MuError> {
+        let mut file = self.file.borrow_mut();
+        let span = self.span(&mut file)?;
+        let file_size = span.end;
+
+        let mut entries = Vec::new();
+        let mut regions = Vec::new();
+
+        for (filename, header) in self.entries.iter().chain(&self.hidden) {
+            let data_start = header.offset;
+            let data_end = data_start + header.compressed_size;
+
+            let mut region_end = data_end;
+            if header.general_purpose_bit_flag & FLAG_DATA_DESCRIPTOR != 0 {
+                region_end += data_descriptor_len(&mut *file, data_end, header.zip64)?;
+            }
+
+            entries.push(EntryLayout {
+                filename: filename.clone(),
+                central_index: header.central_index,
+                header_start: header.local_header_offset,
+                data_start,
+                data_end,
+            });
+            regions.push(Region {
+                kind: RegionKind::Entry(header.central_index),
+                start: header.local_header_offset,
+                end: region_end,
+            });
+        }
+
+        // the hidden entries were chained on after the rest
+        entries.sort_by_key(|e| e.central_index);
+
+        let cd_start = self.cd_offset;
+        regions.push(Region {
+            kind: RegionKind::CentralDirectory,
+            start: cd_start,
+            end: cd_start + self.end_rec.central_directory_size,
+        });
+
+        // the end record was only accepted if its comment runs to the end of the file
+        let er_len =
+            std::mem::size_of::<EndRecord>() as u64 + self.end_rec.record.zip_comment_length as u64;
+        let er_start = file_size - er_len;
+        regions.push(Region {
+            kind: RegionKind::EndRecord,
+            start: er_start,
+            end: file_size,
+        });
+
+        if let Some(start) =
+            zip64_end_record_start(&mut *file, span.start, er_start, &self.end_rec)?
+        {
+            regions.push(Region {
+                kind: RegionKind::Zip64EndRecord,
+                start,
+                end: er_start,
+            });
+        }
+
+        regions.sort_by_key(|r| (r.start, r.end));
+
+        let mut overlaps = Vec::new();
+        let mut gaps = Vec::new();
+        let mut covered = span.start;
+        for (i, region) in regions.iter().enumerate() {
+            if region.start > covered {
+                gaps.push((covered, region.start));
+            }
+            for (j, later) in regions.iter().enumerate().skip(i + 1) {
+                if later.start >= region.end {
+                    break;
+                }
+                overlaps.push((i, j));
+            }
+            covered = covered.max(region.end);
+        }
+
+        Ok(LayoutReport {
+            entries,
+            regions,
+            overlaps,
+            gaps,
+        })
+    }
+}
+
+// A data descriptor is a crc and two sizes, 8 bytes each for a ZIP64 entry,
+// optionally preceded by a signature.
+fn data_descriptor_len(
+    file: &mut (impl Read + Seek),
+    position: u64,
+    zip64: bool,
+) -> Result<u64, MuError> {
+    file.seek(SeekFrom::Start(position))?;
+
+    let mut sig = [0; 4];
+    file.read_exact(&mut sig)?;
+
+    let signed = u32::from_le_bytes(sig) == DATA_DESCRIPTOR_SIGNATURE;
+    Ok(signed as u64 * 4 + 4 + if zip64 { 16 } else { 8 })
+}
+
+// Where the ZIP64 end record starts, if the end record starting at `er_start` was
+// resolved through one. The locator just before the end record says where, counting
+// from `base`, the start of the archive.
+fn zip64_end_record_start(
+    file: &mut (impl Read + Seek),
+    base: u64,
+    er_start: u64,
+    end_rec: &DirectoryEnd,
+) -> Result<Option<u64>, MuError> {
+    let er = &end_rec.record;
+    let placeholders = er.num_entries == u16::MAX
+        || er.central_directory_size == u32::MAX
+        || er.central_directory_offset == u32::MAX;
+    let Some(locator_at) = er_start.checked_sub(ZIP64_END_LOCATOR_SIZE as u64) else {
+        return Ok(None);
+    };
+    if !placeholders || locator_at < base {
+        return Ok(None);
+    }
+
+    let mut locator = [0; ZIP64_END_LOCATOR_SIZE];
+    file.seek(SeekFrom::Start(locator_at))?;
+    file.read_exact(&mut locator)?;
+    if locator[..4] != ZIP64_END_LOCATOR_SIGNATURE.to_le_bytes() {
+        return Ok(None);
+    }
+
+    let record_at = u64::from_le_bytes(locator[8..16].try_into().unwrap());
+    Ok(Some(base + record_at))
+}
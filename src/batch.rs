@@ -0,0 +1,148 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use crate::iterable::Entry;
+use crate::searchable::SearchableArchive;
+use crate::shared::*;
+use crate::types::*;
+
+// reading through a gap this small is quicker than seeking over it
+const MERGE_GAP: u64 = 64 * 1024;
+// nor are spans merged past this, as each is read into memory whole
+const MAX_SPAN: u64 = 16 * 1024 * 1024;
+
+/// The entries found by `SearchableArchive::by_names()`, and a plan for reading
+/// them with as few seeks as possible.
+pub struct BatchLookup<'a, R: Read + Seek = &'a mut File> {
+    /// The entries found, sorted by where their data is in the archive
+    pub entries: Vec<Entry<'a, R>>,
+    /// The names asked for that no entry has
+    pub missing: Vec<String>,
+    /// The spans of the archive to read, in order, each covering one or more of
+    /// `entries`
+    pub plan: Vec<ReadSpan>,
+}
+
+/// A span of the archive that `BatchLookup::read_all()` reads with one seek.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadSpan {
+    /// Where the span starts in the archive file
+    pub start: u64,
+    /// Where it ends, exclusive
+    pub end: u64,
+    /// The indices into `BatchLookup::entries` of the entries whose data it holds
+    pub entries: Range<usize>,
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    /// Looks up every name in `names` at once, and plans how to read them. The
+    /// entries found come back in the order their data is stored, and neighbours
+    /// whose data is at most 64 KiB apart are grouped into one span, read with a
+    /// single seek, so a loader that always needs the same dozen files out of a
+    /// bundle reads through it front to back. Names asked for twice are looked up
+    /// once.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("bundle.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// let batch = zi.by_names(&["shaders/main.wgsl", "textures/atlas.png", "config.toml"]);
+    /// println!("{} seeks for {} entries", batch.plan.len(), batch.entries.len());
+    /// for (entry, data) in batch.entries.iter().zip(batch.read_all().unwrap()) {
+    ///     println!("{}: {} bytes", entry.filename(), data.len());
+    /// }
+    /// ```
+    pub fn by_names(&self, names: &[&str]) -> BatchLookup<'a, R> {
+        let mut indices = Vec::new();
+        let mut missing = Vec::new();
+        for &name in names {
+            match self.index_of(name) {
+                Some(index) if !indices.contains(&index) => indices.push(index),
+                Some(_) => {}
+                None if !missing.iter().any(|m| m == name) => missing.push(name.to_string()),
+                None => {}
+            }
+        }
+
+        let mut entries: Vec<Entry<'a, R>> = indices
+            .into_iter()
+            .filter_map(|index| self.by_index(index))
+            .collect();
+        entries.sort_by_key(|entry| entry.header.offset);
+
+        let mut plan: Vec<ReadSpan> = Vec::new();
+        for (i, entry) in entries.iter().enumerate() {
+            let start = entry.header.offset;
+            let end = start + entry.header.compressed_size;
+            match plan.last_mut() {
+                Some(span) if start <= span.end + MERGE_GAP && end - span.start <= MAX_SPAN => {
+                    span.end = span.end.max(end);
+                    span.entries.end = i + 1;
+                }
+                _ => plan.push(ReadSpan {
+                    start,
+                    end,
+                    entries: i..i + 1,
+                }),
+            }
+        }
+
+        BatchLookup {
+            entries,
+            missing,
+            plan,
+        }
+    }
+}
+
+impl<'a, R: Read + Seek> BatchLookup<'a, R> {
+    /// Reads and decompresses every entry by following the plan, returning their
+    /// data in the same order as `entries`. Each entry's CRC-32 is checked as the
+    /// archive's `CrcPolicy` says.
+    pub fn read_all(&self) -> Result<Vec<Vec<u8>>, MuError> {
+        let mut data = Vec::with_capacity(self.entries.len());
+        let Some(first) = self.entries.first() else {
+            return Ok(data);
+        };
+
+        let mut file = first.file.borrow_mut();
+        let mut buf = Vec::new();
+        for span in &self.plan {
+            buf.resize((span.end - span.start) as usize, 0);
+            file.seek(SeekFrom::Start(span.start))?;
+            file.read_exact(&mut buf)?;
+
+            for entry in &self.entries[span.entries.clone()] {
+                #[cfg(feature = "metrics")]
+                let start = std::time::Instant::now();
+
+                let from = (entry.header.offset - span.start) as usize;
+                let compressed = &buf[from..from + entry.header.compressed_size as usize];
+                let decompressed = check_flags(&entry.header)
+                    .and_then(|_| decompress(entry.header.compression_method, compressed.to_vec()))
+                    .and_then(|decompressed| {
+                        entry
+                            .crc
+                            .check_data(&entry.filename, &entry.header, &decompressed)?;
+                        Ok(decompressed)
+                    })
+                    .map_err(|e| e.with_entry(entry.meta()))?;
+
+                #[cfg(feature = "metrics")]
+                entry.metrics.borrow_mut().record(
+                    &entry.filename,
+                    &entry.header,
+                    decompressed.len() as u64,
+                    start,
+                );
+
+                data.push(decompressed);
+            }
+        }
+
+        Ok(data)
+    }
+}
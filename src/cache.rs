@@ -0,0 +1,128 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::sync::Arc;
+
+use crate::searchable::SearchableArchive;
+
+/// How a `SearchableArchive`'s entry cache has fared, from
+/// `SearchableArchive::cache_stats()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// Reads served from the cache
+    pub hits: u64,
+    /// Reads that had to decompress the entry
+    pub misses: u64,
+    /// How many entries the cache holds
+    pub entries: usize,
+    /// The decompressed size of the entries the cache holds
+    pub bytes: u64,
+}
+
+// Recently decompressed entries, keyed by central directory index, up to
+// `ArchiveOptions::entry_cache()` bytes of them, shared by the archive and every
+// entry from it. The least recently used go first once it's full.
+#[derive(Debug, Default)]
+pub struct EntryCache {
+    capacity: u64,
+    state: RefCell<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    // each entry's data, and when it was last used
+    entries: HashMap<usize, (Arc<[u8]>, u64)>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl EntryCache {
+    pub fn new(capacity: u64) -> Self {
+        EntryCache {
+            capacity,
+            state: RefCell::new(CacheState::default()),
+        }
+    }
+
+    // Whether there's any point handing data to `insert()`.
+    pub fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    // The entry at `index`, if it's cached, counting the lookup as a hit or miss.
+    pub fn get(&self, index: usize) -> Option<Arc<[u8]>> {
+        if !self.enabled() {
+            return None;
+        }
+
+        let mut state = self.state.borrow_mut();
+        state.clock += 1;
+        let now = state.clock;
+        match state.entries.get_mut(&index) {
+            Some((data, used)) => {
+                *used = now;
+                let data = Arc::clone(data);
+                state.stats.hits += 1;
+                Some(data)
+            }
+            None => {
+                state.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    // Cache the entry at `index`, making room by dropping the least recently used.
+    // Entries larger than the whole cache aren't kept.
+    pub fn insert(&self, index: usize, data: Arc<[u8]>) {
+        let size = data.len() as u64;
+        if !self.enabled() || size > self.capacity {
+            return;
+        }
+
+        let mut state = self.state.borrow_mut();
+        state.clock += 1;
+        let now = state.clock;
+        if let Some((old, _)) = state.entries.insert(index, (data, now)) {
+            state.stats.bytes -= old.len() as u64;
+        }
+        state.stats.bytes += size;
+
+        while state.stats.bytes > self.capacity {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(&index, _)| index);
+            match oldest.and_then(|index| state.entries.remove(&index)) {
+                Some((old, _)) => state.stats.bytes -= old.len() as u64,
+                None => break,
+            }
+        }
+        state.stats.entries = state.entries.len();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.state.borrow().stats
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state.borrow_mut();
+        state.entries.clear();
+        state.stats.entries = 0;
+        state.stats.bytes = 0;
+    }
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    /// Returns how many reads the cache set by `ArchiveOptions::entry_cache()` has
+    /// served, and what it holds. All zeroes when there's no cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Empties the entry cache, keeping its hit and miss counts
+    pub fn clear_cache(&self) {
+        self.cache.clear()
+    }
+}
@@ -0,0 +1,109 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::iterable::Shared;
+use crate::searchable::SearchableArchive;
+use crate::shared::*;
+use crate::types::*;
+
+/// Where a `SearchableArchive`'s central directory lies in the archive file, record
+/// by record, from `SearchableArchive::central_directory_bytes()`. Signing tools can
+/// hash exactly these bytes, and tools that only append can copy them unchanged.
+pub struct CentralDirectoryBytes<'a, R: Read + Seek = &'a mut File> {
+    /// Where the central directory starts, as the end record says
+    pub offset: u64,
+    /// How long it is, as the end record says
+    pub len: u64,
+    /// The span of each central directory record, in the order they're stored, so
+    /// the index of each is its entry's `central_directory_index()`. Every entry the
+    /// end record counts is here, including any left out of `entries()`.
+    pub records: Vec<Range<u64>>,
+    /// The span of the end record, with its comment, which runs to the end of the
+    /// file
+    pub end_record: Range<u64>,
+    file: Rc<RefCell<R>>,
+    lifetime: PhantomData<&'a ()>,
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    /// Walks the central directory, returning where it and each of its records lie.
+    /// Only the fixed part of each record is read, and the records are always
+    /// listed in the order they're stored in, however the archive was opened.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("app.apk").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// let cd = zi.central_directory_bytes().unwrap();
+    /// println!("central directory at {}, {} bytes", cd.offset, cd.len);
+    /// let mut digest = std::fs::File::create("app.apk.cd").unwrap();
+    /// std::io::copy(&mut cd.reader(), &mut digest).unwrap();
+    /// ```
+    pub fn central_directory_bytes(&self) -> Result<CentralDirectoryBytes<'a, R>, MuError> {
+        let offset = self.cd_offset;
+        let len = self.end_rec.central_directory_size;
+
+        let mut file = self.file.borrow_mut();
+        let mut records = Vec::with_capacity(self.end_rec.num_entries as usize);
+        let mut position = offset;
+        for _ in 0..self.end_rec.num_entries {
+            let (_, next) = next_global_header(&mut *file, position)?;
+            records.push(position..next);
+            position = next;
+        }
+
+        let end_size = END_RECORD_SIZE as u64 + self.end_rec.record.zip_comment_length as u64;
+        let file_size = self.span(&mut file)?.end;
+
+        Ok(CentralDirectoryBytes {
+            offset,
+            len,
+            records,
+            end_record: file_size - end_size..file_size,
+            file: Rc::clone(&self.file),
+            lifetime: PhantomData,
+        })
+    }
+}
+
+impl<'a, R: Read + Seek> CentralDirectoryBytes<'a, R> {
+    /// Returns the span of the whole central directory
+    pub fn range(&self) -> Range<u64> {
+        self.offset..self.offset + self.len
+    }
+
+    /// Returns a reader over the central directory's bytes, exactly as stored,
+    /// sharing the archive's file handle
+    pub fn reader(&self) -> Box<dyn Read + 'a>
+    where
+        R: 'a,
+    {
+        Box::new(Shared::new(&self.file, self.offset).take(self.len))
+    }
+
+    /// Reads the whole central directory into memory, exactly as stored
+    pub fn read(&self) -> Result<Vec<u8>, MuError> {
+        self.read_range(self.range())
+    }
+
+    /// Reads the record at `index`, which is its entry's `central_directory_index()`,
+    /// with its name, extra field and comment
+    pub fn read_record(&self, index: usize) -> Result<Option<Vec<u8>>, MuError> {
+        match self.records.get(index) {
+            Some(range) => self.read_range(range.clone()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn read_range(&self, range: Range<u64>) -> Result<Vec<u8>, MuError> {
+        let mut data = vec![0; (range.end - range.start) as usize];
+        Shared::new(&self.file, range.start).read_exact(&mut data)?;
+        Ok(data)
+    }
+}
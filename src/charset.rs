@@ -0,0 +1,254 @@
+// Decoding for filenames written in a legacy code page, and a guess at which one an
+// archive used. The double byte tables are generated from Python's codecs, each a
+// grid of little endian UTF-16 units (0 where unmapped) indexed by lead and trail
+// byte:
+//
+//     bytes([lead, trail]).decode("cp932" or "gbk")
+
+use std::io::{Read, Seek};
+
+use crate::shared::*;
+use crate::types::*;
+
+static CP932: &[u8] = include_bytes!("charset/cp932.bin");
+static GBK: &[u8] = include_bytes!("charset/gbk.bin");
+
+const CP437: [char; 128] = [
+    '\u{00C7}', '\u{00FC}', '\u{00E9}', '\u{00E2}', '\u{00E4}', '\u{00E0}', '\u{00E5}', '\u{00E7}',
+    '\u{00EA}', '\u{00EB}', '\u{00E8}', '\u{00EF}', '\u{00EE}', '\u{00EC}', '\u{00C4}', '\u{00C5}',
+    '\u{00C9}', '\u{00E6}', '\u{00C6}', '\u{00F4}', '\u{00F6}', '\u{00F2}', '\u{00FB}', '\u{00F9}',
+    '\u{00FF}', '\u{00D6}', '\u{00DC}', '\u{00A2}', '\u{00A3}', '\u{00A5}', '\u{20A7}', '\u{0192}',
+    '\u{00E1}', '\u{00ED}', '\u{00F3}', '\u{00FA}', '\u{00F1}', '\u{00D1}', '\u{00AA}', '\u{00BA}',
+    '\u{00BF}', '\u{2310}', '\u{00AC}', '\u{00BD}', '\u{00BC}', '\u{00A1}', '\u{00AB}', '\u{00BB}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{2561}', '\u{2562}', '\u{2556}',
+    '\u{2555}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255D}', '\u{255C}', '\u{255B}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{255E}', '\u{255F}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256C}', '\u{2567}',
+    '\u{2568}', '\u{2564}', '\u{2565}', '\u{2559}', '\u{2558}', '\u{2552}', '\u{2553}', '\u{256B}',
+    '\u{256A}', '\u{2518}', '\u{250C}', '\u{2588}', '\u{2584}', '\u{258C}', '\u{2590}', '\u{2580}',
+    '\u{03B1}', '\u{00DF}', '\u{0393}', '\u{03C0}', '\u{03A3}', '\u{03C3}', '\u{00B5}', '\u{03C4}',
+    '\u{03A6}', '\u{0398}', '\u{03A9}', '\u{03B4}', '\u{221E}', '\u{03C6}', '\u{03B5}', '\u{2229}',
+    '\u{2261}', '\u{00B1}', '\u{2265}', '\u{2264}', '\u{2320}', '\u{2321}', '\u{00F7}', '\u{2248}',
+    '\u{00B0}', '\u{2219}', '\u{00B7}', '\u{221A}', '\u{207F}', '\u{00B2}', '\u{25A0}', '\u{00A0}',
+];
+
+const KOI8_R: [char; 128] = [
+    '\u{2500}', '\u{2502}', '\u{250C}', '\u{2510}', '\u{2514}', '\u{2518}', '\u{251C}', '\u{2524}',
+    '\u{252C}', '\u{2534}', '\u{253C}', '\u{2580}', '\u{2584}', '\u{2588}', '\u{258C}', '\u{2590}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2320}', '\u{25A0}', '\u{2219}', '\u{221A}', '\u{2248}',
+    '\u{2264}', '\u{2265}', '\u{00A0}', '\u{2321}', '\u{00B0}', '\u{00B2}', '\u{00B7}', '\u{00F7}',
+    '\u{2550}', '\u{2551}', '\u{2552}', '\u{0451}', '\u{2553}', '\u{2554}', '\u{2555}', '\u{2556}',
+    '\u{2557}', '\u{2558}', '\u{2559}', '\u{255A}', '\u{255B}', '\u{255C}', '\u{255D}', '\u{255E}',
+    '\u{255F}', '\u{2560}', '\u{2561}', '\u{0401}', '\u{2562}', '\u{2563}', '\u{2564}', '\u{2565}',
+    '\u{2566}', '\u{2567}', '\u{2568}', '\u{2569}', '\u{256A}', '\u{256B}', '\u{256C}', '\u{00A9}',
+    '\u{044E}', '\u{0430}', '\u{0431}', '\u{0446}', '\u{0434}', '\u{0435}', '\u{0444}', '\u{0433}',
+    '\u{0445}', '\u{0438}', '\u{0439}', '\u{043A}', '\u{043B}', '\u{043C}', '\u{043D}', '\u{043E}',
+    '\u{043F}', '\u{044F}', '\u{0440}', '\u{0441}', '\u{0442}', '\u{0443}', '\u{0436}', '\u{0432}',
+    '\u{044C}', '\u{044B}', '\u{0437}', '\u{0448}', '\u{044D}', '\u{0449}', '\u{0447}', '\u{044A}',
+    '\u{042E}', '\u{0410}', '\u{0411}', '\u{0426}', '\u{0414}', '\u{0415}', '\u{0424}', '\u{0413}',
+    '\u{0425}', '\u{0418}', '\u{0419}', '\u{041A}', '\u{041B}', '\u{041C}', '\u{041D}', '\u{041E}',
+    '\u{041F}', '\u{042F}', '\u{0420}', '\u{0421}', '\u{0422}', '\u{0423}', '\u{0416}', '\u{0412}',
+    '\u{042C}', '\u{042B}', '\u{0417}', '\u{0428}', '\u{042D}', '\u{0429}', '\u{0427}', '\u{042A}',
+];
+
+// with fewer than this share of plausible characters, a guess is no better than CP437
+const CONFIDENCE: f64 = 0.6;
+
+/// A legacy, non-Unicode encoding for filenames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Charset {
+    /// IBM PC code page 437, which the zip spec names as the default
+    Cp437,
+    /// Shift-JIS, as Windows' code page 932, for Japanese
+    ShiftJis,
+    /// GBK, as Windows' code page 936, for Simplified Chinese
+    Gbk,
+    /// KOI8-R, for Russian
+    Koi8R,
+}
+
+impl Charset {
+    /// Decodes `bytes`, or returns `None` if they aren't valid in this charset.
+    pub fn decode(self, bytes: &[u8]) -> Option<String> {
+        let mut out = String::with_capacity(bytes.len());
+        for (c, _) in self.chars(bytes)? {
+            out.push(c);
+        }
+        Some(out)
+    }
+
+    /// Guesses the charset of names that aren't UTF-8, by how many of their non-ASCII
+    /// characters are common ones in each candidate. Falls back to CP437 when no
+    /// candidate is convincing.
+    pub fn detect<'n>(names: impl IntoIterator<Item = &'n [u8]>) -> Charset {
+        let names: Vec<&[u8]> = names.into_iter().collect();
+
+        let mut best = (Charset::Cp437, CONFIDENCE);
+        // on a tie, the earlier candidate wins
+        'candidates: for charset in [Charset::ShiftJis, Charset::Koi8R, Charset::Gbk] {
+            let mut common = 0;
+            let mut total = 0;
+            for name in &names {
+                let Some(chars) = charset.chars(name) else {
+                    continue 'candidates;
+                };
+                let (c, t) = charset.score(&chars);
+                common += c;
+                total += t;
+            }
+
+            let score = common as f64 / total.max(1) as f64;
+            if score > best.1 {
+                best = (charset, score);
+            }
+        }
+
+        best.0
+    }
+
+    // How many of the non-ASCII characters are typical of text in this charset's
+    // language, rather than what turns up when another encoding is misread as this
+    // one, and how many there are.
+    fn score(self, chars: &[(char, u16)]) -> (usize, usize) {
+        let total = chars.iter().filter(|&&(_, code)| code >= 0x80).count();
+
+        let common = match self {
+            // Russian case and vowels follow patterns that misread bytes don't, so
+            // letters only count in words that look like words
+            Charset::Koi8R => {
+                let letter = |code: u16| matches!(code, 0xA3 | 0xB3 | 0xC0..=0xFF);
+                let lower = |code: u16| matches!(code, 0xA3 | 0xC0..=0xDF);
+                chars
+                    .split(|&(_, code)| !letter(code))
+                    .filter(|word| {
+                        let rest_lower = word.iter().skip(1).all(|&(_, c)| lower(c));
+                        let all_upper = word.iter().all(|&(_, c)| !lower(c));
+                        let vowel = word
+                            .iter()
+                            .any(|&(ch, _)| "аеёиоуыэюяАЕЁИОУЫЭЮЯ".contains(ch));
+                        (rest_lower || all_upper) && (word.len() < 3 || vowel)
+                    })
+                    .map(|word| word.len())
+                    .sum()
+            }
+            _ => chars
+                .iter()
+                .filter(|&&(_, code)| code >= 0x80 && self.is_common(code))
+                .count(),
+        };
+
+        (common, total)
+    }
+
+    // Each character, with the one or two bytes it was encoded as.
+    fn chars(self, bytes: &[u8]) -> Option<Vec<(char, u16)>> {
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let b = bytes[i];
+            i += 1;
+            if b < 0x80 {
+                out.push((b as char, b as u16));
+                continue;
+            }
+
+            let double = |i: &mut usize, range: std::ops::RangeInclusive<u8>| {
+                let trail = *bytes.get(*i).filter(|t| range.contains(t))?;
+                *i += 1;
+                Some(trail)
+            };
+
+            let (c, code) = match self {
+                Charset::Cp437 => (CP437[b as usize - 0x80], b as u16),
+                Charset::Koi8R => (KOI8_R[b as usize - 0x80], b as u16),
+                Charset::ShiftJis if (0xA1..=0xDF).contains(&b) => {
+                    // half width katakana
+                    (char::from_u32(0xFF61 + (b - 0xA1) as u32)?, b as u16)
+                }
+                Charset::ShiftJis => {
+                    let lead = match b {
+                        0x81..=0x9F => b - 0x81,
+                        0xE0..=0xFC => b - 0xE0 + 31,
+                        _ => return None,
+                    };
+                    let trail = double(&mut i, 0x40..=0xFC)?;
+                    let c = unit(CP932, lead as usize * 189 + (trail - 0x40) as usize)?;
+                    (c, u16::from_be_bytes([b, trail]))
+                }
+                Charset::Gbk => {
+                    if !(0x81..=0xFE).contains(&b) {
+                        return None;
+                    }
+                    let trail = double(&mut i, 0x40..=0xFE)?;
+                    let c = unit(GBK, (b - 0x81) as usize * 191 + (trail - 0x40) as usize)?;
+                    (c, u16::from_be_bytes([b, trail]))
+                }
+            };
+            out.push((c, code));
+        }
+        Some(out)
+    }
+
+    // Whether an encoded character is one of the common ones of a double byte charset.
+    fn is_common(self, code: u16) -> bool {
+        let [lead, trail] = code.to_be_bytes();
+        match self {
+            // punctuation, hiragana, katakana, and the JIS level 1 kanji
+            Charset::ShiftJis => matches!(
+                code,
+                0x8140..=0x81AC | 0x829F..=0x82F1 | 0x8340..=0x8396 | 0x889F..=0x9872
+            ),
+            // punctuation, and the GB 2312 level 1 hanzi
+            Charset::Gbk => matches!(lead, 0xA1 | 0xB0..=0xD7) && trail >= 0xA1,
+            _ => false,
+        }
+    }
+}
+
+fn unit(table: &[u8], index: usize) -> Option<char> {
+    let bytes = table.get(index * 2..index * 2 + 2)?;
+    match u16::from_le_bytes([bytes[0], bytes[1]]) {
+        0 => None,
+        u => char::from_u32(u as u32),
+    }
+}
+
+// Decode a name that isn't UTF-8 with `charset`, or fail the way UTF-8 would have.
+pub fn decode_name(bytes: &[u8], charset: Charset) -> Result<String, MuError> {
+    charset.decode(bytes).ok_or_else(|| {
+        MuError::new(
+            ErrorKind::Utf8,
+            format!("filename isn't valid UTF-8 or {charset:?}"),
+        )
+    })
+}
+
+// Guess a charset from the names in the central directory that aren't UTF-8, if
+// there are any.
+pub fn detect_archive(
+    file: &mut (impl Read + Seek),
+    end_rec: &DirectoryEnd,
+    cd_offset: u64,
+) -> Result<Option<Charset>, MuError> {
+    let mut names = Vec::new();
+
+    let mut next = cd_offset;
+    for _ in 0..end_rec.num_entries {
+        let (gfh, after) = next_global_header(file, next)?;
+
+        // next_global_header() leaves the file at the name
+        let mut name = vec![0; gfh.file_name_length as usize];
+        file.read_exact(&mut name)?;
+        if gfh.general_purpose_bit_flag & FLAG_UTF8 == 0 && std::str::from_utf8(&name).is_err() {
+            names.push(name);
+        }
+
+        next = after;
+    }
+
+    if names.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Charset::detect(names.iter().map(|n| n.as_slice()))))
+}
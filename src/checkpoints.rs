@@ -0,0 +1,568 @@
+// A DEFLATE decoder that can stop between blocks and pick up again later from a bit
+// offset and the window before it, the way zlib's zran example does. `inflate` keeps
+// its state to itself, so random access into deflated entries needs its own.
+
+use std::io::{BufReader, Read, Seek, Write};
+
+use crate::iterable::{Entry, Shared};
+use crate::shared::*;
+use crate::types::*;
+
+// the furthest back a match can reach
+const WINDOW: usize = 32768;
+
+// "MUCP", then a format version
+const INDEX_MAGIC: [u8; 4] = *b"MUCP";
+const INDEX_VERSION: u8 = 1;
+
+// lengths and distances: base values, and how many extra bits follow
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// the order code length code lengths are sent in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Checkpoints into a deflated entry, made by `Entry::inflate_index()`, that let
+/// `Entry::indexed_range_reader()` start inflating close to where a range begins
+/// rather than at the start of the entry.
+///
+/// Each checkpoint keeps the 32 KiB of output before it, so an index costs about
+/// that much per `span` of the entry. Save one with `write_to()`, and load it with
+/// `read_from()`, or with the `serde` feature, in any format serde supports.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InflateIndex {
+    span: u64,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    crc32: u32,
+    points: Vec<Checkpoint>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Checkpoint {
+    // where the block starts, in the output, and in bits into the compressed data
+    output: u64,
+    input_bits: u64,
+    window: Vec<u8>,
+}
+
+impl InflateIndex {
+    /// Returns how many checkpoints the index holds
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns true if the index holds no checkpoints, as for an entry shorter than
+    /// its span
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Returns the least output between checkpoints the index was made with
+    pub fn span(&self) -> u64 {
+        self.span
+    }
+
+    /// Writes the index out in a compact binary form, which `read_from()` reads back.
+    pub fn write_to(&self, mut out: impl Write) -> Result<(), MuError> {
+        let count = u32::try_from(self.points.len()).map_err(|_| {
+            MuError::new(
+                ErrorKind::Other,
+                "too many checkpoints for an inflate index",
+            )
+        })?;
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&INDEX_MAGIC);
+        buf.push(INDEX_VERSION);
+        buf.extend_from_slice(&self.span.to_le_bytes());
+        buf.extend_from_slice(&self.compressed_size.to_le_bytes());
+        buf.extend_from_slice(&self.uncompressed_size.to_le_bytes());
+        buf.extend_from_slice(&self.crc32.to_le_bytes());
+        buf.extend_from_slice(&count.to_le_bytes());
+
+        for point in &self.points {
+            buf.extend_from_slice(&point.output.to_le_bytes());
+            buf.extend_from_slice(&point.input_bits.to_le_bytes());
+            buf.extend_from_slice(&(point.window.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&point.window);
+        }
+
+        out.write_all(&buf)?;
+        Ok(())
+    }
+
+    /// Reads an index written by `write_to()`.
+    pub fn read_from(mut input: impl Read) -> Result<Self, MuError> {
+        let mut buf = Vec::new();
+        input.read_to_end(&mut buf)?;
+
+        if buf.get(..4) != Some(&INDEX_MAGIC[..]) || buf.get(4) != Some(&INDEX_VERSION) {
+            return Err(MuError::new(ErrorKind::Format, "not an inflate index"));
+        }
+
+        let mut rest = &buf[5..];
+        let span = u64::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?);
+        let compressed_size = u64::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?);
+        let uncompressed_size = u64::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?);
+        let crc32 = u32::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?);
+        let count = u32::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?);
+
+        let mut points = Vec::new();
+        for _ in 0..count {
+            let output = u64::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?);
+            let input_bits = u64::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?);
+            let window_len =
+                u32::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?) as usize;
+            if window_len > WINDOW || rest.len() < window_len {
+                return Err(truncated());
+            }
+            let (window, after) = rest.split_at(window_len);
+            rest = after;
+
+            points.push(Checkpoint {
+                output,
+                input_bits,
+                window: window.to_vec(),
+            });
+        }
+
+        Ok(Self {
+            span,
+            compressed_size,
+            uncompressed_size,
+            crc32,
+            points,
+        })
+    }
+}
+
+impl<'a, R: Read + Seek> Entry<'a, R> {
+    /// Inflates the whole entry once, recording a checkpoint at the first block
+    /// boundary after every `span` bytes of output, for `indexed_range_reader()`.
+    /// A smaller span makes ranges quicker to reach, and the index larger.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # use std::io::Read;
+    /// # let mut file = std::fs::File::open("logs.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    /// let log = zi.entries().find(|e| e.filename() == "server.log").unwrap();
+    ///
+    /// let index = log.inflate_index(1 << 20).unwrap();
+    /// let mut tail = String::new();
+    /// log.indexed_range_reader(&index, 900_000_000..u64::MAX)
+    ///     .unwrap()
+    ///     .read_to_string(&mut tail)
+    ///     .unwrap();
+    /// ```
+    pub fn inflate_index(&self, span: u64) -> Result<InflateIndex, MuError> {
+        let mut index = InflateIndex {
+            span,
+            compressed_size: self.header.compressed_size,
+            uncompressed_size: self.header.uncompressed_size,
+            crc32: self.header.crc32,
+            points: Vec::new(),
+        };
+        if self.header.compression_method != 8 {
+            return Ok(index);
+        }
+        check_flags(&self.header)?;
+
+        let mut inflater = Inflater::new(self.compressed(0), 0, Vec::new());
+        let mut last = 0;
+        let mut buf = vec![0; WINDOW];
+        loop {
+            if inflater.between_blocks() && inflater.output - last >= span.max(1) {
+                last = inflater.output;
+                index.points.push(inflater.checkpoint());
+            }
+            if read_retrying(&mut inflater, &mut buf)? == 0 {
+                break;
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Returns a reader over bytes `range` of the decompressed contents, like
+    /// `range_reader()`, but for a deflated entry starts inflating from the last of
+    /// `index`'s checkpoints before the range, rather than from the start. `index`
+    /// must have been made from this entry, which is checked by its sizes and CRC.
+    pub fn indexed_range_reader(
+        &self,
+        index: &InflateIndex,
+        range: std::ops::Range<u64>,
+    ) -> Result<Box<dyn Read + 'a>, MuError>
+    where
+        R: 'a,
+    {
+        if self.header.compression_method != 8 {
+            return self.range_reader(range);
+        }
+        if (index.compressed_size, index.uncompressed_size, index.crc32)
+            != (
+                { self.header.compressed_size },
+                { self.header.uncompressed_size },
+                self.header.crc32,
+            )
+        {
+            return Err(MuError::new(
+                ErrorKind::Other,
+                format!("index wasn't made from '{}'", self.filename),
+            ));
+        }
+        check_flags(&self.header)?;
+
+        let size = self.header.uncompressed_size;
+        if range.start > size {
+            return Err(MuError::new(
+                ErrorKind::Other,
+                format!("range starts past the end of '{}'", self.filename),
+            ));
+        }
+        let len = range.end.min(size).saturating_sub(range.start);
+
+        let mut inflater = match index.points.iter().rfind(|p| p.output <= range.start) {
+            Some(point) => {
+                let mut inflater = Inflater::new(
+                    self.compressed(point.input_bits / 8),
+                    point.input_bits / 8,
+                    point.window.clone(),
+                );
+                inflater.bits.bits((point.input_bits % 8) as u32)?;
+                inflater.output = point.output;
+                inflater
+            }
+            None => Inflater::new(self.compressed(0), 0, Vec::new()),
+        };
+
+        let skip = range.start - inflater.output;
+        let skipped = std::io::copy(&mut (&mut inflater).take(skip), &mut std::io::sink())?;
+        if skipped < skip {
+            return Err(MuError::new(
+                ErrorKind::Format,
+                format!("'{}' is shorter than its recorded size", self.filename),
+            ));
+        }
+
+        Ok(Box::new(inflater.take(len)))
+    }
+
+    // A buffered reader over the compressed data, `from` bytes in.
+    fn compressed(&self, from: u64) -> BufReader<std::io::Take<Shared<R>>> {
+        let start = self.header.offset + from;
+        let rest = self.header.compressed_size.saturating_sub(from);
+        BufReader::new(Shared::new(&self.file, start).take(rest))
+    }
+}
+
+// Reads the compressed stream a bit at a time, least significant first.
+struct Bits<R> {
+    input: R,
+    buf: u64,
+    count: u32,
+    // bytes taken from `input`, counted from the start of the stream
+    consumed: u64,
+}
+
+impl<R: Read> Bits<R> {
+    fn bits(&mut self, n: u32) -> std::io::Result<u32> {
+        while self.count < n {
+            let mut byte = [0];
+            if read_retrying(&mut self.input, &mut byte)? == 0 {
+                return Err(corrupt("compressed data ends early"));
+            }
+            self.buf |= (byte[0] as u64) << self.count;
+            self.count += 8;
+            self.consumed += 1;
+        }
+
+        let value = (self.buf & ((1u64 << n) - 1)) as u32;
+        self.buf >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    // Drop what's left of the current byte, as stored blocks start on a byte.
+    fn align(&mut self) {
+        let partial = self.count % 8;
+        self.buf >>= partial;
+        self.count -= partial;
+    }
+
+    fn position(&self) -> u64 {
+        self.consumed * 8 - self.count as u64
+    }
+}
+
+// A canonical Huffman code, decoded a bit at a time as in zlib's puff.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> std::io::Result<Huffman> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+
+        // more codes of a length than there's room for can't be decoded
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(corrupt("over-subscribed Huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        counts[0] = 0;
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode<R: Read>(&self, bits: &mut Bits<R>) -> std::io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.bits(1)? as i32;
+            let count = count as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(corrupt("invalid Huffman code"))
+    }
+}
+
+enum State {
+    // at a block header, or past the final block
+    Header,
+    Stored(u16),
+    Codes(Huffman, Huffman),
+    Done,
+}
+
+// Inflates a stream, keeping the window needed to resume it.
+pub(crate) struct Inflater<R> {
+    bits: Bits<R>,
+    state: State,
+    last: bool,
+    history: Vec<u8>,
+    // a match still to be copied, as distance and length
+    pending: (usize, usize),
+    output: u64,
+}
+
+impl<R: Read> Inflater<R> {
+    // Start at byte `consumed` of the stream, with `window` as what came before.
+    pub(crate) fn new(input: R, consumed: u64, window: Vec<u8>) -> Self {
+        Inflater {
+            bits: Bits {
+                input,
+                buf: 0,
+                count: 0,
+                consumed,
+            },
+            state: State::Header,
+            last: false,
+            history: window,
+            pending: (0, 0),
+            output: 0,
+        }
+    }
+
+    fn between_blocks(&self) -> bool {
+        matches!(self.state, State::Header) && !self.last
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        let keep = self.history.len().min(WINDOW);
+        Checkpoint {
+            output: self.output,
+            input_bits: self.bits.position(),
+            window: self.history[self.history.len() - keep..].to_vec(),
+        }
+    }
+
+    fn push(&mut self, byte: u8, buf: &mut [u8], n: &mut usize) {
+        self.history.push(byte);
+        buf[*n] = byte;
+        *n += 1;
+    }
+
+    fn read_header(&mut self) -> std::io::Result<()> {
+        self.last = self.bits.bits(1)? == 1;
+        self.state = match self.bits.bits(2)? {
+            0 => {
+                self.bits.align();
+                let len = self.bits.bits(16)? as u16;
+                let nlen = self.bits.bits(16)? as u16;
+                if len != !nlen {
+                    return Err(corrupt("stored block length doesn't match its complement"));
+                }
+                State::Stored(len)
+            }
+            1 => {
+                let mut lengths = [8u8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                State::Codes(Huffman::new(&lengths)?, Huffman::new(&[5; 30])?)
+            }
+            2 => self.read_dynamic()?,
+            _ => return Err(corrupt("invalid block type")),
+        };
+        Ok(())
+    }
+
+    fn read_dynamic(&mut self) -> std::io::Result<State> {
+        let nlen = self.bits.bits(5)? as usize + 257;
+        let ndist = self.bits.bits(5)? as usize + 1;
+        let ncode = self.bits.bits(4)? as usize + 4;
+        if nlen > 286 || ndist > 30 {
+            return Err(corrupt("too many length or distance codes"));
+        }
+
+        let mut code_lengths = [0u8; 19];
+        for &i in &CODE_LENGTH_ORDER[..ncode] {
+            code_lengths[i] = self.bits.bits(3)? as u8;
+        }
+        let code_code = Huffman::new(&code_lengths)?;
+
+        let mut lengths = vec![0u8; nlen + ndist];
+        let mut i = 0;
+        while i < lengths.len() {
+            let symbol = code_code.decode(&mut self.bits)?;
+            let (value, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 if i > 0 => (lengths[i - 1], 3 + self.bits.bits(2)? as usize),
+                17 => (0, 3 + self.bits.bits(3)? as usize),
+                18 => (0, 11 + self.bits.bits(7)? as usize),
+                _ => return Err(corrupt("invalid code length repeat")),
+            };
+            if i + repeat > lengths.len() {
+                return Err(corrupt("code lengths overrun"));
+            }
+            lengths[i..i + repeat].fill(value);
+            i += repeat;
+        }
+        if lengths[256] == 0 {
+            return Err(corrupt("no end of block code"));
+        }
+
+        let (lit, dist) = lengths.split_at(nlen);
+        Ok(State::Codes(Huffman::new(lit)?, Huffman::new(dist)?))
+    }
+}
+
+impl<R: Read> Read for Inflater<R> {
+    // Returns early at the end of each block, so checkpoints can be taken there.
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut n = 0;
+
+        while n < buf.len() {
+            let (distance, length) = self.pending;
+            if length > 0 {
+                let count = length.min(buf.len() - n);
+                for _ in 0..count {
+                    let byte = self.history[self.history.len() - distance];
+                    self.push(byte, buf, &mut n);
+                }
+                self.pending.1 -= count;
+                continue;
+            }
+
+            match &mut self.state {
+                State::Done => break,
+                State::Header if self.last => {
+                    self.state = State::Done;
+                }
+                State::Header => {
+                    if n > 0 {
+                        break;
+                    }
+                    self.read_header()?;
+                }
+                State::Stored(0) => self.state = State::Header,
+                State::Stored(remaining) => {
+                    *remaining -= 1;
+                    let byte = self.bits.bits(8)? as u8;
+                    self.push(byte, buf, &mut n);
+                }
+                State::Codes(lit, dist) => {
+                    let symbol = lit.decode(&mut self.bits)? as usize;
+                    if symbol < 256 {
+                        self.push(symbol as u8, buf, &mut n);
+                    } else if symbol == 256 {
+                        self.state = State::Header;
+                    } else {
+                        let symbol = symbol - 257;
+                        if symbol >= 29 {
+                            return Err(corrupt("invalid length code"));
+                        }
+                        let length = LENGTH_BASE[symbol] as usize
+                            + self.bits.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+
+                        let symbol = dist.decode(&mut self.bits)? as usize;
+                        if symbol >= 30 {
+                            return Err(corrupt("invalid distance code"));
+                        }
+                        let distance = DIST_BASE[symbol] as usize
+                            + self.bits.bits(DIST_EXTRA[symbol] as u32)? as usize;
+                        if distance > self.history.len() {
+                            return Err(corrupt("distance reaches before the start"));
+                        }
+                        self.pending = (distance, length);
+                    }
+                }
+            }
+        }
+
+        // only the window's worth is ever looked back on
+        if self.history.len() > 4 * WINDOW {
+            self.history.drain(..self.history.len() - WINDOW);
+        }
+        self.output += n as u64;
+        Ok(n)
+    }
+}
+
+fn corrupt(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+fn truncated() -> MuError {
+    MuError::new(ErrorKind::Format, "inflate index truncated")
+}
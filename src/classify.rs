@@ -0,0 +1,203 @@
+// Sorting entries into broad kinds of file, by extension and by the magic bytes
+// their contents start with.
+
+use std::io::{Read, Seek};
+
+use crate::iterable::Entry;
+use crate::shared::*;
+
+// enough of the start of a file for every signature below, tar's being furthest in
+const SNIFF_LEN: usize = 512;
+
+/// A broad kind of file, for `ExtractOptions::only()`. A file's class goes by its
+/// extension, or when that's not one any class knows, by whether its contents start
+/// the way the class's formats do, so a PNG named `thumbnail` is still an image.
+///
+/// # Examples
+///
+/// ```
+/// use munzip::FileClass;
+///
+/// assert!(FileClass::Images.matches("photos/cat.JPG", &[]));
+/// assert!(FileClass::Images.matches("thumbnail", b"\x89PNG\r\n\x1a\n"));
+/// assert!(FileClass::Text.matches("README", b"# munzip\n"));
+/// assert!(!FileClass::Text.matches("cat.png", b"text, oddly"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FileClass {
+    /// PNG, JPEG, GIF, BMP, WebP, TIFF, ICO, SVG, HEIC and AVIF
+    Images,
+    /// MP3, WAV, FLAC, Ogg, Opus, AAC, M4A, AIFF and MIDI
+    Audio,
+    /// MP4, MOV, AVI, Matroska, WebM, FLV and MPEG
+    Video,
+    /// Plain text and source code: by extension, or contents that are UTF-8 with no
+    /// control characters beyond whitespace
+    Text,
+    /// PDF, RTF, and Office and OpenDocument files
+    Documents,
+    /// zip, gzip, bzip2, xz, zstd, 7z, RAR and tar
+    Archives,
+    /// Windows, ELF and Mach-O binaries and libraries, and scripts starting `#!`
+    Executables,
+}
+
+impl FileClass {
+    const ALL: [FileClass; 7] = [
+        FileClass::Images,
+        FileClass::Audio,
+        FileClass::Video,
+        FileClass::Text,
+        FileClass::Documents,
+        FileClass::Archives,
+        FileClass::Executables,
+    ];
+
+    /// Returns true if a file named `name`, whose contents start with `head`, is of
+    /// this class. A known extension settles it, so a `.png` holding only text is
+    /// still just an image; otherwise `head` is sniffed. `head` can be empty to go
+    /// by the name alone, and needs no more than the first 512 bytes.
+    pub fn matches(&self, name: &str, head: &[u8]) -> bool {
+        match known_extension(name) {
+            Some(ext) => self.extensions().contains(&ext.as_str()),
+            None => !head.is_empty() && self.sniff(head),
+        }
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            FileClass::Images => &[
+                "png", "jpg", "jpeg", "gif", "bmp", "webp", "tif", "tiff", "ico", "svg", "heic",
+                "heif", "avif",
+            ],
+            FileClass::Audio => &[
+                "mp3", "wav", "flac", "ogg", "oga", "opus", "aac", "m4a", "aif", "aiff", "wma",
+                "mid", "midi",
+            ],
+            FileClass::Video => &[
+                "mp4", "m4v", "mov", "avi", "mkv", "webm", "wmv", "flv", "mpg", "mpeg", "3gp",
+            ],
+            FileClass::Text => &[
+                "txt", "md", "csv", "tsv", "json", "xml", "html", "htm", "css", "js", "ts", "toml",
+                "yaml", "yml", "ini", "cfg", "conf", "log", "rs", "c", "h", "cpp", "hpp", "py",
+                "rb", "go", "java", "sh",
+            ],
+            FileClass::Documents => &[
+                "pdf", "rtf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp",
+                "epub",
+            ],
+            FileClass::Archives => &[
+                "zip", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "tar", "jar",
+            ],
+            FileClass::Executables => &["exe", "dll", "sys", "scr", "com", "msi", "so", "dylib"],
+        }
+    }
+
+    fn sniff(&self, head: &[u8]) -> bool {
+        let at =
+            |offset: usize, magic: &[u8]| head.get(offset..offset + magic.len()) == Some(magic);
+        // ISO base media files (MP4, MOV, HEIC...) say which they are in their first box
+        let brand = head.get(8..12).filter(|_| at(4, b"ftyp"));
+
+        match self {
+            FileClass::Images => {
+                at(0, b"\x89PNG\r\n\x1a\n")
+                    || at(0, b"\xff\xd8\xff")
+                    || at(0, b"GIF87a")
+                    || at(0, b"GIF89a")
+                    || at(0, b"BM")
+                    || (at(0, b"RIFF") && at(8, b"WEBP"))
+                    || at(0, b"II*\0")
+                    || at(0, b"MM\0*")
+                    || at(0, b"\0\0\x01\0")
+                    || brand.is_some_and(|b| matches!(b, b"avif" | b"heic" | b"heix" | b"mif1"))
+            }
+            FileClass::Audio => {
+                at(0, b"ID3")
+                    || at(0, b"\xff\xfb")
+                    || at(0, b"\xff\xf3")
+                    || at(0, b"\xff\xf2")
+                    || (at(0, b"RIFF") && at(8, b"WAVE"))
+                    || at(0, b"fLaC")
+                    || at(0, b"OggS")
+                    || (at(0, b"FORM") && at(8, b"AIFF"))
+                    || at(0, b"MThd")
+                    || brand.is_some_and(|b| b == b"M4A ")
+            }
+            FileClass::Video => {
+                (at(0, b"RIFF") && at(8, b"AVI "))
+                    || at(0, b"\x1a\x45\xdf\xa3")
+                    || at(0, b"FLV")
+                    || at(0, b"\0\0\x01\xba")
+                    || brand.is_some_and(|b| {
+                        !matches!(b, b"avif" | b"heic" | b"heix" | b"mif1" | b"M4A ")
+                    })
+            }
+            FileClass::Text => is_text(head),
+            FileClass::Documents => {
+                at(0, b"%PDF") || at(0, b"{\\rtf") || at(0, b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1")
+            }
+            FileClass::Archives => {
+                at(0, b"PK\x03\x04")
+                    || at(0, b"PK\x05\x06")
+                    || at(0, b"\x1f\x8b")
+                    || at(0, b"BZh")
+                    || at(0, b"\xfd7zXZ\0")
+                    || at(0, b"\x28\xb5\x2f\xfd")
+                    || at(0, b"7z\xbc\xaf\x27\x1c")
+                    || at(0, b"Rar!\x1a\x07")
+                    || at(257, b"ustar")
+            }
+            FileClass::Executables => {
+                at(0, b"MZ")
+                    || at(0, b"\x7fELF")
+                    || at(0, b"\xfe\xed\xfa\xce")
+                    || at(0, b"\xfe\xed\xfa\xcf")
+                    || at(0, b"\xce\xfa\xed\xfe")
+                    || at(0, b"\xcf\xfa\xed\xfe")
+                    || at(0, b"#!")
+            }
+        }
+    }
+}
+
+// The lowercased extension of `name`, if it's one some class claims.
+pub(crate) fn known_extension(name: &str) -> Option<String> {
+    name.rsplit('/')
+        .next()
+        .and_then(|file| file.rsplit_once('.'))
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .filter(|ext| {
+            FileClass::ALL
+                .iter()
+                .any(|class| class.extensions().contains(&ext.as_str()))
+        })
+}
+
+// UTF-8, allowing for a character cut off at the end of `head`, with no control
+// characters other than whitespace and escape.
+fn is_text(head: &[u8]) -> bool {
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => match std::str::from_utf8(&head[..e.valid_up_to()]) {
+            Ok(text) => text,
+            Err(_) => return false,
+        },
+        Err(_) => return false,
+    };
+
+    text.chars()
+        .all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r' | '\x0c' | '\x1b'))
+}
+
+// The first `SNIFF_LEN` bytes of an entry's contents, or as much as there is. An
+// entry that can't be read just has nothing to sniff.
+pub(crate) fn head<R: Read + Seek>(entry: &Entry<'_, R>) -> Vec<u8> {
+    let mut file = entry.file.borrow_mut();
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    if let Ok(data) = data_reader(&mut *file, &entry.header) {
+        let _ = data.take(SNIFF_LEN as u64).read_to_end(&mut head);
+    }
+    head
+}
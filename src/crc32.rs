@@ -0,0 +1,238 @@
+// CRC-32 as used by zip (the IEEE 802.3 polynomial, reflected).
+
+use std::cell::RefCell;
+use std::io::Read;
+
+use crate::options::CrcPolicy;
+use crate::types::*;
+
+const POLYNOMIAL: u32 = 0xEDB88320;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+// One step of the CRC-32 on its own, without the inversion before and after, as
+// traditional PKWARE encryption's key schedule uses it.
+#[cfg(feature = "crypto")]
+pub fn crc32_byte(crc: u32, b: u8) -> u32 {
+    TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8)
+}
+
+/// A running CRC-32, as zip stores for each entry's data, for checking data or
+/// filling in headers and extra fields munzip doesn't write itself. It's the same
+/// implementation munzip checks entries with, so the two can't disagree.
+///
+/// # Examples
+///
+/// ```
+/// let mut crc = munzip::Crc32::new();
+/// crc.update(b"hello ");
+/// crc.update(b"world");
+/// assert_eq!(crc.finish(), munzip::Crc32::checksum(b"hello world"));
+/// assert_eq!(crc.finish(), 0x0d4a1185);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    /// Creates a `Crc32` over no data yet.
+    pub fn new() -> Self {
+        Crc32 { state: 0xFFFFFFFF }
+    }
+
+    /// Returns the CRC-32 of `data`, all at once
+    pub fn checksum(data: &[u8]) -> u32 {
+        let mut crc = Crc32::new();
+        crc.update(data);
+        crc.finish()
+    }
+
+    /// Feeds `data` into the CRC-32
+    pub fn update(&mut self, data: &[u8]) {
+        let mut crc = self.state;
+        for &b in data {
+            crc = TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+        }
+        self.state = crc;
+    }
+
+    /// Returns the CRC-32 of everything fed in so far. More can still be fed in.
+    pub fn finish(&self) -> u32 {
+        !self.state
+    }
+}
+
+/// Wraps a reader, computing the CRC-32 of everything read through it, as zip
+/// stores it for each entry. Handy for checking data read some way other than
+/// `Entry::buffer()`, such as straight from `Entry::compressed_range()` of a stored
+/// entry.
+///
+/// With `expect()`, the reader checks the CRC itself, and reports a mismatch as an
+/// `InvalidData` error from the read that reaches the end.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # use std::io::Read;
+/// # let mut file = std::fs::File::open("archive.zip").unwrap();
+/// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+/// let entry = zi.entries().next().unwrap();
+///
+/// let reader = entry.range_reader(0..u64::MAX).unwrap();
+/// let mut reader = munzip::CrcReader::new(reader);
+/// std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+/// println!("{:08x}", reader.crc32());
+/// ```
+pub struct CrcReader<R> {
+    inner: R,
+    crc: Crc32,
+    bytes_read: u64,
+    expected: Option<u32>,
+}
+
+impl<R: Read> CrcReader<R> {
+    /// Creates a `CrcReader` over `inner`.
+    pub fn new(inner: R) -> Self {
+        CrcReader {
+            inner,
+            crc: Crc32::new(),
+            bytes_read: 0,
+            expected: None,
+        }
+    }
+
+    /// Sets the CRC-32 the data must have once `inner` is exhausted.
+    pub fn expect(mut self, crc32: u32) -> Self {
+        self.expected = Some(crc32);
+        self
+    }
+
+    /// Returns the CRC-32 of what's been read so far
+    pub fn crc32(&self) -> u32 {
+        self.crc.finish()
+    }
+
+    /// Returns how many bytes have been read so far
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Returns the wrapped reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for CrcReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.crc.update(&buf[..n]);
+        self.bytes_read += n as u64;
+
+        match self.expected {
+            Some(expected) if n == 0 && !buf.is_empty() && self.crc32() != expected => {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("CRC-32 is {:08x}, expected {expected:08x}", self.crc32()),
+                ))
+            }
+            _ => Ok(n),
+        }
+    }
+}
+
+// The `CrcPolicy` an archive was opened with, and the entries it's found with a wrong
+// CRC-32 so far, shared by the archive and every entry from it.
+#[derive(Debug, Default)]
+pub struct CrcCheck {
+    policy: CrcPolicy,
+    mismatches: RefCell<Vec<String>>,
+}
+
+impl CrcCheck {
+    pub fn new(policy: CrcPolicy) -> Self {
+        CrcCheck {
+            policy,
+            mismatches: RefCell::new(Vec::new()),
+        }
+    }
+
+    // Whether there's any point computing a CRC for `check()`.
+    pub fn enabled(&self) -> bool {
+        self.policy != CrcPolicy::Trust
+    }
+
+    // Compare the CRC-32 of the entry `name`'s data with what `header` records.
+    pub fn check(&self, name: &str, header: &InternalHeader, actual: u32) -> Result<(), MuError> {
+        if actual == header.crc32 {
+            return Ok(());
+        }
+
+        match self.policy {
+            CrcPolicy::Trust => Ok(()),
+            CrcPolicy::Verify => Err(MuError::new(
+                ErrorKind::CrcMismatch {
+                    expected: header.crc32,
+                    actual,
+                },
+                format!(
+                    "'{name}' has CRC-32 {actual:08x}, expected {:08x}",
+                    header.crc32
+                ),
+            )),
+            CrcPolicy::Record | CrcPolicy::Fix => {
+                if !self.has_mismatch(name) {
+                    self.mismatches.borrow_mut().push(name.to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // `check()` for data read in full.
+    pub fn check_data(
+        &self,
+        name: &str,
+        header: &InternalHeader,
+        data: &[u8],
+    ) -> Result<(), MuError> {
+        if !self.enabled() {
+            return Ok(());
+        }
+
+        self.check(name, header, Crc32::checksum(data))
+    }
+
+    pub fn mismatches(&self) -> Vec<String> {
+        self.mismatches.borrow().clone()
+    }
+
+    pub fn has_mismatch(&self, name: &str) -> bool {
+        self.mismatches.borrow().iter().any(|m| m == name)
+    }
+}
@@ -0,0 +1,261 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::iterable::{Entry, EntryMeta};
+use crate::password::{check_byte, Password, ZipCryptoKeys, ENCRYPTION_HEADER_SIZE};
+use crate::shared::*;
+use crate::types::*;
+
+/// How an entry is encrypted, as its flags and compression method say.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EncryptionScheme {
+    /// Traditional PKWARE encryption, general purpose bit 0 on its own
+    ZipCrypto,
+    /// WinZip AES, marked by compression method 99, with the key strength and the
+    /// real method in a `WINZIP_AES_TAG` extra field
+    WinZipAes,
+    /// PKWARE strong encryption, general purpose bit 6
+    Strong,
+}
+
+impl EncryptionScheme {
+    // The scheme an entry's header says it's encrypted with, if it's encrypted.
+    pub(crate) fn of(header: &InternalHeader) -> Option<Self> {
+        let flags = GeneralPurposeFlags(header.general_purpose_bit_flag);
+        if !flags.is_encrypted() {
+            return None;
+        }
+        Some(
+            match (flags.is_strongly_encrypted(), header.compression_method) {
+                (true, _) => EncryptionScheme::Strong,
+                (false, METHOD_WINZIP_AES) => EncryptionScheme::WinZipAes,
+                (false, _) => EncryptionScheme::ZipCrypto,
+            },
+        )
+    }
+}
+
+/// Decrypts entries for `Entry::buffer_decrypted()`, so schemes munzip doesn't
+/// implement, such as WinZip AES or a vendor's own, can be read without changing
+/// how the rest of the entry is. `ZipCrypto` is one, for traditional encryption.
+///
+/// The decryptor is only handed the data as stored. It passes back the data as it
+/// was before it was encrypted, still compressed, and munzip decompresses it and
+/// checks its CRC-32 as it would any other entry's.
+///
+/// # Examples
+///
+/// ``` no_run
+/// use std::io::Read;
+///
+/// // a scheme that XORs every byte with a key
+/// struct Xor(u8);
+///
+/// struct XorReader<'d>(Box<dyn Read + 'd>, u8);
+///
+/// impl Read for XorReader<'_> {
+///     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+///         let n = self.0.read(buf)?;
+///         buf[..n].iter_mut().for_each(|b| *b ^= self.1);
+///         Ok(n)
+///     }
+/// }
+///
+/// impl munzip::Decryptor for Xor {
+///     fn handles(&self, scheme: munzip::EncryptionScheme) -> bool {
+///         scheme == munzip::EncryptionScheme::ZipCrypto
+///     }
+///
+///     fn decrypt<'d>(
+///         &mut self,
+///         _entry: &munzip::EntryMeta,
+///         _extra: &[u8],
+///         data: Box<dyn Read + 'd>,
+///     ) -> Result<Box<dyn Read + 'd>, munzip::MuError> {
+///         Ok(Box::new(XorReader(data, self.0)))
+///     }
+/// }
+///
+/// # let mut file = std::fs::File::open("licensed.zip").unwrap();
+/// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+/// let mut entry = zi.entries().next().unwrap();
+/// let data = entry.buffer_decrypted(&mut Xor(0x5A)).unwrap();
+/// ```
+pub trait Decryptor {
+    /// Returns true if the decryptor can decrypt entries encrypted with `scheme`
+    fn handles(&self, scheme: EncryptionScheme) -> bool;
+
+    /// Returns a reader over `data`, decrypted. `data` is all of the entry's data as
+    /// stored, `Entry::compressed_size()` bytes, including any header the scheme
+    /// puts before it. `extra` is the entry's local extra field, where schemes keep
+    /// their parameters.
+    fn decrypt<'d>(
+        &mut self,
+        entry: &EntryMeta,
+        extra: &[u8],
+        data: Box<dyn Read + 'd>,
+    ) -> Result<Box<dyn Read + 'd>, MuError>;
+}
+
+/// A `Decryptor` for traditional PKWARE encryption, with a password. A wrong
+/// password is found from the check byte in the entry's encryption header, which
+/// lets one wrong password in 256 through, to be caught by the CRC-32 under a
+/// `CrcPolicy` that checks it.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # let mut file = std::fs::File::open("locked.zip").unwrap();
+/// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+///
+/// let mut decryptor = munzip::ZipCrypto::new("hunter2");
+/// for mut entry in zi.entries() {
+///     let data = entry.buffer_decrypted(&mut decryptor).unwrap();
+///     println!("{}: {} bytes", entry.filename(), data.len());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ZipCrypto {
+    password: Password,
+}
+
+impl ZipCrypto {
+    /// Creates a `ZipCrypto` decrypting with `password`
+    pub fn new(password: impl Into<Password>) -> Self {
+        ZipCrypto {
+            password: password.into(),
+        }
+    }
+}
+
+impl Decryptor for ZipCrypto {
+    fn handles(&self, scheme: EncryptionScheme) -> bool {
+        scheme == EncryptionScheme::ZipCrypto
+    }
+
+    fn decrypt<'d>(
+        &mut self,
+        entry: &EntryMeta,
+        _extra: &[u8],
+        mut data: Box<dyn Read + 'd>,
+    ) -> Result<Box<dyn Read + 'd>, MuError> {
+        let mut keys = ZipCryptoKeys::new(self.password.as_bytes());
+
+        let mut encryption_header = [0; ENCRYPTION_HEADER_SIZE];
+        data.read_exact(&mut encryption_header).map_err(|_| {
+            MuError::new(
+                ErrorKind::Format,
+                format!(
+                    "'{}' is too short to hold an encryption header",
+                    entry.filename
+                ),
+            )
+        })?;
+        let last = encryption_header.iter().map(|&b| keys.decrypt(b)).last();
+        if last != Some(check_byte(&entry.header)) {
+            return Err(MuError::new(
+                ErrorKind::Encrypted,
+                format!("wrong password for '{}'", entry.filename),
+            ));
+        }
+
+        Ok(Box::new(ZipCryptoReader { data, keys }))
+    }
+}
+
+// Decrypts traditionally encrypted data as it's read, past its encryption header.
+pub(crate) struct ZipCryptoReader<'d> {
+    pub(crate) data: Box<dyn Read + 'd>,
+    pub(crate) keys: ZipCryptoKeys,
+}
+
+impl Read for ZipCryptoReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.data.read(buf)?;
+        for byte in &mut buf[..n] {
+            *byte = self.keys.decrypt(*byte);
+        }
+        Ok(n)
+    }
+}
+
+impl<'a, R: Read + Seek> Entry<'a, R> {
+    /// Returns how the entry is encrypted, or `None` if it isn't
+    pub fn encryption(&self) -> Option<EncryptionScheme> {
+        EncryptionScheme::of(&self.header)
+    }
+
+    /// Reads and decompresses the data as `buffer()` does, decrypting it with
+    /// `decryptor` first if the entry is encrypted. An entry that isn't is read just
+    /// as `buffer()` reads it. It's an `ErrorKind::Encrypted` error if `decryptor`
+    /// doesn't handle the entry's scheme. For a WinZip AES entry, the method it's
+    /// compressed with is taken from its extra field, and as AE-2 leaves the CRC-32
+    /// out, it's only checked if it's there; the decryptor is left to authenticate
+    /// the data. Decrypted data isn't cached.
+    pub fn buffer_decrypted(&mut self, decryptor: &mut dyn Decryptor) -> Result<Vec<u8>, MuError> {
+        let Some(scheme) = self.encryption() else {
+            return self.buffer();
+        };
+        self.read_decrypted(scheme, decryptor)
+            .map_err(|e| e.with_entry(self.meta()))
+    }
+
+    fn read_decrypted(
+        &self,
+        scheme: EncryptionScheme,
+        decryptor: &mut dyn Decryptor,
+    ) -> Result<Vec<u8>, MuError> {
+        if !decryptor.handles(scheme) {
+            return Err(MuError::new(
+                ErrorKind::Encrypted,
+                format!("no decryptor for {scheme:?} encryption"),
+            ));
+        }
+
+        // everything else that would stop the entry being read
+        let mut unencrypted = self.header.clone();
+        unencrypted.general_purpose_bit_flag &=
+            !(FLAG_ENCRYPTED | FLAG_STRONG_ENCRYPTION | FLAG_MASKED_HEADERS);
+        check_flags(&unencrypted)?;
+
+        let mut file = self.file.borrow_mut();
+        let mut lfh = [0; LOCAL_FILE_HEADER_SIZE];
+        file.seek(SeekFrom::Start(self.header.local_header_offset))?;
+        file.read_exact(&mut lfh)?;
+        let name_len = u16::from_le_bytes([lfh[26], lfh[27]]) as i64;
+        let extra_len = u16::from_le_bytes([lfh[28], lfh[29]]) as usize;
+        let mut extra = vec![0; extra_len];
+        file.seek(SeekFrom::Current(name_len))?;
+        file.read_exact(&mut extra)?;
+
+        let method = match scheme {
+            // vendor version, vendor ID, strength, then the method
+            EncryptionScheme::WinZipAes => find_extra_field(&extra, WINZIP_AES_TAG)
+                .and_then(|aes| aes.get(5..7))
+                .map(|method| u16::from_le_bytes([method[0], method[1]]))
+                .ok_or_else(|| {
+                    MuError::new(ErrorKind::Format, "WinZip AES entry has no AES extra field")
+                })?,
+            _ => self.header.compression_method,
+        };
+
+        file.seek(SeekFrom::Start(self.header.offset))?;
+        let stored = Read::take(&mut *file, self.header.compressed_size);
+        let decrypted = decryptor.decrypt(&self.meta(), &extra, Box::new(stored))?;
+        let mut data =
+            Vec::with_capacity(self.header.uncompressed_size.min(MAX_PREALLOCATION) as usize);
+        decompressor(method, decrypted)?.read_to_end(&mut data)?;
+
+        if scheme != EncryptionScheme::WinZipAes || self.header.crc32 != 0 {
+            self.crc.check_data(&self.filename, &self.header, &data)?;
+        }
+        Ok(data)
+    }
+}
+
+impl EntryMeta {
+    /// Returns how the entry is encrypted, as `Entry::encryption()` does
+    pub fn encryption(&self) -> Option<EncryptionScheme> {
+        EncryptionScheme::of(&self.header)
+    }
+}
@@ -0,0 +1,538 @@
+// A streaming DEFLATE encoder: LZ77 over hash chains, with each block written
+// stored, or with fixed or dynamic Huffman codes, whichever comes out smallest.
+// It won't match zlib's ratios, but it's small and has no dependencies.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: usize = 15;
+const NONE: usize = usize::MAX;
+
+// a block is written once it holds this many symbols
+const BLOCK_TOKENS: usize = 16384;
+
+// matches at least this long are taken without looking one byte ahead
+const LAZY_LIMIT: usize = 32;
+
+const END_OF_BLOCK: usize = 256;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+// the order code length code lengths are written in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, count: u32) {
+        self.acc |= value << self.bits;
+        self.bits += count;
+        while self.bits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    // huffman codes are packed starting from their most significant bit
+    fn put_code(&mut self, code: u32, count: u32) {
+        self.put(code.reverse_bits() >> (32 - count), count);
+    }
+
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.out.push(self.acc as u8);
+            self.acc = 0;
+            self.bits = 0;
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Token {
+    Literal(u8),
+    Match { len: u16, dist: u16 },
+}
+
+impl Token {
+    // literal/length symbol, and the extra bits after it
+    fn length_symbol(self) -> (usize, u32, u32) {
+        match self {
+            Token::Literal(b) => (b as usize, 0, 0),
+            Token::Match { len, .. } => {
+                let i = LENGTH_BASE.iter().rposition(|&b| b <= len).unwrap();
+                (
+                    257 + i,
+                    (len - LENGTH_BASE[i]) as u32,
+                    LENGTH_EXTRA[i] as u32,
+                )
+            }
+        }
+    }
+
+    fn dist_symbol(self) -> Option<(usize, u32, u32)> {
+        match self {
+            Token::Literal(_) => None,
+            Token::Match { dist, .. } => {
+                let i = DIST_BASE.iter().rposition(|&b| b <= dist).unwrap();
+                Some((i, (dist - DIST_BASE[i]) as u32, DIST_EXTRA[i] as u32))
+            }
+        }
+    }
+}
+
+struct Code {
+    lengths: Vec<u8>,
+    codes: Vec<u32>,
+}
+
+impl Code {
+    fn from_lengths(lengths: Vec<u8>) -> Code {
+        let max = *lengths.iter().max().unwrap_or(&0) as usize;
+        let mut count = vec![0u32; max + 1];
+        for &l in lengths.iter().filter(|&&l| l > 0) {
+            count[l as usize] += 1;
+        }
+
+        // the first code of each length, as in RFC 1951 section 3.2.2
+        let mut next = vec![0u32; max + 1];
+        let mut code = 0;
+        for bits in 1..=max {
+            code = (code + count[bits - 1]) << 1;
+            next[bits] = code;
+        }
+
+        let codes = lengths
+            .iter()
+            .map(|&l| {
+                let c = next[l as usize];
+                next[l as usize] += 1;
+                c
+            })
+            .collect();
+
+        Code { lengths, codes }
+    }
+
+    fn fixed_literals() -> Code {
+        let mut lengths = vec![8; 288];
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        Code::from_lengths(lengths)
+    }
+
+    fn fixed_distances() -> Code {
+        Code::from_lengths(vec![5; 30])
+    }
+
+    fn put(&self, bits: &mut BitWriter, sym: usize) {
+        bits.put_code(self.codes[sym], self.lengths[sym] as u32);
+    }
+
+    fn cost(&self, freq: &[u32]) -> u64 {
+        freq.iter()
+            .zip(&self.lengths)
+            .map(|(&f, &l)| f as u64 * l as u64)
+            .sum()
+    }
+}
+
+// Huffman code lengths no longer than `limit`. Frequencies are flattened until the
+// tree fits, which costs a little compression on pathological inputs only.
+fn code_lengths(freq: &[u32], limit: u8) -> Vec<u8> {
+    let mut freq = freq.to_vec();
+
+    // a code needs two symbols to be complete
+    let mut used = freq.iter().filter(|&&f| f > 0).count();
+    for f in freq.iter_mut() {
+        if used >= 2 {
+            break;
+        }
+        if *f == 0 {
+            *f = 1;
+            used += 1;
+        }
+    }
+
+    loop {
+        let mut parent = vec![NONE; freq.len()];
+        let mut heap: BinaryHeap<Reverse<(u64, usize)>> = freq
+            .iter()
+            .enumerate()
+            .filter(|&(_, &f)| f > 0)
+            .map(|(i, &f)| Reverse((f as u64, i)))
+            .collect();
+
+        while heap.len() > 1 {
+            let Reverse((wa, a)) = heap.pop().unwrap();
+            let Reverse((wb, b)) = heap.pop().unwrap();
+            let node = parent.len();
+            parent.push(NONE);
+            parent[a] = node;
+            parent[b] = node;
+            heap.push(Reverse((wa + wb, node)));
+        }
+
+        let lengths: Vec<u8> = (0..freq.len())
+            .map(|sym| {
+                if freq[sym] == 0 {
+                    return 0;
+                }
+                let mut depth = 0;
+                let mut node = sym;
+                while parent[node] != NONE {
+                    node = parent[node];
+                    depth += 1;
+                }
+                depth
+            })
+            .collect();
+
+        if lengths.iter().all(|&l| l <= limit) {
+            return lengths;
+        }
+        for f in freq.iter_mut().filter(|f| **f > 0) {
+            *f = f.div_ceil(2);
+        }
+    }
+}
+
+// Run length encode code lengths with symbols 16 (repeat previous), 17 and 18
+// (repeat zero). Each item is the symbol, its extra bits and their count.
+fn run_lengths(lengths: &[u8]) -> Vec<(usize, u32, u32)> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < lengths.len() {
+        let l = lengths[i];
+        let run = lengths[i..].iter().take_while(|&&x| x == l).count();
+
+        if l == 0 && run >= 3 {
+            let n = run.min(138);
+            if n >= 11 {
+                out.push((18, (n - 11) as u32, 7));
+            } else {
+                out.push((17, (n - 3) as u32, 3));
+            }
+            i += n;
+        } else if l != 0 && run >= 4 {
+            out.push((l as usize, 0, 0));
+            let n = (run - 1).min(6);
+            out.push((16, (n - 3) as u32, 2));
+            i += 1 + n;
+        } else {
+            out.push((l as usize, 0, 0));
+            i += 1;
+        }
+    }
+    out
+}
+
+pub struct Deflater {
+    bits: BitWriter,
+    // history and pending input; buf[0] is absolute position `base`
+    buf: Vec<u8>,
+    base: usize,
+    // next position to encode, relative to buf
+    pos: usize,
+    // where the unwritten block's input begins, relative to buf
+    block_start: usize,
+    tokens: Vec<Token>,
+    // most recent absolute position per hash, and the one before it per position
+    head: Vec<usize>,
+    prev: Vec<usize>,
+    max_chain: usize,
+    lazy: bool,
+}
+
+impl Deflater {
+    // `level` runs from 1 (fastest) to 9 (smallest).
+    pub fn new(level: u8) -> Self {
+        let max_chain = match level {
+            0 | 1 => 4,
+            2 => 8,
+            3 => 16,
+            4 => 32,
+            5 => 64,
+            6 => 128,
+            7 => 256,
+            8 => 1024,
+            _ => 4096,
+        };
+
+        Deflater {
+            bits: BitWriter {
+                out: Vec::new(),
+                acc: 0,
+                bits: 0,
+            },
+            buf: Vec::new(),
+            base: 0,
+            pos: 0,
+            block_start: 0,
+            tokens: Vec::with_capacity(BLOCK_TOKENS),
+            head: vec![NONE; 1 << HASH_BITS],
+            prev: vec![NONE; WINDOW],
+            max_chain,
+            lazy: level >= 4,
+        }
+    }
+
+    pub fn write(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+        self.encode(false);
+
+        // keep one window of history behind the encode position, and the input of
+        // the pending block in case it's written stored
+        if self.pos > WINDOW * 2 {
+            let drop = (self.pos - WINDOW).min(self.block_start);
+            self.buf.drain(..drop);
+            self.base += drop;
+            self.pos -= drop;
+            self.block_start -= drop;
+        }
+    }
+
+    // Take the compressed bytes produced so far.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.bits.out)
+    }
+
+    // Encode everything left and end the stream, returning the last of the output.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.encode(true);
+        self.write_block(true);
+        self.bits.align();
+        self.bits.out
+    }
+
+    fn hash(&self, at: usize) -> usize {
+        let b = &self.buf[at..at + MIN_MATCH];
+        ((b[0] as usize) << 10 ^ (b[1] as usize) << 5 ^ b[2] as usize) & ((1 << HASH_BITS) - 1)
+    }
+
+    fn insert(&mut self, at: usize) {
+        if at + MIN_MATCH > self.buf.len() {
+            return;
+        }
+        let h = self.hash(at);
+        let abs = self.base + at;
+        self.prev[abs % WINDOW] = self.head[h];
+        self.head[h] = abs;
+    }
+
+    fn longest_match(&self, at: usize) -> (usize, usize) {
+        let abs = self.base + at;
+        let max = (self.buf.len() - at).min(MAX_MATCH);
+        let mut best = (0, 0);
+        if max < MIN_MATCH {
+            return best;
+        }
+
+        let mut cand = self.head[self.hash(at)];
+        for _ in 0..self.max_chain {
+            if cand == NONE || cand < self.base || abs - cand > WINDOW || cand >= abs {
+                break;
+            }
+            let c = cand - self.base;
+            let mut len = 0;
+            while len < max && self.buf[c + len] == self.buf[at + len] {
+                len += 1;
+            }
+            if len > best.0 {
+                best = (len, abs - cand);
+                if len == max {
+                    break;
+                }
+            }
+            cand = self.prev[cand % WINDOW];
+        }
+
+        best
+    }
+
+    fn encode(&mut self, finish: bool) {
+        // without the whole input, stop short so every match can reach full length
+        let end = if finish {
+            self.buf.len()
+        } else {
+            self.buf.len().saturating_sub(MAX_MATCH + 1)
+        };
+
+        while self.pos < end {
+            let (len, dist) = self.longest_match(self.pos);
+
+            // a literal now may allow a longer match from the next byte
+            let defer = self.lazy
+                && (MIN_MATCH..LAZY_LIMIT).contains(&len)
+                && self.longest_match(self.pos + 1).0 > len;
+
+            if len >= MIN_MATCH && !defer {
+                self.tokens.push(Token::Match {
+                    len: len as u16,
+                    dist: dist as u16,
+                });
+                for at in self.pos..self.pos + len {
+                    self.insert(at);
+                }
+                self.pos += len;
+            } else {
+                self.tokens.push(Token::Literal(self.buf[self.pos]));
+                self.insert(self.pos);
+                self.pos += 1;
+            }
+
+            if self.tokens.len() >= BLOCK_TOKENS {
+                self.write_block(false);
+            }
+        }
+    }
+
+    fn write_block(&mut self, last: bool) {
+        let mut lit_freq = vec![0u32; 286];
+        let mut dist_freq = vec![0u32; 30];
+        // extra bits are the same whichever codes are used
+        let mut extra_bits = 0u64;
+        for &t in &self.tokens {
+            let (sym, _, n) = t.length_symbol();
+            lit_freq[sym] += 1;
+            extra_bits += n as u64;
+            if let Some((sym, _, n)) = t.dist_symbol() {
+                dist_freq[sym] += 1;
+                extra_bits += n as u64;
+            }
+        }
+        lit_freq[END_OF_BLOCK] += 1;
+
+        let fixed_lit = Code::fixed_literals();
+        let fixed_dist = Code::fixed_distances();
+        let fixed_cost = 3 + fixed_lit.cost(&lit_freq) + fixed_dist.cost(&dist_freq) + extra_bits;
+
+        let dyn_lit = Code::from_lengths(code_lengths(&lit_freq, 15));
+        let dyn_dist = Code::from_lengths(code_lengths(&dist_freq, 15));
+        let (header, header_cost) = dynamic_header(&dyn_lit, &dyn_dist);
+        let dyn_cost =
+            3 + header_cost + dyn_lit.cost(&lit_freq) + dyn_dist.cost(&dist_freq) + extra_bits;
+
+        let raw_len = self.pos - self.block_start;
+        let stored_cost =
+            (3 + 7 + 32) * raw_len.div_ceil(0xFFFF).max(1) as u64 + raw_len as u64 * 8;
+
+        if stored_cost < fixed_cost.min(dyn_cost) {
+            self.write_stored(last);
+        } else if dyn_cost < fixed_cost {
+            self.bits.put(last as u32, 1);
+            self.bits.put(2, 2);
+            for (value, count) in header {
+                self.bits.put(value, count);
+            }
+            self.write_tokens(&dyn_lit, &dyn_dist);
+        } else {
+            self.bits.put(last as u32, 1);
+            self.bits.put(1, 2);
+            self.write_tokens(&fixed_lit, &fixed_dist);
+        }
+
+        self.tokens.clear();
+        self.block_start = self.pos;
+    }
+
+    fn write_tokens(&mut self, lit: &Code, dist: &Code) {
+        for &t in &self.tokens {
+            let (sym, value, n) = t.length_symbol();
+            lit.put(&mut self.bits, sym);
+            self.bits.put(value, n);
+            if let Some((sym, value, n)) = t.dist_symbol() {
+                dist.put(&mut self.bits, sym);
+                self.bits.put(value, n);
+            }
+        }
+        lit.put(&mut self.bits, END_OF_BLOCK);
+    }
+
+    fn write_stored(&mut self, last: bool) {
+        let data = &self.buf[self.block_start..self.pos];
+        let mut chunks = data.chunks(0xFFFF).peekable();
+        if chunks.peek().is_none() {
+            // an empty stored block is still a block
+            self.bits.put(last as u32, 1);
+            self.bits.put(0, 2);
+            self.bits.align();
+            self.bits.out.extend_from_slice(&[0, 0, 0xFF, 0xFF]);
+            return;
+        }
+
+        while let Some(chunk) = chunks.next() {
+            let final_chunk = last && chunks.peek().is_none();
+            self.bits.put(final_chunk as u32, 1);
+            self.bits.put(0, 2);
+            self.bits.align();
+            let len = chunk.len() as u16;
+            self.bits.out.extend_from_slice(&len.to_le_bytes());
+            self.bits.out.extend_from_slice(&(!len).to_le_bytes());
+            self.bits.out.extend_from_slice(chunk);
+        }
+    }
+}
+
+// The bits describing a dynamic block's codes, and how many there are.
+fn dynamic_header(lit: &Code, dist: &Code) -> (Vec<(u32, u32)>, u64) {
+    let hlit = 257.max(lit.lengths.iter().rposition(|&l| l > 0).unwrap_or(0) + 1);
+    let hdist = 1.max(dist.lengths.iter().rposition(|&l| l > 0).unwrap_or(0) + 1);
+
+    let mut all = lit.lengths[..hlit].to_vec();
+    all.extend_from_slice(&dist.lengths[..hdist]);
+    let runs = run_lengths(&all);
+
+    let mut cl_freq = vec![0u32; 19];
+    for &(sym, _, _) in &runs {
+        cl_freq[sym] += 1;
+    }
+    let cl = Code::from_lengths(code_lengths(&cl_freq, 7));
+    let hclen = 4.max(
+        CODE_LENGTH_ORDER
+            .iter()
+            .rposition(|&s| cl.lengths[s] > 0)
+            .unwrap_or(0)
+            + 1,
+    );
+
+    let mut out = vec![
+        ((hlit - 257) as u32, 5),
+        ((hdist - 1) as u32, 5),
+        ((hclen - 4) as u32, 4),
+    ];
+    for &s in &CODE_LENGTH_ORDER[..hclen] {
+        out.push((cl.lengths[s] as u32, 3));
+    }
+    for (sym, value, n) in runs {
+        let code = cl.codes[sym].reverse_bits() >> (32 - cl.lengths[sym] as u32);
+        out.push((code, cl.lengths[sym] as u32));
+        out.push((value, n));
+    }
+
+    let cost = out.iter().map(|&(_, n)| n as u64).sum();
+    (out, cost)
+}
@@ -0,0 +1,112 @@
+use std::io::{Read, Seek};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::iterable::{Entry, EntryMeta};
+use crate::shared::{dos_to_unix, unix_to_dos};
+
+/// A DOS date and time, as zip headers record modification times: two 16-bit
+/// fields, good from 1980 to 2107 at two second precision, with no time zone.
+/// munzip takes them as UTC, converting them as `Entry::modified()` does and
+/// `ZipWriter` writes them.
+///
+/// # Examples
+///
+/// ``` no_run
+/// use std::time::SystemTime;
+///
+/// let dos = munzip::DosDateTime::from_system_time(SystemTime::now()).unwrap();
+/// println!("{}-{:02}-{:02}", dos.year(), dos.month(), dos.day());
+/// let (date, time) = (dos.date(), dos.time());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DosDateTime {
+    date: u16,
+    time: u16,
+}
+
+impl DosDateTime {
+    /// Creates a `DosDateTime` from the date and time fields as they're stored.
+    /// They aren't checked, so `to_system_time()` is how to tell if they're valid.
+    pub fn from_fields(date: u16, time: u16) -> Self {
+        DosDateTime { date, time }
+    }
+
+    /// Converts `time`, rounding down to an even second. Returns `None` if it's
+    /// outside 1980 to 2107.
+    pub fn from_system_time(time: SystemTime) -> Option<Self> {
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => i64::try_from(d.as_secs()).ok()?,
+            Err(e) => -i64::try_from(e.duration().as_secs()).ok()?,
+        };
+        let (date, time) = unix_to_dos(secs)?;
+        Some(DosDateTime { date, time })
+    }
+
+    /// Returns the time, or `None` if the fields aren't a valid date and time
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        let secs = dos_to_unix(self.date, self.time)?;
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+    }
+
+    /// Returns the date field as stored
+    pub fn date(&self) -> u16 {
+        self.date
+    }
+
+    /// Returns the time field as stored
+    pub fn time(&self) -> u16 {
+        self.time
+    }
+
+    /// Returns the year, from 1980 to 2107
+    pub fn year(&self) -> u16 {
+        1980 + (self.date >> 9)
+    }
+
+    /// Returns the month, from 1 to 12 if the date is valid
+    pub fn month(&self) -> u8 {
+        ((self.date >> 5) & 0xF) as u8
+    }
+
+    /// Returns the day of the month, from 1 to 31 if the date is valid
+    pub fn day(&self) -> u8 {
+        (self.date & 0x1F) as u8
+    }
+
+    /// Returns the hour, from 0 to 23 if the time is valid
+    pub fn hour(&self) -> u8 {
+        (self.time >> 11) as u8
+    }
+
+    /// Returns the minute, from 0 to 59 if the time is valid
+    pub fn minute(&self) -> u8 {
+        ((self.time >> 5) & 0x3F) as u8
+    }
+
+    /// Returns the second, always even, from 0 to 58 if the time is valid
+    pub fn second(&self) -> u8 {
+        ((self.time & 0x1F) * 2) as u8
+    }
+}
+
+impl<'a, R: Read + Seek> Entry<'a, R> {
+    /// Returns the DOS date and time the entry's header records, which
+    /// `modified()` only falls back on without an extended timestamp
+    pub fn dos_modified(&self) -> DosDateTime {
+        DosDateTime::from_fields(
+            self.header.last_mod_file_date,
+            self.header.last_mod_file_time,
+        )
+    }
+}
+
+impl EntryMeta {
+    /// Returns the DOS date and time the entry's header records, as
+    /// `Entry::dos_modified()` does
+    pub fn dos_modified(&self) -> DosDateTime {
+        DosDateTime::from_fields(
+            self.header.last_mod_file_date,
+            self.header.last_mod_file_time,
+        )
+    }
+}
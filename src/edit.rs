@@ -0,0 +1,444 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::crc32::{CrcCheck, CrcReader};
+use crate::iterable::EntryMeta;
+use crate::options::*;
+use crate::patient::PatientReader;
+use crate::shared::*;
+use crate::types::*;
+
+const GFH_SIZE: usize = std::mem::size_of::<GlobalFileHeader>();
+const LFH_SIZE: usize = std::mem::size_of::<LocalFileHeader>();
+
+/// Metadata changes for one entry, for `ArchiveEditor::patch()`. Only the fields
+/// that are set get changed.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataPatch {
+    pub(crate) modified: Option<SystemTime>,
+    pub(crate) unix_mode: Option<u32>,
+}
+
+impl MetadataPatch {
+    /// Creates a `MetadataPatch` that changes nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the modification time. The DOS time only holds 1980 to 2107 at two
+    /// second precision; an extended timestamp field is added to the central
+    /// directory record if it doesn't already have one, to hold the exact second.
+    pub fn modified(mut self, time: SystemTime) -> Self {
+        self.modified = Some(time);
+        self
+    }
+
+    /// Sets the Unix permission and file type bits (as in `st_mode`), marking the
+    /// entry as made on Unix so readers know to interpret them.
+    pub fn unix_mode(mut self, mode: u32) -> Self {
+        self.unix_mode = Some(mode);
+        self
+    }
+}
+
+struct CentralRecord {
+    filename: String,
+    bytes: Vec<u8>,
+    local_header_offset: u64,
+    // new dos date and time, and unix mtime, to carry into the local header
+    local_times: Option<(u16, u16, i32)>,
+    // a corrected crc, to carry into the local header
+    local_crc: Option<u32>,
+}
+
+/// Rewrites entry metadata in place, without touching any entry's data. The file
+/// must be opened for both reading and writing.
+///
+/// Changes are made in memory by `patch()`, then `commit()` rewrites the central
+/// directory and end record, truncating or extending the file to fit. Local
+/// headers are only rewritten if `patch_local_headers(true)` is set, and then only
+/// their fixed-size fields, since growing them would move the data behind them.
+///
+/// # Examples
+///
+/// ``` no_run
+/// let mut file = std::fs::OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .open("archive.zip")
+///     .unwrap();
+///
+/// let mut editor = munzip::ArchiveEditor::new(&mut file).unwrap();
+/// editor.patch("bin/tool", &munzip::MetadataPatch::new().unix_mode(0o100755)).unwrap();
+/// editor.commit().unwrap();
+/// ```
+pub struct ArchiveEditor<'a> {
+    file: &'a mut File,
+    // where the central directory starts, which the end record can't hold past 4 GB
+    cd_offset: u64,
+    comment: Vec<u8>,
+    records: Vec<CentralRecord>,
+    patch_local: bool,
+}
+
+impl<'a> ArchiveEditor<'a> {
+    /// Reads the central directory of `file` into memory.
+    pub fn new(file: &'a mut File) -> Result<Self, MuError> {
+        let mut reader = PatientReader::new(&mut *file);
+        let (end_rec, cd_offset) = read_end_record(&mut reader)?;
+
+        let mut comment = vec![0; end_rec.record.zip_comment_length as usize];
+        reader.seek(SeekFrom::End(-(comment.len() as i64)))?;
+        reader.read_exact(&mut comment)?;
+
+        let mut cd = vec![0; end_rec.central_directory_size as usize];
+        reader.seek(SeekFrom::Start(cd_offset))?;
+        reader.read_exact(&mut cd)?;
+
+        let mut records = Vec::new();
+        let mut pos = 0;
+        for _ in 0..end_rec.num_entries {
+            let fixed = cd.get(pos..pos + GFH_SIZE).ok_or_else(truncated)?;
+            let gfh = get_global_file_header(fixed)?;
+
+            let len = GFH_SIZE
+                + gfh.file_name_length as usize
+                + gfh.extra_field_length as usize
+                + gfh.file_comment_length as usize;
+            let bytes = cd.get(pos..pos + len).ok_or_else(truncated)?.to_vec();
+
+            let name_end = GFH_SIZE + gfh.file_name_length as usize;
+            let name_bytes = &bytes[GFH_SIZE..name_end];
+            let filename = normalize_separators(&String::from_utf8_lossy(name_bytes));
+
+            // past 4 GB, the offset is in the record's ZIP64 field
+            let extra = &bytes[name_end..name_end + gfh.extra_field_length as usize];
+            let recorded = [
+                gfh.uncompressed_size as u64,
+                gfh.compressed_size as u64,
+                gfh.relative_offset_of_local_header as u64,
+            ];
+            let [_, _, local_header_offset] = match find_extra_field(extra, ZIP64_TAG) {
+                Some(field) => resolve_zip64(field, recorded)?,
+                None => recorded,
+            };
+
+            records.push(CentralRecord {
+                filename,
+                bytes,
+                local_header_offset,
+                local_times: None,
+                local_crc: None,
+            });
+            pos += len;
+        }
+
+        Ok(Self {
+            file,
+            cd_offset,
+            comment,
+            records,
+            patch_local: false,
+        })
+    }
+
+    /// Sets whether `commit()` also rewrites the modification time and CRC-32 in the
+    /// local headers of patched entries. Defaults to `false`, in which case readers
+    /// that go by local headers keep seeing the old ones. A local header without an
+    /// extended timestamp field doesn't get one, as that would grow it, so only the
+    /// central record has the exact second, and the local header the DOS time.
+    pub fn patch_local_headers(&mut self, patch: bool) {
+        self.patch_local = patch;
+    }
+
+    /// Applies `patch` to the entry named `name`. Returns `false` if there is no
+    /// such entry. Nothing is written until `commit()`.
+    pub fn patch(&mut self, name: &str, patch: &MetadataPatch) -> Result<bool, MuError> {
+        let record = match self.records.iter_mut().find(|r| r.filename == name) {
+            Some(record) => record,
+            None => return Ok(false),
+        };
+
+        if let Some(time) = patch.modified {
+            let secs = match time.duration_since(UNIX_EPOCH) {
+                Ok(d) => d.as_secs() as i64,
+                Err(e) => -(e.duration().as_secs() as i64),
+            };
+
+            let (date, time) = unix_to_dos(secs).ok_or_else(|| {
+                MuError::new(ErrorKind::Other, "time can't be represented as a DOS time")
+            })?;
+            let unix = i32::try_from(secs).map_err(|_| {
+                MuError::new(ErrorKind::Other, "time can't be represented as a Unix time")
+            })?;
+
+            set_central_mtime(&mut record.bytes, unix)?;
+            put_u16(&mut record.bytes, 12, time);
+            put_u16(&mut record.bytes, 14, date);
+            record.local_times = Some((date, time, unix));
+        }
+
+        if let Some(mode) = patch.unix_mode {
+            let made_by = get_u16(&record.bytes, 4);
+            put_u16(
+                &mut record.bytes,
+                4,
+                (HOST_UNIX as u16) << 8 | (made_by & 0xFF),
+            );
+
+            let attrs = get_u32(&record.bytes, 38);
+            put_u32(&mut record.bytes, 38, mode << 16 | (attrs & 0xFFFF));
+        }
+
+        Ok(true)
+    }
+
+    /// Decompresses every entry and checks its data against its recorded CRC-32,
+    /// returning the names of those that don't match. What else happens to them
+    /// is up to `policy`: `CrcPolicy::Verify` fails with the first, and
+    /// `CrcPolicy::Fix` changes the recorded CRC-32 to the data's, to be written
+    /// by `commit()`. `CrcPolicy::Trust` checks nothing.
+    ///
+    /// Fixing only makes the CRC match the data, which may still not be what was
+    /// archived. An entry with a data descriptor keeps the old CRC-32 there, as
+    /// readers take the central directory's.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::OpenOptions::new().read(true).write(true).open("archive.zip").unwrap();
+    /// let mut editor = munzip::ArchiveEditor::new(&mut file).unwrap();
+    /// editor.patch_local_headers(true);
+    /// let fixed = editor.check_crcs(munzip::CrcPolicy::Fix).unwrap();
+    /// editor.commit().unwrap();
+    /// println!("{} CRCs fixed", fixed.len());
+    /// ```
+    pub fn check_crcs(&mut self, policy: CrcPolicy) -> Result<Vec<String>, MuError> {
+        let check = CrcCheck::new(policy);
+        if !check.enabled() {
+            return Ok(Vec::new());
+        }
+
+        let mut reader = PatientReader::new(&mut *self.file);
+        let mut position = self.cd_offset;
+        for (index, record) in self.records.iter_mut().enumerate() {
+            let gfh = get_global_file_header(&record.bytes[..GFH_SIZE])?;
+            let (header, _) = local_header(
+                &mut reader,
+                0,
+                &gfh,
+                position,
+                index,
+                &NameDecoding::default(),
+                &Compatibility::new(),
+            )?;
+            position += record.bytes.len() as u64;
+
+            let mut data = CrcReader::new(data_reader(&mut reader, &header)?);
+            std::io::copy(&mut data, &mut std::io::sink())?;
+            let actual = data.crc32();
+
+            check
+                .check(&record.filename, &header, actual)
+                .map_err(|e| {
+                    e.with_entry(EntryMeta {
+                        header: header.clone(),
+                        filename: record.filename.clone(),
+                        local_read: true,
+                    })
+                })?;
+            if policy == CrcPolicy::Fix && actual != header.crc32 {
+                put_u32(&mut record.bytes, 16, actual);
+                record.local_crc = Some(actual);
+            }
+        }
+
+        Ok(check.mismatches())
+    }
+
+    /// Writes the patched central directory and end record back to the file.
+    pub fn commit(self) -> Result<(), MuError> {
+        if self.patch_local {
+            for record in &self.records {
+                if let Some((date, time, unix)) = record.local_times {
+                    patch_local_header(self.file, record.local_header_offset, date, time, unix)?;
+                }
+                if let Some(crc) = record.local_crc {
+                    patch_local_crc(self.file, record.local_header_offset, crc)?;
+                }
+            }
+        }
+
+        let cd: Vec<u8> = self
+            .records
+            .iter()
+            .flat_map(|r| r.bytes.iter().copied())
+            .collect();
+        let entries = self.records.len() as u64;
+        let cd_size = cd.len() as u64;
+
+        // a ZIP64 end record and its locator go first, whenever the end record can't
+        // hold the count, size or offset
+        let mut end = Vec::new();
+        if entries >= u16::MAX as u64 || cd_size >= PLACEHOLDER || self.cd_offset >= PLACEHOLDER {
+            let record_at = self.cd_offset + cd_size;
+            end.extend_from_slice(&ZIP64_END_RECORD_SIGNATURE.to_le_bytes());
+            end.extend_from_slice(&(ZIP64_END_RECORD_SIZE as u64 - 12).to_le_bytes());
+            end.extend_from_slice(&45u16.to_le_bytes()); // version made by
+            end.extend_from_slice(&45u16.to_le_bytes()); // version needed
+            end.extend_from_slice(&0u32.to_le_bytes()); // disk number
+            end.extend_from_slice(&0u32.to_le_bytes()); // central directory disk number
+            end.extend_from_slice(&entries.to_le_bytes());
+            end.extend_from_slice(&entries.to_le_bytes());
+            end.extend_from_slice(&cd_size.to_le_bytes());
+            end.extend_from_slice(&self.cd_offset.to_le_bytes());
+
+            end.extend_from_slice(&ZIP64_END_LOCATOR_SIGNATURE.to_le_bytes());
+            end.extend_from_slice(&0u32.to_le_bytes()); // disk with the ZIP64 end record
+            end.extend_from_slice(&record_at.to_le_bytes());
+            end.extend_from_slice(&1u32.to_le_bytes()); // total disks
+        }
+
+        let num_entries = u16::try_from(entries).unwrap_or(u16::MAX);
+        let cd_size = u32::try_from(cd_size).unwrap_or(u32::MAX);
+        let cd_offset = u32::try_from(self.cd_offset).unwrap_or(u32::MAX);
+        end.extend_from_slice(&END_RECORD_SIGNATURE.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // disk number
+        end.extend_from_slice(&0u16.to_le_bytes()); // central directory disk number
+        end.extend_from_slice(&num_entries.to_le_bytes());
+        end.extend_from_slice(&num_entries.to_le_bytes());
+        end.extend_from_slice(&cd_size.to_le_bytes());
+        end.extend_from_slice(&cd_offset.to_le_bytes());
+        end.extend_from_slice(&(self.comment.len() as u16).to_le_bytes());
+        end.extend_from_slice(&self.comment);
+
+        self.file.seek(SeekFrom::Start(self.cd_offset))?;
+        self.file.write_all(&cd)?;
+        self.file.write_all(&end)?;
+
+        let len = self.file.stream_position()?;
+        self.file.set_len(len)?;
+        self.file.flush()?;
+
+        Ok(())
+    }
+}
+
+fn truncated() -> MuError {
+    MuError::new(ErrorKind::Format, "central directory truncated")
+}
+
+// Update the extended timestamp extra field of a central directory record, which
+// only ever holds the mtime, or append one if the extra field has room.
+fn set_central_mtime(record: &mut Vec<u8>, unix: i32) -> Result<(), MuError> {
+    let name_len = get_u16(record, 28) as usize;
+    let extra_len = get_u16(record, 30) as usize;
+    let extra_start = GFH_SIZE + name_len;
+
+    // a field running past the extra field, or the record, ends the search
+    let extra_end = (extra_start + extra_len).min(record.len());
+    let mut pos = extra_start;
+    while pos + 4 <= extra_end {
+        let id = get_u16(record, pos);
+        let len = get_u16(record, pos + 2) as usize;
+        if pos + 4 + len > extra_end {
+            break;
+        }
+        if id == EXTENDED_TIMESTAMP_TAG && len >= 5 && record[pos + 4] & 1 != 0 {
+            record[pos + 5..pos + 9].copy_from_slice(&unix.to_le_bytes());
+            return Ok(());
+        }
+        pos += 4 + len;
+    }
+
+    let new_len = u16::try_from(extra_len + 9).map_err(|_| {
+        MuError::new(
+            ErrorKind::Other,
+            "extra field has no room for an extended timestamp",
+        )
+    })?;
+    let mut field = Vec::with_capacity(9);
+    field.extend_from_slice(&EXTENDED_TIMESTAMP_TAG.to_le_bytes());
+    field.extend_from_slice(&5u16.to_le_bytes());
+    field.push(1); // mtime present
+    field.extend_from_slice(&unix.to_le_bytes());
+
+    let end = extra_start + extra_len;
+    record.splice(end..end, field);
+    put_u16(record, 30, new_len);
+    Ok(())
+}
+
+// Rewrite the fixed-size time fields of a local header, and its extended timestamp
+// mtime if it has one.
+fn patch_local_header(
+    file: &mut File,
+    offset: u64,
+    date: u16,
+    time: u16,
+    unix: i32,
+) -> Result<(), MuError> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut header = [0; LFH_SIZE];
+    file.read_exact(&mut header)?;
+    let lfh = get_internal_file_header(&header)?;
+
+    let mut extra = vec![0; lfh.extra_field_length as usize];
+    file.seek(SeekFrom::Current(lfh.file_name_length as i64))?;
+    file.read_exact(&mut extra)?;
+
+    put_u16(&mut header, 10, time);
+    put_u16(&mut header, 12, date);
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(&header)?;
+
+    let mut pos = 0;
+    while pos + 4 <= extra.len() {
+        let id = get_u16(&extra, pos);
+        let len = get_u16(&extra, pos + 2) as usize;
+        if pos + 4 + len > extra.len() {
+            break;
+        }
+        if id == EXTENDED_TIMESTAMP_TAG && len >= 5 && extra[pos + 4] & 1 != 0 {
+            let at = offset + (LFH_SIZE + lfh.file_name_length as usize + pos + 5) as u64;
+            file.seek(SeekFrom::Start(at))?;
+            file.write_all(&unix.to_le_bytes())?;
+            break;
+        }
+        pos += 4 + len;
+    }
+
+    Ok(())
+}
+
+// Rewrite the CRC-32 of a local header, unless it's left to a data descriptor.
+fn patch_local_crc(file: &mut File, offset: u64, crc: u32) -> Result<(), MuError> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut header = [0; LFH_SIZE];
+    file.read_exact(&mut header)?;
+    let lfh = get_internal_file_header(&header)?;
+
+    if lfh.general_purpose_bit_flag & FLAG_DATA_DESCRIPTOR == 0 {
+        file.seek(SeekFrom::Start(offset + 14))?;
+        file.write_all(&crc.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+fn get_u16(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([buf[at], buf[at + 1]])
+}
+
+fn get_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+}
+
+fn put_u16(buf: &mut [u8], at: usize, value: u16) {
+    buf[at..at + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut [u8], at: usize, value: u32) {
+    buf[at..at + 4].copy_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,216 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::path::Path;
+
+use crate::crc32::Crc32;
+use crate::searchable::SearchableArchive;
+use crate::shared::*;
+use crate::types::*;
+
+/// An archive built into the program by `embed_archive()`. The archive's bytes
+/// are included as they are, with a table of where each entry's data is worked out
+/// at build time, so nothing is parsed at runtime and entries are only
+/// decompressed when they're asked for.
+pub struct EmbeddedArchive {
+    bytes: &'static [u8],
+    // sorted by name
+    entries: &'static [EmbeddedEntry],
+}
+
+/// Where an entry of an `EmbeddedArchive` is. Only made by generated code.
+#[doc(hidden)]
+pub struct EmbeddedEntry {
+    name: &'static str,
+    method: u16,
+    offset: usize,
+    compressed_size: usize,
+    uncompressed_size: usize,
+    crc32: u32,
+}
+
+impl EmbeddedEntry {
+    #[doc(hidden)]
+    pub const fn new(
+        name: &'static str,
+        method: u16,
+        offset: usize,
+        compressed_size: usize,
+        uncompressed_size: usize,
+        crc32: u32,
+    ) -> Self {
+        EmbeddedEntry {
+            name,
+            method,
+            offset,
+            compressed_size,
+            uncompressed_size,
+            crc32,
+        }
+    }
+}
+
+impl EmbeddedArchive {
+    #[doc(hidden)]
+    pub const fn new(bytes: &'static [u8], entries: &'static [EmbeddedEntry]) -> Self {
+        EmbeddedArchive { bytes, entries }
+    }
+
+    /// Decompresses the entry named `name`, checking its CRC-32. Names use `/` as
+    /// the separator, as for `SearchableArchive::by_name()`.
+    pub fn get(&self, name: &str) -> Result<Option<Vec<u8>>, MuError> {
+        let Some(entry) = self.entry(name) else {
+            return Ok(None);
+        };
+
+        let end = entry.offset + entry.compressed_size;
+        let data = decompress(entry.method, self.bytes[entry.offset..end].to_vec())?;
+
+        let mut crc = Crc32::new();
+        crc.update(&data);
+        if data.len() != entry.uncompressed_size || crc.finish() != entry.crc32 {
+            return Err(MuError::new(
+                ErrorKind::Format,
+                format!("'{name}' doesn't match its recorded size and CRC-32"),
+            ));
+        }
+
+        Ok(Some(data))
+    }
+
+    /// Returns the data of a stored entry as it sits in the program, without
+    /// copying it. Returns `None` if there's no such entry, or it's compressed.
+    pub fn get_stored(&self, name: &str) -> Option<&'static [u8]> {
+        let entry = self.entry(name)?;
+        match entry.method {
+            0 => Some(&self.bytes[entry.offset..entry.offset + entry.compressed_size]),
+            _ => None,
+        }
+    }
+
+    /// Returns true if there's an entry named `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.entry(name).is_some()
+    }
+
+    /// Returns the names of every entry, in order
+    pub fn names(&self) -> impl Iterator<Item = &'static str> {
+        self.entries.iter().map(|e| e.name)
+    }
+
+    /// Returns how many entries there are
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the whole archive, as it was at build time
+    pub fn bytes(&self) -> &'static [u8] {
+        self.bytes
+    }
+
+    fn entry(&self, name: &str) -> Option<&'static EmbeddedEntry> {
+        let entries = self.entries;
+        let i = entries.binary_search_by(|e| e.name.cmp(name)).ok()?;
+        Some(&entries[i])
+    }
+}
+
+/// For build scripts: reads the archive at `zip` and writes Rust source to `out`
+/// that embeds it, as a static `ARCHIVE: munzip::EmbeddedArchive` with `get()`,
+/// `get_stored()` and `names()` functions wrapping it. Include the source in a
+/// module to get typed access to the assets. Requires the `embed` feature, on both
+/// the build dependency and the normal one.
+///
+/// Entries that can't be read without a password or a patch are left out, along
+/// with directories. The build script is rerun when the archive changes.
+///
+/// # Examples
+///
+/// In `build.rs`:
+///
+/// ``` no_run
+/// let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("assets.rs");
+/// munzip::embed_archive("assets.zip", out).unwrap();
+/// ```
+///
+/// Then in the crate:
+///
+/// ``` ignore
+/// mod assets {
+///     include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+/// }
+///
+/// let hero = assets::get("sprites/hero.png").unwrap().unwrap();
+/// ```
+pub fn embed_archive<P: AsRef<Path>, Q: AsRef<Path>>(zip: P, out: Q) -> Result<(), MuError> {
+    let zip = zip.as_ref().canonicalize()?;
+    let path = zip.to_str().ok_or_else(|| {
+        MuError::new(
+            ErrorKind::Utf8,
+            format!("'{}' isn't valid UTF-8", zip.display()),
+        )
+    })?;
+
+    let mut file = File::open(&zip)?;
+    let archive = SearchableArchive::new(&mut file)?;
+
+    let mut entries: Vec<_> = archive
+        .offset_cache()
+        .iter()
+        .filter(|(name, _)| !name.ends_with('/'))
+        .map(|(name, entry)| (name.to_string(), *entry))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    // `{:?}` of a str is a valid Rust string literal
+    let mut source = String::new();
+    let _ = writeln!(
+        source,
+        "// generated by munzip::embed_archive() from {path:?}"
+    );
+    let _ = writeln!(
+        source,
+        "pub static ARCHIVE: munzip::EmbeddedArchive = munzip::EmbeddedArchive::new("
+    );
+    let _ = writeln!(source, "    include_bytes!({path:?}),");
+    let _ = writeln!(source, "    &[");
+    for (name, entry) in &entries {
+        let _ = writeln!(
+            source,
+            "        munzip::EmbeddedEntry::new({name:?}, {}, {}, {}, {}, {:#010x}),",
+            entry.method,
+            entry.data_offset,
+            entry.compressed_size,
+            entry.uncompressed_size,
+            entry.crc32,
+        );
+    }
+    let _ = writeln!(source, "    ],");
+    let _ = writeln!(source, ");");
+    source.push_str(concat!(
+        "\n",
+        "/// Decompresses the asset named `name`\n",
+        "pub fn get(name: &str) -> Result<Option<Vec<u8>>, munzip::MuError> {\n",
+        "    ARCHIVE.get(name)\n",
+        "}\n",
+        "\n",
+        "/// Returns the asset named `name` without copying it, if it's stored uncompressed\n",
+        "pub fn get_stored(name: &str) -> Option<&'static [u8]> {\n",
+        "    ARCHIVE.get_stored(name)\n",
+        "}\n",
+        "\n",
+        "/// Returns the names of every asset, in order\n",
+        "pub fn names() -> impl Iterator<Item = &'static str> {\n",
+        "    ARCHIVE.names()\n",
+        "}\n",
+    ));
+
+    std::fs::write(out, source)?;
+    println!("cargo:rerun-if-changed={path}");
+
+    Ok(())
+}
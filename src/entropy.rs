@@ -0,0 +1,123 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::iterable::Entry;
+use crate::shared::*;
+use crate::types::*;
+
+// stored entries are sampled from this many places spread through them
+const STORED_SPANS: u64 = 4;
+
+// above this, a general purpose compressor rarely gains enough to be worth it
+const COMPRESSIBLE_BITS_PER_BYTE: f64 = 7.5;
+
+/// An estimate of how well an entry's contents would compress, from
+/// `Entry::estimate_entropy()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntropyEstimate {
+    /// How many bytes were sampled
+    pub sampled: u64,
+    /// The Shannon entropy of the sampled bytes taken one at a time, in bits per
+    /// byte: 0 for a file of one repeated byte, up to 8 for random or already
+    /// compressed data.
+    pub bits_per_byte: f64,
+}
+
+impl EntropyEstimate {
+    /// Returns the size a compressor coding each byte on its own could shrink the
+    /// contents to, as a fraction of their size. DEFLATE also finds repeats, so it
+    /// usually does better on text, but seldom on data this puts near 1.
+    pub fn ratio(&self) -> f64 {
+        self.bits_per_byte / 8.0
+    }
+
+    /// Returns true if recompressing looks worthwhile, with under 7.5 bits per byte.
+    /// An empty sample counts as compressible.
+    pub fn is_compressible(&self) -> bool {
+        self.bits_per_byte < COMPRESSIBLE_BITS_PER_BYTE
+    }
+}
+
+impl<'a, R: Read + Seek> Entry<'a, R> {
+    /// Estimates how compressible the entry's contents are from a sample of at most
+    /// `sample_bytes` of them, so tools repacking an archive can choose between
+    /// storing and deflating without decompressing everything. A stored entry is
+    /// sampled from a few places spread through it; a deflated one can only be
+    /// inflated from the start, so its sample is the first `sample_bytes`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("archive.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    /// for entry in zi.entries() {
+    ///     let estimate = entry.estimate_entropy(64 * 1024).unwrap();
+    ///     let method = match estimate.is_compressible() {
+    ///         true => "deflate",
+    ///         false => "store",
+    ///     };
+    ///     println!("{}: {:.2} bits/byte, {method}", entry.filename(), estimate.bits_per_byte);
+    /// }
+    /// ```
+    pub fn estimate_entropy(&self, sample_bytes: u64) -> Result<EntropyEstimate, MuError> {
+        let mut counts = [0u64; 256];
+        let mut file = self.file.borrow_mut();
+        let size = self.header.uncompressed_size;
+        let mut sampled = 0;
+
+        match self.header.compression_method {
+            // a stored entry can be read anywhere, so the sample needn't all be its start
+            0 if size > sample_bytes => {
+                check_flags(&self.header)?;
+                let span = sample_bytes / STORED_SPANS;
+                let stride = size / STORED_SPANS;
+                for i in 0..STORED_SPANS {
+                    let len = match i {
+                        // whatever doesn't divide evenly goes in the last span
+                        i if i == STORED_SPANS - 1 => {
+                            (sample_bytes - span * i).min(size - stride * i)
+                        }
+                        _ => span,
+                    };
+                    file.seek(SeekFrom::Start(self.header.offset + stride * i))?;
+                    let buf = &mut self.buffer_sizes.for_len(len);
+                    sampled += tally((&mut *file).take(len), buf, &mut counts)?;
+                }
+            }
+            _ => {
+                let data = data_reader(&mut *file, &self.header)?.take(sample_bytes);
+                let buf = &mut self.buffer_sizes.for_len(sample_bytes.min(size));
+                sampled += tally(data, buf, &mut counts)?;
+            }
+        }
+
+        let bits_per_byte = counts
+            .iter()
+            .filter(|&&n| n > 0)
+            .map(|&n| {
+                let p = n as f64 / sampled as f64;
+                p * (1.0 / p).log2()
+            })
+            .fold(0.0, |total, bits| total + bits);
+
+        Ok(EntropyEstimate {
+            sampled,
+            bits_per_byte,
+        })
+    }
+}
+
+// Count each byte value `data` holds, reading it through `buf`, and returning how
+// many bytes there were.
+fn tally(mut data: impl Read, buf: &mut [u8], counts: &mut [u64; 256]) -> Result<u64, MuError> {
+    let mut total = 0;
+    loop {
+        let n = read_retrying(&mut data, buf)?;
+        if n == 0 {
+            return Ok(total);
+        }
+        for &b in &buf[..n] {
+            counts[b as usize] += 1;
+        }
+        total += n as u64;
+    }
+}
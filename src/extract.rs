@@ -0,0 +1,1018 @@
+use std::fs::{File, Metadata};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::attributes::{dos_attributes, EntryKind};
+use crate::classify::{self, FileClass};
+use crate::crc32::{Crc32, CrcReader};
+use crate::inspect::*;
+use crate::iterable::Entry;
+use crate::platform;
+use crate::receipt::{Receipt, ReceiptEntry, Sanitization};
+use crate::searchable::SearchableArchive;
+use crate::shared::*;
+use crate::types::*;
+
+/// Settings for `SearchableArchive::extract_all()`.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    pub(crate) preserve_mtime: bool,
+    pub(crate) preserve_attributes: bool,
+    pub(crate) restore_apple_double: bool,
+    #[cfg(feature = "io-uring")]
+    pub(crate) io_uring: bool,
+    pub(crate) max_entries: Option<usize>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) max_path_length: Option<usize>,
+    pub(crate) max_file_size: Option<u64>,
+    pub(crate) max_total_size: Option<u64>,
+    pub(crate) max_compression_ratio: Option<u64>,
+    pub(crate) atomic: bool,
+    pub(crate) preallocate: bool,
+    pub(crate) check_free_space: bool,
+    pub(crate) skip_unchanged: Option<ChangeCheck>,
+    pub(crate) on_reject: RejectPolicy,
+    pub(crate) only: Vec<FileClass>,
+}
+
+/// How `ExtractOptions::skip_unchanged()` decides a file already on disk matches
+/// its entry. Either way, the sizes must match first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeCheck {
+    /// Read the file and compare its CRC-32 with the entry's. Certain, but reads
+    /// every file whose size matches.
+    Crc,
+    /// Compare the file's modification time with the entry's, to within the two
+    /// seconds DOS timestamps are rounded to. Cheap, but only meaningful if the
+    /// files were extracted with `preserve_mtime()`.
+    Mtime,
+}
+
+/// What `SearchableArchive::extract_all()` did with the files in the archive.
+/// Directories aren't counted.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractSummary {
+    /// Files that didn't exist yet
+    pub created: usize,
+    /// Files that existed, and were overwritten
+    pub updated: usize,
+    /// Files left alone because `ExtractOptions::skip_unchanged()` found them
+    /// unchanged
+    pub skipped: usize,
+    /// Files a `ContentInspector` rejected, and its reasons, under
+    /// `RejectPolicy::Skip`
+    pub rejected: Vec<(String, String)>,
+    /// Files whose data didn't match their CRC-32, and were kept anyway under
+    /// `CrcPolicy::Record` or `CrcPolicy::Fix`
+    pub crc_mismatches: Vec<String>,
+    /// Files left out because they're of none of the classes given to
+    /// `ExtractOptions::only()`
+    pub excluded: usize,
+    /// Every file and directory written, and what was done to them
+    pub receipt: Receipt,
+}
+
+impl ExtractOptions {
+    /// Creates `ExtractOptions` with every setting at its default.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether extracted files and directories get the entry's modification
+    /// time, rather than the time of extraction. Defaults to `false`.
+    pub fn preserve_mtime(mut self, preserve: bool) -> Self {
+        self.preserve_mtime = preserve;
+        self
+    }
+
+    /// Sets whether extracted files and directories get the entry's attributes, read
+    /// as the system it was made on wrote them. On Windows, that's the readonly,
+    /// hidden, system and archive bits, with readonly also set for Unix entries with
+    /// no write permission. On Unix, it's the permission bits of the entry's Unix
+    /// mode, without setuid, setgid or sticky, or for entries from elsewhere, just
+    /// the write bits cleared if they're readonly. Defaults to `false`.
+    pub fn preserve_attributes(mut self, preserve: bool) -> Self {
+        self.preserve_attributes = preserve;
+        self
+    }
+
+    /// Sets whether the AppleDouble files Finder puts under `__MACOSX/` are unpacked
+    /// back into the extended attributes, Finder info and resource forks of the
+    /// files they describe. Only macOS has anywhere to put these, so this only has
+    /// an effect there. They're restored whether or not
+    /// `ArchiveOptions::include_apple_double()` lists them. Defaults to `false`.
+    pub fn restore_apple_double(mut self, restore: bool) -> Self {
+        self.restore_apple_double = restore;
+        self
+    }
+
+    /// Sets whether file reads and writes go through io_uring, submitting each
+    /// entry's as a batch. Only 64-bit Linux has the backend; elsewhere, or where the
+    /// kernel refuses a ring, extraction quietly takes the standard path. Defaults to
+    /// `false`. Requires the `io-uring` feature.
+    #[cfg(feature = "io-uring")]
+    pub fn io_uring(mut self, enable: bool) -> Self {
+        self.io_uring = enable;
+        self
+    }
+
+    /// Sets the most entries, files and directories alike, an archive may hold to be
+    /// extracted. Defaults to no limit.
+    pub fn max_entries(mut self, max: usize) -> Self {
+        self.max_entries = Some(max);
+        self
+    }
+
+    /// Sets how many components deep an entry's path may go, counting the file
+    /// itself, so `a/b/c.txt` is 3 deep. Defaults to no limit.
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// Sets the longest an entry's path may be, in bytes, once any leading `/`s and
+    /// `.` components are dropped. Defaults to no limit.
+    pub fn max_path_length(mut self, max: usize) -> Self {
+        self.max_path_length = Some(max);
+        self
+    }
+
+    /// Sets the largest any one extracted file may be, in bytes. Sizes recorded in
+    /// the archive are checked up front, and since those can lie, compressed entries
+    /// are also decompressed in a stream and cut off as soon as they go over.
+    /// Defaults to no limit.
+    pub fn max_file_size(mut self, max: u64) -> Self {
+        self.max_file_size = Some(max);
+        self
+    }
+
+    /// Sets the most all the extracted files together may come to, in bytes. As with
+    /// `max_file_size()`, the sizes recorded in the archive are added up front, and
+    /// compressed entries are cut off once what's really been written goes over.
+    /// Defaults to no limit.
+    pub fn max_total_size(mut self, max: u64) -> Self {
+        self.max_total_size = Some(max);
+        self
+    }
+
+    /// Sets how many times its compressed size any one file may decompress to, so
+    /// 100 allows a ratio of up to 100:1. Checked against the sizes recorded in the
+    /// archive up front, and against what compressed entries really decompress to as
+    /// they're written. Defaults to no limit.
+    pub fn max_compression_ratio(mut self, max: u64) -> Self {
+        self.max_compression_ratio = Some(max);
+        self
+    }
+
+    /// Sets whether extraction goes into a hidden directory beside `dest`, which is
+    /// renamed to `dest` only once every entry is out. If anything fails, or the
+    /// thread panics, the hidden directory is removed instead, so `dest` never holds
+    /// a partial tree. `dest` must not exist yet, or be an empty directory. Defaults
+    /// to `false`.
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    /// Sets whether each file has the size its entry records reserved on disk before
+    /// any of it is written, which keeps it in one piece and
+    /// makes a full disk fail before the file is started rather than halfway through.
+    /// Where the filesystem can't reserve space, the file's length is just set. If
+    /// the entry turns out shorter than recorded, the file is cut back to what was
+    /// written. Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("dataset.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// let options = munzip::ExtractOptions::new().preallocate(true);
+    /// match zi.extract_all(std::path::Path::new("/mnt/scratch/dataset"), &options) {
+    ///     Ok(summary) => println!("{} files out", summary.created),
+    ///     Err(e) => eprintln!("no room for the dataset: {e}"),
+    /// }
+    /// ```
+    pub fn preallocate(mut self, preallocate: bool) -> Self {
+        self.preallocate = preallocate;
+        self
+    }
+
+    /// Sets whether the free space on the destination's filesystem is checked
+    /// against the total size of the files to extract before any are written,
+    /// failing with `ErrorKind::InsufficientSpace` if it falls short. The total is
+    /// what the archive records, counting every file, so it doesn't allow for files
+    /// that would be skipped or overwritten. Systems with no way to ask how much
+    /// space is free skip the check. Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("backup.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// let options = munzip::ExtractOptions::new().check_free_space(true);
+    /// match zi.extract_all("restore".as_ref(), &options) {
+    ///     Err(e) if e.kind() == munzip::ErrorKind::InsufficientSpace => eprintln!("{e}"),
+    ///     result => println!("{} files restored", result.unwrap().created),
+    /// }
+    /// ```
+    pub fn check_free_space(mut self, check: bool) -> Self {
+        self.check_free_space = check;
+        self
+    }
+
+    /// Sets that files which already exist under `dest` and match their entry are
+    /// left alone rather than written again, with `check` deciding what matches. The
+    /// files that are written, and the directories, get their mtime and attributes
+    /// as usual. Defaults to writing every file.
+    pub fn skip_unchanged(mut self, check: ChangeCheck) -> Self {
+        self.skip_unchanged = Some(check);
+        self
+    }
+
+    /// Sets what `SearchableArchive::extract_inspected()` does once its
+    /// `ContentInspector` rejects a file. Defaults to `RejectPolicy::Skip`.
+    pub fn on_reject(mut self, policy: RejectPolicy) -> Self {
+        self.on_reject = policy;
+        self
+    }
+
+    /// Sets that only files of `class` are extracted, such as just the images for a
+    /// thumbnailer. Call it again to add more classes. Files whose extension doesn't
+    /// say are sniffed, decompressing the start of each to check for the magic bytes
+    /// of the class's formats. Directories are only created to hold the files that
+    /// are extracted. Defaults to extracting every file.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut input = std::fs::File::open("mixed.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut input).unwrap();
+    ///
+    /// let options = munzip::ExtractOptions::new().only(munzip::FileClass::Images);
+    /// let summary = zi.extract_all("thumbs".as_ref(), &options).unwrap();
+    /// println!("{} images, {} other files left out", summary.created, summary.excluded);
+    /// ```
+    pub fn only(mut self, class: FileClass) -> Self {
+        if !self.only.contains(&class) {
+            self.only.push(class);
+        }
+        self
+    }
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    /// Extracts every entry beneath `dest`, creating directories as needed. Leading
+    /// `/`s are stripped from names, and a name with a `..` component is refused with
+    /// an error before anything is written for it.
+    ///
+    /// On Linux, under `CrcPolicy::Trust`, stored entries are copied kernel side
+    /// with `copy_file_range` or `sendfile`, so they never pass through a buffer.
+    /// Every other `CrcPolicy` needs their CRC-32 computed, and an archive opened
+    /// with `from_reader()` has no file for the kernel to copy from.
+    ///
+    /// Any quotas set on `options` are checked against every entry before anything
+    /// is written, and an archive that goes over one fails with
+    /// `ErrorKind::QuotaExceeded`, holding the `Quota` that tripped. A file that
+    /// turns out larger than its recorded size is removed when it does.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut input = std::fs::File::open("archive.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut input).unwrap();
+    ///
+    /// let options = munzip::ExtractOptions::new().preserve_mtime(true);
+    /// let summary = zi.extract_all("out".as_ref(), &options).unwrap();
+    /// println!("{} files written", summary.created + summary.updated);
+    /// ```
+    pub fn extract_all(
+        &self,
+        dest: &Path,
+        options: &ExtractOptions,
+    ) -> Result<ExtractSummary, MuError> {
+        self.extract(dest, options, None)
+    }
+
+    /// Extracts every entry beneath `dest` as `extract_all()` does, but passes each
+    /// file's contents through `inspector` as they're decompressed, before they're
+    /// written. A file it rejects is cut short and removed, along with any file that
+    /// was at its path, and then skipped or the extraction aborted as
+    /// `ExtractOptions::on_reject()` says.
+    ///
+    /// Every file is streamed through the inspector, so the faster paths for stored
+    /// entries and io_uring aren't taken.
+    pub fn extract_inspected(
+        &self,
+        dest: &Path,
+        options: &ExtractOptions,
+        inspector: &mut dyn ContentInspector,
+    ) -> Result<ExtractSummary, MuError> {
+        self.extract(dest, options, Some(inspector))
+    }
+
+    fn extract(
+        &self,
+        dest: &Path,
+        options: &ExtractOptions,
+        inspector: Option<&mut dyn ContentInspector>,
+    ) -> Result<ExtractSummary, MuError> {
+        if options.check_free_space {
+            self.check_free_space(dest)?;
+        }
+        if options.atomic {
+            return self.extract_atomic(dest, options, inspector);
+        }
+
+        self.extract_into(dest, options, inspector)
+    }
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    // Extract everything into `dest`, leaving whatever was written if it fails.
+    fn extract_into(
+        &self,
+        dest: &Path,
+        options: &ExtractOptions,
+        mut inspector: Option<&mut dyn ContentInspector>,
+    ) -> Result<ExtractSummary, MuError> {
+        self.check_quotas(options)?;
+
+        let mut summary = ExtractSummary::default();
+        // bytes written so far, for `max_total_size()`
+        let mut extracted: u64 = 0;
+
+        // writing into a directory bumps its mtime, so those are set once everything's
+        // out, along with the index of its receipt
+        let mut dir_times: Vec<(PathBuf, SystemTime, usize)> = Vec::new();
+        // and a readonly directory can't be written into at all
+        let mut dir_attributes: Vec<(PathBuf, InternalHeader, usize)> = Vec::new();
+
+        #[cfg(all(feature = "io-uring", target_os = "linux", target_pointer_width = "64"))]
+        let mut ring = match options.io_uring {
+            true => crate::uring::Ring::new().ok(),
+            false => None,
+        };
+
+        for mut entry in self.entries() {
+            let (relative, sanitized) = sanitize(&entry.filename)?;
+            let path = dest.join(&relative);
+
+            if entry.kind() == EntryKind::Directory {
+                if !options.only.is_empty() {
+                    continue;
+                }
+                std::fs::create_dir_all(&path)?;
+
+                let index = summary.receipt.paths.len();
+                let mut receipt = receipt_entry(&entry, relative, sanitized);
+                receipt.kind = EntryKind::Directory;
+                receipt.crc32 = 0;
+                summary.receipt.paths.push(receipt);
+
+                if options.preserve_attributes {
+                    dir_attributes.push((path.clone(), entry.header.clone(), index));
+                }
+                if let (true, Some(mtime)) = (options.preserve_mtime, entry.modified()) {
+                    dir_times.push((path, mtime, index));
+                }
+                continue;
+            }
+
+            if !wanted(&entry, &options.only) {
+                summary.excluded += 1;
+                continue;
+            }
+
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            let existing = std::fs::metadata(&path).ok().filter(Metadata::is_file);
+            if let (Some(check), Some(metadata)) = (options.skip_unchanged, &existing) {
+                if unchanged(&entry, &path, metadata, check)? {
+                    summary.skipped += 1;
+                    continue;
+                }
+            }
+
+            let limit = entry_limit(options, &entry.header, extracted);
+
+            #[cfg(all(feature = "io-uring", target_os = "linux", target_pointer_width = "64"))]
+            let done = match ring.as_mut() {
+                // the ring decompresses whole entries in memory, so it can't cut one off
+                Some(_) if limit.is_some() && entry.compression_method() != 0 => false,
+                Some(_) if inspector.is_some() || entry.crc.enabled() => false,
+                Some(ring) => match self.as_file {
+                    Some(as_file) => crate::uring::extract_entry(ring, &entry, as_file, &path)?,
+                    None => false,
+                },
+                None => false,
+            };
+            #[cfg(not(all(
+                feature = "io-uring",
+                target_os = "linux",
+                target_pointer_width = "64"
+            )))]
+            let done = false;
+
+            if done {
+                // already written through the ring
+            } else if let Some(inspector) = inspector.as_deref_mut() {
+                let rejected = write_streamed(&entry, &path, options, limit, Some(inspector))?;
+                if let Some(reason) = rejected {
+                    match options.on_reject {
+                        RejectPolicy::Skip => {
+                            summary.rejected.push((entry.filename.clone(), reason));
+                            continue;
+                        }
+                        RejectPolicy::Abort => {
+                            return Err(MuError::new(
+                                ErrorKind::Rejected,
+                                format!("'{}' was rejected: {reason}", entry.filename),
+                            ))
+                        }
+                    }
+                }
+            } else if let (0, false, Some(as_file)) = (
+                entry.header.compression_method,
+                entry.crc.enabled(),
+                self.as_file,
+            ) {
+                write_stored(&entry, as_file, &path, options)?;
+            } else if entry.header.compression_method == 0 || entry.crc.enabled() || limit.is_some()
+            {
+                write_streamed(&entry, &path, options, limit, None)?;
+            } else {
+                let data = entry.buffer()?;
+                let mut out = File::create(&path)?;
+                if options.preallocate && !data.is_empty() {
+                    platform::preallocate(&out, data.len() as u64)?;
+                }
+                out.write_all(&data)?;
+            }
+
+            let mut receipt = receipt_entry(&entry, relative, sanitized);
+            receipt.size = std::fs::metadata(&path)?.len();
+            extracted += receipt.size;
+            receipt.replaced = existing.is_some();
+
+            if let (true, Some(mtime)) = (options.preserve_mtime, entry.modified()) {
+                platform::set_modified(&path, mtime)?;
+                receipt.mtime = Some(mtime);
+            }
+            if options.preserve_attributes {
+                set_attributes(&path, &entry.header, &mut receipt)?;
+            }
+            summary.receipt.paths.push(receipt);
+
+            if entry.crc.enabled() && entry.crc.has_mismatch(&entry.filename) {
+                summary.crc_mismatches.push(entry.filename.clone());
+            }
+            match existing {
+                Some(_) => summary.updated += 1,
+                None => summary.created += 1,
+            }
+        }
+
+        #[cfg(target_os = "macos")]
+        if options.restore_apple_double {
+            self.restore_apple_double(dest)?;
+        }
+
+        for (path, mtime, index) in dir_times {
+            platform::set_modified(&path, mtime)?;
+            summary.receipt.paths[index].mtime = Some(mtime);
+        }
+        for (path, header, index) in dir_attributes {
+            set_attributes(&path, &header, &mut summary.receipt.paths[index])?;
+        }
+
+        Ok(summary)
+    }
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    // Extract into a staging directory beside `dest`, then rename it into place.
+    fn extract_atomic(
+        &self,
+        dest: &Path,
+        options: &ExtractOptions,
+        inspector: Option<&mut dyn ContentInspector>,
+    ) -> Result<ExtractSummary, MuError> {
+        if dest.exists() && std::fs::read_dir(dest)?.next().is_some() {
+            return Err(MuError::new(
+                ErrorKind::Io,
+                format!("'{}' already exists and isn't empty", dest.display()),
+            ));
+        }
+
+        let staging = Staging::create(dest)?;
+        let summary = self.extract_into(&staging.path, options, inspector)?;
+
+        // renaming over even an empty directory fails on Windows
+        if dest.exists() {
+            std::fs::remove_dir(dest)?;
+        }
+        std::fs::rename(&staging.path, dest)?;
+        staging.keep();
+
+        Ok(summary)
+    }
+
+    // Fail if the filesystem `dest` is on, or will be on once it's created, has less
+    // room than every file in the archive would take.
+    fn check_free_space(&self, dest: &Path) -> Result<(), MuError> {
+        let needed: u64 = self
+            .entries
+            .iter()
+            .filter(|(filename, _)| !filename.ends_with('/'))
+            .map(|(_, header)| header.uncompressed_size)
+            .sum();
+        if needed == 0 {
+            return Ok(());
+        }
+
+        let Some(existing) = dest.ancestors().find(|p| p.exists()) else {
+            return Ok(());
+        };
+        let existing = match existing.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => existing,
+        };
+        match platform::available_space(existing)? {
+            Some(available) if available < needed => Err(MuError::new(
+                ErrorKind::InsufficientSpace,
+                format!(
+                    "extracting needs {needed} bytes, but only {available} are free at '{}'",
+                    existing.display()
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // Check every entry against the quotas in `options`, so that nothing is written
+    // for an archive that goes over one.
+    fn check_quotas(&self, options: &ExtractOptions) -> Result<(), MuError> {
+        let exceeded =
+            |quota, message: String| Err(MuError::new(ErrorKind::QuotaExceeded(quota), message));
+
+        match options.max_entries {
+            Some(max) if self.entries.len() > max => {
+                return exceeded(
+                    Quota::Entries,
+                    format!(
+                        "archive holds {} entries, over the limit of {max}",
+                        self.entries.len()
+                    ),
+                );
+            }
+            _ => {}
+        }
+
+        let mut total: u64 = 0;
+        for (filename, header) in &self.entries {
+            let path = sanitize_path(filename)?;
+
+            let depth = path.components().count();
+            match options.max_depth {
+                Some(max) if depth > max => {
+                    return exceeded(
+                        Quota::Depth,
+                        format!("'{filename}' is {depth} deep, over the limit of {max}"),
+                    );
+                }
+                _ => {}
+            }
+
+            let length = path.as_os_str().len();
+            match options.max_path_length {
+                Some(max) if length > max => {
+                    return exceeded(
+                        Quota::PathLength,
+                        format!("'{filename}' is {length} bytes long, over the limit of {max}"),
+                    );
+                }
+                _ => {}
+            }
+
+            // a stored entry's data is copied as is, however large it claims to be
+            let size = match header.compression_method {
+                0 => header.compressed_size.max(header.uncompressed_size),
+                _ => header.uncompressed_size,
+            };
+            match options.max_file_size {
+                Some(max) if size > max => {
+                    return exceeded(
+                        Quota::FileSize,
+                        format!("'{filename}' is {size} bytes, over the limit of {max}"),
+                    );
+                }
+                _ => {}
+            }
+
+            total = total.saturating_add(size);
+            match options.max_total_size {
+                Some(max) if total > max => {
+                    return exceeded(
+                        Quota::TotalSize,
+                        format!(
+                            "files come to {total} bytes by '{filename}', over the limit of {max}"
+                        ),
+                    );
+                }
+                _ => {}
+            }
+
+            match options.max_compression_ratio {
+                Some(max)
+                    if header.uncompressed_size > header.compressed_size.saturating_mul(max) =>
+                {
+                    return exceeded(
+                        Quota::Ratio,
+                        format!(
+                            "'{filename}' is {} bytes from {}, over the ratio limit of {max}",
+                            header.uncompressed_size, header.compressed_size
+                        ),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // Set the attributes from each AppleDouble entry on the file it describes, if
+    // that was extracted.
+    #[cfg(target_os = "macos")]
+    fn restore_apple_double(&self, dest: &Path) -> Result<(), MuError> {
+        let apple_double = self.entries.iter().chain(&self.hidden);
+
+        for (filename, header) in apple_double.filter(|(name, _)| is_apple_double(name)) {
+            let Some(target) = crate::appledouble::target(filename) else {
+                continue;
+            };
+            let path = dest.join(sanitize_path(&target)?);
+            if std::fs::symlink_metadata(&path).is_err() {
+                continue;
+            }
+
+            let data = data_from_internal(&mut *self.file.borrow_mut(), header)?;
+            for (name, value) in crate::appledouble::xattrs(&data).unwrap_or_default() {
+                platform::set_xattr(&path, &name, &value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// A directory for an extraction in progress, removed when dropped unless `keep()`
+// was called. Dropping covers errors and panics alike.
+struct Staging {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl Staging {
+    // Create a hidden, empty directory alongside `dest`.
+    fn create(dest: &Path) -> Result<Staging, MuError> {
+        let Some(name) = dest.file_name() else {
+            return Err(MuError::new(
+                ErrorKind::Io,
+                format!("'{}' can't be extracted to atomically", dest.display()),
+            ));
+        };
+        let parent = dest.parent().unwrap_or(Path::new(""));
+
+        for attempt in 0.. {
+            let mut staging = std::ffi::OsString::from(".");
+            staging.push(name);
+            staging.push(format!(".munzip-{}-{attempt}", platform::process_id()));
+
+            let path = parent.join(staging);
+            match std::fs::create_dir(&path) {
+                Ok(()) => return Ok(Staging { path, keep: false }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        unreachable!()
+    }
+
+    fn keep(mut self) {
+        self.keep = true;
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+// Copy a stored entry straight from the archive, the file `as_file` finds beneath
+// its reader, into a new file at `path`.
+fn write_stored<R: Read + Seek>(
+    entry: &Entry<'_, R>,
+    as_file: fn(&R) -> &File,
+    path: &Path,
+    options: &ExtractOptions,
+) -> Result<(), MuError> {
+    check_flags(&entry.header)?;
+
+    #[cfg(feature = "metrics")]
+    let start = std::time::Instant::now();
+
+    let offset = entry.header.offset;
+    let len = entry.header.compressed_size;
+
+    let mut file = entry.file.borrow_mut();
+    let mut out = File::create(path)?;
+    if options.preallocate && len > 0 {
+        platform::preallocate(&out, len)?;
+    }
+
+    let copied = platform::copy_range(as_file(&file), offset, len, &out)?;
+    if copied < len {
+        file.seek(SeekFrom::Start(offset + copied))?;
+        let mut buf = entry.buffer_sizes.for_len(len - copied);
+        let rest = copy_through(&mut (&mut *file).take(len - copied), &mut out, &mut buf)?;
+        if copied + rest < len {
+            return Err(MuError::new(
+                ErrorKind::Format,
+                format!("'{}' is truncated", entry.filename),
+            ));
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    entry
+        .metrics
+        .borrow_mut()
+        .record(&entry.filename, &entry.header, len, start);
+
+    Ok(())
+}
+
+// Give the file or directory at `path` the attributes `header` records, as far as
+// this system has anywhere to put them, noting what was set in its receipt.
+fn set_attributes(
+    path: &Path,
+    header: &InternalHeader,
+    receipt: &mut ReceiptEntry,
+) -> std::io::Result<()> {
+    let dos = dos_attributes(header);
+    receipt.dos_attributes = platform::set_dos_attributes(path, dos as u32)?;
+    receipt.permissions =
+        platform::set_unix_permissions(path, unix_mode(header), dos as u32 & DOS_READONLY != 0)?;
+    Ok(())
+}
+
+// A receipt for `entry`, written to `path` beneath the destination, with nothing
+// applied to it yet.
+fn receipt_entry<R: Read + Seek>(
+    entry: &Entry<'_, R>,
+    path: PathBuf,
+    mut sanitized: Vec<Sanitization>,
+) -> ReceiptEntry {
+    if entry.header.filename_raw.contains(&b'\\') {
+        sanitized.insert(0, Sanitization::Backslashes);
+    }
+    if entry.header.name_lossy {
+        sanitized.push(Sanitization::LossyName);
+    }
+
+    ReceiptEntry {
+        name: entry.filename.clone(),
+        path,
+        kind: EntryKind::File,
+        size: 0,
+        crc32: entry.header.crc32,
+        replaced: false,
+        mtime: None,
+        permissions: None,
+        dos_attributes: None,
+        sanitized,
+    }
+}
+
+// Whether `entry` is of one of `classes`, going by its name where that settles it,
+// so only the entries it doesn't are decompressed to sniff.
+fn wanted<R: Read + Seek>(entry: &Entry<'_, R>, classes: &[FileClass]) -> bool {
+    if classes.is_empty() {
+        return true;
+    }
+
+    let head = match classify::known_extension(&entry.filename) {
+        Some(_) => Vec::new(),
+        None => classify::head(entry),
+    };
+    classes.iter().any(|c| c.matches(&entry.filename, &head))
+}
+
+// Whether the file at `path` already holds what `entry` would write there.
+fn unchanged<R: Read + Seek>(
+    entry: &Entry<'_, R>,
+    path: &Path,
+    metadata: &Metadata,
+    check: ChangeCheck,
+) -> Result<bool, MuError> {
+    if metadata.len() != entry.header.uncompressed_size {
+        return Ok(false);
+    }
+
+    match check {
+        ChangeCheck::Crc => {
+            let mut file = CrcReader::new(File::open(path)?);
+            std::io::copy(&mut file, &mut std::io::sink())?;
+            Ok(file.crc32() == entry.header.crc32)
+        }
+        ChangeCheck::Mtime => {
+            let Some(secs) = modified_secs(&entry.header) else {
+                return Ok(false);
+            };
+            let on_disk = match metadata.modified()?.duration_since(UNIX_EPOCH) {
+                Ok(since) => since.as_secs() as i64,
+                Err(before) => -(before.duration().as_secs() as i64),
+            };
+            Ok((on_disk - secs).abs() < 2)
+        }
+    }
+}
+
+// The most an entry may decompress to under the quotas in `options`, given that
+// `extracted` bytes are already out, with the quota that sets it.
+fn entry_limit(
+    options: &ExtractOptions,
+    header: &InternalHeader,
+    extracted: u64,
+) -> Option<(u64, Quota)> {
+    [
+        options.max_file_size.map(|max| (max, Quota::FileSize)),
+        options
+            .max_total_size
+            .map(|max| (max.saturating_sub(extracted), Quota::TotalSize)),
+        options
+            .max_compression_ratio
+            .map(|max| (header.compressed_size.saturating_mul(max), Quota::Ratio)),
+    ]
+    .into_iter()
+    .flatten()
+    .min_by_key(|&(limit, _)| limit)
+}
+
+// Decompress an entry into a new file at `path` as a stream, passing it through
+// `inspector` if there is one. The file is removed once it's written more than
+// `limit` bytes, which is an error of the quota that set it, or if the inspector
+// rejects it, which returns the reason, or if its CRC-32 is wrong and the archive's
+// `CrcPolicy` says to fail.
+fn write_streamed<R: Read + Seek>(
+    entry: &Entry<'_, R>,
+    path: &Path,
+    options: &ExtractOptions,
+    limit: Option<(u64, Quota)>,
+    mut inspector: Option<&mut dyn ContentInspector>,
+) -> Result<Option<String>, MuError> {
+    #[cfg(feature = "metrics")]
+    let start = std::time::Instant::now();
+
+    let mut file = entry.file.borrow_mut();
+    let mut data = data_reader(&mut *file, &entry.header)?;
+
+    let mut out = File::create(path)?;
+    let expected = entry.header.uncompressed_size;
+    let preallocated =
+        options.preallocate && expected > 0 && limit.is_none_or(|(l, _)| expected <= l);
+    if preallocated {
+        platform::preallocate(&out, expected)?;
+    }
+    if let Some(inspector) = inspector.as_deref_mut() {
+        inspector.start(&entry.meta());
+    }
+
+    let mut buf = entry.buffer_sizes.for_len(expected);
+    let mut written: u64 = 0;
+    let mut crc = Crc32::new();
+    let verdict = loop {
+        let n = match data.read(&mut buf) {
+            Ok(0) => {
+                break inspector
+                    .as_deref_mut()
+                    .map_or(Verdict::Accept, |i| i.finish())
+            }
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        if let Some((limit, quota)) = limit.filter(|&(limit, _)| written + n as u64 > limit) {
+            drop(out);
+            std::fs::remove_file(path)?;
+            let message = match quota {
+                Quota::TotalSize => format!(
+                    "'{}' takes the files extracted over the total limit of {} bytes",
+                    entry.filename,
+                    options.max_total_size.unwrap_or(limit)
+                ),
+                Quota::Ratio => format!(
+                    "'{}' decompresses to over {} times its compressed size",
+                    entry.filename,
+                    options.max_compression_ratio.unwrap_or(0)
+                ),
+                _ => format!(
+                    "'{}' decompresses to over the limit of {limit} bytes",
+                    entry.filename
+                ),
+            };
+            return Err(MuError::new(ErrorKind::QuotaExceeded(quota), message));
+        }
+
+        if let Some(inspector) = inspector.as_deref_mut() {
+            if let Verdict::Reject(reason) = inspector.inspect(written, &buf[..n]) {
+                break Verdict::Reject(reason);
+            }
+        }
+
+        out.write_all(&buf[..n])?;
+        written += n as u64;
+        if entry.crc.enabled() {
+            crc.update(&buf[..n]);
+        }
+    };
+
+    if let Verdict::Reject(reason) = verdict {
+        drop(out);
+        std::fs::remove_file(path)?;
+        return Ok(Some(reason));
+    }
+
+    let checked = match entry.crc.enabled() {
+        true => entry
+            .crc
+            .check(&entry.filename, &entry.header, crc.finish()),
+        false => Ok(()),
+    };
+    if let Err(e) = checked {
+        drop(out);
+        std::fs::remove_file(path)?;
+        return Err(e.with_entry(entry.meta()));
+    }
+
+    // the recorded size was only a hint, so drop whatever of it wasn't written
+    if preallocated && written != expected {
+        out.set_len(written)?;
+    }
+
+    #[cfg(feature = "metrics")]
+    entry
+        .metrics
+        .borrow_mut()
+        .record(&entry.filename, &entry.header, written, start);
+
+    Ok(None)
+}
+
+// Turn an entry name into a relative path that can't escape the destination.
+pub fn sanitize_path(name: &str) -> Result<PathBuf, MuError> {
+    sanitize(name).map(|(path, _)| path)
+}
+
+// Turn an entry name into a relative path as `sanitize_path()` does, noting what had
+// to be changed.
+fn sanitize(name: &str) -> Result<(PathBuf, Vec<Sanitization>), MuError> {
+    let mut path = PathBuf::new();
+    let mut sanitized = Vec::new();
+    let mut note = |s| {
+        if !sanitized.contains(&s) {
+            sanitized.push(s);
+        }
+    };
+
+    let parts = name.split(['/', '\\']).count();
+    for (i, part) in name.split(['/', '\\']).enumerate() {
+        match Path::new(part).components().next() {
+            // a directory's trailing `/`
+            None if i == parts - 1 => {}
+            None if path.as_os_str().is_empty() => note(Sanitization::Absolute),
+            None | Some(Component::CurDir) => note(Sanitization::Redundant),
+            // a drive letter, or an NTFS alternate data stream
+            Some(Component::Normal(_)) if !(cfg!(windows) && part.contains(':')) => path.push(part),
+            _ => {
+                return Err(MuError::new(
+                    ErrorKind::UnsafePath,
+                    format!("refusing unsafe path '{name}'"),
+                ))
+            }
+        }
+    }
+
+    Ok((path, sanitized))
+}
@@ -0,0 +1,144 @@
+use crate::audit::{LayoutReport, RegionKind};
+use crate::truncation::TruncationReport;
+use crate::types::*;
+
+/// How much a `Finding` matters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Worth knowing, but nothing is wrong with the archive's contents
+    Info,
+    /// Something was wrong, but it was worked around
+    Warning,
+    /// Something is lost, or can't be trusted
+    Error,
+}
+
+/// What a `Finding` is about. Messages are left to the caller, to word (or
+/// translate) as it likes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FindingKind {
+    /// The end record or central directory couldn't be read, so the entries were
+    /// found by walking the local headers
+    CentralDirectoryUnreadable,
+    /// An entry's recorded uncompressed size isn't the size of its data
+    SizeMismatch { recorded: u64, actual: u64 },
+    /// An entry's recorded CRC-32 isn't that of its data
+    CrcMismatch { recorded: u32, actual: u32 },
+    /// An entry couldn't be read, and was left out. Holds why.
+    Unreadable(ErrorKind),
+    /// Bytes that no entry, the central directory or the end record accounts for,
+    /// such as a self-extractor stub. Holds how many.
+    Gap(u64),
+    /// Two regions of the archive share bytes, as in an overlapping zip bomb
+    Overlap(RegionKind, RegionKind),
+    /// There's no end record, as when the archive has been cut off
+    EndRecordMissing,
+    /// Fewer central directory records are in the file than there should be
+    CentralDirectoryIncomplete { present: usize, expected: usize },
+}
+
+/// One thing a scan of an archive found, for a GUI to present or a pipeline to act
+/// on without parsing messages. From `RepairReport::findings`,
+/// `LayoutReport::findings()` and `TruncationReport::findings()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub kind: FindingKind,
+    /// Where in the file it is, if it's about one place
+    pub position: Option<u64>,
+    /// The name of the entry it's about, if it's about one
+    pub entry: Option<String>,
+}
+
+impl Finding {
+    /// Returns how much the finding matters, which depends only on its kind
+    pub fn severity(&self) -> Severity {
+        match self.kind {
+            FindingKind::Gap(_) => Severity::Info,
+            FindingKind::CentralDirectoryUnreadable
+            | FindingKind::SizeMismatch { .. }
+            | FindingKind::CrcMismatch { .. } => Severity::Warning,
+            FindingKind::Unreadable(_)
+            | FindingKind::Overlap(..)
+            | FindingKind::EndRecordMissing
+            | FindingKind::CentralDirectoryIncomplete { .. } => Severity::Error,
+        }
+    }
+}
+
+impl LayoutReport {
+    /// Returns the gaps and overlaps as `Finding`s, in order of where they start.
+    /// An overlap is placed where the later of its regions starts, and named for
+    /// the entry that region belongs to, if it does.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("upload.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// let findings = zi.audit_layout().unwrap().findings();
+    /// if findings.iter().any(|f| f.severity() == munzip::Severity::Error) {
+    ///     println!("rejecting upload");
+    /// }
+    /// ```
+    pub fn findings(&self) -> Vec<Finding> {
+        let name = |kind: &RegionKind| match kind {
+            RegionKind::Entry(index) => self
+                .entries
+                .binary_search_by_key(index, |e| e.central_index)
+                .ok()
+                .map(|at| self.entries[at].filename.clone()),
+            _ => None,
+        };
+
+        let gaps = self.gaps.iter().map(|&(start, end)| Finding {
+            kind: FindingKind::Gap(end - start),
+            position: Some(start),
+            entry: None,
+        });
+        let overlaps = self.overlaps.iter().map(|&(i, j)| {
+            let (first, later) = (&self.regions[i], &self.regions[j]);
+            Finding {
+                kind: FindingKind::Overlap(first.kind.clone(), later.kind.clone()),
+                position: Some(later.start),
+                entry: name(&later.kind).or_else(|| name(&first.kind)),
+            }
+        });
+
+        let mut findings: Vec<Finding> = gaps.chain(overlaps).collect();
+        findings.sort_by_key(|f| f.position);
+        findings
+    }
+}
+
+impl TruncationReport {
+    /// Returns what's missing as `Finding`s: the end record, and central directory
+    /// records, counting against the records the end record declares, or without
+    /// one, the intact entries. An archive where `is_complete()` has none.
+    pub fn findings(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+
+        if !self.end_record_found {
+            findings.push(Finding {
+                kind: FindingKind::EndRecordMissing,
+                position: Some(self.file_size),
+                entry: None,
+            });
+        }
+
+        let expected = self.declared_entries.unwrap_or(self.intact_entries);
+        if self.central_records < expected {
+            findings.push(Finding {
+                kind: FindingKind::CentralDirectoryIncomplete {
+                    present: self.central_records,
+                    expected,
+                },
+                position: self.central_directory_offset,
+                entry: None,
+            });
+        }
+
+        findings
+    }
+}
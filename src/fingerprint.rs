@@ -0,0 +1,79 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::searchable::SearchableArchive;
+use crate::shared::read_retrying;
+use crate::types::*;
+
+// FNV-1a, 128 bit
+const FNV_OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// A digest of an archive's central directory and end record, from
+/// `SearchableArchive::fingerprint()`. It's the same every time the same archive is
+/// opened, on any platform, and `Display`s as 32 hex digits for use as a cache key.
+///
+/// It's not cryptographic, so it shouldn't be trusted to tell apart archives that
+/// were made to collide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fingerprint(u128);
+
+impl Fingerprint {
+    /// Returns the fingerprint as a number
+    pub fn as_u128(&self) -> u128 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    /// Computes a digest of the archive's central directory and end record, which
+    /// between them hold every entry's name, sizes, CRC-32, times and offset, and
+    /// the archive's comment. No entry data is read, so it's quick for an archive
+    /// of any size, and an application can key a cache of extracted files by it.
+    ///
+    /// Changing an entry's data changes its CRC-32, so the fingerprint changes too,
+    /// except for data rewritten in place to one with the same CRC and size.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("assets.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    /// let cache = std::path::Path::new("cache").join(zi.fingerprint().unwrap().to_string());
+    /// if !cache.exists() {
+    ///     zi.extract_all(&cache, &munzip::ExtractOptions::new()).unwrap();
+    /// }
+    /// ```
+    pub fn fingerprint(&self) -> Result<Fingerprint, MuError> {
+        let mut file = self.file.borrow_mut();
+        let mut hash = FNV_OFFSET;
+        let cd_offset = self.cd_offset;
+        let cd_size = self.end_rec.central_directory_size;
+        let mut buf = self.buffer_sizes.for_len(cd_size);
+        // the end record's comment runs to the end of the file
+        let end_size =
+            std::mem::size_of::<EndRecord>() as u64 + self.end_rec.record.zip_comment_length as u64;
+        let end_offset = self.span(&mut file)?.end - end_size;
+
+        for (offset, len) in [(cd_offset, cd_size), (end_offset, end_size)] {
+            file.seek(SeekFrom::Start(offset))?;
+            let mut data = (&mut *file).take(len);
+            loop {
+                let n = read_retrying(&mut data, &mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                for &b in &buf[..n] {
+                    hash = (hash ^ b as u128).wrapping_mul(FNV_PRIME);
+                }
+            }
+        }
+
+        Ok(Fingerprint(hash))
+    }
+}
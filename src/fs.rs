@@ -0,0 +1,419 @@
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::attributes::{entry_kind, is_readonly, EntryKind};
+use crate::iterable::EntryMeta;
+use crate::prefix::PrefixIndex;
+use crate::searchable::SearchableArchive;
+use crate::shared::*;
+use crate::types::*;
+
+/// A read-only view of an archive shaped like `std::fs`, so code written against
+/// the filesystem can be pointed at a zip instead. Paths are relative to the root
+/// of the archive, with or without a leading `/`, and errors are `std::io::Error`s
+/// of the kinds `std::fs` would give.
+///
+/// Directories exist wherever an entry's name implies them, whether or not the
+/// archive has an entry for the directory itself.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # let mut file = std::fs::File::open("site.zip").unwrap();
+/// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+/// let fs = munzip::ArchiveFs::new(&zi);
+///
+/// for entry in fs.read_dir("/static").unwrap() {
+///     let entry = entry.unwrap();
+///     println!("{:?} {}", entry.file_name(), entry.metadata().unwrap().len());
+/// }
+/// let index = fs.read_to_string("/index.html").unwrap();
+/// ```
+pub struct ArchiveFs<'s, 'a, R: Read + Seek = &'a mut File> {
+    archive: &'s SearchableArchive<'a, R>,
+    // the archive's own, if it was opened with `ArchiveOptions::prefix_index()`
+    index: Cow<'s, PrefixIndex>,
+}
+
+/// Metadata for a path in an `ArchiveFs`, in the shape of `std::fs::Metadata`.
+#[derive(Debug, Clone)]
+pub struct FsMetadata {
+    is_dir: bool,
+    len: u64,
+    modified: Option<SystemTime>,
+    permissions: FsPermissions,
+}
+
+/// The permissions of a path in an `ArchiveFs`, or of anything `MetadataLike`, in
+/// the shape of `std::fs::Permissions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsPermissions {
+    readonly: bool,
+    mode: Option<u32>,
+}
+
+impl FsPermissions {
+    /// Returns true if the path is readonly
+    pub fn readonly(&self) -> bool {
+        self.readonly
+    }
+
+    /// Returns the Unix mode, file type bits included, where there is one: for
+    /// entries made on Unix or macOS, and for files on Unix
+    pub fn mode(&self) -> Option<u32> {
+        self.mode
+    }
+}
+
+/// The parts of `std::fs::Metadata` a zip entry has too, so code that only needs
+/// those can take a file on disk, a path in an `ArchiveFs`, or an entry of an
+/// archive alike. Implemented for `std::fs::Metadata`, `FsMetadata` and
+/// `EntryMeta`.
+///
+/// # Examples
+///
+/// ``` no_run
+/// use munzip::MetadataLike;
+///
+/// fn describe(metadata: &impl MetadataLike) -> String {
+///     match metadata.is_dir() {
+///         true => "directory".to_string(),
+///         false => format!("{} bytes", metadata.len()),
+///     }
+/// }
+///
+/// # let mut file = std::fs::File::open("site.zip").unwrap();
+/// println!("{}", describe(&std::fs::metadata("site.zip").unwrap()));
+/// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+/// for entry in zi.entries() {
+///     println!("{}: {}", entry.filename(), describe(&entry.meta()));
+/// }
+/// ```
+pub trait MetadataLike {
+    /// Returns the size of a file's contents, decompressed for an entry
+    fn len(&self) -> u64;
+
+    /// Returns true if the size is 0
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the modification time, or an `Unsupported` error if none is recorded
+    fn modified(&self) -> io::Result<SystemTime>;
+
+    /// Returns the permissions
+    fn permissions(&self) -> FsPermissions;
+
+    /// Returns true for a directory
+    fn is_dir(&self) -> bool;
+
+    /// Returns true for a regular file
+    fn is_file(&self) -> bool;
+}
+
+impl FsMetadata {
+    /// Returns true for a directory
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// Returns true for a file
+    pub fn is_file(&self) -> bool {
+        !self.is_dir
+    }
+
+    /// Returns the decompressed size of a file, or 0 for a directory
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true for an empty file, and for any directory
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the modification time. Directories only implied by their contents
+    /// have none, which is an `Unsupported` error, as for a filesystem that doesn't
+    /// record it.
+    pub fn modified(&self) -> io::Result<SystemTime> {
+        self.modified.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, "no modification time recorded")
+        })
+    }
+
+    /// Returns the permissions. Directories only implied by their contents are
+    /// writable, with no mode.
+    pub fn permissions(&self) -> FsPermissions {
+        self.permissions
+    }
+}
+
+impl MetadataLike for FsMetadata {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn modified(&self) -> io::Result<SystemTime> {
+        self.modified()
+    }
+
+    fn permissions(&self) -> FsPermissions {
+        self.permissions
+    }
+
+    fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    fn is_file(&self) -> bool {
+        !self.is_dir
+    }
+}
+
+impl MetadataLike for EntryMeta {
+    fn len(&self) -> u64 {
+        self.header.uncompressed_size
+    }
+
+    fn modified(&self) -> io::Result<SystemTime> {
+        modified_time(&self.header).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, "no modification time recorded")
+        })
+    }
+
+    fn permissions(&self) -> FsPermissions {
+        permissions(&self.header)
+    }
+
+    fn is_dir(&self) -> bool {
+        entry_kind(&self.filename, &self.header) == EntryKind::Directory
+    }
+
+    fn is_file(&self) -> bool {
+        entry_kind(&self.filename, &self.header) == EntryKind::File
+    }
+}
+
+impl MetadataLike for std::fs::Metadata {
+    fn len(&self) -> u64 {
+        self.len()
+    }
+
+    fn modified(&self) -> io::Result<SystemTime> {
+        self.modified()
+    }
+
+    fn permissions(&self) -> FsPermissions {
+        FsPermissions {
+            readonly: self.permissions().readonly(),
+            mode: crate::platform::unix_mode(self),
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        self.is_dir()
+    }
+
+    fn is_file(&self) -> bool {
+        self.is_file()
+    }
+}
+
+/// An entry of a directory, yielded by `ReadDir`.
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    path: PathBuf,
+    metadata: FsMetadata,
+}
+
+impl DirEntry {
+    /// Returns the full path of the entry, from the root of the archive
+    pub fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    /// Returns the last component of the entry's path
+    pub fn file_name(&self) -> OsString {
+        self.path.file_name().unwrap_or_default().to_os_string()
+    }
+
+    /// Returns the entry's metadata
+    pub fn metadata(&self) -> io::Result<FsMetadata> {
+        Ok(self.metadata.clone())
+    }
+}
+
+/// An iterator over the contents of a directory, from `ArchiveFs::read_dir()`, in
+/// name order.
+pub struct ReadDir {
+    entries: std::vec::IntoIter<DirEntry>,
+}
+
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(Ok)
+    }
+}
+
+impl<'s, 'a, R: Read + Seek> ArchiveFs<'s, 'a, R> {
+    /// Creates an `ArchiveFs` over `archive`, working out its directory tree unless
+    /// the archive was opened with `ArchiveOptions::prefix_index()`.
+    pub fn new(archive: &'s SearchableArchive<'a, R>) -> Self {
+        let index = match &archive.prefix_index {
+            Some(index) => Cow::Borrowed(index),
+            None => Cow::Owned(PrefixIndex::new(&archive.entries)),
+        };
+        ArchiveFs { archive, index }
+    }
+
+    /// Reads the whole of a file, like `std::fs::read()`.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<u8>> {
+        let name = normalize(path.as_ref())?;
+        if self.index.is_dir(&name) {
+            return Err(io::Error::new(
+                io::ErrorKind::IsADirectory,
+                format!("'{name}' is a directory"),
+            ));
+        }
+
+        let header = self.archive.header(&name).ok_or_else(|| not_found(&name))?;
+        let data =
+            data_from_internal(&mut *self.archive.file.borrow_mut(), header).map_err(to_io)?;
+        self.archive
+            .crc
+            .check_data(&name, header, &data)
+            .map_err(to_io)?;
+        Ok(data)
+    }
+
+    /// Reads the whole of a file as UTF-8, like `std::fs::read_to_string()`.
+    pub fn read_to_string<P: AsRef<Path>>(&self, path: P) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Lists a directory, like `std::fs::read_dir()`.
+    pub fn read_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<ReadDir> {
+        let name = normalize(path.as_ref())?;
+        let Some(children) = self.index.children(&name) else {
+            return match self.archive.header(&name) {
+                Some(_) => Err(io::Error::new(
+                    io::ErrorKind::NotADirectory,
+                    format!("'{name}' is not a directory"),
+                )),
+                None => Err(not_found(&name)),
+            };
+        };
+
+        let entries: Vec<DirEntry> = children
+            .into_iter()
+            .map(|(child, is_dir, index)| DirEntry {
+                path: PathBuf::from(join(&name, &child)),
+                metadata: metadata(is_dir, index.map(|i| &self.archive.entries[i].1)),
+            })
+            .collect();
+
+        Ok(ReadDir {
+            entries: entries.into_iter(),
+        })
+    }
+
+    /// Returns the metadata of a file or directory, like `std::fs::metadata()`.
+    pub fn metadata<P: AsRef<Path>>(&self, path: P) -> io::Result<FsMetadata> {
+        let name = normalize(path.as_ref())?;
+        self.lookup(&name).ok_or_else(|| not_found(&name))
+    }
+
+    /// Returns true if there's a file or directory at `path`, like `Path::exists()`.
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    // The metadata of a normalized path, if anything is there.
+    fn lookup(&self, name: &str) -> Option<FsMetadata> {
+        let is_dir = self.index.is_dir(name);
+        let header = self.index.entry(name).map(|i| &self.archive.entries[i].1);
+        match is_dir || header.is_some() {
+            true => Some(metadata(is_dir, header)),
+            false => None,
+        }
+    }
+}
+
+// The metadata of a directory, which may have no entry, or of a file.
+fn metadata(is_dir: bool, header: Option<&InternalHeader>) -> FsMetadata {
+    FsMetadata {
+        is_dir,
+        len: match is_dir {
+            true => 0,
+            false => header.map_or(0, |h| h.uncompressed_size),
+        },
+        modified: header.and_then(modified_time),
+        permissions: header.map(permissions).unwrap_or(FsPermissions {
+            readonly: false,
+            mode: None,
+        }),
+    }
+}
+
+fn modified_time(header: &InternalHeader) -> Option<SystemTime> {
+    let secs = modified_secs(header)?;
+    match secs >= 0 {
+        true => UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64)),
+        false => UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs())),
+    }
+}
+
+fn permissions(header: &InternalHeader) -> FsPermissions {
+    FsPermissions {
+        readonly: is_readonly(header),
+        mode: unix_mode(header),
+    }
+}
+
+// Turn a path into an entry name: `/` separated, with no leading `/`, and `.` and
+// `..` resolved.
+fn normalize(path: &Path) -> io::Result<String> {
+    let mut parts: Vec<&str> = Vec::new();
+
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(part) => parts.push(part.to_str().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "path isn't valid UTF-8")
+            })?),
+        }
+    }
+
+    Ok(parts.join("/"))
+}
+
+fn join(dir: &str, child: &str) -> String {
+    match dir.is_empty() {
+        true => child.to_string(),
+        false => format!("{dir}/{child}"),
+    }
+}
+
+fn not_found(name: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("'{name}' not found"))
+}
+
+pub(crate) fn to_io(err: MuError) -> io::Error {
+    let kind = match (err.kind(), err.io_error()) {
+        (ErrorKind::Io, Some(io)) => io.kind(),
+        (ErrorKind::Io, None) => io::ErrorKind::Other,
+        (ErrorKind::Format | ErrorKind::Utf8, _) => io::ErrorKind::InvalidData,
+        _ => io::ErrorKind::Unsupported,
+    };
+    io::Error::new(kind, err)
+}
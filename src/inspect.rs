@@ -0,0 +1,69 @@
+use crate::iterable::EntryMeta;
+
+/// Looks over the contents of each file as `SearchableArchive::extract_inspected()`
+/// writes it, such as to scan uploads for malware before they're kept. The data is
+/// passed in chunks as it's decompressed, and any call can reject the file, which
+/// stops it there and removes what was written of it. What happens next is up to
+/// `ExtractOptions::on_reject()`.
+///
+/// # Examples
+///
+/// ``` no_run
+/// struct NoElf;
+///
+/// impl munzip::ContentInspector for NoElf {
+///     fn start(&mut self, _entry: &munzip::EntryMeta) {}
+///
+///     fn inspect(&mut self, offset: u64, chunk: &[u8]) -> munzip::Verdict {
+///         match offset == 0 && chunk.starts_with(b"\x7fELF") {
+///             true => munzip::Verdict::Reject("executable".to_string()),
+///             false => munzip::Verdict::Accept,
+///         }
+///     }
+///
+///     fn finish(&mut self) -> munzip::Verdict {
+///         munzip::Verdict::Accept
+///     }
+/// }
+///
+/// # let mut input = std::fs::File::open("upload.zip").unwrap();
+/// let zi = munzip::SearchableArchive::new(&mut input).unwrap();
+/// let options = munzip::ExtractOptions::new();
+/// let summary = zi.extract_inspected("out".as_ref(), &options, &mut NoElf).unwrap();
+/// for (name, reason) in &summary.rejected {
+///     eprintln!("rejected {name}: {reason}");
+/// }
+/// ```
+pub trait ContentInspector {
+    /// Called before each file's contents, so the inspector can reset for it
+    fn start(&mut self, entry: &EntryMeta);
+
+    /// Called with each chunk of the file's contents, in order. `offset` is where
+    /// the chunk starts in the file.
+    fn inspect(&mut self, offset: u64, chunk: &[u8]) -> Verdict;
+
+    /// Called once the whole file has been passed to `inspect()`, for a verdict that
+    /// needs all of it.
+    fn finish(&mut self) -> Verdict;
+}
+
+/// A `ContentInspector`'s decision on a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Carry on writing the file, or keep it if it's done
+    Accept,
+    /// Stop writing the file and remove it, giving the reason
+    Reject(String),
+}
+
+/// What `SearchableArchive::extract_inspected()` does once a file is rejected, for
+/// `ExtractOptions::on_reject()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RejectPolicy {
+    /// Leave the file out, and carry on with the rest of the archive
+    #[default]
+    Skip,
+    /// Stop extracting with an `ErrorKind::Rejected` error. Files already written
+    /// are left in place, unless extraction is `ExtractOptions::atomic()`.
+    Abort,
+}
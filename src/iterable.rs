@@ -1,8 +1,16 @@
 use std::cell::RefCell;
 use std::fs::File;
-use std::io::{Seek, SeekFrom};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::marker::PhantomData;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use crate::cache::EntryCache;
+use crate::crc32::{CrcCheck, CrcReader};
+#[cfg(feature = "metrics")]
+use crate::metrics::*;
+use crate::options::*;
 use crate::shared::*;
 use crate::types::*;
 
@@ -13,6 +21,8 @@ use crate::types::*;
 /// # Examples
 ///
 /// ``` no_run
+/// # fn write_file(_: &str, _: &[u8]) -> std::io::Result<()> { Ok(()) }
+/// # let mut my_file = std::fs::File::open("archive.zip").unwrap();
 /// let zi = munzip::IterableArchive::new(&mut my_file).unwrap();
 ///
 /// for entry in zi {
@@ -24,87 +34,756 @@ use crate::types::*;
 ///     write_file(&filename, &buffer).unwrap();
 /// }
 /// ```
-pub struct IterableArchive<'a> {
-    file: Rc<RefCell<&'a mut File>>,
-    end_rec: EndRecord,
+pub struct IterableArchive<'a, R: Read + Seek = &'a mut File> {
+    file: Rc<RefCell<R>>,
+    lifetime: PhantomData<&'a ()>,
+    end_rec: DirectoryEnd,
     next_gfh: u64,
-    next_entry: u16,
+    next_entry: u64,
     did_error: bool,
+    error_mode: ErrorMode,
+    include_apple_double: bool,
+    names: NameDecoding,
+    compatibility: Compatibility,
+    crc: Rc<CrcCheck>,
+    cache: Rc<EntryCache>,
+    buffer_sizes: BufferSizes,
+    #[cfg(feature = "metrics")]
+    metrics: Rc<RefCell<Recorder>>,
 }
 
-impl<'a> IterableArchive<'a> {
-    pub fn new(file: &'a mut File) -> Result<Self, MuError> {
-        let end_rec = read_end_record(file)?;
+impl<'a, R: Read + Seek> IterableArchive<'a, &'a mut R> {
+    /// Creates a new `IterableArchive` with the default `ArchiveOptions`. The archive
+    /// can be read from anything that's `Read + Seek`, not only a `File`. For bytes
+    /// already in memory, there's also `from_bytes()`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # fn download(_: &str) -> Vec<u8> { Vec::new() }
+    /// let mut body = std::io::Cursor::new(download("https://example.com/fonts.zip"));
+    /// let zi = munzip::IterableArchive::new(&mut body).unwrap();
+    ///
+    /// for entry in zi {
+    ///     let mut entry = entry.unwrap();
+    ///     println!("{}: {} bytes", entry.filename(), entry.buffer().unwrap().len());
+    /// }
+    /// ```
+    pub fn new(file: &'a mut R) -> Result<Self, MuError> {
+        Self::with_options(file, &ArchiveOptions::default())
+    }
+
+    /// Creates a new `IterableArchive`, configured by `options`.
+    pub fn with_options(file: &'a mut R, options: &ArchiveOptions) -> Result<Self, MuError> {
+        Self::open(file, options)
+    }
+}
+
+impl<'a, B: AsRef<[u8]>> IterableArchive<'a, Cursor<B>> {
+    /// Creates a new `IterableArchive` over an archive that's already in memory, such
+    /// as one received over the network, with the default `ArchiveOptions`. `bytes`
+    /// can be borrowed, as a `&[u8]`, or owned, as a `Vec<u8>`. Entries of borrowed
+    /// bytes can hand out their stored data without copying it, see
+    /// `Entry::stored_data()`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # fn receive() -> Vec<u8> { Vec::new() }
+    /// let payload: Vec<u8> = receive();
+    /// let zi = munzip::IterableArchive::from_bytes(&payload[..]).unwrap();
+    ///
+    /// for entry in zi {
+    ///     let mut entry = entry.unwrap();
+    ///     let data = match entry.stored_data() {
+    ///         Some(data) => std::borrow::Cow::Borrowed(data),
+    ///         None => std::borrow::Cow::Owned(entry.buffer().unwrap()),
+    ///     };
+    ///     println!("{}: {} bytes", entry.filename(), data.len());
+    /// }
+    /// ```
+    pub fn from_bytes(bytes: B) -> Result<Self, MuError> {
+        Self::from_bytes_with_options(bytes, &ArchiveOptions::default())
+    }
+
+    /// Creates a new `IterableArchive` over an archive in memory, configured by
+    /// `options`.
+    pub fn from_bytes_with_options(bytes: B, options: &ArchiveOptions) -> Result<Self, MuError> {
+        Self::open(Cursor::new(bytes), options)
+    }
+}
+
+impl<'a, R: Read + Seek> IterableArchive<'a, R> {
+    fn open(mut file: R, options: &ArchiveOptions) -> Result<Self, MuError> {
+        let limit = options
+            .end_record_search_limit
+            .unwrap_or(END_RECORD_SEARCH_LIMIT);
+        let (end_rec, cd_offset) = find_end_record(&mut file, limit)?;
+        check_declared_size(&end_rec, options)?;
+        let names = name_decoding(&mut file, &end_rec, cd_offset, options)?;
         let next_entry = 0;
         let did_error = false;
 
-        file.seek(SeekFrom::Start(end_rec.central_directory_offset as u64))?;
+        file.seek(SeekFrom::Start(cd_offset))?;
         let next_gfh = file.stream_position()?;
 
         Ok(Self {
             file: Rc::new(RefCell::new(file)),
+            lifetime: PhantomData,
             end_rec,
             next_gfh,
             next_entry,
             did_error,
+            error_mode: options.error_mode,
+            include_apple_double: options.include_apple_double,
+            names,
+            compatibility: options.compatibility,
+            crc: Rc::new(CrcCheck::new(options.crc_policy)),
+            cache: Rc::new(EntryCache::new(options.entry_cache)),
+            buffer_sizes: options.buffer_sizes,
+            #[cfg(feature = "metrics")]
+            metrics: Rc::new(RefCell::new(Recorder::default())),
         })
     }
+
+    /// Registers a callback invoked after each `Entry::buffer()` with that entry's
+    /// timings. Requires the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn on_entry_metrics(&mut self, callback: impl FnMut(&EntryMetrics) + 'static) {
+        self.metrics.borrow_mut().callback = Some(Box::new(callback));
+    }
+
+    /// Returns the timings of every entry buffered so far. Iterate with `by_ref()`
+    /// to keep the archive around after a bulk extraction. Requires the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> MetricsSummary {
+        self.metrics.borrow().summary.clone()
+    }
+
+    /// Returns the names of the entries read so far whose data didn't match their
+    /// CRC-32, under `CrcPolicy::Record` or `CrcPolicy::Fix`. Iterate with
+    /// `by_ref()` to keep the archive around to ask.
+    pub fn crc_mismatches(&self) -> Vec<String> {
+        self.crc.mismatches()
+    }
+
+    /// Turns the archive into an iterator over each entry's metadata, read from the
+    /// central directory alone, for listing names and sizes without so much as
+    /// seeking to a local header. The local header can disagree with the central
+    /// directory, so an entry's `EntryMeta::data_offset()` isn't known, and `EntryMeta::provenance()`
+    /// has every field from the central directory record or its extra fields. A
+    /// record that can't be read ends iteration, and `__MACOSX/` entries are left
+    /// out unless `ArchiveOptions::include_apple_double()` is set.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("archive.zip").unwrap();
+    /// let zi = munzip::IterableArchive::new(&mut file).unwrap();
+    ///
+    /// for meta in zi.metadata_only() {
+    ///     let meta = meta.unwrap();
+    ///     println!("{}: {} bytes", meta.filename(), meta.uncompressed_size());
+    /// }
+    /// ```
+    pub fn metadata_only(self) -> CentralEntries<'a, R> {
+        CentralEntries { inner: self }
+    }
+
+    /// Turns the archive into an iterator over just the entries that can be read,
+    /// handing the error for each one that can't to `on_error`, to be logged or
+    /// counted, and carrying on past it as `ErrorMode::Continue` does, whichever mode
+    /// the archive was opened with. A central directory record that can't be read
+    /// still ends iteration, once its error has been handed over.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("damaged.zip").unwrap();
+    /// let zi = munzip::IterableArchive::new(&mut file).unwrap();
+    ///
+    /// for mut entry in zi.skip_data_errors(|e| eprintln!("skipping: {e}")) {
+    ///     let data = entry.buffer().unwrap();
+    ///     println!("{}: {} bytes", entry.filename(), data.len());
+    /// }
+    /// ```
+    pub fn skip_data_errors<F: FnMut(MuError)>(mut self, on_error: F) -> SkipDataErrors<'a, R, F> {
+        self.error_mode = ErrorMode::Continue;
+        SkipDataErrors {
+            inner: self,
+            on_error,
+        }
+    }
 }
 
-impl<'a> Iterator for IterableArchive<'a> {
-    type Item = Result<Entry<'a>, MuError>;
+impl<'a, R: Read + Seek> Iterator for IterableArchive<'a, R> {
+    type Item = Result<Entry<'a, R>, MuError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // shouldn't be possible
-        if self.next_entry > self.end_rec.num_entries {
-            panic!("wtf");
-        }
-        // the end
-        if self.next_entry == self.end_rec.num_entries || self.did_error {
-            return None;
+        loop {
+            // shouldn't be possible
+            if self.next_entry > self.end_rec.num_entries {
+                panic!("wtf");
+            }
+            // the end
+            if self.next_entry == self.end_rec.num_entries || self.did_error {
+                return None;
+            }
+
+            let mut file = self.file.borrow_mut();
+
+            let gh = next_global_header(&mut *file, self.next_gfh);
+            if let Err(e) = gh {
+                self.did_error = true;
+                return Some(Err(e));
+            }
+
+            let (gfh, new_next_gfh) = gh.unwrap();
+            let position = std::mem::replace(&mut self.next_gfh, new_next_gfh);
+            let index = self.next_entry as usize;
+            self.next_entry += 1;
+
+            let lh = local_header(
+                &mut *file,
+                0,
+                &gfh,
+                position,
+                index,
+                &self.names,
+                &self.compatibility,
+            );
+            if let Err(e) = lh {
+                self.did_error = self.error_mode == ErrorMode::Stop;
+                return Some(Err(central_context(
+                    &mut *file,
+                    &gfh,
+                    position,
+                    index,
+                    &self.names,
+                    e,
+                )));
+            }
+
+            let (header, filename) = lh.unwrap();
+
+            if !self.include_apple_double && is_apple_double(&filename) {
+                continue;
+            }
+
+            return Some(Ok(Entry {
+                file: Rc::clone(&self.file),
+                lifetime: PhantomData,
+                header,
+                filename,
+                crc: Rc::clone(&self.crc),
+                cache: Rc::clone(&self.cache),
+                buffer_sizes: self.buffer_sizes,
+                #[cfg(feature = "metrics")]
+                metrics: Rc::clone(&self.metrics),
+            }));
         }
+    }
+}
 
-        let nh = next_header(&mut *self.file.borrow_mut(), self.next_gfh);
-        if let Err(e) = nh {
-            self.did_error = true;
-            return Some(Err(e));
+/// An iterator over the entries of an `IterableArchive` that can be read, from
+/// `IterableArchive::skip_data_errors()`.
+pub struct SkipDataErrors<'a, R: Read + Seek, F> {
+    inner: IterableArchive<'a, R>,
+    on_error: F,
+}
+
+impl<'a, R: Read + Seek, F: FnMut(MuError)> Iterator for SkipDataErrors<'a, R, F> {
+    type Item = Entry<'a, R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(entry) => return Some(entry),
+                Err(e) => (self.on_error)(e),
+            }
         }
+    }
+}
 
-        let (header, filename, new_next_gfh) = nh.unwrap();
-        self.next_gfh = new_next_gfh;
-        self.next_entry += 1;
+/// An iterator over the metadata of an `IterableArchive`'s entries, from the
+/// central directory alone, from `IterableArchive::metadata_only()`.
+pub struct CentralEntries<'a, R: Read + Seek> {
+    inner: IterableArchive<'a, R>,
+}
 
-        Some(Ok(Entry {
-            file: Rc::clone(&self.file),
-            header,
-            filename,
-        }))
+impl<'a, R: Read + Seek> Iterator for CentralEntries<'a, R> {
+    type Item = Result<EntryMeta, MuError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let archive = &mut self.inner;
+        loop {
+            if archive.next_entry == archive.end_rec.num_entries || archive.did_error {
+                return None;
+            }
+
+            let mut file = archive.file.borrow_mut();
+            let index = archive.next_entry as usize;
+            archive.next_entry += 1;
+
+            let meta = next_global_header(&mut *file, archive.next_gfh).and_then(|(gfh, next)| {
+                let position = std::mem::replace(&mut archive.next_gfh, next);
+                central_meta(&mut *file, &gfh, position, index, &archive.names)
+            });
+            let meta = match meta {
+                Ok(meta) => meta,
+                Err(e) => {
+                    archive.did_error = true;
+                    return Some(Err(e));
+                }
+            };
+
+            if !archive.include_apple_double && is_apple_double(&meta.filename) {
+                continue;
+            }
+            return Some(Ok(meta));
+        }
     }
 }
 
 /// An entry in the archive. An entry may be a file or a directory.
 /// No contents are read until `Entry::buffer()` is invoked.
-pub struct Entry<'a> {
-    file: Rc<RefCell<&'a mut File>>,
-    header: InternalHeader,
-    filename: String,
+pub struct Entry<'a, R: Read + Seek = &'a mut File> {
+    pub(crate) file: Rc<RefCell<R>>,
+    pub(crate) lifetime: PhantomData<&'a ()>,
+    pub(crate) header: InternalHeader,
+    pub(crate) filename: String,
+    pub(crate) crc: Rc<CrcCheck>,
+    pub(crate) cache: Rc<EntryCache>,
+    pub(crate) buffer_sizes: BufferSizes,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics: Rc<RefCell<Recorder>>,
 }
 
-impl<'a> Entry<'a> {
-    /// Reads in the compressed data, then decompresses it.
+impl<'a, R: Read + Seek> Entry<'a, R> {
+    /// Reads in the compressed data, then decompresses it. With
+    /// `ArchiveOptions::entry_cache()` set, an entry still in the cache is copied
+    /// out of it instead.
     pub fn buffer(&mut self) -> Result<Vec<u8>, MuError> {
-        data_from_internal(&mut *self.file.borrow_mut(), &self.header)
+        if let Some(data) = self.cache.get(self.header.central_index) {
+            return Ok(data.to_vec());
+        }
+
+        let data = self.read_data()?;
+        if self.cache.enabled() {
+            self.cache
+                .insert(self.header.central_index, Arc::from(data.as_slice()));
+        }
+        Ok(data)
+    }
+
+    // Read and decompress the data, checking its CRC-32, bypassing the cache.
+    fn read_data(&self) -> Result<Vec<u8>, MuError> {
+        #[cfg(feature = "metrics")]
+        let start = std::time::Instant::now();
+
+        let data = data_from_internal(&mut *self.file.borrow_mut(), &self.header)
+            .and_then(|data| {
+                self.crc.check_data(&self.filename, &self.header, &data)?;
+                Ok(data)
+            })
+            .map_err(|e| e.with_entry(self.meta()))?;
+
+        #[cfg(feature = "metrics")]
+        self.metrics
+            .borrow_mut()
+            .record(&self.filename, &self.header, data.len() as u64, start);
+
+        Ok(data)
     }
-    /// Returns a copy of the Entry's filename
+    /// Reads and decompresses the data as `buffer()` does, into an `Arc<[u8]>`, so a
+    /// server can cache an entry and hand it to every request that wants it by
+    /// cloning the `Arc` rather than the data. With `ArchiveOptions::entry_cache()`
+    /// set, an entry still in the cache is returned without reading anything.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// use std::collections::HashMap;
+    /// use std::sync::Arc;
+    ///
+    /// # let mut file = std::fs::File::open("site.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// let mut cache: HashMap<String, Arc<[u8]>> = HashMap::new();
+    /// for mut entry in zi.entries() {
+    ///     cache.insert(entry.filename(), entry.read_shared().unwrap());
+    /// }
+    /// let index = Arc::clone(&cache["index.html"]);
+    /// std::thread::spawn(move || println!("{} bytes", index.len()));
+    /// ```
+    pub fn read_shared(&mut self) -> Result<Arc<[u8]>, MuError> {
+        if let Some(data) = self.cache.get(self.header.central_index) {
+            return Ok(data);
+        }
+
+        let data: Arc<[u8]> = self.read_data()?.into();
+        self.cache
+            .insert(self.header.central_index, Arc::clone(&data));
+        Ok(data)
+    }
+    /// Decompresses no more than the first `n` bytes of the entry and returns them,
+    /// fewer only if the entry is shorter, for reading a magic number or a header
+    /// without inflating the rest of a large file. Nothing past `n` is read, so the
+    /// CRC-32 isn't checked. An entry still in the cache set by
+    /// `ArchiveOptions::entry_cache()` is copied out of it instead.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("uploads.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// for entry in zi.entries() {
+    ///     let magic = entry.read_at_most(4).unwrap();
+    ///     if magic == b"%PDF" {
+    ///         println!("{} is a PDF", entry.filename());
+    ///     }
+    /// }
+    /// ```
+    pub fn read_at_most(&self, n: u64) -> Result<Vec<u8>, MuError> {
+        if let Some(data) = self.cache.get(self.header.central_index) {
+            return Ok(data[..n.min(data.len() as u64) as usize].to_vec());
+        }
+
+        let mut data = Vec::with_capacity(self.uncompressed_size().min(n) as usize);
+        self.range_reader(0..n)?
+            .read_to_end(&mut data)
+            .map_err(|e| MuError::from(e).with_entry(self.meta()))?;
+        Ok(data)
+    }
+    /// Returns a copy of the Entry's filename. Any `\` separators written by
+    /// Windows tools are replaced with `/`.
     pub fn filename(&self) -> String {
         self.filename.clone()
     }
+    /// Returns the Entry's filename exactly as it's stored in the archive
+    pub fn filename_raw(&self) -> &[u8] {
+        &self.header.filename_raw
+    }
+    /// Returns true if the filename couldn't be decoded, as UTF-8 or the detected
+    /// charset, so `filename()` has `U+FFFD` in place of the bytes that weren't
+    /// valid. `filename_raw()` still has them.
+    pub fn name_is_lossy(&self) -> bool {
+        self.header.name_lossy
+    }
+    /// Returns the modification time, from the extended timestamp field if there is
+    /// one, or else the DOS date and time (which have no time zone, and are taken
+    /// as UTC). Returns `None` if the DOS date is invalid.
+    pub fn modified(&self) -> Option<SystemTime> {
+        let secs = modified_secs(&self.header)?;
+
+        if secs >= 0 {
+            UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+        } else {
+            UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+        }
+    }
+    /// Returns the compressed size of the file
+    pub fn compressed_size(&self) -> u64 {
+        self.header.compressed_size
+    }
+    /// Returns the uncompressed size of the file
+    pub fn uncompressed_size(&self) -> u64 {
+        self.header.uncompressed_size
+    }
+    /// Returns the entry's position in the central directory, counting from 0. Entries
+    /// that are left out, such as `__MACOSX/` ones or those skipped under
+    /// `ErrorMode::Continue`, still take up their place, so this is the order the
+    /// archive was written in.
+    pub fn central_directory_index(&self) -> usize {
+        self.header.central_index
+    }
+    /// Returns where the entry's local header starts in the archive file
+    pub fn local_header_offset(&self) -> u64 {
+        self.header.local_header_offset
+    }
+    /// Returns true if the sizes or offset are recorded in a ZIP64 extra field,
+    /// rather than the headers' 32-bit fields. The accessors report the same `u64`s
+    /// either way.
+    pub fn is_zip64(&self) -> bool {
+        self.header.zip64
+    }
+    /// Returns the compression method, `0` for `STORE` or `8` for `DEFLATE`
+    pub fn compression_method(&self) -> u16 {
+        self.header.compression_method
+    }
+    /// Returns true if the entry is encrypted, traditionally or strongly. Its name,
+    /// sizes and the rest of its metadata can be read all the same, from the central
+    /// directory, but reading its data fails with `ErrorKind::Encrypted`.
+    pub fn is_encrypted(&self) -> bool {
+        GeneralPurposeFlags(self.header.general_purpose_bit_flag).is_encrypted()
+    }
+    /// Returns where the Entry's data begins in the archive file, just past its
+    /// local header. Together with `compressed_range()`, this lets a stored entry
+    /// be handed to `sendfile` or `mmap` without going through `buffer()`.
+    pub fn data_offset(&self) -> u64 {
+        self.header.offset
+    }
+    /// Returns the span of the archive file holding the Entry's data, exactly as
+    /// stored. For a `STORE` entry that's the file itself, but no checks (on
+    /// encryption, or the CRC) are made on it.
+    pub fn compressed_range(&self) -> std::ops::Range<u64> {
+        self.data_offset()..self.data_offset() + self.header.compressed_size
+    }
+    /// Returns a reader over bytes `range` of the Entry's decompressed contents, as
+    /// for an HTTP `Range` request. A stored entry is read straight from the range's
+    /// place in the archive. A deflated one has to be inflated from the start, but
+    /// what comes before the range is discarded as it goes, and nothing past it is
+    /// inflated. An `end` past the end of the contents is cut short, as HTTP does,
+    /// but a `start` past it is an error.
+    ///
+    /// The reader shares the archive's file handle, seeking it on every read, so it
+    /// can be interleaved with reads of other entries.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("site.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    /// let video = zi.entries().find(|e| e.filename() == "intro.mp4").unwrap();
+    ///
+    /// // bytes=1000000-1999999
+    /// let mut reader = video.range_reader(1_000_000..2_000_000).unwrap();
+    /// std::io::copy(&mut reader, &mut std::io::stdout()).unwrap();
+    /// ```
+    pub fn range_reader(&self, range: std::ops::Range<u64>) -> Result<Box<dyn Read + 'a>, MuError>
+    where
+        R: 'a,
+    {
+        check_flags(&self.header)?;
+
+        let size = self.header.uncompressed_size;
+        if range.start > size {
+            return Err(MuError::new(
+                ErrorKind::Other,
+                format!("range starts past the end of '{}'", self.filename),
+            ));
+        }
+        let len = range.end.min(size).saturating_sub(range.start);
+
+        let data = self.header.offset;
+        match self.header.compression_method {
+            0 => Ok(Box::new(
+                Shared::new(&self.file, data + range.start).take(len),
+            )),
+            #[cfg(feature = "deflate")]
+            8 => {
+                let compressed = Shared::new(&self.file, data).take(self.compressed_size());
+                let mut inflated = inflate::DeflateDecoder::new(compressed);
+                let skipped =
+                    std::io::copy(&mut (&mut inflated).take(range.start), &mut std::io::sink())?;
+                if skipped < range.start {
+                    return Err(MuError::new(
+                        ErrorKind::Format,
+                        format!("'{}' is shorter than its recorded size", self.filename),
+                    ));
+                }
+                Ok(Box::new(inflated.take(len)))
+            }
+            method => Err(MuError::new(
+                ErrorKind::UnsupportedMethod(method),
+                format!("compression method {method} not supported"),
+            )),
+        }
+    }
+    /// Returns a reader over the Entry's decompressed contents, inflating them only as
+    /// they're read, so an entry of any size can be copied straight to disk with
+    /// `std::io::copy()` rather than held in memory as `buffer()` would. The CRC-32
+    /// is checked under the archive's `CrcPolicy` once the end is reached, and a
+    /// mismatch is an `InvalidData` error from the read that reaches it, which
+    /// converts back to the `ErrorKind::CrcMismatch` `MuError` it wraps.
+    ///
+    /// The reader shares the archive's file handle as `range_reader()`'s does.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("backup.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    /// let image = zi.entries().find(|e| e.filename() == "disk.img").unwrap();
+    ///
+    /// let mut out = std::fs::File::create("disk.img").unwrap();
+    /// std::io::copy(&mut image.reader().unwrap(), &mut out).unwrap();
+    /// ```
+    pub fn reader(&self) -> Result<Box<dyn Read + 'a>, MuError>
+    where
+        R: 'a,
+    {
+        check_flags(&self.header)?;
+
+        let compressed = Shared::new(&self.file, self.header.offset).take(self.compressed_size());
+        let data = decompressor(self.header.compression_method, compressed)?;
+
+        Ok(Box::new(EntryReader {
+            data: CrcReader::new(data),
+            crc: Rc::clone(&self.crc),
+            meta: self.meta(),
+            checked: false,
+        }))
+    }
+    /// Returns a copy of the Entry's metadata, which has no hold on the archive
+    pub fn meta(&self) -> EntryMeta {
+        EntryMeta {
+            header: self.header.clone(),
+            filename: self.filename.clone(),
+            local_read: true,
+        }
+    }
+}
+
+impl<'a> Entry<'a, Cursor<&'a [u8]>> {
+    /// Returns the entry's data as a slice of the bytes the archive was made from with
+    /// `IterableArchive::from_bytes()`, without copying or reading anything. Only a
+    /// stored, unencrypted entry's data can be had this way, and its CRC-32 isn't
+    /// checked. Returns `None` for any other entry, or one whose data runs past the end
+    /// of the bytes.
+    pub fn stored_data(&self) -> Option<&'a [u8]> {
+        if self.header.compression_method != 0 || check_flags(&self.header).is_err() {
+            return None;
+        }
+
+        let bytes: &'a [u8] = self.file.borrow().get_ref();
+        let start = usize::try_from(self.header.offset).ok()?;
+        bytes.get(start..start.checked_add(self.header.compressed_size as usize)?)
+    }
+}
+
+// Reads the archive file from `position` on, through a handle that's shared with every
+// other entry, so it seeks back to where it left off each time.
+pub(crate) struct Shared<R> {
+    file: Rc<RefCell<R>>,
+    position: u64,
+}
+
+impl<R: Read + Seek> Shared<R> {
+    pub(crate) fn new(file: &Rc<RefCell<R>>, position: u64) -> Self {
+        Shared {
+            file: Rc::clone(file),
+            position,
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for Shared<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(self.position))?;
+        let n = file.read(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+// An entry's decompressed contents for `Entry::reader()`, checking the CRC-32 as
+// the end is reached.
+struct EntryReader<'a> {
+    data: CrcReader<Box<dyn Read + 'a>>,
+    crc: Rc<CrcCheck>,
+    meta: EntryMeta,
+    checked: bool,
+}
+
+impl Read for EntryReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.data.read(buf)?;
+        if n == 0 && !buf.is_empty() && !self.checked {
+            self.checked = true;
+            let actual = self.data.crc32();
+            self.crc
+                .check(&self.meta.filename, &self.meta.header, actual)
+                .map_err(|e| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        e.with_entry(self.meta.clone()),
+                    )
+                })?;
+        }
+        Ok(n)
+    }
+}
+
+/// The metadata of an `Entry`, without a way to read its data. See
+/// `Entries::metadata_only()`.
+#[derive(Debug, Clone)]
+pub struct EntryMeta {
+    pub(crate) header: InternalHeader,
+    pub(crate) filename: String,
+    // whether the local header was read, without which the data offset isn't known
+    pub(crate) local_read: bool,
+}
+
+impl EntryMeta {
+    /// Returns the entry's filename, with `/` separators
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+    /// Returns the entry's filename exactly as it's stored in the archive
+    pub fn filename_raw(&self) -> &[u8] {
+        &self.header.filename_raw
+    }
+    /// Returns true if the filename was decoded lossily, as `Entry::name_is_lossy()`
+    /// does
+    pub fn name_is_lossy(&self) -> bool {
+        self.header.name_lossy
+    }
+    /// Returns the modification time, as `Entry::modified()` does
+    pub fn modified(&self) -> Option<SystemTime> {
+        let secs = modified_secs(&self.header)?;
+
+        if secs >= 0 {
+            UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+        } else {
+            UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+        }
+    }
     /// Returns the compressed size of the file
-    pub fn compressed_size(&self) -> usize {
-        self.header.compressed_size as usize
+    pub fn compressed_size(&self) -> u64 {
+        self.header.compressed_size
     }
     /// Returns the uncompressed size of the file
-    pub fn uncompressed_size(&self) -> usize {
-        self.header.uncompressed_size as usize
+    pub fn uncompressed_size(&self) -> u64 {
+        self.header.uncompressed_size
+    }
+    /// Returns the entry's position in the central directory, counting from 0. Entries
+    /// that are left out, such as `__MACOSX/` ones or those skipped under
+    /// `ErrorMode::Continue`, still take up their place, so this is the order the
+    /// archive was written in.
+    pub fn central_directory_index(&self) -> usize {
+        self.header.central_index
+    }
+    /// Returns where the entry's local header starts in the archive file
+    pub fn local_header_offset(&self) -> u64 {
+        self.header.local_header_offset
+    }
+    /// Returns true if the sizes or offset are recorded in a ZIP64 extra field,
+    /// rather than the headers' 32-bit fields. The accessors report the same `u64`s
+    /// either way.
+    pub fn is_zip64(&self) -> bool {
+        self.header.zip64
+    }
+    /// Returns the compression method, `0` for `STORE` or `8` for `DEFLATE`
+    pub fn compression_method(&self) -> u16 {
+        self.header.compression_method
+    }
+    /// Returns true if the entry is encrypted, as `Entry::is_encrypted()` does
+    pub fn is_encrypted(&self) -> bool {
+        GeneralPurposeFlags(self.header.general_purpose_bit_flag).is_encrypted()
+    }
+    /// Returns where the entry's data begins in the archive file, or `None` if its
+    /// local header wasn't read, as for `IterableArchive::metadata_only()` and the
+    /// entry of an error reading it
+    pub fn data_offset(&self) -> Option<u64> {
+        self.local_read.then_some(self.header.offset)
+    }
+    /// Returns the span of the archive file holding the entry's data, exactly as
+    /// stored, or `None` where `data_offset()` is
+    pub fn compressed_range(&self) -> Option<std::ops::Range<u64>> {
+        let offset = self.data_offset()?;
+        Some(offset..offset + self.header.compressed_size)
     }
 }
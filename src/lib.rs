@@ -1,8 +1,122 @@
+mod analysis;
+#[cfg(target_os = "macos")]
+mod appledouble;
+mod attributes;
+mod audit;
+mod batch;
+mod cache;
+mod central;
+#[cfg(feature = "charset")]
+mod charset;
+#[cfg(feature = "deflate")]
+mod checkpoints;
+mod classify;
+mod crc32;
+#[cfg(feature = "crypto")]
+mod decrypt;
+#[cfg(feature = "writer")]
+mod deflate;
+mod dostime;
+#[cfg(feature = "writer")]
+mod edit;
+#[cfg(feature = "embed")]
+mod embed;
+mod entropy;
+mod extract;
+mod findings;
+mod fingerprint;
+mod fs;
+mod inspect;
 mod iterable;
+#[cfg(feature = "writer")]
+mod merge;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod offsets;
+mod options;
+#[cfg(feature = "crypto")]
+mod password;
+mod patient;
+mod peek;
+mod platform;
+mod prefix;
+mod provenance;
+#[cfg(feature = "deflate")]
+mod raw;
+mod receipt;
+#[cfg(feature = "writer")]
+mod recompress;
+#[cfg(feature = "writer")]
+mod repair;
 mod searchable;
+mod seekmap;
+mod sentinel;
 mod shared;
+pub mod spec;
+#[cfg(feature = "stream")]
+mod stream;
+mod tar;
+mod temp;
+mod truncation;
 mod types;
+#[cfg(all(feature = "io-uring", target_os = "linux", target_pointer_width = "64"))]
+mod uring;
+#[cfg(feature = "writer")]
+mod validate;
+#[cfg(feature = "writer")]
+mod write;
 
+pub use analysis::*;
+pub use attributes::{EntryKind, HostSystem, SpecialKind};
+pub use audit::*;
+pub use batch::{BatchLookup, ReadSpan};
+pub use cache::CacheStats;
+pub use central::CentralDirectoryBytes;
+#[cfg(feature = "charset")]
+pub use charset::Charset;
+#[cfg(feature = "deflate")]
+pub use checkpoints::InflateIndex;
+pub use classify::FileClass;
+pub use crc32::{Crc32, CrcReader};
+#[cfg(feature = "crypto")]
+pub use decrypt::{Decryptor, EncryptionScheme, ZipCrypto};
+pub use dostime::DosDateTime;
+#[cfg(feature = "writer")]
+pub use edit::*;
+#[cfg(feature = "embed")]
+pub use embed::{embed_archive, EmbeddedArchive, EmbeddedEntry};
+pub use entropy::EntropyEstimate;
+pub use extract::{ChangeCheck, ExtractOptions, ExtractSummary};
+pub use findings::{Finding, FindingKind, Severity};
+pub use fingerprint::Fingerprint;
+pub use fs::{ArchiveFs, DirEntry, FsMetadata, FsPermissions, MetadataLike, ReadDir};
+pub use inspect::*;
 pub use iterable::*;
+#[cfg(feature = "writer")]
+pub use merge::*;
+#[cfg(feature = "metrics")]
+pub use metrics::{EntryMetrics, MetricsCallback, MetricsSummary};
+pub use offsets::*;
+pub use options::*;
+#[cfg(feature = "crypto")]
+pub use password::{Password, PasswordMatch, PasswordSearch};
+pub use patient::PatientReader;
+pub use peek::{peek, ArchivePeek};
+pub use prefix::DirChild;
+pub use provenance::{FieldSource, Provenance};
+#[cfg(feature = "deflate")]
+pub use raw::{inflate_raw, inflate_raw_with_dictionary, RawInflater};
+pub use receipt::{Receipt, ReceiptEntry, Sanitization};
+#[cfg(feature = "writer")]
+pub use recompress::{recompress, recompress_with_options, RecompressOptions, RecompressReport};
+#[cfg(feature = "writer")]
+pub use repair::{repair, RepairReport};
 pub use searchable::*;
-pub use types::MuError;
+pub use seekmap::EntryRange;
+#[cfg(feature = "stream")]
+pub use stream::ZipStream;
+pub use temp::TempFile;
+pub use truncation::*;
+pub use types::{ErrorKind, MuError, Quota};
+#[cfg(feature = "writer")]
+pub use write::*;
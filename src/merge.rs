@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use crate::searchable::SearchableArchive;
+use crate::types::*;
+use crate::write::{copied_size, ZipWriter};
+
+// an end record, with no comment
+const END_RECORD_SIZE: u64 = 22;
+
+/// What `merge()` does when more than one source has an entry by the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Keep the entry from the earliest source
+    First,
+    /// Keep the entry from the latest source, in the place the first one had
+    Last,
+    /// Fail, naming the entry
+    Error,
+    /// Keep every entry, giving the later ones a ` (2)`, ` (3)`, ... before their
+    /// extension
+    Rename,
+}
+
+/// Copies every entry of `sources` into `dest`, in order, resolving names that
+/// appear in more than one source by `policy`. Directories that several sources
+/// have are written once, whatever the policy.
+///
+/// Entries are copied exactly as they're stored, with `ZipWriter::copy_entry()`,
+/// so nothing is recompressed. `dest` isn't finished, so more can be added to it
+/// afterwards.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # let mut base_file = std::fs::File::open("base.zip").unwrap();
+/// # let mut patch_file = std::fs::File::open("patch.zip").unwrap();
+/// let base = munzip::SearchableArchive::new(&mut base_file).unwrap();
+/// let patch = munzip::SearchableArchive::new(&mut patch_file).unwrap();
+///
+/// let mut zw = munzip::ZipWriter::new(std::fs::File::create("merged.zip").unwrap());
+/// munzip::merge(&[&base, &patch], &mut zw, munzip::DuplicatePolicy::Last).unwrap();
+/// zw.finish().unwrap();
+/// ```
+pub fn merge<W: Write + Seek, R: Read + Seek>(
+    sources: &[&SearchableArchive<'_, R>],
+    dest: &mut ZipWriter<W>,
+    policy: DuplicatePolicy,
+) -> Result<(), MuError> {
+    // which source and entry each output name comes from, in output order
+    let mut plan: Vec<(String, usize, usize)> = Vec::new();
+    let mut planned: HashMap<String, usize> = HashMap::new();
+
+    for (s, source) in sources.iter().enumerate() {
+        for (e, (filename, _)) in source.entries.iter().enumerate() {
+            let Some(&at) = planned.get(filename) else {
+                planned.insert(filename.clone(), plan.len());
+                plan.push((filename.clone(), s, e));
+                continue;
+            };
+
+            if filename.ends_with('/') {
+                continue;
+            }
+            match policy {
+                DuplicatePolicy::First => {}
+                DuplicatePolicy::Last => plan[at] = (filename.clone(), s, e),
+                DuplicatePolicy::Error => {
+                    return Err(MuError::new(
+                        ErrorKind::Other,
+                        format!("'{filename}' is in more than one archive"),
+                    ));
+                }
+                DuplicatePolicy::Rename => {
+                    let renamed = (2..)
+                        .map(|n| numbered(filename, n))
+                        .find(|name| !planned.contains_key(name))
+                        .unwrap();
+                    planned.insert(renamed.clone(), plan.len());
+                    plan.push((renamed, s, e));
+                }
+            }
+        }
+    }
+
+    let entries: Vec<Vec<_>> = sources.iter().map(|s| s.entries().collect()).collect();
+    for (name, s, e) in plan {
+        dest.copy_entry(&entries[s][e], &name)?;
+    }
+
+    Ok(())
+}
+
+/// Spreads the entries of `source` over as many archives as it takes to keep each
+/// one within `max_part_size` bytes, and returns their paths. For a `path` of
+/// `big.zip`, they're named `big-1.zip`, `big-2.zip` and so on.
+///
+/// Unlike `ZipWriter::split()`, each part is a complete archive that can be opened
+/// on its own. Entries keep their order, and are copied as they're stored, without
+/// recompressing. An entry too large for a part by itself is an error, found before
+/// anything is written.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # let mut file = std::fs::File::open("assets.zip").unwrap();
+/// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+///
+/// for part in munzip::repartition(&zi, "upload/assets.zip", 25 << 20).unwrap() {
+///     println!("{}", part.display());
+/// }
+/// ```
+pub fn repartition<R: Read + Seek>(
+    source: &SearchableArchive<'_, R>,
+    path: impl AsRef<Path>,
+    max_part_size: u64,
+) -> Result<Vec<PathBuf>, MuError> {
+    let path = path.as_ref();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let part_path = |n: usize| path.with_file_name(format!("{stem}-{n}.zip"));
+
+    // every size is checked before any part is written
+    let mut sized = Vec::new();
+    for entry in source.entries() {
+        let size = copied_size(&entry, &entry.filename)?;
+        if size + END_RECORD_SIZE > max_part_size {
+            return Err(MuError::new(
+                ErrorKind::Other,
+                format!(
+                    "'{}' takes {size} bytes, too many for a part of {max_part_size}",
+                    entry.filename
+                ),
+            ));
+        }
+        sized.push((entry, size));
+    }
+
+    let mut parts = Vec::new();
+    let mut current: Option<(ZipWriter<File>, u64)> = None;
+
+    for (entry, size) in sized {
+        if let Some((zw, used)) = current.take() {
+            if used + size + END_RECORD_SIZE > max_part_size {
+                zw.finish()?;
+            } else {
+                current = Some((zw, used));
+            }
+        }
+
+        let (zw, used) = match current.as_mut() {
+            Some(part) => part,
+            None => {
+                let path = part_path(parts.len() + 1);
+                let zw = ZipWriter::new(File::create(&path)?);
+                parts.push(path);
+                current.insert((zw, 0))
+            }
+        };
+        zw.copy_entry(&entry, &entry.filename)?;
+        *used += size;
+    }
+
+    if let Some((zw, _)) = current {
+        zw.finish()?;
+    }
+
+    Ok(parts)
+}
+
+// Insert ` (n)` before the extension of the last component of `name`.
+fn numbered(name: &str, n: usize) -> String {
+    let file_start = name.rfind('/').map_or(0, |i| i + 1);
+    // a leading dot starts a hidden file, not an extension
+    match name[file_start..].rfind('.').filter(|&i| i > 0) {
+        Some(i) => {
+            let dot = file_start + i;
+            format!("{} ({n}){}", &name[..dot], &name[dot..])
+        }
+        None => format!("{name} ({n})"),
+    }
+}
@@ -0,0 +1,84 @@
+use std::time::{Duration, Instant};
+
+use crate::types::*;
+
+/// Timing and size figures for a single decompressed entry. Only available with
+/// the `metrics` feature.
+#[derive(Debug, Clone)]
+pub struct EntryMetrics {
+    pub filename: String,
+    pub compression_method: u16,
+    /// Bytes read from the archive
+    pub bytes_in: u64,
+    /// Bytes produced after decompression
+    pub bytes_out: u64,
+    pub duration: Duration,
+}
+
+impl EntryMetrics {
+    /// Returns the decompressed bytes produced per second
+    pub fn throughput(&self) -> f64 {
+        let secs = self.duration.as_secs_f64();
+        if secs == 0.0 {
+            return f64::INFINITY;
+        }
+        self.bytes_out as f64 / secs
+    }
+}
+
+/// Every `EntryMetrics` recorded by an archive, in the order the entries were read.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsSummary {
+    pub entries: Vec<EntryMetrics>,
+}
+
+impl MetricsSummary {
+    /// Returns the total bytes read from the archive
+    pub fn bytes_in(&self) -> u64 {
+        self.entries.iter().map(|e| e.bytes_in).sum()
+    }
+    /// Returns the total bytes produced after decompression
+    pub fn bytes_out(&self) -> u64 {
+        self.entries.iter().map(|e| e.bytes_out).sum()
+    }
+    /// Returns the total time spent reading and decompressing
+    pub fn duration(&self) -> Duration {
+        self.entries.iter().map(|e| e.duration).sum()
+    }
+    /// Returns the entry that took the longest to decompress
+    pub fn slowest(&self) -> Option<&EntryMetrics> {
+        self.entries.iter().max_by_key(|e| e.duration)
+    }
+}
+
+pub type MetricsCallback = Box<dyn FnMut(&EntryMetrics)>;
+
+#[derive(Default)]
+pub(crate) struct Recorder {
+    pub summary: MetricsSummary,
+    pub callback: Option<MetricsCallback>,
+}
+
+impl Recorder {
+    pub fn record(
+        &mut self,
+        filename: &str,
+        header: &InternalHeader,
+        bytes_out: u64,
+        start: Instant,
+    ) {
+        let metrics = EntryMetrics {
+            filename: filename.to_string(),
+            compression_method: header.compression_method,
+            bytes_in: header.compressed_size,
+            bytes_out,
+            duration: start.elapsed(),
+        };
+
+        if let Some(cb) = self.callback.as_mut() {
+            cb(&metrics);
+        }
+
+        self.summary.entries.push(metrics);
+    }
+}
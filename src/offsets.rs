@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::crc32::Crc32;
+use crate::searchable::SearchableArchive;
+use crate::shared::*;
+use crate::types::*;
+
+// "MUOC", then a format version
+const CACHE_MAGIC: [u8; 4] = *b"MUOC";
+const CACHE_VERSION: u8 = 1;
+
+/// Where an entry's data is, and how to read it, as kept by an `OffsetCache`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CachedEntry {
+    /// The compression method, `0` for `STORE` or `8` for `DEFLATE`
+    pub method: u16,
+    /// Where the data starts in the archive file, past the local header
+    pub data_offset: u64,
+    /// The length of the data as stored in the archive file
+    pub compressed_size: u64,
+    /// The length of the data once decompressed, which `read()` checks
+    pub uncompressed_size: u64,
+    /// The CRC-32 of the decompressed data, which `read()` checks
+    pub crc32: u32,
+}
+
+impl CachedEntry {
+    /// Reads the entry's data from `file`, the archive the cache was made from, and
+    /// decompresses it. No headers are read, but the data is checked against the
+    /// size and CRC-32 the cache recorded, so a cache left over from another version
+    /// of the archive fails with `ErrorKind::Format` or `ErrorKind::CrcMismatch`
+    /// rather than returning the wrong data.
+    pub fn read(&self, file: &mut (impl Read + Seek)) -> Result<Vec<u8>, MuError> {
+        file.seek(SeekFrom::Start(self.data_offset))?;
+
+        let data = decompress(self.method, read_stored(file, self.compressed_size)?)?;
+        if data.len() as u64 != self.uncompressed_size {
+            return Err(MuError::new(
+                ErrorKind::Format,
+                format!(
+                    "data at {} is {} bytes, but the cache recorded {}",
+                    self.data_offset,
+                    data.len(),
+                    self.uncompressed_size
+                ),
+            ));
+        }
+
+        let actual = Crc32::checksum(&data);
+        if actual != self.crc32 {
+            return Err(MuError::new(
+                ErrorKind::CrcMismatch {
+                    expected: self.crc32,
+                    actual,
+                },
+                format!(
+                    "data at {} has CRC-32 {actual:08x}, but the cache recorded {:08x}",
+                    self.data_offset, self.crc32
+                ),
+            ));
+        }
+
+        Ok(data)
+    }
+
+    /// Returns the span of the archive file holding the entry's data, exactly as stored
+    pub fn compressed_range(&self) -> std::ops::Range<u64> {
+        self.data_offset..self.data_offset + self.compressed_size
+    }
+}
+
+/// A map from entry name to where its data lies, so servers that open the same
+/// archives over and over can skip parsing their headers. Build one with
+/// `SearchableArchive::offset_cache()`, save it with `write_to()`, and load it
+/// with `read_from()`, or with the `serde` feature, in any format serde supports.
+///
+/// Nothing ties a cache to its archive, so it's up to the caller to rebuild it
+/// when the archive changes.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # let mut file = std::fs::File::open("media.zip").unwrap();
+/// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+/// let cache = zi.offset_cache();
+/// cache.write_to(std::fs::File::create("media.zip.offsets").unwrap()).unwrap();
+/// drop(zi);
+///
+/// // later, without parsing the archive
+/// let saved = std::fs::File::open("media.zip.offsets").unwrap();
+/// let cache = munzip::OffsetCache::read_from(saved).unwrap();
+/// let entry = cache.get("video/intro.mp4").unwrap();
+/// let data = entry.read(&mut file).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OffsetCache {
+    entries: HashMap<String, CachedEntry>,
+}
+
+impl OffsetCache {
+    /// Returns where the entry named `name` is, if the cache has it
+    pub fn get(&self, name: &str) -> Option<&CachedEntry> {
+        self.entries.get(name)
+    }
+
+    /// Returns how many entries the cache holds
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the cache holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over every name and entry, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &CachedEntry)> {
+        self.entries
+            .iter()
+            .map(|(name, entry)| (name.as_str(), entry))
+    }
+
+    /// Writes the cache out in a compact binary form, which `read_from()` reads back.
+    /// Fails with `ErrorKind::Other` if a name is over 65535 bytes, as can happen
+    /// once a name that wasn't UTF-8 is decoded, and nothing is written then.
+    pub fn write_to(&self, mut out: impl Write) -> Result<(), MuError> {
+        let count = u32::try_from(self.entries.len())
+            .map_err(|_| MuError::new(ErrorKind::Other, "too many entries for an offset cache"))?;
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&CACHE_MAGIC);
+        buf.push(CACHE_VERSION);
+        buf.extend_from_slice(&count.to_le_bytes());
+
+        for (name, entry) in &self.entries {
+            let name_len = u16::try_from(name.len()).map_err(|_| {
+                MuError::new(ErrorKind::Other, "entry name too long for an offset cache")
+            })?;
+            buf.extend_from_slice(&name_len.to_le_bytes());
+            buf.extend_from_slice(name.as_bytes());
+            buf.extend_from_slice(&entry.method.to_le_bytes());
+            buf.extend_from_slice(&entry.data_offset.to_le_bytes());
+            buf.extend_from_slice(&entry.compressed_size.to_le_bytes());
+            buf.extend_from_slice(&entry.uncompressed_size.to_le_bytes());
+            buf.extend_from_slice(&entry.crc32.to_le_bytes());
+        }
+
+        out.write_all(&buf)?;
+        Ok(())
+    }
+
+    /// Reads a cache written by `write_to()`.
+    pub fn read_from(mut input: impl Read) -> Result<Self, MuError> {
+        let mut buf = Vec::new();
+        input.read_to_end(&mut buf)?;
+
+        if buf.get(..4) != Some(&CACHE_MAGIC[..]) || buf.get(4) != Some(&CACHE_VERSION) {
+            return Err(MuError::new(ErrorKind::Format, "not an offset cache"));
+        }
+
+        let mut rest = &buf[5..];
+        let count = u32::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?);
+
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            let name_len =
+                u16::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?) as usize;
+            if rest.len() < name_len {
+                return Err(truncated());
+            }
+            let (name, after) = rest.split_at(name_len);
+            let name = std::str::from_utf8(name)?.to_string();
+            rest = after;
+
+            let entry = CachedEntry {
+                method: u16::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?),
+                data_offset: u64::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?),
+                compressed_size: u64::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?),
+                uncompressed_size: u64::from_le_bytes(
+                    split_array(&mut rest).ok_or_else(truncated)?,
+                ),
+                crc32: u32::from_le_bytes(split_array(&mut rest).ok_or_else(truncated)?),
+            };
+            entries.insert(name, entry);
+        }
+
+        Ok(Self { entries })
+    }
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    /// Builds an `OffsetCache` of every entry whose data can be read as is. Entries
+    /// that are encrypted or otherwise refused by `by_name()` are left out.
+    pub fn offset_cache(&self) -> OffsetCache {
+        let entries = self
+            .entries
+            .iter()
+            .filter(|(_, header)| check_flags(header).is_ok())
+            .map(|(filename, header)| {
+                let entry = CachedEntry {
+                    method: header.compression_method,
+                    data_offset: header.offset,
+                    compressed_size: header.compressed_size,
+                    uncompressed_size: header.uncompressed_size,
+                    crc32: header.crc32,
+                };
+                (filename.clone(), entry)
+            })
+            .collect();
+
+        OffsetCache { entries }
+    }
+}
+
+fn truncated() -> MuError {
+    MuError::new(ErrorKind::Format, "offset cache truncated")
+}
@@ -0,0 +1,287 @@
+use crate::shared::BufferSizes;
+
+/// Settings applied when opening an archive. Every setting has a default, so only
+/// the ones that matter need to be set, and a configured `ArchiveOptions` can be
+/// cloned for use with many archives.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # let mut my_file = std::fs::File::open("archive.zip").unwrap();
+/// let options = munzip::ArchiveOptions::new().error_mode(munzip::ErrorMode::Continue);
+/// let zi = munzip::IterableArchive::with_options(&mut my_file, &options).unwrap();
+///
+/// for entry in zi {
+///     match entry {
+///         Ok(entry) => println!("{}", entry.filename()),
+///         Err(e) => eprintln!("skipping: {e}"),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveOptions {
+    pub(crate) error_mode: ErrorMode,
+    pub(crate) include_apple_double: bool,
+    #[cfg(feature = "charset")]
+    pub(crate) detect_charset: bool,
+    pub(crate) compatibility: Compatibility,
+    pub(crate) max_entries: Option<usize>,
+    pub(crate) max_central_directory_size: Option<u64>,
+    pub(crate) end_record_search_limit: Option<u64>,
+    pub(crate) crc_policy: CrcPolicy,
+    pub(crate) buffer_sizes: BufferSizes,
+    pub(crate) entry_cache: u64,
+    pub(crate) prefix_index: bool,
+}
+
+impl ArchiveOptions {
+    /// Creates `ArchiveOptions` with every setting at its default.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how entries with unreadable headers are handled. Defaults to
+    /// `ErrorMode::Stop`.
+    pub fn error_mode(mut self, mode: ErrorMode) -> Self {
+        self.error_mode = mode;
+        self
+    }
+
+    /// Sets whether the `__MACOSX/` entries Finder adds to archives it makes are
+    /// listed. These are AppleDouble files holding the extended attributes and
+    /// resource forks of the real entries, so they're left out by default.
+    /// `ExtractOptions::restore_apple_double()` can still put them to use.
+    pub fn include_apple_double(mut self, include: bool) -> Self {
+        self.include_apple_double = include;
+        self
+    }
+
+    /// Sets whether filenames that aren't UTF-8, and aren't flagged as UTF-8, are
+    /// decoded in a charset guessed from all of the archive's names: Shift-JIS, GBK
+    /// or KOI8-R, falling back to the spec's CP437. Without this, such names are
+    /// decoded lossily, and `Entry::name_is_lossy()` is true for them. Defaults to
+    /// `false`. Requires the `charset` feature.
+    #[cfg(feature = "charset")]
+    pub fn detect_charset(mut self, detect: bool) -> Self {
+        self.detect_charset = detect;
+        self
+    }
+
+    /// Sets which checks are relaxed for archives from writers that don't quite
+    /// follow the spec. Defaults to `Compatibility::new()`, which relaxes none.
+    pub fn compatibility(mut self, compatibility: Compatibility) -> Self {
+        self.compatibility = compatibility;
+        self
+    }
+
+    /// Sets the most entries an archive's end record may declare. An archive that
+    /// declares more fails to open with `ErrorKind::QuotaExceeded(Quota::Entries)`,
+    /// before any of its central directory is read, so a forged end record can't
+    /// send the reader through thousands of garbage records. Defaults to no limit.
+    pub fn max_entries(mut self, max: usize) -> Self {
+        self.max_entries = Some(max);
+        self
+    }
+
+    /// Sets the largest central directory, in bytes, an archive's end record may
+    /// declare. An archive that declares a larger one fails to open with
+    /// `ErrorKind::QuotaExceeded(Quota::CentralDirectorySize)`. Defaults to no
+    /// limit.
+    pub fn max_central_directory_size(mut self, max: u64) -> Self {
+        self.max_central_directory_size = Some(max);
+        self
+    }
+
+    /// Sets how far back from the end of the file to look for the end record,
+    /// in bytes. The search reads backwards a few KiB at a time and stops at the
+    /// first valid record, which for an archive without a long comment is in the
+    /// first read, so this only bounds the work done on files that aren't archives,
+    /// or whose comment is long. A record further back fails to open like one that
+    /// isn't there. Defaults to 65,557 bytes, enough for the longest comment.
+    pub fn end_record_search_limit(mut self, limit: u64) -> Self {
+        self.end_record_search_limit = Some(limit);
+        self
+    }
+
+    /// Sets whether the CRC-32 recorded for each entry is checked against its data,
+    /// and what happens when they don't match. Entries read in full are checked:
+    /// `Entry::buffer()`, `SearchableArchive::by_name()`, `ArchiveFs::read()`, and
+    /// extraction. `Entry::range_reader()` can't be, as it reads only part of an
+    /// entry. Defaults to `CrcPolicy::Verify`, and `CrcPolicy::Trust` skips the
+    /// check for speed.
+    pub fn crc_policy(mut self, policy: CrcPolicy) -> Self {
+        self.crc_policy = policy;
+        self
+    }
+
+    /// Sets the smallest and largest buffer an entry's data is copied through, when
+    /// extracting or reading it as a stream. Each entry gets one the size of its data,
+    /// rounded up to a power of two, within these bounds, so archives of small files
+    /// don't each take a large buffer, and large files are copied in large reads.
+    /// Defaults to 4 KiB and 1 MiB.
+    pub fn buffer_sizes(mut self, min: usize, max: usize) -> Self {
+        self.buffer_sizes = BufferSizes::new(min, max);
+        self
+    }
+
+    /// Keeps up to `max_bytes` of recently decompressed entries in memory, so reading
+    /// a hot asset again through `Entry::buffer()`, `Entry::read_shared()` or
+    /// `SearchableArchive::by_name()` doesn't inflate it again. The least recently
+    /// used entry is dropped first once the cache is full, and an entry larger than
+    /// `max_bytes` is never kept. Defaults to `0`, no cache.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("assets.zip").unwrap();
+    /// let options = munzip::ArchiveOptions::new().entry_cache(16 * 1024 * 1024);
+    /// let mut zi = munzip::SearchableArchive::with_options(&mut file, &options).unwrap();
+    ///
+    /// for _frame in 0..60 {
+    ///     let shader = zi.by_name("shaders/main.wgsl").unwrap().unwrap();
+    ///     # drop(shader);
+    /// }
+    /// println!("{} hits", zi.cache_stats().hits);
+    /// ```
+    pub fn entry_cache(mut self, max_bytes: u64) -> Self {
+        self.entry_cache = max_bytes;
+        self
+    }
+
+    /// Sets whether `SearchableArchive` builds a tree of its entries' names when
+    /// it's opened, so `SearchableArchive::read_dir()` only visits the directory's
+    /// children, rather than every entry. Worth it for an archive with many entries
+    /// that's browsed a directory at a time. An `ArchiveFs` over the archive uses
+    /// the same tree, rather than building its own. `IterableArchive` ignores this.
+    /// Defaults to `false`.
+    pub fn prefix_index(mut self, build: bool) -> Self {
+        self.prefix_index = build;
+        self
+    }
+}
+
+/// Relaxations for reading archives from nonstandard writers, for
+/// `ArchiveOptions::compatibility()`. Some firmware writes records that are a little
+/// off, which these let through where they'd otherwise be an error or misread.
+///
+/// Some fields are never checked, so need no relaxing: the version made by and
+/// version needed to extract (even `0xFFFF`), the internal file attributes, and the
+/// disk numbers.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # let mut file = std::fs::File::open("camera.zip").unwrap();
+/// let options = munzip::ArchiveOptions::new().compatibility(munzip::Compatibility::lenient());
+/// let zi = munzip::SearchableArchive::with_options(&mut file, &options).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Compatibility {
+    pub(crate) trust_central_directory: bool,
+    pub(crate) ignore_reserved_flags: bool,
+    pub(crate) recover_placeholder_sizes: bool,
+    pub(crate) allow_stored_size_mismatch: bool,
+}
+
+impl Compatibility {
+    /// Creates a `Compatibility` that relaxes nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a `Compatibility` with every relaxation on.
+    pub fn lenient() -> Self {
+        Self {
+            trust_central_directory: true,
+            ignore_reserved_flags: true,
+            recover_placeholder_sizes: true,
+            allow_stored_size_mismatch: true,
+        }
+    }
+
+    /// Sets whether an entry's sizes, compression method, flags and DOS time come
+    /// from its central directory record rather than its local header, for writers
+    /// that leave local headers zeroed or stale. The local header is still read to
+    /// find where the data starts. Defaults to `false`.
+    pub fn trust_central_directory(mut self, trust: bool) -> Self {
+        self.trust_central_directory = trust;
+        self
+    }
+
+    /// Sets whether general purpose bits the spec reserves are ignored, rather than
+    /// making the entry's data an `ErrorKind::ReservedFlags` error. Defaults to
+    /// `false`.
+    pub fn ignore_reserved_flags(mut self, ignore: bool) -> Self {
+        self.ignore_reserved_flags = ignore;
+        self
+    }
+
+    /// Sets whether an entry whose sizes are ZIP64's `0xFFFFFFFF` placeholder, but
+    /// which has no ZIP64 field to hold the real ones, gets them worked out instead:
+    /// from its data descriptor if it has one, or else by finding the next header
+    /// signature after its data, and inflating it to count. This reads the whole
+    /// entry when the archive is opened, and may be fooled by a signature in stored
+    /// data. Without it, such entries are an `ErrorKind::Format` error. Defaults to
+    /// `false`.
+    pub fn recover_placeholder_sizes(mut self, recover: bool) -> Self {
+        self.recover_placeholder_sizes = recover;
+        self
+    }
+
+    /// Sets whether a stored, unencrypted entry whose compressed and uncompressed
+    /// sizes differ is read anyway, rather than being an `ErrorKind::Format` error.
+    /// The central directory's sizes are used if they agree, as when only the local
+    /// header is stale, and otherwise the compressed size, which is how much data
+    /// there is, is taken as both. Defaults to `false`.
+    pub fn allow_stored_size_mismatch(mut self, allow: bool) -> Self {
+        self.allow_stored_size_mismatch = allow;
+        self
+    }
+}
+
+/// How an archive reacts to an entry whose headers can't be read.
+///
+/// Either way, an error that leaves the rest of the central directory unreachable
+/// (a truncated or corrupt central directory record) is yielded and ends iteration.
+/// Errors from `Entry::buffer()` never affect iteration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorMode {
+    /// Yield the error, then end iteration. `SearchableArchive` fails to open.
+    #[default]
+    Stop,
+    /// Yield the error, then carry on with the next entry. `SearchableArchive`
+    /// leaves the entry out.
+    Continue,
+}
+
+/// What's done about an entry whose data doesn't match its recorded CRC-32, for
+/// `ArchiveOptions::crc_policy()` and `ArchiveEditor::check_crcs()`.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # let mut file = std::fs::File::open("archive.zip").unwrap();
+/// let options = munzip::ArchiveOptions::new().crc_policy(munzip::CrcPolicy::Record);
+/// let zi = munzip::SearchableArchive::with_options(&mut file, &options).unwrap();
+///
+/// zi.extract_all("out".as_ref(), &munzip::ExtractOptions::new()).unwrap();
+/// for name in zi.crc_mismatches() {
+///     eprintln!("{name} may be corrupt");
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrcPolicy {
+    /// Don't compute CRCs at all, which is fastest, and lets stored entries be
+    /// extracted without passing through a buffer, but lets corrupt data through
+    Trust,
+    /// Fail the read with an `ErrorKind::CrcMismatch` error, holding the recorded
+    /// and actual CRC-32. A file being extracted is removed. The default.
+    #[default]
+    Verify,
+    /// Carry on, and note the entry's name, for `SearchableArchive::crc_mismatches()`
+    /// or `IterableArchive::crc_mismatches()`. Extracted files are kept.
+    Record,
+    /// As `Record` when reading. `ArchiveEditor::check_crcs()` also rewrites the
+    /// recorded CRC-32 to match the data.
+    Fix,
+}
@@ -0,0 +1,295 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::crc32::{crc32_byte, CrcReader};
+use crate::decrypt::ZipCryptoReader;
+use crate::searchable::SearchableArchive;
+use crate::shared::decompressor;
+use crate::spec::*;
+use crate::types::*;
+
+// the random bytes traditional PKWARE encryption puts before an entry's data, the
+// last of which checks the password
+pub(crate) const ENCRYPTION_HEADER_SIZE: usize = 12;
+
+/// A password for an encrypted archive. Its `Debug` output never shows the
+/// password, and with the `zeroize` feature, its memory is overwritten with zeros
+/// when it's dropped, so the password doesn't linger in freed memory.
+///
+/// A `Password` made from a `String` or `Vec<u8>` takes over its buffer, so no
+/// unzeroed copy is left behind. One made from a `&str` or `&[u8]` copies it, and
+/// the original is up to the caller.
+///
+/// # Examples
+///
+/// ```
+/// let password = munzip::Password::from("hunter2");
+/// assert_eq!(format!("{password:?}"), "Password(..)");
+/// assert_eq!(password.as_bytes(), b"hunter2");
+/// ```
+pub struct Password {
+    bytes: Vec<u8>,
+}
+
+impl Password {
+    /// Creates a `Password` from any bytes. Zip passwords have no set encoding,
+    /// though most tools use the bytes of the password as typed.
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        Password {
+            bytes: bytes.into(),
+        }
+    }
+
+    /// Returns the password's bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the length of the password, in bytes
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns true for an empty password
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl From<&str> for Password {
+    fn from(password: &str) -> Self {
+        Password::new(password)
+    }
+}
+
+impl From<String> for Password {
+    fn from(password: String) -> Self {
+        Password::new(password)
+    }
+}
+
+impl From<&[u8]> for Password {
+    fn from(password: &[u8]) -> Self {
+        Password::new(password)
+    }
+}
+
+impl From<Vec<u8>> for Password {
+    fn from(password: Vec<u8>) -> Self {
+        Password::new(password)
+    }
+}
+
+impl std::fmt::Debug for Password {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Password(..)")
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Password {
+    fn drop(&mut self) {
+        // the whole allocation, in case it once held a longer password
+        let capacity = self.bytes.capacity();
+        self.bytes.resize(capacity, 0);
+        for byte in self.bytes.iter_mut() {
+            // SAFETY: `byte` is a valid, aligned reference. Volatile writes aren't
+            // optimized away, though the memory is about to be freed
+            unsafe { std::ptr::write_volatile(byte, 0) };
+        }
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+/// What `SearchableArchive::try_passwords()` found.
+#[derive(Debug, Default)]
+pub struct PasswordSearch {
+    /// Each password that unlocked something, with the names of the entries it
+    /// unlocked, in the order the passwords were tried
+    pub found: Vec<PasswordMatch>,
+    /// Encrypted entries no candidate unlocked
+    pub locked: Vec<String>,
+    /// Encrypted entries whose encryption can't be checked: strong encryption, and
+    /// WinZip AES
+    pub unsupported: Vec<String>,
+    /// How many candidates were tried before every entry was unlocked, or the
+    /// candidates ran out
+    pub tried: usize,
+}
+
+/// A password that `SearchableArchive::try_passwords()` found, and what it unlocked.
+#[derive(Debug)]
+pub struct PasswordMatch {
+    /// The password
+    pub password: Password,
+    /// The entries it unlocked
+    pub entries: Vec<String>,
+}
+
+// The three keys of traditional PKWARE encryption, as set up from a password.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ZipCryptoKeys {
+    keys: [u32; 3],
+}
+
+impl ZipCryptoKeys {
+    pub fn new(password: &[u8]) -> Self {
+        let mut keys = ZipCryptoKeys {
+            keys: [0x12345678, 0x23456789, 0x34567890],
+        };
+        for &b in password {
+            keys.update(b);
+        }
+        keys
+    }
+
+    fn update(&mut self, plain: u8) {
+        let [k0, k1, k2] = &mut self.keys;
+        *k0 = crc32_byte(*k0, plain);
+        *k1 = k1
+            .wrapping_add(*k0 & 0xFF)
+            .wrapping_mul(134775813)
+            .wrapping_add(1);
+        *k2 = crc32_byte(*k2, (*k1 >> 24) as u8);
+    }
+
+    pub fn decrypt(&mut self, cipher: u8) -> u8 {
+        let temp = (self.keys[2] | 2) & 0xFFFF;
+        let plain = cipher ^ ((temp * (temp ^ 1)) >> 8) as u8;
+        self.update(plain);
+        plain
+    }
+}
+
+// The byte the last of an entry's encryption header decrypts to under the right
+// password: the top of its CRC-32, or of its DOS time when the CRC-32 comes after
+// the data, as it wasn't known when the header was written.
+pub(crate) fn check_byte(header: &InternalHeader) -> u8 {
+    match header.general_purpose_bit_flag & FLAG_DATA_DESCRIPTOR != 0 {
+        true => (header.last_mod_file_time >> 8) as u8,
+        false => (header.crc32 >> 24) as u8,
+    }
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    /// Tries each of `candidates` against the archive's encrypted entries, for
+    /// recovering an archive whose password was lost, and reports which password
+    /// unlocked which. Each entry's 12 byte encryption header is read once, and a
+    /// password is checked against it first. The search stops once every entry is
+    /// unlocked.
+    ///
+    /// Only traditional PKWARE encryption can be checked. Its header has a single
+    /// check byte, which one wrong password in 256 passes too, so a password that
+    /// passes is confirmed by decrypting and decompressing the entry and checking
+    /// its CRC-32 before the entry is credited to it. An entry compressed with a
+    /// method munzip can't decompress is credited on its check byte alone. Entries
+    /// that aren't encrypted aren't reported at all.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// use std::io::BufRead;
+    ///
+    /// # let mut file = std::fs::File::open("locked.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// let words = std::io::BufReader::new(std::fs::File::open("words.txt").unwrap());
+    /// let search = zi.try_passwords(words.lines().map_while(Result::ok)).unwrap();
+    /// for found in &search.found {
+    ///     println!("{:?} unlocks {}", found.password.as_bytes(), found.entries.join(", "));
+    /// }
+    /// println!("still locked: {:?}", search.locked);
+    /// ```
+    pub fn try_passwords<I>(&self, candidates: I) -> Result<PasswordSearch, MuError>
+    where
+        I: IntoIterator,
+        I::Item: Into<Password>,
+    {
+        let mut search = PasswordSearch::default();
+
+        // the encryption header and check byte of each entry to unlock
+        let mut locked = Vec::new();
+        {
+            let mut file = self.file.borrow_mut();
+            for (name, header) in self.entries.iter().chain(&self.hidden) {
+                let flags = GeneralPurposeFlags(header.general_purpose_bit_flag);
+                if !flags.is_encrypted() {
+                    continue;
+                }
+                if flags.is_strongly_encrypted() || header.compression_method == METHOD_WINZIP_AES {
+                    search.unsupported.push(name.clone());
+                    continue;
+                }
+                if (header.compressed_size as usize) < ENCRYPTION_HEADER_SIZE {
+                    return Err(MuError::new(
+                        ErrorKind::Format,
+                        format!("'{name}' is too short to hold an encryption header"),
+                    ));
+                }
+
+                let mut encryption_header = [0; ENCRYPTION_HEADER_SIZE];
+                file.seek(SeekFrom::Start(header.offset))?;
+                file.read_exact(&mut encryption_header)?;
+                locked.push((name, header, encryption_header));
+            }
+        }
+
+        for candidate in candidates {
+            if locked.is_empty() {
+                break;
+            }
+            search.tried += 1;
+
+            let password = candidate.into();
+            let keys = ZipCryptoKeys::new(password.as_bytes());
+            let mut entries = Vec::new();
+            let mut still_locked = Vec::new();
+            for (name, header, encryption_header) in locked {
+                let mut keys = keys;
+                let last = encryption_header.iter().map(|&b| keys.decrypt(b)).last();
+                let unlocked = last == Some(check_byte(header)) && self.confirm(header, keys)?;
+                match unlocked {
+                    true => entries.push(name.clone()),
+                    false => still_locked.push((name, header, encryption_header)),
+                }
+            }
+            locked = still_locked;
+
+            if !entries.is_empty() {
+                search.found.push(PasswordMatch { password, entries });
+            }
+        }
+
+        search.locked = locked
+            .into_iter()
+            .map(|(name, _, _)| name.clone())
+            .collect();
+        Ok(search)
+    }
+
+    // Whether the entry's data, decrypted with `keys` as they are past its encryption
+    // header, matches its CRC-32. Data decrypted with the wrong password rarely
+    // inflates, and when it does, its CRC-32 is all but certain to be wrong.
+    fn confirm(&self, header: &InternalHeader, keys: ZipCryptoKeys) -> Result<bool, MuError> {
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(
+            header.offset + ENCRYPTION_HEADER_SIZE as u64,
+        ))?;
+        let stored = Read::take(
+            &mut *file,
+            header.compressed_size - ENCRYPTION_HEADER_SIZE as u64,
+        );
+        let decrypted = ZipCryptoReader {
+            data: Box::new(stored),
+            keys,
+        };
+
+        let mut data = match decompressor(header.compression_method, decrypted) {
+            Ok(data) => CrcReader::new(data),
+            Err(_) => return Ok(true),
+        };
+        match std::io::copy(&mut data, &mut std::io::sink()) {
+            Ok(_) => Ok(data.crc32() == header.crc32),
+            Err(_) => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::time::{Duration, Instant};
+
+/// Wraps a reader that can be slow to give up its data, as network filesystems
+/// and FUSE mounts are, retrying reads and seeks that fail with `WouldBlock` or
+/// `TimedOut` instead of failing the archive, and those `Interrupted` for as long
+/// as they are. Only a lack of progress gives up: every read that returns data
+/// starts the retries and the timeout over. Its `read_exact()`, which header
+/// parsing reads through, keeps what short reads give it and says how much it got
+/// if it gives up.
+///
+/// Anything `IterableArchive` or `SearchableArchive::from_reader()` reads, it reads
+/// through its reader, so wrapping that reader covers every header and entry read.
+/// `peek()`, `repair()`, `recompress()` and `ArchiveEditor`, which open or are
+/// handed a `File` themselves, read it through a `PatientReader` with the default
+/// settings.
+///
+/// # Examples
+///
+/// ``` no_run
+/// use std::time::Duration;
+///
+/// let file = std::fs::File::open("/mnt/share/archive.zip").unwrap();
+/// let mut reader = munzip::PatientReader::new(file)
+///     .retries(20)
+///     .timeout(Duration::from_secs(60))
+///     .on_progress(|read| eprintln!("{read} bytes"));
+///
+/// let zi = munzip::IterableArchive::new(&mut reader).unwrap();
+/// for entry in zi {
+///     println!("{}", entry.unwrap().filename());
+/// }
+/// ```
+pub struct PatientReader<R> {
+    inner: R,
+    retries: u32,
+    retry_delay: Duration,
+    timeout: Option<Duration>,
+    on_progress: Option<Box<dyn FnMut(u64)>>,
+    bytes_read: u64,
+    retried: u64,
+}
+
+impl<R> PatientReader<R> {
+    /// Creates a `PatientReader` over `inner`.
+    pub fn new(inner: R) -> Self {
+        PatientReader {
+            inner,
+            retries: 8,
+            retry_delay: Duration::from_millis(100),
+            timeout: None,
+            on_progress: None,
+            bytes_read: 0,
+            retried: 0,
+        }
+    }
+
+    /// Sets how many times in a row a read or seek that fails with `WouldBlock` or
+    /// `TimedOut` is retried before the error is returned. Defaults to 8.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets how long to wait before each retry. Defaults to 100 ms.
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Sets how long to keep retrying without any progress, however many retries
+    /// are left, after which a `TimedOut` error is returned. Defaults to no limit.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets a callback called after every read that returns data, with how many
+    /// bytes have been read in all. Defaults to none.
+    pub fn on_progress(mut self, on_progress: impl FnMut(u64) + 'static) -> Self {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
+
+    /// Returns how many bytes have been read so far
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Returns how many reads and seeks have been retried so far, interruptions
+    /// left out
+    pub fn retried(&self) -> u64 {
+        self.retried
+    }
+
+    /// Returns the wrapped reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // Run `op` until it succeeds, fails with an error not worth retrying, or runs
+    // out of retries or time.
+    fn patiently<T>(&mut self, mut op: impl FnMut(&mut R) -> io::Result<T>) -> io::Result<T> {
+        let started = Instant::now();
+        let mut attempts = 0;
+
+        loop {
+            let e = match op(&mut self.inner) {
+                Err(e) => e,
+                result => return result,
+            };
+            match e.kind() {
+                io::ErrorKind::Interrupted => continue,
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {}
+                _ => return Err(e),
+            }
+
+            let timed_out = self.timeout.is_some_and(|t| started.elapsed() >= t);
+            if attempts >= self.retries || timed_out {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "no progress after {attempts} retries, {} bytes read: {e}",
+                        self.bytes_read
+                    ),
+                ));
+            }
+
+            attempts += 1;
+            self.retried += 1;
+            std::thread::sleep(self.retry_delay);
+        }
+    }
+}
+
+impl<R: Read> Read for PatientReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.patiently(|inner| inner.read(buf))?;
+        if n > 0 {
+            self.bytes_read += n as u64;
+            if let Some(on_progress) = &mut self.on_progress {
+                on_progress(self.bytes_read);
+            }
+        }
+        Ok(n)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            let n = self.read(&mut buf[filled..]).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("got {filled} of {} bytes: {e}", buf.len()),
+                )
+            })?;
+            if n == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("got {filled} of {} bytes before the end", buf.len()),
+                ));
+            }
+            filled += n;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Seek> Seek for PatientReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.patiently(|inner| inner.seek(pos))
+    }
+}
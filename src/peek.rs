@@ -0,0 +1,71 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::patient::PatientReader;
+use crate::shared::*;
+use crate::types::*;
+
+/// What the end of an archive says about it, from `peek()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivePeek {
+    /// How many entries the end record declares
+    pub entries: usize,
+    /// Where the central directory starts
+    pub central_directory_offset: u64,
+    /// How long the central directory is
+    pub central_directory_size: u64,
+    /// The archive comment
+    pub comment: Vec<u8>,
+    /// Whether the archive has a ZIP64 end record, or the end record holds ZIP64's
+    /// placeholders, so the figures here may not be the whole story
+    pub zip64: bool,
+    /// The size of the archive file
+    pub file_size: u64,
+}
+
+/// Reads just the end of the archive at `path`, for listing facts about many
+/// archives at once without opening any of them: how many entries there are, how
+/// big the central directory is, the comment and whether it's ZIP64. It takes two
+/// reads for an archive with a short comment, and none of the central directory.
+///
+/// # Examples
+///
+/// ``` no_run
+/// for path in std::fs::read_dir("downloads").unwrap() {
+///     let path = path.unwrap().path();
+///     match munzip::peek(&path) {
+///         Ok(peek) => println!("{}: {} entries, {} bytes", path.display(), peek.entries, peek.file_size),
+///         Err(e) => println!("{}: not a zip ({e})", path.display()),
+///     }
+/// }
+/// ```
+pub fn peek<P: AsRef<Path>>(path: P) -> Result<ArchivePeek, MuError> {
+    let mut file = PatientReader::new(File::open(path)?);
+    let (end_rec, cd_offset) = read_end_record(&mut file)?;
+    let file_size = file.seek(SeekFrom::End(0))?;
+
+    // the comment, and where a ZIP64 locator would be just before the end record
+    let comment_length = end_rec.record.zip_comment_length as u64;
+    let end_position = file_size - END_RECORD_SIZE as u64 - comment_length;
+    let from = end_position.saturating_sub(ZIP64_END_LOCATOR_SIZE as u64);
+    let mut tail = vec![0; (file_size - from) as usize];
+    file.seek(SeekFrom::Start(from))?;
+    file.read_exact(&mut tail)?;
+
+    let locator = end_position - from == ZIP64_END_LOCATOR_SIZE as u64
+        && tail[..4] == ZIP64_END_LOCATOR_SIGNATURE.to_le_bytes();
+    let record = end_rec.record;
+    let placeholders = record.num_entries == u16::MAX
+        || record.central_directory_size == u32::MAX
+        || record.central_directory_offset == u32::MAX;
+
+    Ok(ArchivePeek {
+        entries: end_rec.num_entries as usize,
+        central_directory_offset: cd_offset,
+        central_directory_size: end_rec.central_directory_size,
+        comment: tail[tail.len() - comment_length as usize..].to_vec(),
+        zip64: locator || placeholders,
+        file_size,
+    })
+}
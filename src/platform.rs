@@ -0,0 +1,393 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+// Copy `len` bytes from `src` at `offset` to the current position of `dst` without
+// passing them through user space. Returns how many were copied; anything short of
+// `len` is left for the caller to copy the ordinary way.
+#[cfg(target_os = "linux")]
+pub fn copy_range(src: &File, offset: u64, len: u64, dst: &File) -> io::Result<u64> {
+    use std::os::fd::AsRawFd;
+
+    extern "C" {
+        fn copy_file_range(
+            fd_in: i32,
+            off_in: *mut i64,
+            fd_out: i32,
+            off_out: *mut i64,
+            len: usize,
+            flags: u32,
+        ) -> isize;
+        #[cfg(target_pointer_width = "64")]
+        fn sendfile(out_fd: i32, in_fd: i32, offset: *mut i64, count: usize) -> isize;
+    }
+
+    // errors meaning "not here", rather than failures of the copy itself
+    const ENOSYS: i32 = 38;
+    const EXDEV: i32 = 18;
+    const EINVAL: i32 = 22;
+    const EOPNOTSUPP: i32 = 95;
+    const EINTR: i32 = 4;
+
+    let mut off = offset as i64;
+    let mut copied = 0;
+    let mut use_sendfile = false;
+
+    while copied < len {
+        let chunk = (len - copied).min(1 << 30) as usize;
+        // SAFETY: both descriptors are open for the duration, and `off` outlives the call
+        let n = unsafe {
+            if use_sendfile {
+                #[cfg(target_pointer_width = "64")]
+                let n = sendfile(dst.as_raw_fd(), src.as_raw_fd(), &mut off, chunk);
+                #[cfg(not(target_pointer_width = "64"))]
+                let n = -1;
+                n
+            } else {
+                copy_file_range(
+                    src.as_raw_fd(),
+                    &mut off,
+                    dst.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    chunk,
+                    0,
+                )
+            }
+        };
+
+        match n {
+            0 => break,
+            n if n > 0 => copied += n as u64,
+            _ => {
+                let err = io::Error::last_os_error();
+                match err.raw_os_error() {
+                    Some(EINTR) => {}
+                    // copy_file_range won't cross filesystems on some kernels
+                    Some(ENOSYS | EXDEV | EINVAL | EOPNOTSUPP)
+                        if !use_sendfile && cfg!(target_pointer_width = "64") =>
+                    {
+                        use_sendfile = true
+                    }
+                    Some(ENOSYS | EXDEV | EINVAL | EOPNOTSUPP) => break,
+                    _ => return Err(err),
+                }
+            }
+        }
+    }
+
+    Ok(copied)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn copy_range(_src: &File, _offset: u64, _len: u64, _dst: &File) -> io::Result<u64> {
+    Ok(0)
+}
+
+// Reserve `len` bytes of disk for `file`, setting its length to match, so running
+// out of space fails here rather than partway through writing it. Filesystems that
+// can't reserve space, and systems without fallocate, just get the length set.
+#[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+pub fn preallocate(file: &File, len: u64) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    extern "C" {
+        fn fallocate(fd: i32, mode: i32, offset: i64, len: i64) -> i32;
+    }
+
+    const ENOSYS: i32 = 38;
+    const EINVAL: i32 = 22;
+    const EOPNOTSUPP: i32 = 95;
+    const EINTR: i32 = 4;
+
+    loop {
+        // SAFETY: the descriptor is open for the duration
+        if unsafe { fallocate(file.as_raw_fd(), 0, 0, len as i64) } == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(EINTR) => {}
+            Some(ENOSYS | EINVAL | EOPNOTSUPP) => return file.set_len(len),
+            _ => return Err(err),
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_pointer_width = "64")))]
+pub fn preallocate(file: &File, len: u64) -> io::Result<()> {
+    file.set_len(len)
+}
+
+// How many bytes are free for this user on the filesystem holding `path`, or `None`
+// where there's no way to ask.
+#[cfg(any(
+    all(target_os = "linux", target_pointer_width = "64"),
+    target_os = "macos"
+))]
+pub fn available_space(path: &Path) -> io::Result<Option<u64>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    // only the leading fields, which glibc, musl and macOS lay out alike, with room
+    // for the rest
+    #[repr(C)]
+    struct StatVfs {
+        f_bsize: u64,
+        f_frsize: u64,
+        #[cfg(target_os = "linux")]
+        blocks: [u64; 3],
+        #[cfg(target_os = "macos")]
+        blocks: [u32; 3],
+        rest: [u64; 16],
+    }
+
+    extern "C" {
+        fn statvfs(path: *const std::ffi::c_char, buf: *mut StatVfs) -> i32;
+    }
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let mut buf = StatVfs {
+        f_bsize: 0,
+        f_frsize: 0,
+        blocks: [0; 3],
+        rest: [0; 16],
+    };
+
+    // SAFETY: the path is NUL terminated, and `buf` is larger than a `struct statvfs`
+    if unsafe { statvfs(path.as_ptr(), &mut buf) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let fragment = match buf.f_frsize {
+        0 => buf.f_bsize,
+        size => size,
+    };
+    // f_bavail, the blocks free to unprivileged users
+    #[cfg(target_os = "linux")]
+    let available = buf.blocks[2];
+    #[cfg(target_os = "macos")]
+    let available = u64::from(buf.blocks[2]);
+    Ok(Some(available.saturating_mul(fragment)))
+}
+
+#[cfg(windows)]
+pub fn available_space(path: &Path) -> io::Result<Option<u64>> {
+    use std::os::windows::ffi::OsStrExt;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetDiskFreeSpaceExW(
+            directory: *const u16,
+            available: *mut u64,
+            total: *mut u64,
+            free: *mut u64,
+        ) -> i32;
+    }
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available = 0;
+
+    // SAFETY: `wide` is NUL terminated, and everything outlives the call
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some(available))
+}
+
+#[cfg(not(any(
+    all(target_os = "linux", target_pointer_width = "64"),
+    target_os = "macos",
+    windows
+)))]
+pub fn available_space(_path: &Path) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+// A number for telling apart files made by this process from other processes'.
+// WASI has no process IDs, and std panics asking for one, so the clock stands in.
+#[cfg(not(target_os = "wasi"))]
+pub fn process_id() -> u32 {
+    std::process::id()
+}
+
+#[cfg(target_os = "wasi")]
+pub fn process_id() -> u32 {
+    match SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        Ok(since) => since.subsec_nanos() ^ since.as_secs() as u32,
+        Err(_) => 0,
+    }
+}
+
+// The directory for temp files. WASI has no standard one, and std panics asking,
+// so it's `TMPDIR`, or `/tmp` for runtimes that preopen it.
+#[cfg(not(target_os = "wasi"))]
+pub fn temp_dir() -> std::path::PathBuf {
+    std::env::temp_dir()
+}
+
+#[cfg(target_os = "wasi")]
+pub fn temp_dir() -> std::path::PathBuf {
+    std::env::var_os("TMPDIR")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| std::path::PathBuf::from("/tmp"))
+}
+
+// Set the modification time of a file or directory.
+pub fn set_modified(path: &Path, time: SystemTime) -> io::Result<()> {
+    open_for_times(path)?.set_modified(time)
+}
+
+// Apply the readonly, hidden, system and archive bits of a DOS attribute byte,
+// returning the bits that were set.
+#[cfg(windows)]
+pub fn set_dos_attributes(path: &Path, attributes: u32) -> io::Result<Option<u8>> {
+    use std::os::windows::ffi::OsStrExt;
+
+    const KEPT: u32 = 0x01 | 0x02 | 0x04 | 0x20;
+    const FILE_ATTRIBUTE_NORMAL: u32 = 0x80;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetFileAttributesW(name: *const u16, attributes: u32) -> i32;
+    }
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let kept = attributes & KEPT;
+    let attributes = match kept {
+        0 => FILE_ATTRIBUTE_NORMAL,
+        kept => kept,
+    };
+
+    // SAFETY: `wide` is NUL terminated, and outlives the call
+    if unsafe { SetFileAttributesW(wide.as_ptr(), attributes) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some(kept as u8))
+}
+
+// Only Windows has DOS attributes to set.
+#[cfg(not(windows))]
+pub fn set_dos_attributes(_path: &Path, _attributes: u32) -> io::Result<Option<u8>> {
+    Ok(None)
+}
+
+// Apply the permission bits of a Unix mode, or without one, clear the write bits of
+// a file DOS attributes say is readonly. Setuid, setgid and sticky bits are dropped,
+// as an archive shouldn't be able to hand them out. Returns the permissions set.
+#[cfg(unix)]
+pub fn set_unix_permissions(
+    path: &Path,
+    mode: Option<u32>,
+    readonly: bool,
+) -> io::Result<Option<u32>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    match mode {
+        Some(mode) => permissions.set_mode(mode & 0o777),
+        None if readonly => permissions.set_mode(permissions.mode() & 0o777 & !0o222),
+        None => return Ok(None),
+    }
+    let set = permissions.mode();
+    std::fs::set_permissions(path, permissions)?;
+    Ok(Some(set))
+}
+
+// Only Unix has modes to set.
+#[cfg(not(unix))]
+pub fn set_unix_permissions(
+    _path: &Path,
+    _mode: Option<u32>,
+    _readonly: bool,
+) -> io::Result<Option<u32>> {
+    Ok(None)
+}
+
+// The Unix mode, file type bits included, of what `metadata` describes.
+#[cfg(unix)]
+pub fn unix_mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.mode())
+}
+
+// Only Unix has modes.
+#[cfg(not(unix))]
+pub fn unix_mode(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+// Set an extended attribute on a file, without following symlinks.
+#[cfg(target_os = "macos")]
+pub fn set_xattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    const XATTR_NOFOLLOW: i32 = 0x0001;
+
+    extern "C" {
+        fn setxattr(
+            path: *const std::ffi::c_char,
+            name: *const std::ffi::c_char,
+            value: *const u8,
+            size: usize,
+            position: u32,
+            options: i32,
+        ) -> i32;
+    }
+
+    let invalid = |_| io::Error::from(io::ErrorKind::InvalidInput);
+    let path = CString::new(path.as_os_str().as_bytes()).map_err(invalid)?;
+    let name = CString::new(name).map_err(invalid)?;
+
+    // SAFETY: both strings are NUL terminated, and everything outlives the call
+    let ret = unsafe {
+        setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr(),
+            value.len(),
+            0,
+            XATTR_NOFOLLOW,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(any(unix, target_os = "wasi"))]
+fn open_for_times(path: &Path) -> io::Result<File> {
+    // futimens doesn't care how the descriptor was opened, so this covers directories,
+    // and WASI's fd_filestat_set_times is granted to every descriptor std opens
+    File::open(path)
+}
+
+#[cfg(windows)]
+fn open_for_times(path: &Path) -> io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const FILE_WRITE_ATTRIBUTES: u32 = 0x0100;
+    // required to open a handle to a directory
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+
+    std::fs::OpenOptions::new()
+        .access_mode(FILE_WRITE_ATTRIBUTES)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)
+}
+
+#[cfg(not(any(unix, windows, target_os = "wasi")))]
+fn open_for_times(path: &Path) -> io::Result<File> {
+    std::fs::OpenOptions::new().write(true).open(path)
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Seek};
+
+use crate::iterable::Entry;
+use crate::searchable::SearchableArchive;
+use crate::types::*;
+
+/// A file or directory directly inside a directory, from
+/// `SearchableArchive::read_dir()`.
+pub struct DirChild<'a, R: Read + Seek = &'a mut File> {
+    /// The last component of its name, without a trailing `/`
+    pub name: String,
+    /// Whether it's a directory, either because the archive has an entry for it, or
+    /// because other entries' names put it inside it
+    pub is_dir: bool,
+    /// The archive's entry for it, which a directory that's only implied doesn't have
+    pub entry: Option<Entry<'a, R>>,
+}
+
+// The entry names of an archive as a tree of their `/` separated components, built
+// once under `ArchiveOptions::prefix_index()`, so a directory can be listed without
+// a pass over every entry. `ArchiveFs` builds one if the archive hasn't. The root
+// is the first node.
+#[derive(Debug, Default, Clone)]
+pub struct PrefixIndex {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Node {
+    children: BTreeMap<String, usize>,
+    // the index into `SearchableArchive::entries` of the file, or the directory
+    // entry, at this node
+    file: Option<usize>,
+    dir: Option<usize>,
+}
+
+impl Node {
+    fn is_dir(&self) -> bool {
+        self.dir.is_some() || !self.children.is_empty()
+    }
+
+    fn entry(&self) -> Option<usize> {
+        match self.is_dir() {
+            true => self.dir,
+            false => self.file,
+        }
+    }
+}
+
+impl PrefixIndex {
+    pub fn new(entries: &[(String, InternalHeader)]) -> Self {
+        let mut index = PrefixIndex {
+            nodes: vec![Node::default()],
+        };
+
+        for (i, (filename, _)) in entries.iter().enumerate() {
+            let mut node = 0;
+            for component in filename.split('/').filter(|c| !c.is_empty()) {
+                node = match index.nodes[node].children.get(component) {
+                    Some(&child) => child,
+                    None => {
+                        let child = index.nodes.len();
+                        index.nodes.push(Node::default());
+                        index.nodes[node]
+                            .children
+                            .insert(component.to_string(), child);
+                        child
+                    }
+                };
+            }
+            match filename.ends_with('/') {
+                true => index.nodes[node].dir = Some(i),
+                false => index.nodes[node].file = Some(i),
+            }
+        }
+
+        index
+    }
+
+    // The node at `path`, a normalized name.
+    fn node(&self, path: &str) -> Option<&Node> {
+        let mut node = 0;
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            node = *self.nodes[node].children.get(component)?;
+        }
+        Some(&self.nodes[node])
+    }
+
+    // Whether `path`, a normalized name, is the root or a directory, either with an
+    // entry of its own or implied by other entries' names.
+    pub fn is_dir(&self, path: &str) -> bool {
+        path.is_empty() || self.node(path).is_some_and(Node::is_dir)
+    }
+
+    // The index of the entry at `path`, a normalized name: the directory entry if
+    // it's a directory, the file's if not.
+    pub fn entry(&self, path: &str) -> Option<usize> {
+        self.node(path)?.entry()
+    }
+
+    // The children of the directory `dir`, a normalized name, as (name, is a
+    // directory, index of its entry).
+    pub fn children(&self, dir: &str) -> Option<Vec<(String, bool, Option<usize>)>> {
+        if !self.is_dir(dir) {
+            return None;
+        }
+
+        Some(
+            self.node(dir)?
+                .children
+                .iter()
+                .map(|(name, &child)| {
+                    let child = &self.nodes[child];
+                    (name.clone(), child.is_dir(), child.entry())
+                })
+                .collect(),
+        )
+    }
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    /// Lists what's directly inside the directory `dir`, in name order, or returns
+    /// `None` if there's no such directory. `dir` is an entry name, with or without
+    /// a trailing `/`, and `""` is the root. Directories are listed wherever an
+    /// entry's name implies one, whether or not the archive has an entry for it.
+    ///
+    /// This takes a pass over every entry, unless the archive was opened with
+    /// `ArchiveOptions::prefix_index()`, when it only visits the directory's
+    /// children.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("game.zip").unwrap();
+    /// let options = munzip::ArchiveOptions::new().prefix_index(true);
+    /// let zi = munzip::SearchableArchive::with_options(&mut file, &options).unwrap();
+    ///
+    /// for child in zi.read_dir("assets/textures/").unwrap() {
+    ///     let kind = if child.is_dir { "dir " } else { "file" };
+    ///     println!("{kind} {}", child.name);
+    /// }
+    /// ```
+    pub fn read_dir(&self, dir: &str) -> Option<Vec<DirChild<'a, R>>> {
+        let dir = dir.trim_matches('/');
+        let children = match &self.prefix_index {
+            Some(index) => index.children(dir)?,
+            None => self.scan_dir(dir)?,
+        };
+
+        Some(
+            children
+                .into_iter()
+                .map(|(name, is_dir, index)| DirChild {
+                    name,
+                    is_dir,
+                    entry: index.and_then(|index| self.by_index(index)),
+                })
+                .collect(),
+        )
+    }
+
+    // `PrefixIndex::children()`, by a pass over every entry.
+    fn scan_dir(&self, dir: &str) -> Option<Vec<(String, bool, Option<usize>)>> {
+        let prefix = match dir.is_empty() {
+            true => String::new(),
+            false => format!("{dir}/"),
+        };
+
+        let mut found = dir.is_empty();
+        // whether each child has anything inside it, and its file and directory entries
+        let mut children: BTreeMap<String, (bool, Option<usize>, Option<usize>)> = BTreeMap::new();
+        for (i, (filename, _)) in self.entries.iter().enumerate() {
+            let Some(rest) = filename.strip_prefix(&prefix) else {
+                continue;
+            };
+            let rest = rest.trim_start_matches('/');
+            found = true;
+
+            let (name, inside) = match rest.split_once('/') {
+                Some((name, inside)) => (name, Some(inside)),
+                None => (rest, None),
+            };
+            if name.is_empty() {
+                continue;
+            }
+
+            let child = children.entry(name.to_string()).or_default();
+            match inside {
+                Some("") => child.2 = Some(i),
+                Some(_) => child.0 = true,
+                None => child.1 = Some(i),
+            }
+        }
+
+        match found {
+            true => Some(
+                children
+                    .into_iter()
+                    .map(
+                        |(name, (inside, file, dir))| match inside || dir.is_some() {
+                            true => (name, true, dir),
+                            false => (name, false, file),
+                        },
+                    )
+                    .collect(),
+            ),
+            false => None,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::io::{Read, Seek};
+
+use crate::iterable::{Entry, EntryMeta};
+
+/// Where the value of one of an entry's header fields was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FieldSource {
+    /// The entry's central directory record
+    CentralDirectory,
+    /// The entry's local header
+    LocalHeader,
+    /// The data descriptor after the entry's data
+    DataDescriptor,
+    /// The ZIP64 extra field of the central directory record, in place of a
+    /// placeholder there
+    CentralZip64,
+    /// The ZIP64 extra field of the local header, in place of a placeholder there
+    LocalZip64,
+    /// An extended timestamp extra field, the local header's, or the central
+    /// directory record's where only that's read
+    ExtendedTimestamp,
+    /// Worked out from the file rather than read from any record, as for sizes
+    /// recovered from placeholders with no ZIP64 field to go by, or the offset of
+    /// an entry found by walking the local headers
+    Scan,
+}
+
+/// Where each of an entry's header fields came from, for telling which record was
+/// believed where the central directory, local header and data descriptor disagree.
+/// Which is read depends on the entry's flags, and on the archive's
+/// `Compatibility`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Provenance {
+    pub compressed_size: FieldSource,
+    pub uncompressed_size: FieldSource,
+    /// Always the central directory, as a local header's CRC-32 may be left zero
+    /// for a data descriptor, unless the entry was found by walking local headers
+    pub crc32: FieldSource,
+    pub compression_method: FieldSource,
+    pub flags: FieldSource,
+    /// The modification time as `Entry::modified()` returns it
+    pub modified: FieldSource,
+    pub local_header_offset: FieldSource,
+}
+
+impl Provenance {
+    // Every field from the central directory record, before the local header is
+    // read.
+    pub(crate) fn central() -> Self {
+        Provenance {
+            compressed_size: FieldSource::CentralDirectory,
+            uncompressed_size: FieldSource::CentralDirectory,
+            crc32: FieldSource::CentralDirectory,
+            compression_method: FieldSource::CentralDirectory,
+            flags: FieldSource::CentralDirectory,
+            modified: FieldSource::CentralDirectory,
+            local_header_offset: FieldSource::CentralDirectory,
+        }
+    }
+}
+
+impl<'a, R: Read + Seek> Entry<'a, R> {
+    /// Returns where each of the entry's header fields was read from
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("evidence.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// for entry in zi.entries() {
+    ///     let provenance = entry.provenance();
+    ///     if provenance.uncompressed_size != munzip::FieldSource::CentralDirectory {
+    ///         println!("{}: size from {:?}", entry.filename(), provenance.uncompressed_size);
+    ///     }
+    /// }
+    /// ```
+    pub fn provenance(&self) -> Provenance {
+        self.header.provenance
+    }
+}
+
+impl EntryMeta {
+    /// Returns where each of the entry's header fields was read from, as
+    /// `Entry::provenance()` does
+    pub fn provenance(&self) -> Provenance {
+        self.header.provenance
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::{BufReader, Read};
+
+use crate::checkpoints::Inflater;
+use crate::types::*;
+
+// the most of a dictionary a match can reach back into
+const DICTIONARY_WINDOW: usize = 32768;
+
+/// Inflates a raw DEFLATE stream (no zlib or gzip wrapper) with the same decoder
+/// used for zip entries, for formats that embed DEFLATE data themselves, such as a
+/// gzip member once its header is stripped. Requires the `deflate` feature.
+///
+/// # Examples
+///
+/// ```
+/// // a single fixed Huffman block holding "hi"
+/// let data = munzip::inflate_raw(&[0xcb, 0xc8, 0x04, 0x00]).unwrap();
+/// assert_eq!(data, b"hi");
+/// ```
+pub fn inflate_raw(data: &[u8]) -> Result<Vec<u8>, MuError> {
+    inflate::inflate_bytes(data).map_err(|e| MuError::new(ErrorKind::Format, e))
+}
+
+/// Inflates a raw DEFLATE stream that was compressed against a preset dictionary,
+/// as zlib's `deflateSetDictionary()` makes. Only the last 32 KiB of `dictionary`
+/// can be reached, so only that much is kept. Requires the `deflate` feature.
+pub fn inflate_raw_with_dictionary(data: &[u8], dictionary: &[u8]) -> Result<Vec<u8>, MuError> {
+    let mut out = Vec::new();
+    RawInflater::with_dictionary(data, dictionary).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+/// A reader that inflates a raw DEFLATE stream as it's read, for data too large to
+/// inflate in one go with `inflate_raw()`. Reading stops at the end of the final
+/// block, and corrupt data, or data that ends before the final block does, is an
+/// `InvalidData` error. Requires the `deflate` feature.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # let member = std::fs::File::open("payload.deflate").unwrap();
+/// let mut inflater = munzip::RawInflater::new(member);
+/// std::io::copy(&mut inflater, &mut std::io::stdout()).unwrap();
+/// ```
+pub struct RawInflater<R: Read> {
+    // munzip's own decoder, as `inflate` can't be given a window to start from, and
+    // doesn't notice a stream that stops short
+    inner: Inflater<BufReader<R>>,
+}
+
+impl<R: Read> RawInflater<R> {
+    /// Creates a `RawInflater` reading compressed data from `input`
+    pub fn new(input: R) -> Self {
+        Self::with_dictionary(input, &[])
+    }
+
+    /// Creates a `RawInflater` for a stream compressed against a preset dictionary.
+    /// Only the last 32 KiB of `dictionary` can be reached, so only that much is kept.
+    pub fn with_dictionary(input: R, dictionary: &[u8]) -> Self {
+        let window = &dictionary[dictionary.len().saturating_sub(DICTIONARY_WINDOW)..];
+        RawInflater {
+            inner: Inflater::new(BufReader::new(input), 0, window.to_vec()),
+        }
+    }
+}
+
+impl<R: Read> Read for RawInflater<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
@@ -0,0 +1,156 @@
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::attributes::EntryKind;
+
+/// A record of everything `SearchableArchive::extract_all()` wrote, in
+/// `ExtractSummary::receipt`, for audit logs. Files that were skipped, excluded or
+/// rejected weren't written, so aren't in it.
+#[derive(Debug, Clone, Default)]
+pub struct Receipt {
+    /// Each file and directory written, in the order they were written
+    pub paths: Vec<ReceiptEntry>,
+}
+
+/// One file or directory in a `Receipt`.
+#[derive(Debug, Clone)]
+pub struct ReceiptEntry {
+    /// The entry's name in the archive
+    pub name: String,
+    /// Where it was written, relative to the destination
+    pub path: PathBuf,
+    /// `EntryKind::File` or `EntryKind::Directory`
+    pub kind: EntryKind,
+    /// How many bytes were written, 0 for a directory
+    pub size: u64,
+    /// The CRC-32 the archive records, 0 for a directory. Extracting under
+    /// `CrcPolicy::Trust` doesn't check it.
+    pub crc32: u32,
+    /// Whether a file that was already at `path` was overwritten
+    pub replaced: bool,
+    /// The modification time it was given, under `ExtractOptions::preserve_mtime()`
+    pub mtime: Option<SystemTime>,
+    /// The Unix permission bits it was given, under
+    /// `ExtractOptions::preserve_attributes()` on Unix
+    pub permissions: Option<u32>,
+    /// The DOS attributes it was given, under `ExtractOptions::preserve_attributes()`
+    /// on Windows
+    pub dos_attributes: Option<u8>,
+    /// How the entry's name was changed to make `path`
+    pub sanitized: Vec<Sanitization>,
+}
+
+/// A change made to an entry's name to turn it into a path beneath the destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Sanitization {
+    /// `\` separators, as written by some Windows tools, were taken as `/`
+    Backslashes,
+    /// Leading `/`s were stripped
+    Absolute,
+    /// `.` components, and empty ones from doubled `/`s, were dropped
+    Redundant,
+    /// The name couldn't be decoded, and has `U+FFFD` in place of the bytes that
+    /// weren't valid
+    LossyName,
+}
+
+impl Sanitization {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Sanitization::Backslashes => "backslashes",
+            Sanitization::Absolute => "absolute",
+            Sanitization::Redundant => "redundant",
+            Sanitization::LossyName => "lossy_name",
+        }
+    }
+}
+
+impl Receipt {
+    /// Returns the receipt as JSON: an array holding an object for each path, with
+    /// the fields of `ReceiptEntry`. `kind` is `"file"` or `"directory"`, `mtime` is
+    /// in seconds since the Unix epoch, and fields with no value are `null`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut input = std::fs::File::open("archive.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut input).unwrap();
+    ///
+    /// let options = munzip::ExtractOptions::new().preserve_attributes(true);
+    /// let summary = zi.extract_all("out".as_ref(), &options).unwrap();
+    /// std::fs::write("out.receipt.json", summary.receipt.to_json()).unwrap();
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("[");
+        for (i, entry) in self.paths.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let _ = write!(
+                json,
+                "\n  {{\"name\": {}, \"path\": {}, \"kind\": \"{}\", \"size\": {}, \"crc32\": \"{:08x}\", \"replaced\": {}, ",
+                quote(&entry.name),
+                quote(&entry.path.to_string_lossy()),
+                match entry.kind {
+                    EntryKind::Directory => "directory",
+                    _ => "file",
+                },
+                entry.size,
+                entry.crc32,
+                entry.replaced,
+            );
+            let _ = write!(
+                json,
+                "\"mtime\": {}, \"permissions\": {}, \"dos_attributes\": {}, \"sanitized\": [",
+                or_null(entry.mtime.map(unix_secs)),
+                or_null(entry.permissions.map(|mode| format!("\"{mode:o}\""))),
+                or_null(entry.dos_attributes),
+            );
+            for (j, sanitization) in entry.sanitized.iter().enumerate() {
+                if j > 0 {
+                    json.push_str(", ");
+                }
+                let _ = write!(json, "\"{}\"", sanitization.as_str());
+            }
+            json.push_str("]}");
+        }
+        json.push_str("\n]\n");
+        json
+    }
+}
+
+// A JSON string literal holding `s`.
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn or_null(value: Option<impl std::fmt::Display>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "null".to_string(),
+    }
+}
+
+fn unix_secs(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        Err(before) => -(before.duration().as_secs() as i64),
+    }
+}
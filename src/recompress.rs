@@ -0,0 +1,307 @@
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+use std::sync::mpsc;
+
+use crate::iterable::Entry;
+use crate::options::*;
+use crate::patient::PatientReader;
+use crate::searchable::SearchableArchive;
+use crate::types::*;
+use crate::write::*;
+
+/// Settings for `recompress_with_options()`.
+#[derive(Debug, Clone)]
+pub struct RecompressOptions {
+    method: CompressionMethod,
+    copy_undecodable: bool,
+    threads: usize,
+}
+
+impl Default for RecompressOptions {
+    fn default() -> Self {
+        RecompressOptions {
+            method: CompressionMethod::default(),
+            copy_undecodable: false,
+            threads: 1,
+        }
+    }
+}
+
+impl RecompressOptions {
+    /// Creates `RecompressOptions` with every setting at its default.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the method every entry is compressed with. Defaults to
+    /// `CompressionMethod::DeflateDefault`.
+    pub fn method(mut self, method: CompressionMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Sets whether an entry that can't be decompressed, such as an encrypted one or
+    /// one compressed with a method munzip doesn't read, is copied as it's stored,
+    /// keeping its old method, and listed in `RecompressReport::copied`, rather than
+    /// failing the whole copy. Defaults to `false`.
+    pub fn copy_undecodable(mut self, copy: bool) -> Self {
+        self.copy_undecodable = copy;
+        self
+    }
+
+    /// Sets how many threads entries are decompressed and recompressed on. With more
+    /// than one, each thread reads the archive through its own handle and compresses
+    /// an entry into memory, and they're written out in order as they're done, so up
+    /// to about twice this many entries' compressed data is held at once. With one,
+    /// each entry is streamed straight through instead. Defaults to 1.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+}
+
+/// The result of `recompress()`.
+#[derive(Debug, Default)]
+pub struct RecompressReport {
+    /// The names of the entries decompressed and written with the new method
+    pub recompressed: Vec<String>,
+    /// The entries that couldn't be decompressed, and why, when
+    /// `RecompressOptions::copy_undecodable()` has each copied as it's stored
+    pub copied: Vec<(String, MuError)>,
+}
+
+/// Writes a copy of the archive at `src` to `dst` with every entry compressed with
+/// `method`, as for converting an archive to plain `Store` for a reader that can't
+/// inflate, or to a different deflate level. Each entry is streamed through
+/// `Entry::reader()` into the new archive, so none is held in memory whole, and
+/// keeps its name, modification time, Unix mode and DOS attributes. It's an error
+/// if an entry can't be decompressed; `recompress_with_options()` can copy those
+/// as they're stored instead, and spread the work over threads.
+///
+/// `dst` is overwritten, and removed if writing it fails. Requires the `writer`
+/// feature.
+///
+/// # Examples
+///
+/// ``` no_run
+/// munzip::recompress("legacy.zip", "stored.zip", munzip::CompressionMethod::Store).unwrap();
+/// ```
+pub fn recompress<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dst: Q,
+    method: CompressionMethod,
+) -> Result<RecompressReport, MuError> {
+    recompress_with_options(src, dst, &RecompressOptions::new().method(method))
+}
+
+/// Does the same as `recompress()`, with settings from `options`.
+///
+/// # Examples
+///
+/// ``` no_run
+/// let options = munzip::RecompressOptions::new()
+///     .method(munzip::CompressionMethod::Deflate(9))
+///     .copy_undecodable(true)
+///     .threads(4);
+/// let report = munzip::recompress_with_options("legacy.zip", "small.zip", &options).unwrap();
+/// for (name, error) in &report.copied {
+///     eprintln!("left {name} as it was: {error}");
+/// }
+/// ```
+pub fn recompress_with_options<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dst: Q,
+    options: &RecompressOptions,
+) -> Result<RecompressReport, MuError> {
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    if dst.exists() && src.canonicalize()? == dst.canonicalize()? {
+        return Err(MuError::new(
+            ErrorKind::Other,
+            "can't recompress an archive in place",
+        ));
+    }
+
+    let mut input = PatientReader::new(File::open(src)?);
+    let mut zw = ZipWriter::new(File::create(dst)?);
+
+    let written = match options.threads {
+        1 => write_recompressed(&mut input, &mut zw, options),
+        _ => write_recompressed_threaded(src, &mut input, &mut zw, options),
+    };
+    let written = written.and_then(|report| {
+        zw.finish()?;
+        Ok(report)
+    });
+    if written.is_err() {
+        let _ = std::fs::remove_file(dst);
+    }
+
+    written
+}
+
+// What a worker thread made of an entry, for the writing thread to write.
+enum Prepared {
+    Directory,
+    Compressed(Compressed),
+    Undecodable(MuError),
+}
+
+// The archive options every pass over the source reads it with.
+fn source_options() -> ArchiveOptions {
+    ArchiveOptions::new()
+        .include_apple_double(true)
+        .crc_policy(CrcPolicy::Verify)
+}
+
+// The options `entry` is written with, keeping its metadata.
+fn entry_options<R: Read + Seek>(entry: &Entry<'_, R>, method: CompressionMethod) -> EntryOptions {
+    let mut options = EntryOptions::new()
+        .method(method)
+        .dos_attributes(entry.dos_attributes());
+    if let Some(time) = entry.modified() {
+        options = options.modified(time);
+    }
+    if let Some(mode) = entry.unix_mode() {
+        options = options.unix_mode(mode);
+    }
+    options
+}
+
+// Copy `entry`, which couldn't be decompressed because of `e`, as it's stored, if
+// `options` allows it.
+fn copy_undecodable<R: Read + Seek>(
+    zw: &mut ZipWriter<File>,
+    entry: &Entry<'_, R>,
+    e: MuError,
+    options: &RecompressOptions,
+    report: &mut RecompressReport,
+) -> Result<(), MuError> {
+    if !options.copy_undecodable {
+        return Err(e);
+    }
+    let name = entry.filename();
+    zw.copy_entry(entry, &name)?;
+    report.copied.push((name, e));
+    Ok(())
+}
+
+fn write_recompressed(
+    input: &mut PatientReader<File>,
+    zw: &mut ZipWriter<File>,
+    options: &RecompressOptions,
+) -> Result<RecompressReport, MuError> {
+    let mut report = RecompressReport::default();
+    let archive = SearchableArchive::from_reader_with_options(input, &source_options())?;
+
+    for entry in archive.entries() {
+        let name = entry.filename();
+        let entry_options = entry_options(&entry, options.method);
+
+        if name.ends_with('/') {
+            zw.add_directory(&name, &entry_options)?;
+            report.recompressed.push(name);
+            continue;
+        }
+
+        match entry.reader() {
+            Ok(data) => {
+                zw.add_file(&name, data, &entry_options)?;
+                report.recompressed.push(name);
+            }
+            Err(e) => copy_undecodable(zw, &entry, e, options, &mut report)?,
+        }
+    }
+
+    Ok(report)
+}
+
+// Like `write_recompressed()`, but with worker `k` of `options.threads` decompressing
+// and recompressing entries `k`, `k + threads`, and so on through its own handle on
+// `src`, while this thread writes each entry out in turn as its worker hands it over.
+fn write_recompressed_threaded(
+    src: &Path,
+    input: &mut PatientReader<File>,
+    zw: &mut ZipWriter<File>,
+    options: &RecompressOptions,
+) -> Result<RecompressReport, MuError> {
+    let mut report = RecompressReport::default();
+    let archive = SearchableArchive::from_reader_with_options(input, &source_options())?;
+    let threads = options.threads.min(archive.len()).max(1);
+    let method = options.method;
+
+    std::thread::scope(|scope| {
+        let mut workers = Vec::with_capacity(threads);
+        for first in 0..threads {
+            // a bound of one keeps each worker at most an entry ahead of the writer
+            let (tx, rx) = mpsc::sync_channel(1);
+            workers.push(rx);
+            scope.spawn(move || {
+                let prepare = |archive: &SearchableArchive<&mut PatientReader<File>>, index| {
+                    let Some(entry) = archive.by_index(index) else {
+                        return Err(MuError::new(
+                            ErrorKind::Other,
+                            "the archive changed while being recompressed",
+                        ));
+                    };
+                    if entry.filename().ends_with('/') {
+                        return Ok(Prepared::Directory);
+                    }
+                    match entry.reader() {
+                        Ok(data) => Ok(Prepared::Compressed(compress(
+                            data,
+                            &entry_options(&entry, method),
+                        )?)),
+                        Err(e) => Ok(Prepared::Undecodable(e)),
+                    }
+                };
+
+                let mut file = match File::open(src) {
+                    Ok(file) => PatientReader::new(file),
+                    Err(e) => {
+                        let _ = tx.send(Err(e.into()));
+                        return;
+                    }
+                };
+                let archive =
+                    match SearchableArchive::from_reader_with_options(&mut file, &source_options())
+                    {
+                        Ok(archive) => archive,
+                        Err(e) => {
+                            let _ = tx.send(Err(e));
+                            return;
+                        }
+                    };
+                for index in (first..archive.len()).step_by(threads) {
+                    let prepared = prepare(&archive, index);
+                    let failed = prepared.is_err();
+                    // the writer hung up, having failed on an earlier entry
+                    if tx.send(prepared).is_err() || failed {
+                        return;
+                    }
+                }
+            });
+        }
+
+        // returning drops the receivers, which stops any worker still going
+        for (index, entry) in archive.entries().enumerate() {
+            let name = entry.filename();
+            let prepared = workers[index % threads].recv().map_err(|_| {
+                MuError::new(ErrorKind::Other, format!("no worker prepared '{name}'"))
+            })??;
+            let entry_options = entry_options(&entry, method);
+
+            match prepared {
+                Prepared::Directory => zw.add_directory(&name, &entry_options)?,
+                Prepared::Compressed(data) => zw.add_compressed(&name, data, &entry_options)?,
+                Prepared::Undecodable(e) => {
+                    copy_undecodable(zw, &entry, e, options, &mut report)?;
+                    continue;
+                }
+            }
+            report.recompressed.push(name);
+        }
+
+        Ok(report)
+    })
+}
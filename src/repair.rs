@@ -0,0 +1,238 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::crc32::Crc32;
+use crate::findings::{Finding, FindingKind};
+use crate::options::*;
+use crate::patient::PatientReader;
+use crate::provenance::{FieldSource, Provenance};
+use crate::searchable::SearchableArchive;
+use crate::shared::*;
+use crate::truncation::{lossy_name, read_local_entry};
+use crate::types::*;
+use crate::write::*;
+
+/// The result of `repair()`.
+#[derive(Debug, Default)]
+pub struct RepairReport {
+    /// Whether the entries were found by walking the local headers, because the
+    /// archive's end record or central directory couldn't be read. Unix modes and
+    /// DOS attributes are only kept in the central directory, so they're lost when
+    /// this is set.
+    pub from_local_headers: bool,
+    /// The names of the entries written to the repaired archive
+    pub recovered: Vec<String>,
+    /// The entries among those whose recorded sizes or CRC-32 didn't match their
+    /// data, and were written with the real ones. Their data decompressed cleanly,
+    /// but may still not be what was archived.
+    pub corrected: Vec<String>,
+    /// The entries that couldn't be read, and why
+    pub skipped: Vec<(String, MuError)>,
+    /// All of the above as `Finding`s, each with the entry's local header offset,
+    /// in the order they came up: whether the central directory couldn't be read,
+    /// each size and CRC-32 that was corrected, and each entry that was skipped
+    pub findings: Vec<Finding>,
+}
+
+/// Writes a clean copy of the archive at `src` to `dst`, keeping every entry that
+/// can be read. The archive is opened with lenient `Compatibility`, carrying on past
+/// entries with unreadable headers, and if it can't be opened at all, as when its
+/// end is cut off, entries are found by walking the local headers from the start of
+/// the file instead.
+///
+/// Every entry is decompressed and written afresh, so the copy has sizes and CRCs
+/// that match its data, as `CrcPolicy::Fix` would leave them, a central directory
+/// that matches its local headers, and no stray bytes. Names, modification times,
+/// Unix modes and DOS attributes come along, and entries keep their compression
+/// method. Entries that can't be decompressed, such as encrypted ones, are left
+/// out.
+///
+/// `dst` is overwritten, and removed if writing it fails. Requires the `writer`
+/// feature.
+///
+/// # Examples
+///
+/// ``` no_run
+/// let report = munzip::repair("damaged.zip", "repaired.zip").unwrap();
+/// println!("{} entries recovered", report.recovered.len());
+/// for (name, error) in &report.skipped {
+///     eprintln!("lost {name}: {error}");
+/// }
+/// ```
+pub fn repair<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> Result<RepairReport, MuError> {
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    if dst.exists() && src.canonicalize()? == dst.canonicalize()? {
+        return Err(MuError::new(
+            ErrorKind::Other,
+            "can't repair an archive in place",
+        ));
+    }
+
+    let mut input = PatientReader::new(File::open(src)?);
+    let mut zw = ZipWriter::new(File::create(dst)?);
+
+    let written = write_repaired(&mut input, &mut zw).and_then(|report| {
+        zw.finish()?;
+        Ok(report)
+    });
+    if written.is_err() {
+        let _ = std::fs::remove_file(dst);
+    }
+
+    written
+}
+
+fn write_repaired(
+    input: &mut (impl Read + Seek),
+    zw: &mut ZipWriter<File>,
+) -> Result<RepairReport, MuError> {
+    let mut report = RepairReport::default();
+
+    let options = ArchiveOptions::new()
+        .error_mode(ErrorMode::Continue)
+        .include_apple_double(true)
+        .compatibility(Compatibility::lenient());
+
+    let headers = match SearchableArchive::from_reader_with_options(&mut *input, &options) {
+        Ok(archive) => archive.entries,
+        Err(_) => {
+            report.from_local_headers = true;
+            report.findings.push(Finding {
+                kind: FindingKind::CentralDirectoryUnreadable,
+                position: None,
+                entry: None,
+            });
+            local_headers(input)?
+        }
+    };
+
+    for (name, header) in &headers {
+        let finding = |kind| Finding {
+            kind,
+            position: Some(header.local_header_offset),
+            entry: Some(name.clone()),
+        };
+
+        let data = match data_from_internal(input, header) {
+            Ok(data) => data,
+            Err(e) => {
+                report
+                    .findings
+                    .push(finding(FindingKind::Unreadable(e.kind())));
+                report.skipped.push((name.clone(), e));
+                continue;
+            }
+        };
+
+        let mut crc = Crc32::new();
+        crc.update(&data);
+        let (crc32, size) = (crc.finish(), data.len() as u64);
+        let recorded_size = header.uncompressed_size;
+        if size != recorded_size {
+            report.findings.push(finding(FindingKind::SizeMismatch {
+                recorded: recorded_size,
+                actual: size,
+            }));
+        }
+        if crc32 != header.crc32 {
+            report.findings.push(finding(FindingKind::CrcMismatch {
+                recorded: header.crc32,
+                actual: crc32,
+            }));
+        }
+        if crc32 != header.crc32 || size != recorded_size {
+            report.corrected.push(name.clone());
+        }
+
+        let mut options = EntryOptions::new()
+            .method(match header.compression_method {
+                0 => CompressionMethod::Store,
+                _ => CompressionMethod::DeflateDefault,
+            })
+            .dos_attributes(header.external_file_attributes as u8);
+        if let Some(time) = modified(header) {
+            options = options.modified(time);
+        }
+        if let Some(mode) = unix_mode(header) {
+            options = options.unix_mode(mode);
+        }
+
+        match name.ends_with('/') {
+            true => zw.add_directory(name, &options)?,
+            false => zw.add_file(name, &data[..], &options)?,
+        }
+        report.recovered.push(name.clone());
+    }
+
+    Ok(report)
+}
+
+// The headers of every entry found by walking the local headers from the start of
+// the file, for an archive whose central directory can't be read.
+fn local_headers(input: &mut (impl Read + Seek)) -> Result<Vec<(String, InternalHeader)>, MuError> {
+    let file_size = input.seek(SeekFrom::End(0))?;
+
+    let mut headers = Vec::new();
+    let mut position = 0;
+    while let Some(entry) = read_local_entry(input, position, file_size)? {
+        let lfh = entry.lfh;
+
+        // with a data descriptor, the crc and sizes come after the data, and without
+        // one, sizes the header leaves as placeholders are in its ZIP64 field
+        let descriptor = lfh.general_purpose_bit_flag & FLAG_DATA_DESCRIPTOR != 0;
+        let source = |recorded: u32| match (descriptor, recorded as u64) {
+            (true, _) => FieldSource::DataDescriptor,
+            (false, PLACEHOLDER) if entry.zip64 => FieldSource::LocalZip64,
+            (false, _) => FieldSource::LocalHeader,
+        };
+
+        let header = InternalHeader {
+            compressed_size: entry.data_end - entry.data_start,
+            uncompressed_size: entry.uncompressed_size,
+            crc32: entry.crc32,
+            compression_method: lfh.compression_method,
+            general_purpose_bit_flag: lfh.general_purpose_bit_flag & !FLAGS_RESERVED,
+            local_header_offset: position,
+            offset: entry.data_start,
+            last_mod_file_time: lfh.last_mod_file_time,
+            last_mod_file_date: lfh.last_mod_file_date,
+            mtime_unix: extended_mtime(&entry.extra),
+            version_made_by: 0,
+            external_file_attributes: 0,
+            central_index: headers.len(),
+            zip64: entry.zip64,
+            provenance: Provenance {
+                compressed_size: source(lfh.compressed_size),
+                uncompressed_size: source(lfh.uncompressed_size),
+                crc32: match descriptor {
+                    true => FieldSource::DataDescriptor,
+                    false => FieldSource::LocalHeader,
+                },
+                compression_method: FieldSource::LocalHeader,
+                flags: FieldSource::LocalHeader,
+                modified: match extended_mtime(&entry.extra) {
+                    Some(_) => FieldSource::ExtendedTimestamp,
+                    None => FieldSource::LocalHeader,
+                },
+                local_header_offset: FieldSource::Scan,
+            },
+            name_lossy: std::str::from_utf8(&entry.name).is_err(),
+            filename_raw: entry.name,
+        };
+
+        headers.push((lossy_name(&header.filename_raw), header));
+        position = entry.end;
+    }
+
+    Ok(headers)
+}
+
+fn modified(header: &InternalHeader) -> Option<SystemTime> {
+    let secs = modified_secs(header)?;
+    match secs >= 0 {
+        true => UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64)),
+        false => UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs())),
+    }
+}
@@ -1,7 +1,19 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::cache::EntryCache;
+use crate::crc32::CrcCheck;
+use crate::iterable::{Entry, EntryMeta};
+#[cfg(feature = "metrics")]
+use crate::metrics::*;
+use crate::options::*;
+use crate::prefix::PrefixIndex;
 use crate::shared::*;
 use crate::types::*;
 
@@ -9,62 +21,563 @@ use crate::types::*;
 /// to hold the file handle open, and load in specific files, by their name, on demand.
 ///
 /// If you intend to load all your files at once, it'd probably be better to just use the
-/// `IterableArchive`.
+/// `IterableArchive`. Though if you'll be walking the archive more than once, `entries()`
+/// reuses the headers that were scanned at creation instead of reading them again.
 ///
 /// # Examples
 ///
 /// ``` no_run
+/// # mod write { pub fn write_file(_: &str, _: &[u8]) -> std::io::Result<()> { Ok(()) } }
+/// # let mut input = std::fs::File::open("archive.zip").unwrap();
 /// let mut zi = munzip::SearchableArchive::new(&mut input).unwrap();
 ///
 /// let filename = "munzip/Cargo.toml";
 /// let cargo_toml = zi.by_name(filename).unwrap().unwrap();
-/// write::write_file(&"Cargo.toml".to_owned(), &cargo_toml).unwrap();
+/// write::write_file("Cargo.toml", &cargo_toml).unwrap();
 /// ```
-pub struct SearchableArchive<'a> {
-    file: &'a mut File,
-    map: HashMap<String, InternalHeader>,
-    end_rec: EndRecord,
+pub struct SearchableArchive<'a, R: Read + Seek = &'a mut File> {
+    pub(crate) file: Rc<RefCell<R>>,
+    lifetime: PhantomData<&'a ()>,
+    // the file underneath `file`, for the kernel side copies and io_uring, which
+    // only opening a `File` directly gets
+    pub(crate) as_file: Option<fn(&R) -> &File>,
+    pub(crate) entries: Vec<(String, InternalHeader)>,
+    // AppleDouble entries left out of `entries`
+    pub(crate) hidden: Vec<(String, InternalHeader)>,
+    map: HashMap<String, usize>,
+    pub(crate) prefix_index: Option<PrefixIndex>,
+    pub(crate) end_rec: DirectoryEnd,
+    // where the central directory starts, which `end_rec` can't hold past 4 GB
+    pub(crate) cd_offset: u64,
+    // the part of the file that holds the archive, if it was opened within one
+    pub(crate) window: Option<Range<u64>>,
     next_gfh: u64,
+    error_mode: ErrorMode,
+    include_apple_double: bool,
+    names: NameDecoding,
+    compatibility: Compatibility,
+    pub(crate) crc: Rc<CrcCheck>,
+    pub(crate) cache: Rc<EntryCache>,
+    pub(crate) buffer_sizes: BufferSizes,
+    #[cfg(feature = "metrics")]
+    metrics: Rc<RefCell<Recorder>>,
 }
 
 impl<'a> SearchableArchive<'a> {
-    /// Creates a new `SearchableArchive`, and scans the entire archive for file headers.
+    /// Creates a new `SearchableArchive` with the default `ArchiveOptions`, and scans
+    /// the entire archive for file headers.
     pub fn new(file: &'a mut File) -> Result<Self, MuError> {
-        let end_rec = read_end_record(file)?;
+        Self::with_options(file, &ArchiveOptions::default())
+    }
+
+    /// Creates a new `SearchableArchive` configured by `options`, and scans the entire
+    /// archive for file headers.
+    pub fn with_options(file: &'a mut File, options: &ArchiveOptions) -> Result<Self, MuError> {
+        Self::open(file, None, options, Some(|file| &**file))
+    }
 
-        file.seek(SeekFrom::Start(end_rec.central_directory_offset as u64))?;
+    /// Creates a new `SearchableArchive` with the default `ArchiveOptions`, for an
+    /// archive that takes up `length` bytes of `file` from `offset`, as in a firmware
+    /// image or a game's `.pak`. See `open_within_with_options()`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut image = std::fs::File::open("firmware.bin").unwrap();
+    /// // the image's header says where its resources are
+    /// let zi = munzip::SearchableArchive::open_within(&mut image, 0x40000, 0x1c000).unwrap();
+    /// for entry in zi.entries() {
+    ///     println!("{} at {}", entry.filename(), entry.data_offset());
+    /// }
+    /// ```
+    pub fn open_within(file: &'a mut File, offset: u64, length: u64) -> Result<Self, MuError> {
+        Self::open_within_with_options(file, offset, length, &ArchiveOptions::default())
+    }
+
+    /// Creates a new `SearchableArchive` configured by `options`, for an archive
+    /// that takes up `length` bytes of `file` from `offset`. The archive's own
+    /// offsets count from `offset`, and the end record must end exactly at
+    /// `offset + length`. An entry whose header or data would lie outside the window
+    /// is an `ErrorKind::Format` error, rather than being read from the rest of the
+    /// file. The offsets the
+    /// archive's entries report, such as `Entry::data_offset()`, are positions in
+    /// `file`.
+    pub fn open_within_with_options(
+        file: &'a mut File,
+        offset: u64,
+        length: u64,
+        options: &ArchiveOptions,
+    ) -> Result<Self, MuError> {
+        let end = offset
+            .checked_add(length)
+            .ok_or_else(|| MuError::new(ErrorKind::Other, "the archive's window ends past 2^64"))?;
+        if end > file.metadata()?.len() {
+            return Err(MuError::new(
+                ErrorKind::Format,
+                "the archive's window runs past the end of the file",
+            ));
+        }
+        Self::open(file, Some(offset..end), options, Some(|file| &**file))
+    }
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, &'a mut R> {
+    /// Creates a new `SearchableArchive` with the default `ArchiveOptions`, reading
+    /// the archive from anything that's `Read + Seek`, such as a `PatientReader`
+    /// around a file on a slow mount. Everything reads through it, though the kernel
+    /// side copies and io_uring of `extract_all()` need a `File` opened with `new()`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// let file = std::fs::File::open("/mnt/share/archive.zip").unwrap();
+    /// let mut reader = munzip::PatientReader::new(file);
+    ///
+    /// let zi = munzip::SearchableArchive::from_reader(&mut reader).unwrap();
+    /// let readme = zi.entry("README").unwrap().buffer().unwrap();
+    /// ```
+    pub fn from_reader(reader: &'a mut R) -> Result<Self, MuError> {
+        Self::from_reader_with_options(reader, &ArchiveOptions::default())
+    }
+
+    /// Creates a new `SearchableArchive` reading from `reader`, configured by
+    /// `options`.
+    pub fn from_reader_with_options(
+        reader: &'a mut R,
+        options: &ArchiveOptions,
+    ) -> Result<Self, MuError> {
+        Self::open(reader, None, options, None)
+    }
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    fn open(
+        mut file: R,
+        window: Option<Range<u64>>,
+        options: &ArchiveOptions,
+        as_file: Option<fn(&R) -> &File>,
+    ) -> Result<Self, MuError> {
+        let limit = options
+            .end_record_search_limit
+            .unwrap_or(END_RECORD_SEARCH_LIMIT);
+        let (end_rec, cd_offset) = match &window {
+            Some(window) => find_end_record_within(&mut file, window.clone(), limit)?,
+            None => find_end_record(&mut file, limit)?,
+        };
+        check_declared_size(&end_rec, options)?;
+        let names = name_decoding(&mut file, &end_rec, cd_offset, options)?;
+
+        file.seek(SeekFrom::Start(cd_offset))?;
         let next_gfh = file.stream_position()?;
 
         let mut sa = Self {
-            file,
+            file: Rc::new(RefCell::new(file)),
+            lifetime: PhantomData,
+            as_file,
+            entries: Vec::new(),
+            hidden: Vec::new(),
             map: HashMap::new(),
+            prefix_index: None,
             end_rec,
+            cd_offset,
+            window,
             next_gfh,
+            error_mode: options.error_mode,
+            include_apple_double: options.include_apple_double,
+            names,
+            compatibility: options.compatibility,
+            crc: Rc::new(CrcCheck::new(options.crc_policy)),
+            cache: Rc::new(EntryCache::new(options.entry_cache)),
+            buffer_sizes: options.buffer_sizes,
+            #[cfg(feature = "metrics")]
+            metrics: Rc::new(RefCell::new(Recorder::default())),
         };
 
         sa.build_map()?;
+        if options.prefix_index {
+            sa.prefix_index = Some(PrefixIndex::new(&sa.entries));
+        }
 
         Ok(sa)
     }
 
     fn build_map(&mut self) -> Result<(), MuError> {
-        for _ in 0..self.end_rec.num_entries {
-            let (header, filename, new_next_gfh) = next_header(self.file, self.next_gfh)?;
-            self.next_gfh = new_next_gfh;
-            eprintln!("{filename}");
-            self.map.insert(filename, header);
+        let mut file = self.file.borrow_mut();
+        for index in 0..self.end_rec.num_entries as usize {
+            let (gfh, new_next_gfh) = next_global_header(&mut *file, self.next_gfh)?;
+            let position = std::mem::replace(&mut self.next_gfh, new_next_gfh);
+
+            let base = self.window.as_ref().map_or(0, |window| window.start);
+            // a ZIP64 offset is only known once the local header is read
+            let recorded = match gfh.relative_offset_of_local_header {
+                u32::MAX => Ok(()),
+                offset => self.check_within(base + offset as u64, 0),
+            };
+            let read = recorded
+                .and_then(|_| {
+                    local_header(
+                        &mut *file,
+                        base,
+                        &gfh,
+                        position,
+                        index,
+                        &self.names,
+                        &self.compatibility,
+                    )
+                })
+                .and_then(|(header, filename)| {
+                    // `local_header()` has checked the data is in the file
+                    let data_end = header.offset + header.compressed_size;
+                    self.check_within(header.local_header_offset, data_end)?;
+                    Ok((header, filename))
+                });
+            let (header, filename) = match read {
+                Ok(lh) => lh,
+                Err(_) if self.error_mode == ErrorMode::Continue => continue,
+                Err(e) => {
+                    return Err(central_context(
+                        &mut *file,
+                        &gfh,
+                        position,
+                        index,
+                        &self.names,
+                        e,
+                    ))
+                }
+            };
+
+            if !self.include_apple_double && is_apple_double(&filename) {
+                self.hidden.push((filename, header));
+                continue;
+            }
+
+            self.map.insert(filename.clone(), self.entries.len());
+            self.entries.push((filename, header));
         }
 
         Ok(())
     }
 
-    /// Performs a lookup based on the filenames of all entries.
-    pub fn by_name(&mut self, name: &str) -> Result<Option<Vec<u8>>, MuError> {
-        let ih_opt = self.map.get(&(name.to_owned())).cloned();
+    // An entry of an archive opened within a window must have its local header, at
+    // `start`, and its data, ending at `end`, inside it.
+    fn check_within(&self, start: u64, end: u64) -> Result<(), MuError> {
+        match &self.window {
+            Some(window) if !window.contains(&start) || end > window.end => Err(MuError::new(
+                ErrorKind::Format,
+                "entry lies outside the archive's window",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // The part of the file the archive takes up: its window, or the whole file.
+    pub(crate) fn span(&self, file: &mut R) -> Result<Range<u64>, MuError> {
+        match &self.window {
+            Some(window) => Ok(window.clone()),
+            None => Ok(0..file.seek(SeekFrom::End(0))?),
+        }
+    }
 
-        match ih_opt {
+    /// Performs a lookup based on the filenames of all entries. Names use `/` as the
+    /// separator, even when the archive was written with `\`.
+    pub fn by_name(&mut self, name: &str) -> Result<Option<Vec<u8>>, MuError> {
+        match self.entry(name) {
             None => Ok(None),
-            Some(ih) => Ok(Some(data_from_internal(&mut self.file, &ih)?)),
+            Some(mut entry) => entry.buffer().map(Some),
         }
     }
+
+    /// Returns the entry named `name`, found through the index built when the
+    /// archive was opened, so nothing is read or decompressed until its data is
+    /// asked for. Names use `/` as the separator, as for `by_name()`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("app.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// let mut manifest = zi.entry("META-INF/MANIFEST.MF").unwrap();
+    /// let data = manifest.buffer().unwrap();
+    /// ```
+    pub fn entry(&self, name: &str) -> Option<Entry<'a, R>> {
+        self.index_of(name).and_then(|index| self.by_index(index))
+    }
+
+    // The header of the entry named `name`.
+    pub(crate) fn header(&self, name: &str) -> Option<&InternalHeader> {
+        self.map.get(name).map(|&i| &self.entries[i].1)
+    }
+
+    // The index in `entries` of the entry named `name`.
+    pub(crate) fn index_of(&self, name: &str) -> Option<usize> {
+        self.map.get(name).copied()
+    }
+
+    /// Returns how many entries `entries()` yields
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the archive has no entries to yield
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the entry at `index` in the order `entries()` yields them, from 0 to
+    /// `len()`, to come back to an entry found on an earlier pass without walking
+    /// the entries again. Entries left out of `entries()` take up no index, so this
+    /// can differ from `Entry::central_directory_index()`. Unlike `by_name()`, this
+    /// returns the entry, as `entry()` does, rather than its data.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("photos.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// let jpegs: Vec<usize> = zi
+    ///     .entries()
+    ///     .enumerate()
+    ///     .filter(|(_, e)| e.filename().ends_with(".jpg"))
+    ///     .map(|(i, _)| i)
+    ///     .collect();
+    /// for index in jpegs {
+    ///     let data = zi.by_index(index).unwrap().buffer().unwrap();
+    /// }
+    /// ```
+    pub fn by_index(&self, index: usize) -> Option<Entry<'a, R>> {
+        let (filename, header) = self.entries.get(index)?;
+        Some(Entry {
+            file: Rc::clone(&self.file),
+            lifetime: PhantomData,
+            header: header.clone(),
+            filename: filename.clone(),
+            crc: Rc::clone(&self.crc),
+            cache: Rc::clone(&self.cache),
+            buffer_sizes: self.buffer_sizes,
+            #[cfg(feature = "metrics")]
+            metrics: Rc::clone(&self.metrics),
+        })
+    }
+
+    /// Returns an iterator over every entry, in central directory order. Unlike
+    /// `IterableArchive`, no headers are read from the file, so this can be called
+    /// as many times as needed. The yielded entries share this archive's file handle.
+    ///
+    /// All the entries are known up front, so the iterator's `len()` is how many are
+    /// left, for a progress bar. Iterating `&archive` does the same as this.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("archive.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// let total = zi.entries().len();
+    /// for (i, entry) in (&zi).into_iter().enumerate() {
+    ///     println!("[{}/{total}] {}", i + 1, entry.filename());
+    /// }
+    /// ```
+    pub fn entries(&self) -> Entries<'_, 'a, R> {
+        Entries {
+            archive: self,
+            next_entry: 0,
+        }
+    }
+
+    /// Returns the charset that names which aren't UTF-8 were decoded with, if
+    /// `ArchiveOptions::detect_charset()` found any. Requires the `charset` feature.
+    #[cfg(feature = "charset")]
+    pub fn charset(&self) -> Option<crate::charset::Charset> {
+        self.names.fallback
+    }
+
+    /// Registers a callback invoked after each successful `by_name()`, or
+    /// `Entry::buffer()` on an entry from `entries()`, with that entry's timings.
+    /// Requires the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn on_entry_metrics(&mut self, callback: impl FnMut(&EntryMetrics) + 'static) {
+        self.metrics.borrow_mut().callback = Some(Box::new(callback));
+    }
+
+    /// Returns the timings of every entry read so far, whether through `by_name()` or
+    /// an entry from `entries()`. Requires the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> MetricsSummary {
+        self.metrics.borrow().summary.clone()
+    }
+
+    /// Returns the names of the entries read so far whose data didn't match their
+    /// CRC-32, under `CrcPolicy::Record` or `CrcPolicy::Fix`, whether through
+    /// `by_name()`, an entry from `entries()`, or extraction.
+    pub fn crc_mismatches(&self) -> Vec<String> {
+        self.crc.mismatches()
+    }
 }
+
+/// An iterator over the cached headers of a `SearchableArchive`.
+pub struct Entries<'s, 'a, R: Read + Seek = &'a mut File> {
+    archive: &'s SearchableArchive<'a, R>,
+    next_entry: usize,
+}
+
+impl<'s, 'a, R: Read + Seek> Iterator for Entries<'s, 'a, R> {
+    type Item = Entry<'a, R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.archive.by_index(self.next_entry)?;
+        self.next_entry += 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.archive.entries.len() - self.next_entry;
+        (left, Some(left))
+    }
+}
+
+impl<'s, 'a, R: Read + Seek> ExactSizeIterator for Entries<'s, 'a, R> {}
+
+impl<'s, 'a, R: Read + Seek> IntoIterator for &'s SearchableArchive<'a, R> {
+    type Item = Entry<'a, R>;
+    type IntoIter = Entries<'s, 'a, R>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries()
+    }
+}
+
+impl<'s, 'a, R: Read + Seek> Entries<'s, 'a, R> {
+    /// Yields only each entry's metadata. An `EntryMeta` has no way to read data, so
+    /// code that lists an archive can't decompress it by accident.
+    pub fn metadata_only(self) -> MetadataOnly<'s, 'a, R> {
+        MetadataOnly { inner: self }
+    }
+
+    /// Yields each entry's metadata along with its decompressed data, reading every
+    /// entry in turn.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("archive.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// for entry in zi.entries().with_data() {
+    ///     let (meta, data) = entry.unwrap();
+    ///     println!("{}: {} bytes", meta.filename(), data.len());
+    /// }
+    /// ```
+    pub fn with_data(self) -> WithData<'s, 'a, R> {
+        WithData { inner: self }
+    }
+
+    /// Yields only the entries modified after `time`, going by `Entry::modified()`,
+    /// without reading any data, so an incremental build can pick out what's
+    /// changed since its last run. DOS times are only good to two seconds, so an
+    /// entry with no extended timestamp whose DOS time is within two seconds before
+    /// `time` is kept, in case it's really newer. So are entries with no valid time
+    /// at all.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("assets.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    /// let last_run = std::fs::metadata("build/.stamp").unwrap().modified().unwrap();
+    ///
+    /// for mut entry in zi.entries().modified_after(last_run) {
+    ///     let data = entry.buffer().unwrap();
+    ///     println!("rebuilding {} ({} bytes)", entry.filename(), data.len());
+    /// }
+    /// ```
+    pub fn modified_after(self, time: SystemTime) -> ModifiedAfter<'s, 'a, R> {
+        let after = match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs() as i64,
+            Err(before) => -(before.duration().as_secs_f64().ceil() as i64),
+        };
+        ModifiedAfter { inner: self, after }
+    }
+}
+
+/// An iterator over the entries of a `SearchableArchive` modified after a time,
+/// from `Entries::modified_after()`.
+pub struct ModifiedAfter<'s, 'a, R: Read + Seek = &'a mut File> {
+    inner: Entries<'s, 'a, R>,
+    // in whole seconds since the Unix epoch, rounded down
+    after: i64,
+}
+
+impl<'s, 'a, R: Read + Seek> Iterator for ModifiedAfter<'s, 'a, R> {
+    type Item = Entry<'a, R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (_, header) = self.inner.archive.entries.get(self.inner.next_entry)?;
+            let newer = match (modified_secs(header), header.mtime_unix) {
+                (None, _) => true,
+                (Some(secs), Some(_)) => secs > self.after,
+                // it was rounded down to an even second, so it could be up to two
+                // seconds later
+                (Some(secs), None) => secs + 2 > self.after,
+            };
+            if newer {
+                return self.inner.next();
+            }
+            self.inner.next_entry += 1;
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+
+/// An iterator over the metadata of a `SearchableArchive`'s entries, from
+/// `Entries::metadata_only()`.
+pub struct MetadataOnly<'s, 'a, R: Read + Seek = &'a mut File> {
+    inner: Entries<'s, 'a, R>,
+}
+
+impl<'s, 'a, R: Read + Seek> Iterator for MetadataOnly<'s, 'a, R> {
+    type Item = EntryMeta;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (filename, header) = self.inner.archive.entries.get(self.inner.next_entry)?;
+        self.inner.next_entry += 1;
+
+        Some(EntryMeta {
+            header: header.clone(),
+            filename: filename.clone(),
+            local_read: true,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'s, 'a, R: Read + Seek> ExactSizeIterator for MetadataOnly<'s, 'a, R> {}
+
+/// An iterator over a `SearchableArchive`'s entries and their decompressed data,
+/// from `Entries::with_data()`.
+pub struct WithData<'s, 'a, R: Read + Seek = &'a mut File> {
+    inner: Entries<'s, 'a, R>,
+}
+
+impl<'s, 'a, R: Read + Seek> Iterator for WithData<'s, 'a, R> {
+    type Item = Result<(EntryMeta, Vec<u8>), MuError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut entry = self.inner.next()?;
+        Some(entry.buffer().map(|data| (entry.meta(), data)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'s, 'a, R: Read + Seek> ExactSizeIterator for WithData<'s, 'a, R> {}
@@ -0,0 +1,89 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use crate::searchable::SearchableArchive;
+use crate::shared::*;
+use crate::types::*;
+
+/// The spans of the archive file one entry takes up, from
+/// `SearchableArchive::seek_map()`. Fetching `range` is enough to extract the entry
+/// on its own, without the rest of the archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryRange {
+    /// The entry's name, as `Entry::filename()` has it
+    pub name: String,
+    /// The entry's position in the central directory
+    pub central_directory_index: usize,
+    /// The local header, with its name and extra field
+    pub local_header: Range<u64>,
+    /// The data, exactly as stored
+    pub data: Range<u64>,
+    /// The data descriptor after the data, if the entry has one
+    pub data_descriptor: Option<Range<u64>>,
+    /// All of the above, from the start of the local header to the end of the data
+    /// or its descriptor
+    pub range: Range<u64>,
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    /// Lists, for every entry, the absolute byte ranges of the archive it takes up,
+    /// so a downloader can fetch only the entries it wants from a remote archive,
+    /// with HTTP `Range` requests, and extract each from what it fetched. Entries
+    /// come in the order `entries()` yields them.
+    ///
+    /// Only the first 4 bytes of each data descriptor are read, to tell whether it
+    /// starts with a signature. Nothing else is read.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("mirror.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    ///
+    /// for entry in zi.seek_map().unwrap() {
+    ///     if entry.name.ends_with(".iso") {
+    ///         let range = entry.range;
+    ///         println!("Range: bytes={}-{}", range.start, range.end - 1);
+    ///     }
+    /// }
+    /// ```
+    pub fn seek_map(&self) -> Result<Vec<EntryRange>, MuError> {
+        let mut file = self.file.borrow_mut();
+
+        self.entries
+            .iter()
+            .map(|(filename, header)| {
+                let local_header = header.local_header_offset..header.offset;
+                let data = header.offset..header.offset + header.compressed_size;
+
+                let data_descriptor = match header.general_purpose_bit_flag & FLAG_DATA_DESCRIPTOR {
+                    0 => None,
+                    _ => {
+                        file.seek(SeekFrom::Start(data.end))?;
+                        let mut signature = [0; 4];
+                        file.read_exact(&mut signature)?;
+                        let signed = u32::from_le_bytes(signature) == DATA_DESCRIPTOR_SIGNATURE;
+
+                        // the CRC-32, then both sizes, which ZIP64 widens to 8 bytes
+                        let sizes = match header.zip64 {
+                            true => 16,
+                            false => 8,
+                        };
+                        let size = signed as u64 * 4 + 4 + sizes;
+                        Some(data.end..data.end + size)
+                    }
+                };
+
+                let end = data_descriptor.as_ref().map_or(data.end, |d| d.end);
+                Ok(EntryRange {
+                    name: filename.clone(),
+                    central_directory_index: header.central_index,
+                    range: local_header.start..end,
+                    local_header,
+                    data,
+                    data_descriptor,
+                })
+            })
+            .collect()
+    }
+}
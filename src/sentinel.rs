@@ -0,0 +1,123 @@
+// Recovering the sizes of entries whose headers give ZIP64's 0xFFFFFFFF placeholder
+// but have no ZIP64 field to hold the real ones, as some broken writers leave them.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::shared::*;
+use crate::truncation::{find_descriptor, NEXT_SIGNATURES};
+use crate::types::*;
+
+// Work out the real compressed and uncompressed sizes of the entry whose data starts
+// at `data_start`, for whichever of them is a placeholder. The data descriptor has
+// them if there is one, and since a writer that leaves placeholders may well have
+// written a ZIP64 descriptor with 8-byte sizes, its width is told by which reading
+// is followed by a signature. Otherwise the data runs up to one of the signatures that
+// can follow it, the nearest that a deflate stream fits in, and a deflated entry is
+// inflated to count its size.
+pub fn recover_sizes(
+    file: &mut (impl Read + Seek),
+    data_start: u64,
+    flags: u16,
+    method: u16,
+    (compressed_size, uncompressed_size): (u32, u32),
+) -> Result<(u64, u64), MuError> {
+    let file_size = file.seek(SeekFrom::End(0))?;
+    let unrecoverable = || {
+        MuError::new(
+            ErrorKind::Format,
+            "entry sizes are ZIP64 placeholders, with no ZIP64 field or data descriptor to hold the real ones",
+        )
+    };
+
+    if flags & FLAG_DATA_DESCRIPTOR != 0 {
+        let descriptor =
+            find_descriptor(file, data_start, file_size, false)?.ok_or_else(unrecoverable)?;
+        return Ok((descriptor.compressed_size, descriptor.uncompressed_size));
+    }
+
+    let compressed_size = match (compressed_size, method) {
+        (u32::MAX, 0) if uncompressed_size != u32::MAX => uncompressed_size,
+        (u32::MAX, _) => {
+            let mut candidates = next_signatures(file, data_start, file_size)?.into_iter();
+            loop {
+                let candidate = candidates.next().ok_or_else(unrecoverable)?;
+                if method != 8 || inflated_size(file, data_start, candidate).is_ok() {
+                    break candidate;
+                }
+            }
+        }
+        (known, _) => known,
+    };
+
+    let uncompressed_size = match (uncompressed_size, method) {
+        (u32::MAX, 0) => compressed_size,
+        (u32::MAX, 8) => inflated_size(file, data_start, compressed_size)?,
+        (known, _) => known,
+    };
+
+    Ok((compressed_size as u64, uncompressed_size as u64))
+}
+
+// The distance from `data_start` to each signature after it, nearest first, that
+// fits in a 32-bit size.
+fn next_signatures(
+    file: &mut (impl Read + Seek),
+    data_start: u64,
+    file_size: u64,
+) -> Result<Vec<u32>, MuError> {
+    let end = file_size.min(data_start + u32::MAX as u64);
+
+    // blocks overlap by three bytes, so a signature across a boundary isn't missed
+    let mut found = Vec::new();
+    let mut block_start = data_start;
+    let mut buf = vec![0; BUFFER_SIZE];
+    while block_start + 4 <= end {
+        let len = (end - block_start).min(BUFFER_SIZE as u64) as usize;
+        file.seek(SeekFrom::Start(block_start))?;
+        file.read_exact(&mut buf[..len])?;
+
+        for i in 0..=len - 4 {
+            let word = u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+            let distance = block_start + i as u64 - data_start;
+            if NEXT_SIGNATURES.contains(&word) && found.last() != Some(&(distance as u32)) {
+                found.push(distance as u32);
+            }
+        }
+
+        if len < BUFFER_SIZE {
+            break;
+        }
+        block_start += (BUFFER_SIZE - 3) as u64;
+    }
+
+    Ok(found)
+}
+
+// The size of the deflate stream in the `len` bytes from `data_start`, inflated,
+// or an error if the stream doesn't end within them.
+#[cfg(feature = "deflate")]
+fn inflated_size(file: &mut (impl Read + Seek), data_start: u64, len: u32) -> Result<u32, MuError> {
+    file.seek(SeekFrom::Start(data_start))?;
+    let input = std::io::BufReader::new(Read::take(&mut *file, len as u64));
+    let mut inflater = crate::checkpoints::Inflater::new(input, 0, Vec::new());
+
+    let size = std::io::copy(&mut inflater, &mut std::io::sink())?;
+    u32::try_from(size).map_err(|_| {
+        MuError::new(
+            ErrorKind::Format,
+            "entry inflates to over 4 GiB, but has no ZIP64 field",
+        )
+    })
+}
+
+#[cfg(not(feature = "deflate"))]
+fn inflated_size(
+    _file: &mut (impl Read + Seek),
+    _data_start: u64,
+    _len: u32,
+) -> Result<u32, MuError> {
+    Err(MuError::new(
+        ErrorKind::UnsupportedMethod(8),
+        "compression method 8 not supported",
+    ))
+}
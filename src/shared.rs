@@ -1,76 +1,255 @@
-use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
 
+use crate::options::Compatibility;
+use crate::provenance::{FieldSource, Provenance};
+pub use crate::spec::*;
 use crate::types::*;
 
 pub const BUFFER_SIZE: usize = 65536;
-pub const END_RECORD_SIGNATURE: u32 = 0x06054B50;
-pub const GLOBAL_FILE_HEADER_SIGNATURE: u32 = 0x02014B50;
-pub const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034B50;
+
+// The smallest and largest buffers entries are copied through, by default.
+pub const MIN_BUFFER_SIZE: usize = 4 * 1024;
+pub const MAX_BUFFER_SIZE: usize = 1024 * 1024;
+
+// The bounds on the buffer an entry's data is copied through, which is sized to the
+// entry: a tiny file needn't take 1 MiB to copy, and a big one goes quicker in large
+// reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferSizes {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Default for BufferSizes {
+    fn default() -> Self {
+        BufferSizes {
+            min: MIN_BUFFER_SIZE,
+            max: MAX_BUFFER_SIZE,
+        }
+    }
+}
+
+impl BufferSizes {
+    pub fn new(min: usize, max: usize) -> Self {
+        let min = min.max(1);
+        BufferSizes {
+            min,
+            max: max.max(min),
+        }
+    }
+
+    // A buffer for copying `len` bytes: the next power of two up, within the bounds.
+    pub fn for_len(&self, len: u64) -> Vec<u8> {
+        let size = usize::try_from(len)
+            .unwrap_or(usize::MAX)
+            .checked_next_power_of_two()
+            .unwrap_or(usize::MAX)
+            .clamp(self.min, self.max);
+        vec![0; size]
+    }
+}
+
+// The most memory to set aside for an entry's data on its header's word alone, as
+// a crafted size can ask for more than there is.
+#[cfg(feature = "crypto")]
+pub const MAX_PREALLOCATION: u64 = 1 << 20;
+
+// How far from the end of the file an end record can start: its own size, plus
+// the longest comment it could have.
+pub const END_RECORD_SEARCH_LIMIT: u64 = std::mem::size_of::<EndRecord>() as u64 + u16::MAX as u64;
+// The backward search reads this much at a time, so a record near the end of the
+// file, as nearly all are, takes one small read.
+const END_RECORD_SEARCH_CHUNK: u64 = 4096;
 
 // Read ZIP file end record. Will move within file.
-pub fn read_end_record(zip: &mut File) -> Result<EndRecord, MuError> {
+pub fn read_end_record(zip: &mut (impl Read + Seek)) -> Result<(DirectoryEnd, u64), MuError> {
+    find_end_record(zip, END_RECORD_SEARCH_LIMIT)
+}
+
+// Read ZIP file end record, looking for its start no further than `limit` bytes
+// from the end of the file, and return it with where the central directory starts.
+// Will move within file.
+pub fn find_end_record(
+    zip: &mut (impl Read + Seek),
+    limit: u64,
+) -> Result<(DirectoryEnd, u64), MuError> {
     zip.seek(SeekFrom::End(0))?;
     let file_size = zip.stream_position()?;
+    find_end_record_within(zip, 0..file_size, limit)
+}
 
-    if file_size <= std::mem::size_of::<EndRecord>() as u64 {
-        return Err(MuError("input file too small".to_string()));
+// `find_end_record()` for an archive that takes up only `window` of the file, whose
+// offsets count from the start of the window. The central directory's start is
+// returned as a position in the file.
+pub fn find_end_record_within(
+    zip: &mut (impl Read + Seek),
+    window: Range<u64>,
+    limit: u64,
+) -> Result<(DirectoryEnd, u64), MuError> {
+    let base = window.start;
+    let file_size = window.end - window.start;
+
+    let record_sz = std::mem::size_of::<EndRecord>();
+    if file_size < record_sz as u64 {
+        return Err(MuError::new(ErrorKind::Format, "input file too small"));
     }
 
-    // Determine the number of bytes to read
-    let read_bytes = if file_size < BUFFER_SIZE as u64 {
-        file_size
-    } else {
-        BUFFER_SIZE as u64
+    // where a record could start, from `lowest` to just before `starts_end`
+    let lowest = file_size - limit.clamp(record_sz as u64, file_size);
+    let mut starts_end = file_size - record_sz as u64 + 1;
+
+    // A comment may contain the signature too, so keep going until a match that
+    // is consistent with the rest of the file. The search goes backwards a chunk
+    // at a time, each read running on into the last by a record's length.
+    let mut er: Option<(DirectoryEnd, u64)> = None;
+    let mut buf = Vec::new();
+    'search: while starts_end > lowest {
+        let window_start = starts_end
+            .saturating_sub(END_RECORD_SEARCH_CHUNK)
+            .max(lowest);
+        let window_end = starts_end + record_sz as u64 - 1;
+
+        zip.seek(SeekFrom::Start(base + window_start))?;
+        buf.resize((window_end - window_start) as usize, 0);
+        zip.read_exact(&mut buf)?;
+
+        for i in (0..=buf.len() - record_sz).rev() {
+            let node = &buf[i..i + record_sz];
+            // signature is the first u32
+            let sig: u32 = (node[3] as u32) << 24
+                | (node[2] as u32) << 16
+                | (node[1] as u32) << 8
+                | (node[0] as u32);
+            if sig != END_RECORD_SIGNATURE {
+                continue;
+            }
+
+            let candidate: EndRecord = unsafe { std::ptr::read(node.as_ptr() as *const _) };
+            let position = window_start + i as u64;
+            let fits = zip64_end_record(zip, base, &candidate, position, file_size).or_else(|| {
+                let classic = DirectoryEnd {
+                    record: candidate,
+                    num_entries: candidate.num_entries as u64,
+                    central_directory_size: candidate.central_directory_size as u64,
+                };
+                end_record_fits(&candidate, position, file_size).map(|cd_start| (classic, cd_start))
+            });
+            if let Some((end, cd_start)) = fits {
+                er = Some((end, base + cd_start));
+                break 'search;
+            }
+        }
+
+        starts_end = window_start;
+    }
+
+    let (end_record, cd_start) = match er {
+        Some(found) => found,
+        None => {
+            return Err(MuError::new(
+                ErrorKind::Format,
+                "end record signature not found in zip",
+            ))
+        }
     };
 
-    // Seek to the position to start reading from
-    zip.seek(SeekFrom::Start(file_size - read_bytes))?;
+    let record = end_record.record;
+    if record.disk_number != 0
+        || record.central_directory_disk_number != 0
+        || record.num_entries != record.num_entries_this_disk
+    {
+        return Err(MuError::new(
+            ErrorKind::Format,
+            "multifile zips not supported!",
+        ));
+    }
 
-    // Read the end of the file into a buffer
-    let mut buf = vec![0; read_bytes as usize];
-    zip.read_exact(&mut buf)?;
+    Ok((end_record, cd_start))
+}
 
-    let mut er: Option<&[u8]> = None;
-    let record_sz = std::mem::size_of::<EndRecord>();
-    for i in (0..=buf.len() - record_sz).rev() {
-        let node = &buf[i..i + record_sz];
-        // signature is the first u32
-        let sig: u32 = (node[3] as u32) << 24
-            | (node[2] as u32) << 16
-            | (node[1] as u32) << 8
-            | (node[0] as u32);
-        if sig == END_RECORD_SIGNATURE {
-            er = Some(node);
-            break;
-        }
+// The comment has to run exactly to the end of the file, and the central directory
+// has to fit between the start of the file and the end record. Returns where the
+// central directory starts if so.
+fn end_record_fits(er: &EndRecord, position: u64, file_size: u64) -> Option<u64> {
+    let comment_end =
+        position + std::mem::size_of::<EndRecord>() as u64 + er.zip_comment_length as u64;
+    if comment_end != file_size {
+        return None;
     }
 
-    if er.is_none() {
-        return Err(MuError("end record signature not found in zip".to_string()));
+    // An archive over 4 GB without ZIP64 can only record the offset of a directory
+    // past 4 GB modulo 4 GB. One that ends right at the end record, at an offset
+    // that agrees, is taken to be where it really is.
+    let cd_size = er.central_directory_size as u64;
+    let recorded = er.central_directory_offset;
+    match position.checked_sub(cd_size) {
+        Some(start) if start > u32::MAX as u64 && start as u32 == recorded => Some(start),
+        _ => (recorded as u64 + cd_size <= position).then_some(recorded as u64),
     }
+}
 
-    let end_record: EndRecord = unsafe { std::ptr::read(er.unwrap().as_ptr() as *const _) };
+// The entry count and central directory size the ZIP64 end record has, with where
+// the central directory starts, for an end record at `position` that leaves any of
+// them as ZIP64's placeholders. Positions other than those read from the file count
+// from `base`.
+fn zip64_end_record(
+    zip: &mut (impl Read + Seek),
+    base: u64,
+    er: &EndRecord,
+    position: u64,
+    file_size: u64,
+) -> Option<(DirectoryEnd, u64)> {
+    let comment_end = position + END_RECORD_SIZE as u64 + er.zip_comment_length as u64;
+    let placeholders = er.num_entries == u16::MAX
+        || er.central_directory_size == u32::MAX
+        || er.central_directory_offset == u32::MAX;
+    if !placeholders || comment_end != file_size {
+        return None;
+    }
 
-    if end_record.disk_number != 0
-        || end_record.central_directory_disk_number != 0
-        || end_record.num_entries != end_record.num_entries_this_disk
-    {
-        return Err(MuError("multifile zips not supported!".to_string()));
+    let mut locator = [0; ZIP64_END_LOCATOR_SIZE];
+    let locator_at = position.checked_sub(ZIP64_END_LOCATOR_SIZE as u64)?;
+    zip.seek(SeekFrom::Start(base + locator_at)).ok()?;
+    zip.read_exact(&mut locator).ok()?;
+    if locator[..4] != ZIP64_END_LOCATOR_SIGNATURE.to_le_bytes() {
+        return None;
+    }
+
+    let record_at = u64::from_le_bytes(locator[8..16].try_into().ok()?);
+    let mut record = [0; ZIP64_END_RECORD_SIZE];
+    zip.seek(SeekFrom::Start(base + record_at)).ok()?;
+    zip.read_exact(&mut record).ok()?;
+    if record[..4] != ZIP64_END_RECORD_SIGNATURE.to_le_bytes() {
+        return None;
     }
 
-    Ok(end_record)
+    let num_entries = u64::from_le_bytes(record[32..40].try_into().ok()?);
+    let cd_size = u64::from_le_bytes(record[40..48].try_into().ok()?);
+    let cd_start = u64::from_le_bytes(record[48..56].try_into().ok()?);
+    let end = DirectoryEnd {
+        record: *er,
+        num_entries,
+        central_directory_size: cd_size,
+    };
+    // every central directory record takes at least its fixed part
+    let fits = cd_start.checked_add(cd_size)? <= record_at
+        && num_entries <= cd_size / std::mem::size_of::<GlobalFileHeader>() as u64;
+    fits.then_some((end, cd_start))
 }
 
 pub fn get_global_file_header(buf: &[u8]) -> Result<GlobalFileHeader, MuError> {
     let file_header: GlobalFileHeader = unsafe { std::ptr::read(buf.as_ptr() as *const _) };
 
     if file_header.signature != GLOBAL_FILE_HEADER_SIGNATURE {
-        return Err(MuError("invalid global file header signature".to_string()));
+        return Err(MuError::new(
+            ErrorKind::Format,
+            "invalid global file header signature",
+        ));
     }
 
     if file_header.file_name_length as usize + 1 >= BUFFER_SIZE {
-        return Err(MuError("file name too long".to_string()));
+        return Err(MuError::new(ErrorKind::Format, "file name too long"));
     }
 
     Ok(file_header)
@@ -80,26 +259,25 @@ pub fn get_internal_file_header(buf: &[u8]) -> Result<LocalFileHeader, MuError>
     let file_header: LocalFileHeader = unsafe { std::ptr::read(buf.as_ptr() as *const _) };
 
     if file_header.signature != LOCAL_FILE_HEADER_SIGNATURE {
-        return Err(MuError("invalid local file header signature".to_string()));
+        return Err(MuError::new(
+            ErrorKind::Format,
+            "invalid local file header signature",
+        ));
     }
 
     if file_header.file_name_length as usize + 1 >= BUFFER_SIZE {
-        return Err(MuError("file name too long".to_string()));
-    }
-
-    if file_header.compression_method == 0
-        && file_header.compressed_size != file_header.uncompressed_size
-    {
-        return Err(MuError("invalid local file header signature".to_string()));
+        return Err(MuError::new(ErrorKind::Format, "file name too long"));
     }
 
     Ok(file_header)
 }
 
-pub fn next_header(
-    file: &mut File,
+// Read the central directory record at `next_gfh`, and find where the one after
+// it starts. If this fails, there's no way to locate the rest of the records.
+pub fn next_global_header(
+    file: &mut (impl Read + Seek),
     next_gfh: u64,
-) -> Result<(InternalHeader, String, u64), MuError> {
+) -> Result<(GlobalFileHeader, u64), MuError> {
     file.seek(SeekFrom::Start(next_gfh))?;
 
     const GFH_SIZE: usize = std::mem::size_of::<GlobalFileHeader>();
@@ -107,10 +285,231 @@ pub fn next_header(
     file.read_exact(&mut fh_buff)?;
 
     let gfh = get_global_file_header(&fh_buff)?;
-    let push_pos = file.stream_position()?;
+
+    // skip filename and comments
+    let skip_len: u64 = gfh.file_name_length as u64
+        + gfh.extra_field_length as u64
+        + gfh.file_comment_length as u64;
+
+    Ok((gfh, next_gfh + GFH_SIZE as u64 + skip_len))
+}
+
+// Attach what the central directory record at `position` says about its entry to
+// an error reading that entry, for when its local header is what failed.
+pub fn central_context(
+    file: &mut (impl Read + Seek),
+    gfh: &GlobalFileHeader,
+    position: u64,
+    central_index: usize,
+    names: &NameDecoding,
+    err: MuError,
+) -> MuError {
+    // a record whose name or extra field can't be read still has its fixed part
+    let meta = central_meta(file, gfh, position, central_index, names)
+        .or_else(|_| record_meta(gfh, Vec::new(), &[], central_index, names));
+    match meta {
+        Ok(meta) => err.with_entry(meta),
+        Err(_) => err,
+    }
+}
+
+// What the central directory record at `position` says about its entry, without
+// reading its local header. Sizes and the offset come from its ZIP64 field where
+// they're placeholders, and the time from its extended timestamp field.
+pub fn central_meta(
+    file: &mut (impl Read + Seek),
+    gfh: &GlobalFileHeader,
+    position: u64,
+    central_index: usize,
+    names: &NameDecoding,
+) -> Result<crate::iterable::EntryMeta, MuError> {
+    const GFH_SIZE: u64 = std::mem::size_of::<GlobalFileHeader>() as u64;
+    let mut filename_raw = vec![0; gfh.file_name_length as usize];
+    let mut extra = vec![0; gfh.extra_field_length as usize];
+    file.seek(SeekFrom::Start(position + GFH_SIZE))?;
+    file.read_exact(&mut filename_raw)?;
+    file.read_exact(&mut extra)?;
+
+    record_meta(gfh, filename_raw, &extra, central_index, names)
+}
+
+// An entry's metadata from its central directory record, once its name and extra
+// field are read.
+fn record_meta(
+    gfh: &GlobalFileHeader,
+    filename_raw: Vec<u8>,
+    extra: &[u8],
+    central_index: usize,
+    names: &NameDecoding,
+) -> Result<crate::iterable::EntryMeta, MuError> {
+    let flags = gfh.general_purpose_bit_flag;
+    let (filename, name_lossy) = names.decode(&filename_raw, flags);
+
+    let zip64 = find_extra_field(extra, ZIP64_TAG);
+    let recorded = [
+        gfh.uncompressed_size as u64,
+        gfh.compressed_size as u64,
+        gfh.relative_offset_of_local_header as u64,
+    ];
+    let [uncompressed_size, compressed_size, offset] = match zip64 {
+        Some(field) => resolve_zip64(field, recorded)?,
+        None => recorded,
+    };
+    let [uncompressed_source, compressed_source, offset_source] =
+        recorded.map(|value| match (value, zip64) {
+            (PLACEHOLDER, Some(_)) => FieldSource::CentralZip64,
+            _ => FieldSource::CentralDirectory,
+        });
+    let mtime_unix = extended_mtime(extra);
+
+    Ok(crate::iterable::EntryMeta {
+        header: InternalHeader {
+            compressed_size,
+            uncompressed_size,
+            crc32: gfh.crc32,
+            compression_method: gfh.compression_method,
+            general_purpose_bit_flag: flags,
+            local_header_offset: offset,
+            // not known without the local header, which `local_read` says
+            offset,
+            filename_raw,
+            name_lossy,
+            last_mod_file_time: gfh.last_mod_file_time,
+            last_mod_file_date: gfh.last_mod_file_date,
+            mtime_unix,
+            version_made_by: gfh.version_made_by,
+            external_file_attributes: gfh.external_file_attributes,
+            central_index,
+            zip64: zip64.is_some() || recorded.contains(&PLACEHOLDER),
+            provenance: Provenance {
+                compressed_size: compressed_source,
+                uncompressed_size: uncompressed_source,
+                modified: match mtime_unix {
+                    Some(_) => FieldSource::ExtendedTimestamp,
+                    None => FieldSource::CentralDirectory,
+                },
+                local_header_offset: offset_source,
+                ..Provenance::central()
+            },
+        },
+        filename: normalize_separators(&filename),
+        local_read: false,
+    })
+}
+
+// How an archive decodes filenames. Names are UTF-8, unless the `charset` feature
+// supplies a fallback for those that aren't, and don't claim to be.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NameDecoding {
+    #[cfg(feature = "charset")]
+    pub fallback: Option<crate::charset::Charset>,
+}
+
+impl NameDecoding {
+    // Decode a name, and whether it had to be decoded lossily, with U+FFFD in place
+    // of what couldn't be, so one bad name doesn't stop the archive being read.
+    pub fn decode(&self, raw: &[u8], flags: u16) -> (String, bool) {
+        let utf8 = std::str::from_utf8(raw);
+
+        #[cfg(feature = "charset")]
+        if let (Err(_), Some(charset), 0) = (utf8, self.fallback, flags & FLAG_UTF8) {
+            if let Ok(name) = crate::charset::decode_name(raw, charset) {
+                return (name, false);
+            }
+        }
+        #[cfg(not(feature = "charset"))]
+        let _ = flags;
+
+        match utf8 {
+            Ok(name) => (name.to_string(), false),
+            Err(_) => (String::from_utf8_lossy(raw).into_owned(), true),
+        }
+    }
+}
+
+// Settle on how to decode an archive's names, which may mean a pass over the central
+// directory to guess their charset.
+pub fn name_decoding(
+    file: &mut (impl Read + Seek),
+    end_rec: &DirectoryEnd,
+    cd_offset: u64,
+    options: &crate::options::ArchiveOptions,
+) -> Result<NameDecoding, MuError> {
+    #[cfg(feature = "charset")]
+    if options.detect_charset {
+        return Ok(NameDecoding {
+            fallback: crate::charset::detect_archive(file, end_rec, cd_offset)?,
+        });
+    }
+    #[cfg(not(feature = "charset"))]
+    let _ = (file, end_rec, cd_offset, options);
+
+    Ok(NameDecoding::default())
+}
+
+// Check what the end record declares against the limits in `options`, before any
+// of the central directory is read.
+pub fn check_declared_size(
+    end_rec: &DirectoryEnd,
+    options: &crate::options::ArchiveOptions,
+) -> Result<(), MuError> {
+    let num_entries = end_rec.num_entries as usize;
+    if let Some(max) = options.max_entries.filter(|&max| num_entries > max) {
+        return Err(MuError::new(
+            ErrorKind::QuotaExceeded(Quota::Entries),
+            format!("archive declares {num_entries} entries, over the limit of {max}"),
+        ));
+    }
+
+    let cd_size = end_rec.central_directory_size;
+    if let Some(max) = options
+        .max_central_directory_size
+        .filter(|&max| cd_size > max)
+    {
+        return Err(MuError::new(
+            ErrorKind::QuotaExceeded(Quota::CentralDirectorySize),
+            format!("archive declares a {cd_size} byte central directory, over the limit of {max}"),
+        ));
+    }
+
+    Ok(())
+}
+
+// Read the local header a central directory record points to. A failure here only
+// concerns this one entry.
+pub fn local_header(
+    file: &mut (impl Read + Seek),
+    base: u64,
+    gfh: &GlobalFileHeader,
+    position: u64,
+    central_index: usize,
+    names: &NameDecoding,
+    compatibility: &Compatibility,
+) -> Result<(InternalHeader, String), MuError> {
+    // the central record's ZIP64 field has whatever it leaves as placeholders
+    let mut central_extra = vec![0; gfh.extra_field_length as usize];
+    file.seek(SeekFrom::Start(
+        position + std::mem::size_of::<GlobalFileHeader>() as u64 + gfh.file_name_length as u64,
+    ))?;
+    file.read_exact(&mut central_extra)?;
+    let central_zip64 = find_extra_field(&central_extra, ZIP64_TAG);
+    let recorded = [
+        gfh.uncompressed_size as u64,
+        gfh.compressed_size as u64,
+        gfh.relative_offset_of_local_header as u64,
+    ];
+    let [central_uncompressed, central_compressed, offset] = match central_zip64 {
+        Some(field) => resolve_zip64(field, recorded)?,
+        None => recorded,
+    };
+    let central_sources = recorded.map(|value| match (value, central_zip64) {
+        (PLACEHOLDER, Some(_)) => FieldSource::CentralZip64,
+        _ => FieldSource::CentralDirectory,
+    });
 
     // seek to local
-    file.seek(SeekFrom::Start(gfh.relative_offset_of_local_header as u64))?;
+    let local_header_offset = base + offset;
+    file.seek(SeekFrom::Start(local_header_offset))?;
 
     const LFH_SIZE: usize = std::mem::size_of::<LocalFileHeader>();
     let mut fh_buff: [u8; LFH_SIZE] = [0; LFH_SIZE];
@@ -120,53 +519,467 @@ pub fn next_header(
 
     let mut filename_buf = vec![0; lfh.file_name_length as usize];
     file.read_exact(&mut filename_buf)?;
-    let filename = std::str::from_utf8(&filename_buf)?.to_string();
+    let (filename, name_lossy) = names.decode(&filename_buf, gfh.general_purpose_bit_flag);
+    let filename = normalize_separators(&filename);
+
+    let mut extra_buf = vec![0; lfh.extra_field_length as usize];
+    file.read_exact(&mut extra_buf)?;
+
+    // the local ZIP64 field has both sizes, though some writers only put in those
+    // the header leaves as placeholders, and without one, the central record's stand
+    let local_zip64 = find_extra_field(&extra_buf, ZIP64_TAG);
+    let local_sizes = [lfh.uncompressed_size as u64, lfh.compressed_size as u64];
+    let real_sizes = match local_zip64 {
+        Some(field) if field.len() >= 16 => resolve_zip64(field, [u32::MAX as u64; 2])?,
+        Some(field) => resolve_zip64(field, local_sizes)?,
+        None => [central_uncompressed, central_compressed],
+    };
+    let [local_uncompressed, local_compressed] = [0, 1].map(|i| match local_sizes[i] {
+        PLACEHOLDER if local_zip64.is_some() => (real_sizes[i], FieldSource::LocalZip64),
+        PLACEHOLDER => (real_sizes[i], central_sources[i]),
+        recorded => (recorded, FieldSource::LocalHeader),
+    });
+
+    // `ArchiveEditor` can only add an extended timestamp to the central record, so
+    // one there stands in for a local header without one
+    let mtime_unix = extended_mtime(&extra_buf).or_else(|| extended_mtime(&central_extra));
+
+    let header_source = match compatibility.trust_central_directory {
+        true => FieldSource::CentralDirectory,
+        false => FieldSource::LocalHeader,
+    };
+    let (method, mut flags, time, date) = match compatibility.trust_central_directory {
+        true => (
+            gfh.compression_method,
+            gfh.general_purpose_bit_flag,
+            gfh.last_mod_file_time,
+            gfh.last_mod_file_date,
+        ),
+        false => (
+            lfh.compression_method,
+            lfh.general_purpose_bit_flag,
+            lfh.last_mod_file_time,
+            lfh.last_mod_file_date,
+        ),
+    };
+    if compatibility.ignore_reserved_flags {
+        flags &= !FLAGS_RESERVED;
+    }
 
-    if lfh.extra_field_length != 0 {
-        file.seek(SeekFrom::Current(lfh.extra_field_length as i64))?;
+    // with a data descriptor, the local header's sizes are left zero, and with masked
+    // headers, they're hidden
+    let ((compressed_size, compressed_source), (uncompressed_size, uncompressed_source)) =
+        match compatibility.trust_central_directory
+            || flags & (FLAG_DATA_DESCRIPTOR | FLAG_MASKED_HEADERS) != 0
+        {
+            true => (
+                (central_compressed, central_sources[1]),
+                (central_uncompressed, central_sources[0]),
+            ),
+            false => (local_compressed, local_uncompressed),
+        };
+
+    // ZIP64's placeholders, with nowhere for the real sizes to be
+    let placeholder = compressed_size == PLACEHOLDER || uncompressed_size == PLACEHOLDER;
+    let has_zip64 = local_zip64.is_some() || central_zip64.is_some();
+    let recovered = placeholder && !has_zip64;
+    let sizes = (
+        (compressed_size, compressed_source),
+        (uncompressed_size, uncompressed_source),
+    );
+    let ((compressed_size, compressed_source), (uncompressed_size, uncompressed_source)) =
+        match (placeholder, has_zip64) {
+            (true, false) if compatibility.recover_placeholder_sizes => {
+                // without a ZIP64 field, both sizes are still their 32-bit fields
+                let data_start = file.stream_position()?;
+                let (compressed, uncompressed) = crate::sentinel::recover_sizes(
+                    file,
+                    data_start,
+                    flags,
+                    method,
+                    (compressed_size as u32, uncompressed_size as u32),
+                )?;
+                file.seek(SeekFrom::Start(data_start))?;
+
+                // a data descriptor has both, and otherwise only placeholders are found
+                let found = |recorded, source| match (flags & FLAG_DATA_DESCRIPTOR, recorded) {
+                    (0, PLACEHOLDER) => FieldSource::Scan,
+                    (0, _) => source,
+                    _ => FieldSource::DataDescriptor,
+                };
+                (
+                    (compressed, found(compressed_size, compressed_source)),
+                    (uncompressed, found(uncompressed_size, uncompressed_source)),
+                )
+            }
+            (true, false) => {
+                return Err(MuError::new(
+                    ErrorKind::Format,
+                    "entry sizes are ZIP64 placeholders, but it has no ZIP64 field",
+                ))
+            }
+            _ => sizes,
+        };
+
+    // stored data is its own uncompressed size, except that an encrypted entry's also
+    // holds its encryption header
+    let encrypted = flags & (FLAG_ENCRYPTED | FLAG_STRONG_ENCRYPTION) != 0;
+    let (compressed_size, uncompressed_size, compressed_source, uncompressed_source) =
+        match method == 0 && !encrypted && compressed_size != uncompressed_size {
+            false => (
+                compressed_size,
+                uncompressed_size,
+                compressed_source,
+                uncompressed_source,
+            ),
+            true if compatibility.allow_stored_size_mismatch => {
+                match central_compressed == central_uncompressed {
+                    true => (
+                        central_compressed,
+                        central_uncompressed,
+                        central_sources[1],
+                        central_sources[0],
+                    ),
+                    false => (
+                        compressed_size,
+                        compressed_size,
+                        compressed_source,
+                        compressed_source,
+                    ),
+                }
+            }
+            true => {
+                return Err(MuError::new(
+                    ErrorKind::Format,
+                    format!(
+                        "stored entry's compressed size {compressed_size} differs from its \
+                     uncompressed size {uncompressed_size}"
+                    ),
+                ))
+            }
+        };
+
+    // whatever the sizes claim, the data has to be in the file
+    let data_start = file.stream_position()?;
+    let file_size = file.seek(SeekFrom::End(0))?;
+    file.seek(SeekFrom::Start(data_start))?;
+    if data_start
+        .checked_add(compressed_size)
+        .is_none_or(|end| end > file_size)
+    {
+        return Err(MuError::new(
+            ErrorKind::Format,
+            format!("entry's compressed size {compressed_size} runs past the end of the file"),
+        ));
     }
 
     let ih: InternalHeader = InternalHeader {
-        compressed_size: lfh.compressed_size,
-        uncompressed_size: lfh.uncompressed_size,
-        compression_method: lfh.compression_method,
-        offset: file.stream_position()? as u32,
+        compressed_size,
+        uncompressed_size,
+        // as is its crc, so the central directory's is used regardless
+        crc32: gfh.crc32,
+        compression_method: method,
+        general_purpose_bit_flag: flags,
+        local_header_offset,
+        offset: data_start,
+        filename_raw: filename_buf,
+        name_lossy,
+        last_mod_file_time: time,
+        last_mod_file_date: date,
+        mtime_unix,
+        version_made_by: gfh.version_made_by,
+        external_file_attributes: gfh.external_file_attributes,
+        central_index,
+        // a field of all ones in the central record means its ZIP64 field has it,
+        // unless the sizes had to be recovered for want of one
+        zip64: has_zip64
+            || (!recovered
+                && (gfh.compressed_size == u32::MAX || gfh.uncompressed_size == u32::MAX))
+            || gfh.relative_offset_of_local_header == u32::MAX,
+        provenance: Provenance {
+            compressed_size: compressed_source,
+            uncompressed_size: uncompressed_source,
+            crc32: FieldSource::CentralDirectory,
+            compression_method: header_source,
+            flags: header_source,
+            modified: match mtime_unix {
+                Some(_) => FieldSource::ExtendedTimestamp,
+                None => header_source,
+            },
+            local_header_offset: central_sources[2],
+        },
     };
 
-    // rewind to GFH
-    file.seek(SeekFrom::Start(push_pos))?;
+    Ok((ih, filename))
+}
 
-    // skip filename and comments
-    let skip_len: i64 = gfh.file_name_length as i64
-        + gfh.extra_field_length as i64
-        + gfh.file_comment_length as i64;
+// A 32-bit size or offset of all ones, which says the ZIP64 field holds the value.
+pub const PLACEHOLDER: u64 = u32::MAX as u64;
+
+// Replace each of `values` that's a placeholder with the next eight bytes of the
+// ZIP64 extra field `field`, which holds only those, in the order the spec gives
+// them: uncompressed size, compressed size, then local header offset.
+pub fn resolve_zip64<const N: usize>(
+    field: &[u8],
+    mut values: [u64; N],
+) -> Result<[u64; N], MuError> {
+    let mut rest = field;
+    for value in values.iter_mut().filter(|value| **value == PLACEHOLDER) {
+        let (real, tail) = rest.split_first_chunk::<8>().ok_or_else(|| {
+            MuError::new(
+                ErrorKind::Format,
+                "ZIP64 field is too short for the values it stands in for",
+            )
+        })?;
+        *value = u64::from_le_bytes(*real);
+        rest = tail;
+    }
+    Ok(values)
+}
 
-    file.seek(SeekFrom::Current(skip_len))?;
+// The modification time from an extended timestamp field: a flags byte, then the
+// times it flags, mtime first.
+pub fn extended_mtime(extra: &[u8]) -> Option<i32> {
+    find_extra_field(extra, EXTENDED_TIMESTAMP_TAG)
+        .filter(|ut| ut.len() >= 5 && ut[0] & 1 != 0)
+        .map(|ut| i32::from_le_bytes([ut[1], ut[2], ut[3], ut[4]]))
+}
+
+// Find the data of the extra field with the given tag.
+pub fn find_extra_field(extra: &[u8], tag: u16) -> Option<&[u8]> {
+    let mut rest = extra;
+    while rest.len() >= 4 {
+        let id = u16::from_le_bytes([rest[0], rest[1]]);
+        let len = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        let data = rest.get(4..4 + len)?;
+        if id == tag {
+            return Some(data);
+        }
+        rest = &rest[4 + len..];
+    }
+    None
+}
 
-    Ok((ih, filename, file.stream_position()?))
+// The entry's modification time in seconds since the Unix epoch, preferring the
+// extended timestamp field over the DOS time.
+pub fn modified_secs(header: &InternalHeader) -> Option<i64> {
+    match header.mtime_unix {
+        Some(secs) => Some(secs as i64),
+        None => dos_to_unix(header.last_mod_file_date, header.last_mod_file_time),
+    }
 }
 
-pub fn data_from_internal(file: &mut File, header: &InternalHeader) -> Result<Vec<u8>, MuError> {
-    let dst_len = header.uncompressed_size;
-    let src_len = header.compressed_size;
+// The Unix mode the external attributes hold, if the entry was made on Unix.
+pub fn unix_mode(header: &InternalHeader) -> Option<u32> {
+    let mode = header.external_file_attributes >> 16;
+    let host = (header.version_made_by >> 8) as u8;
+    if matches!(host, HOST_UNIX | HOST_OSX) && mode != 0 {
+        Some(mode)
+    } else {
+        None
+    }
+}
 
-    file.seek(SeekFrom::Start(header.offset as u64))?;
+// Convert a DOS date and time to seconds since the Unix epoch. DOS times carry no
+// time zone, so they're taken as UTC.
+pub fn dos_to_unix(date: u16, time: u16) -> Option<i64> {
+    let year = 1980 + (date >> 9) as i64;
+    let month = ((date >> 5) & 0xF) as i64;
+    let day = (date & 0x1F) as i64;
+    let hour = (time >> 11) as i64;
+    let minute = ((time >> 5) & 0x3F) as i64;
+    let second = ((time & 0x1F) * 2) as i64;
+
+    if !(1..=12).contains(&month) || day == 0 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
 
-    if header.compression_method == 0 {
-        // Store - just read it
-        let mut data = vec![0; dst_len as usize];
-        file.read_exact(&mut data)?;
-        Ok(data)
-    } else if header.compression_method == 8 {
-        // DEFLATE
-        let mut compressed_data = vec![0; src_len as usize];
-        file.read_exact(&mut compressed_data)?;
-        let data = inflate::inflate_bytes(&compressed_data)?;
-        Ok(data)
+    // days from civil, shifted so the year starts in March
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
     } else {
-        let method = header.compression_method;
-        Err(MuError(
-            format!("compression method {method} not supported").to_string(),
-        ))
+        (year, month - 3)
+    };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * m + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+// Convert seconds since the Unix epoch to a DOS date and time, in UTC. DOS times
+// only run from 1980 to 2107, and only to two second precision.
+pub fn unix_to_dos(secs: i64) -> Option<(u16, u16)> {
+    let days = secs.div_euclid(86400);
+    let rem = secs.rem_euclid(86400);
+
+    // civil from days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    if !(1980..=2107).contains(&year) {
+        return None;
     }
+
+    let date = ((year - 1980) << 9 | month << 5 | day) as u16;
+    let time = ((rem / 3600) << 11 | (rem / 60 % 60) << 5 | (rem % 60 / 2)) as u16;
+
+    Some((date, time))
+}
+
+// Finder keeps AppleDouble files for every entry under this directory.
+pub fn is_apple_double(name: &str) -> bool {
+    name.starts_with("__MACOSX/")
+}
+
+// The spec requires forward slashes, but some Windows tools write backslashes.
+pub fn normalize_separators(name: &str) -> String {
+    name.replace('\\', "/")
+}
+
+// Refuse entries whose flags say the data isn't a plain compressed file.
+pub fn check_flags(header: &InternalHeader) -> Result<(), MuError> {
+    let flags = header.general_purpose_bit_flag;
+
+    if flags & (FLAG_ENCRYPTED | FLAG_STRONG_ENCRYPTION | FLAG_MASKED_HEADERS) != 0 {
+        return Err(MuError::new(
+            ErrorKind::Encrypted,
+            "encrypted entries not supported",
+        ));
+    }
+
+    if flags & FLAG_PATCHED_DATA != 0 {
+        return Err(MuError::new(
+            ErrorKind::PatchedData,
+            "compressed patched data not supported",
+        ));
+    }
+
+    let reserved = flags & FLAGS_RESERVED;
+    if reserved != 0 {
+        return Err(MuError::new(
+            ErrorKind::ReservedFlags(reserved),
+            format!("reserved general purpose flags {reserved:#06x} set"),
+        ));
+    }
+
+    Ok(())
+}
+
+// A reader over an entry's decompressed data, pulling from the file as it goes.
+pub fn data_reader<'f>(
+    file: &'f mut (impl Read + Seek),
+    header: &InternalHeader,
+) -> Result<Box<dyn Read + 'f>, MuError> {
+    check_flags(header)?;
+
+    file.seek(SeekFrom::Start(header.offset))?;
+    decompressor(header.compression_method, file.take(header.compressed_size))
+}
+
+// A reader decompressing `compressed`, an entry's data as stored, by `method`, as
+// it's read.
+pub fn decompressor<'r>(
+    method: u16,
+    compressed: impl Read + 'r,
+) -> Result<Box<dyn Read + 'r>, MuError> {
+    match method {
+        0 => Ok(Box::new(compressed)),
+        #[cfg(feature = "deflate")]
+        8 => Ok(Box::new(inflate::DeflateDecoder::new(compressed))),
+        method => Err(MuError::new(
+            ErrorKind::UnsupportedMethod(method),
+            format!("compression method {method} not supported"),
+        )),
+    }
+}
+
+pub fn data_from_internal(
+    file: &mut (impl Read + Seek),
+    header: &InternalHeader,
+) -> Result<Vec<u8>, MuError> {
+    check_flags(header)?;
+
+    file.seek(SeekFrom::Start(header.offset))?;
+
+    decompress(
+        header.compression_method,
+        read_stored(file, header.compressed_size)?,
+    )
+}
+
+// Read the `len` bytes of an entry's data, as stored, without trusting `len` enough
+// to allocate it all up front.
+pub fn read_stored(file: &mut impl Read, len: u64) -> Result<Vec<u8>, MuError> {
+    let mut stored = Vec::new();
+    file.take(len).read_to_end(&mut stored)?;
+    match stored.len() as u64 == len {
+        true => Ok(stored),
+        false => Err(MuError::new(
+            ErrorKind::Format,
+            format!(
+                "entry's data ends {} bytes short",
+                len - stored.len() as u64
+            ),
+        )),
+    }
+}
+
+// Read from `data` into `buf` as `Read::read()` does, trying again for as long as
+// it's interrupted, as sockets, FIFOs and FUSE mounts can be.
+pub fn read_retrying(data: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    loop {
+        match data.read(buf) {
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+// Copy `data` to `out` through `buf`, as `std::io::copy()` does through its own,
+// returning how many bytes were copied.
+pub fn copy_through(
+    data: &mut impl Read,
+    out: &mut impl std::io::Write,
+    buf: &mut [u8],
+) -> std::io::Result<u64> {
+    let mut copied = 0;
+    loop {
+        let n = match read_retrying(data, buf)? {
+            0 => return Ok(copied),
+            n => n,
+        };
+        out.write_all(&buf[..n])?;
+        copied += n as u64;
+    }
+}
+
+// Decompress an entry's data, already read in whole.
+pub fn decompress(method: u16, compressed: Vec<u8>) -> Result<Vec<u8>, MuError> {
+    match method {
+        // Store - nothing to do
+        0 => Ok(compressed),
+        #[cfg(feature = "deflate")]
+        8 => inflate::inflate_bytes(&compressed).map_err(|e| MuError::new(ErrorKind::Format, e)),
+        method => Err(MuError::new(
+            ErrorKind::UnsupportedMethod(method),
+            format!("compression method {method} not supported"),
+        )),
+    }
+}
+
+// Split `N` bytes off the front of `rest`, if it has that many.
+pub fn split_array<const N: usize>(rest: &mut &[u8]) -> Option<[u8; N]> {
+    let (bytes, after) = rest.split_first_chunk::<N>()?;
+    *rest = after;
+    Some(*bytes)
 }
@@ -0,0 +1,240 @@
+//! The numbers the zip format is built from, as set out in PKWARE's APPNOTE.TXT, and
+//! as munzip reads and writes them. Tools that scan or write archives alongside
+//! munzip can use these rather than declaring their own.
+//!
+//! # Examples
+//!
+//! ```
+//! use munzip::spec::{self, GeneralPurposeFlags, Signature};
+//!
+//! let header = [0x50, 0x4b, 0x03, 0x04, 0x14, 0x00, 0x08, 0x08];
+//! let signature = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+//! assert_eq!(Signature::from_u32(signature), Some(Signature::LocalFileHeader));
+//!
+//! let flags = GeneralPurposeFlags(u16::from_le_bytes([header[6], header[7]]));
+//! assert!(flags.has_data_descriptor() && flags.is_utf8());
+//! assert_eq!(spec::method_name(spec::METHOD_DEFLATE), Some("Deflate"));
+//! ```
+
+/// Starts each local file header
+pub const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034B50;
+/// Starts each central directory record
+pub const GLOBAL_FILE_HEADER_SIGNATURE: u32 = 0x02014B50;
+/// Optionally starts a data descriptor
+pub const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074B50;
+/// Starts the end of central directory record
+pub const END_RECORD_SIGNATURE: u32 = 0x06054B50;
+/// Starts the ZIP64 end of central directory record
+pub const ZIP64_END_RECORD_SIGNATURE: u32 = 0x06064B50;
+/// Starts the ZIP64 end of central directory locator, just before the end record
+pub const ZIP64_END_LOCATOR_SIGNATURE: u32 = 0x07064B50;
+/// Starts the first segment of a split archive. It's the data descriptor's.
+pub const SPLIT_SIGNATURE: u32 = 0x08074B50;
+/// Starts an archive that was to be split, but fit in one segment
+pub const UNSPLIT_SIGNATURE: u32 = 0x30304B50;
+
+/// The size of a local file header, before its name and extra field
+pub const LOCAL_FILE_HEADER_SIZE: usize = 30;
+/// The size of a central directory record, before its name, extra field and comment
+pub const GLOBAL_FILE_HEADER_SIZE: usize = 46;
+/// The size of a data descriptor with its signature, and without ZIP64 sizes
+pub const DATA_DESCRIPTOR_SIZE: usize = 16;
+/// The size of the end record, before its comment
+pub const END_RECORD_SIZE: usize = 22;
+/// The size of the ZIP64 end of central directory record, before its extensible data
+pub const ZIP64_END_RECORD_SIZE: usize = 56;
+/// The size of the ZIP64 end of central directory locator
+pub const ZIP64_END_LOCATOR_SIZE: usize = 20;
+
+/// Bit 0: the entry is encrypted
+pub const FLAG_ENCRYPTED: u16 = 1 << 0;
+/// Bit 3: the CRC-32 and sizes are in a data descriptor after the data, and are
+/// zero in the local header
+pub const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+/// Bit 5: the data is a compressed patch
+pub const FLAG_PATCHED_DATA: u16 = 1 << 5;
+/// Bit 6: the entry uses strong encryption
+pub const FLAG_STRONG_ENCRYPTION: u16 = 1 << 6;
+/// Bit 11: the name and comment are UTF-8
+pub const FLAG_UTF8: u16 = 1 << 11;
+/// Bit 13: the local header's values are masked, for central directory encryption
+pub const FLAG_MASKED_HEADERS: u16 = 1 << 13;
+/// The bits the spec reserves or leaves unused: 4 for enhanced deflating, 7 to 10,
+/// 12 for enhanced compression, and 14 and 15
+pub const FLAGS_RESERVED: u16 = 1 << 4 | 0b1111 << 7 | 1 << 12 | 0b11 << 14;
+
+/// Method 0, stored as is
+pub const METHOD_STORE: u16 = 0;
+/// Method 8, DEFLATE
+pub const METHOD_DEFLATE: u16 = 8;
+/// Method 99, marking WinZip AES encryption, with the real method in its extra field
+pub const METHOD_WINZIP_AES: u16 = 99;
+
+/// Version needed to extract a stored file: 1.0
+pub const VERSION_STORE: u16 = 10;
+/// Version needed to extract a deflated file or a directory: 2.0
+pub const VERSION_DEFLATE: u16 = 20;
+/// Version needed to extract anything else: 6.3, the spec's latest, which covers
+/// every method it defines
+pub const VERSION_ANY: u16 = 63;
+
+/// The high byte of version made by for MS-DOS, and tools writing DOS attributes
+pub const HOST_DOS: u8 = 0;
+/// The high byte of version made by for Unix, whose entries hold a Unix mode
+pub const HOST_UNIX: u8 = 3;
+/// The high byte of version made by for Windows NTFS
+pub const HOST_NTFS: u8 = 10;
+/// The high byte of version made by for Windows VFAT
+pub const HOST_VFAT: u8 = 14;
+/// The high byte of version made by for macOS, whose entries hold a Unix mode
+pub const HOST_OSX: u8 = 19;
+
+/// The extra field holding ZIP64 sizes and offsets
+pub const ZIP64_TAG: u16 = 0x0001;
+/// The extended timestamp extra field, holding Unix times
+pub const EXTENDED_TIMESTAMP_TAG: u16 = 0x5455;
+/// Info-ZIP's Unix extra field, holding the owner's uid and gid
+pub const INFOZIP_UNIX_TAG: u16 = 0x7875;
+/// Android `zipalign`'s extra field, padding a stored entry's data to a boundary
+pub const ALIGNMENT_TAG: u16 = 0xD935;
+/// WinZip's AES extra field, holding the key strength and the real compression method
+pub const WINZIP_AES_TAG: u16 = 0x9901;
+
+/// The DOS readonly attribute, in the low byte of the external attributes
+pub const DOS_READONLY: u32 = 0x01;
+/// The DOS directory attribute, in the low byte of the external attributes
+pub const DOS_DIRECTORY: u32 = 0x10;
+
+/// The file type bits of a Unix mode, which Unix entries keep in the high 16 bits of
+/// their external attributes
+pub const S_IFMT: u32 = 0o170000;
+/// A named pipe
+pub const S_IFIFO: u32 = 0o010000;
+/// A character device
+pub const S_IFCHR: u32 = 0o020000;
+/// A directory
+pub const S_IFDIR: u32 = 0o040000;
+/// A block device
+pub const S_IFBLK: u32 = 0o060000;
+/// A regular file
+pub const S_IFREG: u32 = 0o100000;
+/// A symlink, whose entry's data is its target
+pub const S_IFLNK: u32 = 0o120000;
+/// A Unix domain socket
+pub const S_IFSOCK: u32 = 0o140000;
+
+/// A record in an archive, by the signature it starts with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signature {
+    LocalFileHeader,
+    CentralFileHeader,
+    DataDescriptor,
+    EndRecord,
+}
+
+impl Signature {
+    /// Returns the record `signature` starts, if it's one of these. The data
+    /// descriptor's signature also starts a split archive.
+    pub fn from_u32(signature: u32) -> Option<Signature> {
+        match signature {
+            LOCAL_FILE_HEADER_SIGNATURE => Some(Signature::LocalFileHeader),
+            GLOBAL_FILE_HEADER_SIGNATURE => Some(Signature::CentralFileHeader),
+            DATA_DESCRIPTOR_SIGNATURE => Some(Signature::DataDescriptor),
+            END_RECORD_SIGNATURE => Some(Signature::EndRecord),
+            _ => None,
+        }
+    }
+
+    /// Returns the signature, as it's read little endian from the file
+    pub fn to_u32(self) -> u32 {
+        match self {
+            Signature::LocalFileHeader => LOCAL_FILE_HEADER_SIGNATURE,
+            Signature::CentralFileHeader => GLOBAL_FILE_HEADER_SIGNATURE,
+            Signature::DataDescriptor => DATA_DESCRIPTOR_SIGNATURE,
+            Signature::EndRecord => END_RECORD_SIGNATURE,
+        }
+    }
+}
+
+/// A general purpose bit flag, with the bits munzip acts on named.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GeneralPurposeFlags(pub u16);
+
+impl GeneralPurposeFlags {
+    /// Returns true if the entry is encrypted, whether traditionally or strongly
+    pub fn is_encrypted(&self) -> bool {
+        self.0 & (FLAG_ENCRYPTED | FLAG_STRONG_ENCRYPTION) != 0
+    }
+    /// Returns true if the entry uses strong encryption
+    pub fn is_strongly_encrypted(&self) -> bool {
+        self.0 & FLAG_STRONG_ENCRYPTION != 0
+    }
+    /// Returns true if the sizes and CRC-32 follow the data, in a data descriptor
+    pub fn has_data_descriptor(&self) -> bool {
+        self.0 & FLAG_DATA_DESCRIPTOR != 0
+    }
+    /// Returns true if the data is a compressed patch
+    pub fn is_patched(&self) -> bool {
+        self.0 & FLAG_PATCHED_DATA != 0
+    }
+    /// Returns true if the name and comment are flagged as UTF-8
+    pub fn is_utf8(&self) -> bool {
+        self.0 & FLAG_UTF8 != 0
+    }
+    /// Returns true if the local header's values are masked
+    pub fn has_masked_headers(&self) -> bool {
+        self.0 & FLAG_MASKED_HEADERS != 0
+    }
+    /// Returns the reserved and unused bits that are set, which munzip refuses
+    /// unless `Compatibility::ignore_reserved_flags()` is on
+    pub fn reserved(&self) -> u16 {
+        self.0 & FLAGS_RESERVED
+    }
+    /// Returns the two bits methods 6, 8, 9 and 14 use for their options, such as
+    /// DEFLATE's compression level: 0 normal, 1 maximum, 2 fast and 3 super fast
+    pub fn method_options(&self) -> u8 {
+        ((self.0 >> 1) & 0b11) as u8
+    }
+}
+
+/// Returns the name the spec gives compression method `method`, if it gives one.
+pub fn method_name(method: u16) -> Option<&'static str> {
+    Some(match method {
+        0 => "Store",
+        1 => "Shrink",
+        2..=5 => "Reduce",
+        6 => "Implode",
+        8 => "Deflate",
+        9 => "Deflate64",
+        10 => "PKWARE DCL Implode",
+        12 => "BZIP2",
+        14 => "LZMA",
+        16 => "IBM z/OS CMPSC",
+        18 => "IBM TERSE",
+        19 => "IBM LZ77 z",
+        93 => "Zstandard",
+        94 => "MP3",
+        95 => "XZ",
+        96 => "JPEG",
+        97 => "WavPack",
+        98 => "PPMd",
+        99 => "AE-x encryption marker",
+        _ => return None,
+    })
+}
+
+/// Returns true if munzip can read entries compressed with `method`: always
+/// `METHOD_STORE`, and `METHOD_DEFLATE` with the `deflate` feature.
+pub fn is_supported_method(method: u16) -> bool {
+    method == METHOD_STORE || (method == METHOD_DEFLATE && cfg!(feature = "deflate"))
+}
+
+/// Returns the version needed to extract that `ZipWriter` writes for an entry
+/// compressed with `method`, which is a directory if `directory` is true.
+pub fn version_needed(method: u16, directory: bool) -> u16 {
+    match method {
+        METHOD_STORE if !directory => VERSION_STORE,
+        METHOD_STORE | METHOD_DEFLATE => VERSION_DEFLATE,
+        _ => VERSION_ANY,
+    }
+}
@@ -0,0 +1,158 @@
+use std::io::{self, Read};
+
+use crate::fs::to_io;
+use crate::types::*;
+use crate::write::{EntryOptions, OpenFile, Unseekable, ZipWriter};
+
+/// A zip archive of files read from an iterator, made as it's read, for sending as
+/// an HTTP response body. Only as much of the archive as has been asked for is
+/// made, a buffer's worth of one file at a time, so however many and however large
+/// the files, little is held in memory. The archive is written as
+/// `ZipWriter::streaming()` writes it.
+///
+/// It's both a `Read` and an `Iterator` of chunks, so it can be handed to whatever a
+/// server framework takes: a blocking reader, or, for hyper or axum, a body made
+/// from a stream of the chunks, read on a blocking thread since the files are.
+/// Requires the `stream` feature.
+///
+/// # Examples
+///
+/// ``` no_run
+/// use std::io::Write;
+///
+/// # let mut socket = std::net::TcpStream::connect("127.0.0.1:8080").unwrap();
+/// let names = ["logs/app.log", "logs/db.log"];
+/// let files = names
+///     .iter()
+///     .map(|&name| (name, std::fs::File::open(name).unwrap()));
+/// let body = munzip::ZipStream::new(files, &munzip::EntryOptions::new());
+///
+/// socket.write_all(b"HTTP/1.1 200 OK\r\n").unwrap();
+/// socket.write_all(b"Content-Type: application/zip\r\n").unwrap();
+/// socket.write_all(b"Transfer-Encoding: chunked\r\n\r\n").unwrap();
+/// for chunk in body {
+///     let chunk = chunk.unwrap();
+///     write!(socket, "{:x}\r\n", chunk.len()).unwrap();
+///     socket.write_all(&chunk).unwrap();
+///     socket.write_all(b"\r\n").unwrap();
+/// }
+/// socket.write_all(b"0\r\n\r\n").unwrap();
+/// ```
+pub struct ZipStream<I, R> {
+    files: I,
+    options: EntryOptions,
+    // `None` once the archive is finished, or writing it failed
+    writer: Option<ZipWriter<Unseekable<Vec<u8>>>>,
+    current: Option<(OpenFile, R)>,
+    buf: Vec<u8>,
+    // what's been made but not yet read, from `pending[read..]`
+    pending: Vec<u8>,
+    read: usize,
+}
+
+// How much of a file is read at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+impl<I, S, R> ZipStream<I, R>
+where
+    I: Iterator<Item = (S, R)>,
+    S: AsRef<str>,
+    R: Read,
+{
+    /// Creates a `ZipStream` of `files`, names and their readers, each added with
+    /// `options`. Nothing is read until the stream is.
+    pub fn new(files: impl IntoIterator<IntoIter = I>, options: &EntryOptions) -> Self {
+        ZipStream {
+            files: files.into_iter(),
+            options: options.clone(),
+            writer: Some(ZipWriter::streaming(Vec::new())),
+            current: None,
+            buf: vec![0; CHUNK_SIZE],
+            pending: Vec::new(),
+            read: 0,
+        }
+    }
+
+    // Make more of the archive, until there's some to read or it's all been made.
+    fn fill(&mut self) -> Result<(), MuError> {
+        while self.read == self.pending.len() {
+            let Some(writer) = self.writer.as_mut() else {
+                return Ok(());
+            };
+
+            let step = match self.current.as_mut() {
+                Some((file, data)) => match data.read(&mut self.buf) {
+                    Ok(0) => {
+                        let (file, _) = self.current.take().unwrap();
+                        writer.end_file(file)
+                    }
+                    Ok(n) => writer.write_file_data(file, &self.buf[..n]),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => Ok(()),
+                    Err(e) => Err(e.into()),
+                },
+                None => match self.files.next() {
+                    Some((name, data)) => writer
+                        .begin_file(name.as_ref(), &self.options)
+                        .map(|file| self.current = Some((file, data))),
+                    None => {
+                        let out = self.writer.take().unwrap().finish()?.into_inner();
+                        self.pending = out;
+                        self.read = 0;
+                        return Ok(());
+                    }
+                },
+            };
+            if let Err(e) = step {
+                self.writer = None;
+                return Err(e);
+            }
+
+            if let Some(writer) = self.writer.as_mut() {
+                self.pending = writer.take_output();
+                self.read = 0;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<I, S, R> Read for ZipStream<I, R>
+where
+    I: Iterator<Item = (S, R)>,
+    S: AsRef<str>,
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.fill().map_err(to_io)?;
+
+        let n = buf.len().min(self.pending.len() - self.read);
+        buf[..n].copy_from_slice(&self.pending[self.read..self.read + n]);
+        self.read += n;
+        Ok(n)
+    }
+}
+
+impl<I, S, R> Iterator for ZipStream<I, R>
+where
+    I: Iterator<Item = (S, R)>,
+    S: AsRef<str>,
+    R: Read,
+{
+    type Item = io::Result<Vec<u8>>;
+
+    /// Returns the next part of the archive that's been made, of no set size,
+    /// ending once the archive is finished or after an error.
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.fill() {
+            return Some(Err(to_io(e)));
+        }
+        if self.read == self.pending.len() {
+            return None;
+        }
+
+        let chunk = self.pending.split_off(self.read);
+        self.pending.clear();
+        self.read = 0;
+        Some(Ok(chunk))
+    }
+}
@@ -0,0 +1,472 @@
+use std::io::{Read, Seek, Write};
+#[cfg(feature = "writer")]
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::attributes::{entry_kind, EntryKind};
+use crate::searchable::SearchableArchive;
+use crate::shared::*;
+use crate::types::*;
+#[cfg(feature = "writer")]
+use crate::write::*;
+
+const BLOCK: usize = 512;
+
+// the largest value an 11 digit octal field holds
+const OCTAL_MAX: u64 = 0o77777777777;
+
+/// What `ZipWriter::add_tar()` did with a tar stream.
+#[cfg(feature = "writer")]
+#[derive(Debug, Clone, Default)]
+pub struct TarIngest {
+    /// The number of files, directories and symlinks written to the zip
+    pub added: usize,
+    /// The names of tar members with no zip equivalent (hard links, devices, fifos)
+    pub skipped: Vec<String>,
+}
+
+impl<'a, R: Read + Seek> SearchableArchive<'a, R> {
+    /// Writes every entry to `out` as a POSIX tar stream, decompressing as it goes so
+    /// no entry is ever held in memory whole. Names, sizes and modification times
+    /// carry over, as do Unix modes and symlinks for entries made on Unix. Other
+    /// entries get `0644`, or `0755` for directories. Names too long for a plain tar
+    /// header get a pax extended header.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut input = std::fs::File::open("archive.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut input).unwrap();
+    ///
+    /// let tar = std::fs::File::create("archive.tar").unwrap();
+    /// zi.write_tar(std::io::BufWriter::new(tar)).unwrap();
+    /// ```
+    pub fn write_tar<W: Write>(&self, mut out: W) -> Result<W, MuError> {
+        let mut file = self.file.borrow_mut();
+
+        for (filename, header) in &self.entries {
+            let kind = entry_kind(filename, header);
+            let is_dir = kind == EntryKind::Directory;
+            let mode = unix_mode(header);
+
+            let mut tar = TarHeader {
+                name: filename.clone(),
+                mode: mode.unwrap_or(if is_dir { 0o755 } else { 0o644 }) & 0o7777,
+                size: header.uncompressed_size,
+                mtime: modified_secs(header).unwrap_or(0).max(0) as u64,
+                typeflag: if is_dir { b'5' } else { b'0' },
+                linkname: String::new(),
+            };
+
+            let mut data = data_reader(&mut *file, header)?;
+
+            if kind == EntryKind::Symlink {
+                let mut target = String::new();
+                data.read_to_string(&mut target)?;
+                tar.typeflag = b'2';
+                tar.linkname = target;
+                tar.size = 0;
+            }
+
+            if is_dir || tar.typeflag == b'2' {
+                tar.size = 0;
+                tar.write(&mut out)?;
+                continue;
+            }
+
+            tar.write(&mut out)?;
+
+            let mut buf = self.buffer_sizes.for_len(tar.size);
+            let copied = copy_through(&mut data, &mut out, &mut buf)?;
+            if copied != tar.size {
+                return Err(MuError::new(
+                    ErrorKind::Format,
+                    format!(
+                        "'{filename}' is {copied} bytes, but its header says {}",
+                        tar.size
+                    ),
+                ));
+            }
+
+            pad(&mut out, copied)?;
+        }
+
+        // end of archive
+        out.write_all(&[0; BLOCK * 2])?;
+        out.flush()?;
+
+        Ok(out)
+    }
+}
+
+#[cfg(feature = "writer")]
+impl<W: Write + std::io::Seek> ZipWriter<W> {
+    /// Reads a tar stream to its end, adding each file, directory and symlink to the
+    /// zip. Entries are compressed as `options` says, while their modification time,
+    /// mode and owner come from the tar headers, including pax and GNU long name
+    /// extensions. Symlinks are stored Info-ZIP style, with the target as contents.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// let tar = std::fs::File::open("archive.tar").unwrap();
+    /// let out = std::fs::File::create("archive.zip").unwrap();
+    ///
+    /// let mut zw = munzip::ZipWriter::new(out);
+    /// zw.add_tar(std::io::BufReader::new(tar), &munzip::EntryOptions::new())
+    ///     .unwrap();
+    /// zw.finish().unwrap();
+    /// ```
+    pub fn add_tar<R: Read>(
+        &mut self,
+        mut tar: R,
+        options: &EntryOptions,
+    ) -> Result<TarIngest, MuError> {
+        let mut ingest = TarIngest::default();
+        let mut ext = PaxOverrides::default();
+
+        loop {
+            let mut b = [0u8; BLOCK];
+            if !read_block(&mut tar, &mut b)? || b.iter().all(|&x| x == 0) {
+                break;
+            }
+
+            let expected = parse_number(&b[148..156])?;
+            b[148..156].fill(b' ');
+            let sum: u64 = b.iter().map(|&x| x as u64).sum();
+            if sum != expected {
+                return Err(MuError::new(
+                    ErrorKind::Format,
+                    "tar header checksum mismatch",
+                ));
+            }
+
+            let typeflag = b[156];
+            let mut size = parse_number(&b[124..136])?;
+
+            let mut name = field_str(&b[..100]);
+            if &b[257..262] == b"ustar" && b[345] != 0 {
+                name = format!("{}/{name}", field_str(&b[345..500]));
+            }
+
+            // metadata headers describe the member that follows
+            match typeflag {
+                b'x' | b'L' | b'K' => {
+                    let data = read_data(&mut tar, size)?;
+                    match typeflag {
+                        b'x' => ext.parse_pax(&data)?,
+                        b'L' => ext.path = Some(field_str(&data)),
+                        _ => ext.linkpath = Some(field_str(&data)),
+                    }
+                    continue;
+                }
+                b'g' => {
+                    read_data(&mut tar, size)?;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let ext = std::mem::take(&mut ext);
+            let name = ext.path.unwrap_or(name);
+            size = ext.size.unwrap_or(size);
+            let mtime = match ext.mtime {
+                Some(t) => t,
+                None => parse_number(&b[136..148])? as i64,
+            };
+            let uid = ext.uid.map_or_else(|| parse_number(&b[108..116]), Ok)?;
+            let gid = ext.gid.map_or_else(|| parse_number(&b[116..124]), Ok)?;
+            let mode = parse_number(&b[100..108])? as u32 & 0o7777;
+
+            let mut entry = options.clone().unix_owner(uid as u32, gid as u32);
+            entry = match mtime {
+                t if t >= 0 => entry.modified(UNIX_EPOCH + Duration::from_secs(t as u64)),
+                t => entry.modified(UNIX_EPOCH - Duration::from_secs(t.unsigned_abs())),
+            };
+
+            let name = name.trim_start_matches("./").to_string();
+            match typeflag {
+                b'0' | b'\0' | b'7' => {
+                    let mut data = (&mut tar).take(size);
+                    self.add_file(&name, &mut data, &entry.unix_mode(S_IFREG | mode))?;
+                    if data.limit() != 0 {
+                        return Err(MuError::new(ErrorKind::Format, "tar stream is truncated"));
+                    }
+                    skip_padding(&mut tar, size)?;
+                }
+                b'5' => {
+                    read_data(&mut tar, size)?;
+                    if name.is_empty() {
+                        continue;
+                    }
+                    self.add_directory(&name, &entry.unix_mode(S_IFDIR | mode))?;
+                }
+                b'2' => {
+                    read_data(&mut tar, size)?;
+                    let target = ext.linkpath.unwrap_or_else(|| field_str(&b[157..257]));
+                    self.add_symlink(&name, &target, &entry.unix_mode(mode))?;
+                }
+                _ => {
+                    read_data(&mut tar, size)?;
+                    ingest.skipped.push(name);
+                    continue;
+                }
+            }
+
+            ingest.added += 1;
+        }
+
+        Ok(ingest)
+    }
+}
+
+// Values from pax and GNU extension headers, which replace those of the next member.
+#[cfg(feature = "writer")]
+#[derive(Default)]
+struct PaxOverrides {
+    path: Option<String>,
+    linkpath: Option<String>,
+    size: Option<u64>,
+    mtime: Option<i64>,
+    uid: Option<u64>,
+    gid: Option<u64>,
+}
+
+#[cfg(feature = "writer")]
+impl PaxOverrides {
+    fn parse_pax(&mut self, data: &[u8]) -> Result<(), MuError> {
+        let bad = || MuError::new(ErrorKind::Format, "malformed pax extended header");
+
+        let mut rest = data;
+        while !rest.is_empty() {
+            let space = rest.iter().position(|&c| c == b' ').ok_or_else(bad)?;
+            let len: usize = std::str::from_utf8(&rest[..space])?
+                .parse()
+                .map_err(|_| bad())?;
+            if len <= space || len > rest.len() || rest[len - 1] != b'\n' {
+                return Err(bad());
+            }
+
+            let record = std::str::from_utf8(&rest[space + 1..len - 1])?;
+            let (key, value) = record.split_once('=').ok_or_else(bad)?;
+            let number = |v: &str| v.parse::<u64>().map_err(|_| bad());
+            match key {
+                "path" => self.path = Some(value.to_string()),
+                "linkpath" => self.linkpath = Some(value.to_string()),
+                "size" => self.size = Some(number(value)?),
+                "uid" => self.uid = Some(number(value)?),
+                "gid" => self.gid = Some(number(value)?),
+                "mtime" => {
+                    // fractional seconds are dropped
+                    let whole = value.split('.').next().unwrap_or(value);
+                    self.mtime = Some(whole.parse().map_err(|_| bad())?);
+                }
+                _ => {}
+            }
+
+            rest = &rest[len..];
+        }
+
+        Ok(())
+    }
+}
+
+// Returns false at a clean end of stream.
+#[cfg(feature = "writer")]
+fn read_block<R: Read>(tar: &mut R, b: &mut [u8; BLOCK]) -> Result<bool, MuError> {
+    let mut filled = 0;
+    while filled < BLOCK {
+        match tar.read(&mut b[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(MuError::new(ErrorKind::Format, "tar stream is truncated")),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
+
+// Read a member's data and its padding.
+#[cfg(feature = "writer")]
+fn read_data<R: Read>(tar: &mut R, size: u64) -> Result<Vec<u8>, MuError> {
+    let mut data = Vec::new();
+    tar.take(size).read_to_end(&mut data)?;
+    if (data.len() as u64) < size {
+        return Err(MuError::new(ErrorKind::Format, "tar stream is truncated"));
+    }
+    skip_padding(tar, size)?;
+    Ok(data)
+}
+
+#[cfg(feature = "writer")]
+fn skip_padding<R: Read>(tar: &mut R, size: u64) -> Result<(), MuError> {
+    let rem = (size % BLOCK as u64) as usize;
+    if rem != 0 {
+        let mut skip = [0u8; BLOCK];
+        tar.read_exact(&mut skip[..BLOCK - rem])?;
+    }
+    Ok(())
+}
+
+// Octal, space or NUL terminated, or GNU's base-256 when the high bit is set.
+#[cfg(feature = "writer")]
+fn parse_number(field: &[u8]) -> Result<u64, MuError> {
+    if field[0] & 0x80 != 0 {
+        let mut value = (field[0] & 0x7f) as u64;
+        for &b in &field[1..] {
+            value = value
+                .checked_mul(256)
+                .ok_or_else(|| MuError::new(ErrorKind::Format, "tar number field overflows"))?
+                | b as u64;
+        }
+        return Ok(value);
+    }
+
+    let digits = std::str::from_utf8(field)?.trim_matches(|c| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8)
+        .map_err(|_| MuError::new(ErrorKind::Format, "invalid tar number field"))
+}
+
+#[cfg(feature = "writer")]
+fn field_str(field: &[u8]) -> String {
+    let end = field.iter().position(|&c| c == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+struct TarHeader {
+    name: String,
+    mode: u32,
+    size: u64,
+    mtime: u64,
+    typeflag: u8,
+    linkname: String,
+}
+
+impl TarHeader {
+    fn write<W: Write>(&self, out: &mut W) -> Result<(), MuError> {
+        let mut pax = String::new();
+
+        let split = split_name(&self.name);
+        if split.is_none() {
+            pax_record(&mut pax, "path", &self.name);
+        }
+        if self.linkname.len() > 100 {
+            pax_record(&mut pax, "linkpath", &self.linkname);
+        }
+        if self.size > OCTAL_MAX {
+            pax_record(&mut pax, "size", &self.size.to_string());
+        }
+
+        if !pax.is_empty() {
+            let ext = block(
+                b"././@PaxHeader",
+                b"",
+                0o644,
+                pax.len() as u64,
+                self.mtime,
+                b'x',
+                b"",
+            );
+            out.write_all(&ext)?;
+            out.write_all(pax.as_bytes())?;
+            pad(out, pax.len() as u64)?;
+        }
+
+        // whatever didn't fit is carried by the pax header
+        let (prefix, name) = split.unwrap_or(("", truncate(&self.name, 100)));
+        let header = block(
+            name.as_bytes(),
+            prefix.as_bytes(),
+            self.mode,
+            self.size.min(OCTAL_MAX),
+            self.mtime.min(OCTAL_MAX),
+            self.typeflag,
+            truncate(&self.linkname, 100).as_bytes(),
+        );
+        out.write_all(&header)?;
+
+        Ok(())
+    }
+}
+
+fn block(
+    name: &[u8],
+    prefix: &[u8],
+    mode: u32,
+    size: u64,
+    mtime: u64,
+    typeflag: u8,
+    linkname: &[u8],
+) -> [u8; BLOCK] {
+    let mut b = [0u8; BLOCK];
+    b[..name.len()].copy_from_slice(name);
+    octal(&mut b[100..108], mode as u64);
+    octal(&mut b[108..116], 0); // uid
+    octal(&mut b[116..124], 0); // gid
+    octal(&mut b[124..136], size);
+    octal(&mut b[136..148], mtime);
+    b[156] = typeflag;
+    b[157..157 + linkname.len()].copy_from_slice(linkname);
+    b[257..263].copy_from_slice(b"ustar\0");
+    b[263..265].copy_from_slice(b"00");
+    b[345..345 + prefix.len()].copy_from_slice(prefix);
+
+    // the checksum is calculated with its own field as spaces
+    b[148..156].fill(b' ');
+    let sum: u32 = b.iter().map(|&x| x as u32).sum();
+    octal(&mut b[148..155], sum as u64);
+
+    b
+}
+
+// Zero padded octal, NUL terminated.
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+// Split a name into ustar's 155 byte prefix and 100 byte name, at a `/`.
+fn split_name(name: &str) -> Option<(&str, &str)> {
+    if name.len() <= 100 {
+        return Some(("", name));
+    }
+
+    // a trailing slash can't be the split point
+    let trimmed = name.strip_suffix('/').unwrap_or(name);
+    let (at, _) = trimmed
+        .char_indices()
+        .rev()
+        .find(|&(i, c)| c == '/' && i <= 155 && name.len() - i - 1 <= 100)?;
+
+    Some((&name[..at], &name[at + 1..]))
+}
+
+// A pax record is "<length> <key>=<value>\n", where the length includes itself.
+fn pax_record(pax: &mut String, key: &str, value: &str) {
+    let rest = key.len() + value.len() + 3;
+    let mut len = rest + rest.to_string().len();
+    if len.to_string().len() != rest.to_string().len() {
+        len += 1;
+    }
+    pax.push_str(&format!("{len} {key}={value}\n"));
+}
+
+fn truncate(s: &str, max: usize) -> &str {
+    let mut end = s.len().min(max);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+fn pad<W: Write>(out: &mut W, len: u64) -> Result<(), MuError> {
+    let rem = (len % BLOCK as u64) as usize;
+    if rem != 0 {
+        out.write_all(&[0; BLOCK][..BLOCK - rem])?;
+    }
+    Ok(())
+}
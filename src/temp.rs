@@ -0,0 +1,120 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::crc32::CrcReader;
+use crate::iterable::Entry;
+use crate::options::ArchiveOptions;
+use crate::platform;
+use crate::searchable::SearchableArchive;
+use crate::shared::*;
+use crate::types::*;
+
+// tells apart temp files made by one process
+static NEXT_TEMP: AtomicU32 = AtomicU32::new(0);
+
+/// A file in the system's temp directory (`TMPDIR`, or `/tmp`, under WASI), removed
+/// when it's dropped. Made by `Entry::to_temp_file()`.
+#[derive(Debug)]
+pub struct TempFile {
+    file: File,
+    path: PathBuf,
+}
+
+impl TempFile {
+    // Create an empty file, opened for reading and writing, under a name no other
+    // file has.
+    fn new() -> Result<TempFile, MuError> {
+        let dir = platform::temp_dir();
+        loop {
+            let n = NEXT_TEMP.fetch_add(1, Ordering::Relaxed);
+            let path = dir.join(format!(".munzip-{}-{n}", platform::process_id()));
+
+            let opened = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path);
+            match opened {
+                Ok(file) => return Ok(TempFile { file, path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Returns the open file
+    pub fn file(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// Returns where the file is, for handing to something that wants a path. It's
+    /// only there for as long as the `TempFile` is.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Opens the file as an archive, for an entry that's itself a zip, or a format
+    /// built on one such as `.docx` or `.jar`.
+    pub fn archive(&mut self) -> Result<SearchableArchive<'_>, MuError> {
+        self.archive_with_options(&ArchiveOptions::default())
+    }
+
+    /// Opens the file as an archive, configured by `options`.
+    pub fn archive_with_options(
+        &mut self,
+        options: &ArchiveOptions,
+    ) -> Result<SearchableArchive<'_>, MuError> {
+        self.file.seek(SeekFrom::Start(0))?;
+        SearchableArchive::with_options(&mut self.file, options)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl<'a, R: Read + Seek> Entry<'a, R> {
+    /// Decompresses the entry into a new `TempFile`, streaming it rather than
+    /// holding it all in memory, and returns that positioned at its start. The
+    /// file is removed when the `TempFile` is dropped. The CRC-32 is checked as
+    /// `buffer()` checks it, and under `CrcPolicy::Verify` a mismatch fails with
+    /// `ErrorKind::CrcMismatch`, removing the file.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut file = std::fs::File::open("bundle.zip").unwrap();
+    /// let zi = munzip::SearchableArchive::new(&mut file).unwrap();
+    /// let nested = zi.entries().find(|e| e.filename() == "inner.zip").unwrap();
+    ///
+    /// let mut temp = nested.to_temp_file().unwrap();
+    /// let inner = temp.archive().unwrap();
+    /// for entry in inner.entries() {
+    ///     println!("inner.zip/{}", entry.filename());
+    /// }
+    /// ```
+    pub fn to_temp_file(&self) -> Result<TempFile, MuError> {
+        let mut temp = TempFile::new()?;
+
+        {
+            let mut file = self.file.borrow_mut();
+            let mut data = CrcReader::new(data_reader(&mut *file, &self.header)?);
+            let mut buf = self.buffer_sizes.for_len(self.header.uncompressed_size);
+            copy_through(&mut data, &mut temp.file, &mut buf)?;
+
+            // dropping `temp` on the way out removes the file
+            if self.crc.enabled() {
+                self.crc
+                    .check(&self.filename, &self.header, data.crc32())
+                    .map_err(|e| e.with_entry(self.meta()))?;
+            }
+        }
+
+        temp.file.seek(SeekFrom::Start(0))?;
+        Ok(temp)
+    }
+}
@@ -0,0 +1,374 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::shared::*;
+use crate::types::*;
+
+const LFH_SIZE: u64 = std::mem::size_of::<LocalFileHeader>() as u64;
+const GFH_SIZE: u64 = std::mem::size_of::<GlobalFileHeader>() as u64;
+// a signed data descriptor: signature, crc and two sizes, which ZIP64 widens to 8
+// bytes each
+const DESCRIPTOR_SIZE: usize = 16;
+const ZIP64_DESCRIPTOR_SIZE: usize = 24;
+
+// signatures that can follow an entry's data, or its data descriptor
+pub(crate) const NEXT_SIGNATURES: [u32; 4] = [
+    LOCAL_FILE_HEADER_SIGNATURE,
+    GLOBAL_FILE_HEADER_SIGNATURE,
+    DATA_DESCRIPTOR_SIGNATURE,
+    END_RECORD_SIGNATURE,
+];
+
+/// The result of `diagnose_truncation()`: how much of a possibly cut off archive is
+/// there, worked out from the front of the file so it doesn't need the end record.
+#[derive(Debug, Clone)]
+pub struct TruncationReport {
+    pub file_size: u64,
+    /// Whether the end record was found. If it wasn't, the archive can't be opened,
+    /// but the intact entries can still be salvaged or the download resumed.
+    pub end_record_found: bool,
+    /// How many entries the end record says there are, if it was found
+    pub declared_entries: Option<usize>,
+    /// How many entries have their local header and all of their data in the file
+    pub intact_entries: usize,
+    /// The name of the last of those, the last entry that can be recovered
+    pub last_intact_entry: Option<String>,
+    /// Where the last intact entry ends. The bytes before this are whole entries.
+    pub intact_bytes: u64,
+    /// Where the central directory starts, if the file reaches it
+    pub central_directory_offset: Option<u64>,
+    /// How many central directory records are wholly in the file
+    pub central_records: usize,
+    /// The name of the last of those
+    pub last_central_record: Option<String>,
+}
+
+impl TruncationReport {
+    /// True when nothing is missing: the end record is there, and so is a central
+    /// directory record for every entry.
+    pub fn is_complete(&self) -> bool {
+        self.end_record_found && Some(self.central_records) == self.declared_entries
+    }
+
+    /// Returns how much of the central directory is present, as a percentage of the
+    /// records the end record declares, or without one, of the intact entries (which
+    /// each have a record, when the file reaches the central directory). Returns
+    /// `None` if the file doesn't reach the central directory.
+    pub fn central_directory_percent(&self) -> Option<f64> {
+        self.central_directory_offset?;
+
+        let expected = self.declared_entries.unwrap_or(self.intact_entries);
+        match expected {
+            0 => Some(100.0),
+            n => Some((self.central_records.min(n) as f64 / n as f64) * 100.0),
+        }
+    }
+}
+
+/// Works out how much of an archive survived being cut off, such as by an
+/// interrupted download, for deciding whether to resume it or what can be salvaged.
+/// Unlike opening the archive, this walks the entries from the start of the file,
+/// then as much of the central directory as follows them, so it works when the end
+/// record is missing.
+///
+/// An entry with a data descriptor doesn't record its size up front, so its end is
+/// found by searching for the descriptor that matches it, whose sizes are 8 bytes
+/// each when the entry has a ZIP64 field. Otherwise a ZIP64 entry's sizes come from
+/// that field.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # let mut file = std::fs::File::open("download.zip.part").unwrap();
+/// let report = munzip::diagnose_truncation(&mut file).unwrap();
+///
+/// if !report.is_complete() {
+///     println!("{} entries intact, resume from byte {}", report.intact_entries, report.intact_bytes);
+///     if let Some(percent) = report.central_directory_percent() {
+///         println!("{percent:.0}% of the central directory is there");
+///     }
+/// }
+/// ```
+pub fn diagnose_truncation(file: &mut (impl Read + Seek)) -> Result<TruncationReport, MuError> {
+    let file_size = file.seek(SeekFrom::End(0))?;
+    let end_record = read_end_record(file).ok().map(|(end_record, _)| end_record);
+
+    let mut report = TruncationReport {
+        file_size,
+        end_record_found: end_record.is_some(),
+        declared_entries: end_record.map(|er| er.num_entries as usize),
+        intact_entries: 0,
+        last_intact_entry: None,
+        intact_bytes: 0,
+        central_directory_offset: None,
+        central_records: 0,
+        last_central_record: None,
+    };
+
+    // the entries, one after another, until one runs past the end of the file or
+    // something other than a local header turns up
+    let mut position = 0;
+    let signature = loop {
+        let Some(signature) = signature_at(file, position, file_size)? else {
+            break None;
+        };
+        if signature != LOCAL_FILE_HEADER_SIGNATURE || position + LFH_SIZE > file_size {
+            break Some(signature);
+        }
+
+        let Some(entry) = read_local_entry(file, position, file_size)? else {
+            break None;
+        };
+
+        report.intact_entries += 1;
+        report.last_intact_entry = Some(lossy_name(&entry.name));
+        report.intact_bytes = entry.end;
+        position = entry.end;
+    };
+
+    if signature != Some(GLOBAL_FILE_HEADER_SIGNATURE) {
+        return Ok(report);
+    }
+    report.central_directory_offset = Some(position);
+
+    // as much of the central directory as is there
+    while position + GFH_SIZE <= file_size {
+        let mut buf = [0; GFH_SIZE as usize];
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut buf)?;
+        let Ok(gfh) = get_global_file_header(&buf) else {
+            break;
+        };
+
+        let end = position
+            + GFH_SIZE
+            + gfh.file_name_length as u64
+            + gfh.extra_field_length as u64
+            + gfh.file_comment_length as u64;
+        if end > file_size {
+            break;
+        }
+
+        let mut name = vec![0; gfh.file_name_length as usize];
+        file.read_exact(&mut name)?;
+
+        report.central_records += 1;
+        report.last_central_record = Some(lossy_name(&name));
+        position = end;
+    }
+
+    Ok(report)
+}
+
+// An entry found by walking the local headers, rather than through the central
+// directory.
+#[cfg_attr(not(feature = "writer"), allow(dead_code))]
+pub(crate) struct LocalEntry {
+    pub lfh: LocalFileHeader,
+    pub name: Vec<u8>,
+    pub extra: Vec<u8>,
+    // the crc and uncompressed size, from the data descriptor if there is one, and
+    // the local ZIP64 field if the header's are placeholders
+    pub crc32: u32,
+    pub uncompressed_size: u64,
+    pub zip64: bool,
+    pub data_start: u64,
+    // where the compressed data ends, and where the entry does, past any descriptor
+    pub data_end: u64,
+    pub end: u64,
+}
+
+// A data descriptor found after an entry's data.
+pub(crate) struct Descriptor {
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    // where it ends, which is where the entry does
+    pub end: u64,
+}
+
+// Read the local header at `position`, and find where its entry ends. Returns
+// `None` if there's no local header there, or the entry runs past the end of the
+// file.
+pub(crate) fn read_local_entry(
+    file: &mut (impl Read + Seek),
+    position: u64,
+    file_size: u64,
+) -> Result<Option<LocalEntry>, MuError> {
+    if position + LFH_SIZE > file_size {
+        return Ok(None);
+    }
+    file.seek(SeekFrom::Start(position))?;
+
+    let mut buf = [0; LFH_SIZE as usize];
+    file.read_exact(&mut buf)?;
+    let Ok(lfh) = get_internal_file_header(&buf) else {
+        return Ok(None);
+    };
+
+    let data_start =
+        position + LFH_SIZE + lfh.file_name_length as u64 + lfh.extra_field_length as u64;
+    if data_start > file_size {
+        return Ok(None);
+    }
+
+    let mut name = vec![0; lfh.file_name_length as usize];
+    let mut extra = vec![0; lfh.extra_field_length as usize];
+    file.read_exact(&mut name)?;
+    file.read_exact(&mut extra)?;
+
+    // the local ZIP64 field has both sizes, though some writers only put in those
+    // the header leaves as placeholders
+    let zip64_field = find_extra_field(&extra, ZIP64_TAG);
+    let zip64 = zip64_field.is_some();
+    let recorded = [lfh.uncompressed_size as u64, lfh.compressed_size as u64];
+    let resolved = match zip64_field {
+        Some(field) if field.len() >= 16 => resolve_zip64(field, [PLACEHOLDER; 2]),
+        Some(field) => resolve_zip64(field, recorded),
+        None => Ok(recorded),
+    };
+    let Ok(resolved) = resolved else {
+        return Ok(None);
+    };
+    let [uncompressed_size, compressed_size] = [0, 1].map(|i| match recorded[i] {
+        PLACEHOLDER => resolved[i],
+        recorded => recorded,
+    });
+
+    let found = match lfh.general_purpose_bit_flag & FLAG_DATA_DESCRIPTOR {
+        0 => {
+            let data_end = data_start + compressed_size;
+            Some((lfh.crc32, uncompressed_size, data_end, data_end))
+        }
+        _ => find_descriptor(file, data_start, file_size, zip64)?.map(|d| {
+            let data_end = data_start + d.compressed_size;
+            (d.crc32, d.uncompressed_size, data_end, d.end)
+        }),
+    };
+    let Some((crc32, uncompressed_size, data_end, end)) =
+        found.filter(|&(_, _, _, end)| end <= file_size)
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(LocalEntry {
+        lfh,
+        name,
+        extra,
+        crc32,
+        uncompressed_size,
+        zip64,
+        data_start,
+        data_end,
+        end,
+    }))
+}
+
+// The four byte signature at `position`, leaving the file there, or `None` past the
+// end of the file.
+fn signature_at(
+    file: &mut (impl Read + Seek),
+    position: u64,
+    file_size: u64,
+) -> Result<Option<u32>, MuError> {
+    if position + 4 > file_size {
+        return Ok(None);
+    }
+    file.seek(SeekFrom::Start(position))?;
+
+    let mut sig = [0; 4];
+    file.read_exact(&mut sig)?;
+    file.seek(SeekFrom::Start(position))?;
+
+    Ok(Some(u32::from_le_bytes(sig)))
+}
+
+// Find the data descriptor of data starting at `data_start`: the first signed
+// descriptor whose compressed size is the distance back to `data_start`. An entry
+// with a ZIP64 field has 8-byte sizes in its descriptor. Without one, a descriptor
+// whose 4-byte compressed size matches may still be a ZIP64 one whose size fits in 4
+// bytes, so the wider reading is taken if only it is followed by a signature.
+pub(crate) fn find_descriptor(
+    file: &mut (impl Read + Seek),
+    data_start: u64,
+    file_size: u64,
+    zip64: bool,
+) -> Result<Option<Descriptor>, MuError> {
+    let signature = DATA_DESCRIPTOR_SIGNATURE.to_le_bytes();
+
+    // blocks overlap by three bytes, so a signature across a boundary isn't missed
+    let mut block_start = data_start;
+    let mut buf = vec![0; BUFFER_SIZE];
+    while block_start + DESCRIPTOR_SIZE as u64 <= file_size {
+        let len = (file_size - block_start).min(BUFFER_SIZE as u64) as usize;
+        file.seek(SeekFrom::Start(block_start))?;
+        file.read_exact(&mut buf[..len])?;
+
+        for i in 0..=len - 4 {
+            if buf[i..i + 4] != signature {
+                continue;
+            }
+            let position = block_start + i as u64;
+            if let Some(found) = descriptor_at(file, position, data_start, file_size, zip64)? {
+                return Ok(Some(found));
+            }
+        }
+
+        if len < BUFFER_SIZE {
+            break;
+        }
+        block_start += (BUFFER_SIZE - 3) as u64;
+    }
+
+    Ok(None)
+}
+
+// The descriptor at `position`, if it's the one for data starting at `data_start`.
+// Leaves the file anywhere.
+fn descriptor_at(
+    file: &mut (impl Read + Seek),
+    position: u64,
+    data_start: u64,
+    file_size: u64,
+    zip64: bool,
+) -> Result<Option<Descriptor>, MuError> {
+    // the widest descriptor, and the signature after it
+    let mut buf = [0; ZIP64_DESCRIPTOR_SIZE + 4];
+    let len = (file_size - position).min(buf.len() as u64) as usize;
+    file.seek(SeekFrom::Start(position))?;
+    file.read_exact(&mut buf[..len])?;
+
+    let distance = position - data_start;
+    let u32_at = |at: usize| u32::from_le_bytes(buf[at..at + 4].try_into().unwrap());
+    let u64_at = |at: usize| u64::from_le_bytes(buf[at..at + 8].try_into().unwrap());
+    // the end of the file, where it's been cut off, counts as a signature
+    let followed =
+        |end: usize| end == len || (end + 4 <= len && NEXT_SIGNATURES.contains(&u32_at(end)));
+
+    let wide = len >= ZIP64_DESCRIPTOR_SIZE && u64_at(8) == distance;
+    let narrow = !zip64 && len >= DESCRIPTOR_SIZE && u32_at(8) as u64 == distance;
+    let wide = wide
+        && (zip64 || !narrow || (followed(ZIP64_DESCRIPTOR_SIZE) && !followed(DESCRIPTOR_SIZE)));
+
+    let found = if wide {
+        Some(Descriptor {
+            crc32: u32_at(4),
+            compressed_size: distance,
+            uncompressed_size: u64_at(16),
+            end: position + ZIP64_DESCRIPTOR_SIZE as u64,
+        })
+    } else if narrow {
+        Some(Descriptor {
+            crc32: u32_at(4),
+            compressed_size: distance,
+            uncompressed_size: u32_at(12) as u64,
+            end: position + DESCRIPTOR_SIZE as u64,
+        })
+    } else {
+        None
+    };
+    Ok(found)
+}
+
+pub(crate) fn lossy_name(raw: &[u8]) -> String {
+    normalize_separators(&String::from_utf8_lossy(raw))
+}
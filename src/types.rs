@@ -1,37 +1,202 @@
-/// The munzip Error type. Currently not an enum, just a String wrapper.
+use crate::iterable::EntryMeta;
+use crate::provenance::Provenance;
+
+/// The munzip Error type. An `ErrorKind` to match on, and a message with the details.
+/// An error reading one entry also says which entry it was, through `entry()`, and
+/// one that came from the filesystem keeps the `std::io::Error` as its `source()`.
 #[derive(Debug)]
-pub struct MuError(pub String);
+pub struct MuError {
+    kind: ErrorKind,
+    message: String,
+    entry: Option<Box<EntryMeta>>,
+    source: Option<Box<dyn std::error::Error + Send + Sync + 'static>>,
+}
+
+/// The broad category of a `MuError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Reading from or writing to the filesystem failed.
+    Io,
+    /// The archive is damaged, or isn't a zip at all.
+    Format,
+    /// Text that should be UTF-8 isn't. Entry names that aren't are decoded lossily
+    /// instead, see `Entry::name_is_lossy()`.
+    Utf8,
+    /// The entry is compressed with a method other than `STORE` or `DEFLATE`.
+    UnsupportedMethod(u16),
+    /// The entry is encrypted (general purpose bit 0, 6 or 13), and either no
+    /// `Decryptor` was given or the one given couldn't decrypt it.
+    Encrypted,
+    /// The entry holds PKWARE compressed patched data (general purpose bit 5), which
+    /// is a delta against another file rather than the file itself.
+    PatchedData,
+    /// The entry sets general purpose bits reserved by the spec, so its data can't
+    /// be trusted to mean what munzip would read it as. Holds the offending bits.
+    ReservedFlags(u16),
+    /// An entry's data doesn't have the CRC-32 its header records, under
+    /// `CrcPolicy::Verify`. The data is corrupt, or the header is.
+    CrcMismatch { expected: u32, actual: u32 },
+    /// An entry's name would place it outside of the extraction directory.
+    UnsafePath,
+    /// Opening or extracting would go over one of the limits set on
+    /// `ArchiveOptions` or `ExtractOptions`. Holds which one.
+    QuotaExceeded(Quota),
+    /// A `ContentInspector` rejected a file, under `RejectPolicy::Abort`.
+    Rejected,
+    /// The destination's filesystem hasn't room for what's to be extracted, as
+    /// `ExtractOptions::check_free_space()` found before writing anything.
+    InsufficientSpace,
+    /// Anything else.
+    Other,
+}
+
+/// One of the limits `ArchiveOptions` can put on opening an archive, or
+/// `ExtractOptions` on an extraction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Quota {
+    /// `ArchiveOptions::max_entries()` or `ExtractOptions::max_entries()`
+    Entries,
+    /// `ExtractOptions::max_depth()`
+    Depth,
+    /// `ExtractOptions::max_path_length()`
+    PathLength,
+    /// `ExtractOptions::max_file_size()`
+    FileSize,
+    /// `ExtractOptions::max_total_size()`
+    TotalSize,
+    /// `ExtractOptions::max_compression_ratio()`
+    Ratio,
+    /// `ArchiveOptions::max_central_directory_size()`
+    CentralDirectorySize,
+}
+
+impl MuError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> MuError {
+        MuError {
+            kind,
+            message: message.into(),
+            entry: None,
+            source: None,
+        }
+    }
+
+    /// Returns the category of this error
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the entry this error concerns, if it came from reading one, so an
+    /// entry that fails mid-iteration can be reported by name, index and size. For
+    /// an entry whose local header couldn't be read, everything comes from its
+    /// central directory record, and `data_offset()` and `compressed_range()` are
+    /// `None`, as where its data starts isn't known.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # let mut input = std::fs::File::open("archive.zip").unwrap();
+    /// let options = munzip::ArchiveOptions::new().error_mode(munzip::ErrorMode::Continue);
+    /// let zi = munzip::IterableArchive::with_options(&mut input, &options).unwrap();
+    ///
+    /// for entry in zi {
+    ///     if let Err(e) = entry {
+    ///         match e.entry() {
+    ///             Some(meta) => eprintln!("#{} {}: {e}", meta.central_directory_index(), meta.filename()),
+    ///             None => eprintln!("{e}"),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn entry(&self) -> Option<&EntryMeta> {
+        self.entry.as_deref()
+    }
+
+    /// Returns the `std::io::Error` this error came from, if it came from one, with
+    /// its kind and raw OS error intact, so a caller reading from a network
+    /// filesystem can tell an `Interrupted` or `WouldBlock` worth retrying from a
+    /// `NotFound` that isn't.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// use std::io::ErrorKind;
+    ///
+    /// let count = loop {
+    ///     let mut input = std::fs::File::open("/mnt/share/archive.zip").unwrap();
+    ///     match munzip::SearchableArchive::new(&mut input) {
+    ///         Ok(zi) => break zi.entries().count(),
+    ///         Err(e) => match e.io_error().map(|io| io.kind()) {
+    ///             Some(ErrorKind::Interrupted | ErrorKind::WouldBlock) => continue,
+    ///             _ => panic!("{e}"),
+    ///         },
+    ///     }
+    /// };
+    /// ```
+    pub fn io_error(&self) -> Option<&std::io::Error> {
+        self.source.as_deref()?.downcast_ref()
+    }
+
+    // Attach the entry this error concerns, unless it already has one.
+    pub(crate) fn with_entry(mut self, entry: EntryMeta) -> MuError {
+        if self.entry.is_none() {
+            self.entry = Some(Box::new(entry));
+        }
+        self
+    }
+}
 
 impl std::fmt::Display for MuError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for MuError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_deref().map(|e| e as _)
     }
 }
 
 impl From<std::io::Error> for MuError {
     fn from(err: std::io::Error) -> MuError {
-        MuError(err.to_string())
+        // a MuError that went through a Read impl comes back out as itself
+        if err.get_ref().is_some_and(|inner| inner.is::<MuError>()) {
+            return *err.into_inner().unwrap().downcast::<MuError>().unwrap();
+        }
+        // streaming decompressors report corrupt data this way
+        let kind = match err.kind() {
+            std::io::ErrorKind::InvalidData => ErrorKind::Format,
+            _ => ErrorKind::Io,
+        };
+        let mut mu = MuError::new(kind, err.to_string());
+        mu.source = Some(Box::new(err));
+        mu
     }
 }
 
 impl From<std::str::Utf8Error> for MuError {
     fn from(err: std::str::Utf8Error) -> MuError {
-        MuError(err.to_string())
+        let mut mu = MuError::new(ErrorKind::Utf8, err.to_string());
+        mu.source = Some(Box::new(err));
+        mu
     }
 }
 
 impl From<String> for MuError {
     fn from(err: String) -> MuError {
-        MuError(err)
+        MuError::new(ErrorKind::Other, err)
     }
 }
 
-#[repr(packed)]
+#[repr(C, packed)]
+#[allow(dead_code)]
 #[derive(Debug, Copy, Clone)]
 pub struct LocalFileHeader {
     pub signature: u32,                 // 0x04034B50
     pub version_needed_to_extract: u16, // unsupported
-    pub general_purpose_bit_flag: u16,  // unsupported
+    pub general_purpose_bit_flag: u16,
     pub compression_method: u16,
     pub last_mod_file_time: u16,
     pub last_mod_file_date: u16,
@@ -42,13 +207,14 @@ pub struct LocalFileHeader {
     pub extra_field_length: u16, // unsupported
 }
 
-#[repr(packed)]
+#[repr(C, packed)]
+#[allow(dead_code)]
 #[derive(Debug, Copy, Clone)]
 pub struct GlobalFileHeader {
-    pub signature: u32,                 // 0x02014B50
-    pub version_made_by: u16,           // unsupported
+    pub signature: u32, // 0x02014B50
+    pub version_made_by: u16,
     pub version_needed_to_extract: u16, // unsupported
-    pub general_purpose_bit_flag: u16,  // unsupported
+    pub general_purpose_bit_flag: u16,
     pub compression_method: u16,
     pub last_mod_file_time: u16,
     pub last_mod_file_date: u16,
@@ -60,20 +226,42 @@ pub struct GlobalFileHeader {
     pub file_comment_length: u16,      // unsupported
     pub disk_number_start: u16,        // unsupported
     pub internal_file_attributes: u16, // unsupported
-    pub external_file_attributes: u32, // unsupported
+    pub external_file_attributes: u32,
     pub relative_offset_of_local_header: u32,
 }
 
-#[repr(packed)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct InternalHeader {
-    pub compressed_size: u32,
-    pub uncompressed_size: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub crc32: u32,
     pub compression_method: u16,
-    pub offset: u32,
+    pub general_purpose_bit_flag: u16,
+    pub local_header_offset: u64,
+    pub offset: u64,
+    pub filename_raw: Vec<u8>,
+    pub name_lossy: bool,
+    pub last_mod_file_time: u16,
+    pub last_mod_file_date: u16,
+    pub mtime_unix: Option<i32>,
+    pub version_made_by: u16,
+    pub external_file_attributes: u32,
+    pub central_index: usize,
+    pub zip64: bool,
+    pub provenance: Provenance,
+}
+
+// The end record, with the entry count and central directory size the ZIP64 end
+// record has in place of any it leaves as placeholders.
+#[derive(Debug, Copy, Clone)]
+pub struct DirectoryEnd {
+    pub record: EndRecord,
+    pub num_entries: u64,
+    pub central_directory_size: u64,
 }
 
-#[repr(packed)]
+#[repr(C, packed)]
+#[allow(dead_code)]
 #[derive(Debug, Copy, Clone)]
 pub struct EndRecord {
     pub signature: u32,
@@ -0,0 +1,410 @@
+// A minimal io_uring, driven straight through the syscalls. Only reads and writes at
+// an offset are supported, which is all extraction needs.
+
+use std::fs::File;
+use std::io::{self, Read, Seek};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::iterable::Entry;
+use crate::shared::*;
+use crate::types::*;
+
+const SYS_IO_URING_SETUP: i64 = 425;
+const SYS_IO_URING_ENTER: i64 = 426;
+
+const IORING_OFF_SQ_RING: i64 = 0;
+const IORING_OFF_CQ_RING: i64 = 0x8000000;
+const IORING_OFF_SQES: i64 = 0x10000000;
+const IORING_ENTER_GETEVENTS: u32 = 1;
+const IORING_OP_READ: u8 = 22;
+const IORING_OP_WRITE: u8 = 23;
+
+const PROT_READ: i32 = 1;
+const PROT_WRITE: i32 = 2;
+const MAP_SHARED: i32 = 1;
+const MAP_POPULATE: i32 = 0x8000;
+const MAP_FAILED: *mut u8 = !0 as *mut u8;
+
+const RING_ENTRIES: u32 = 64;
+// each read or write is split into requests of at most this much
+const CHUNK: usize = 1 << 20;
+// entries are read and inflated whole, so larger ones are left to the streamed path
+const MAX_IN_MEMORY: u64 = 64 << 20;
+
+extern "C" {
+    fn syscall(num: i64, ...) -> i64;
+    fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, off: i64) -> *mut u8;
+    fn munmap(addr: *mut u8, len: usize) -> i32;
+    fn close(fd: i32) -> i32;
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    rw_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: i32, len: usize, offset: i64) -> io::Result<Mapping> {
+        // SAFETY: a fresh shared mapping of the ring, which the kernel sized for us
+        let ptr = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_SHARED | MAP_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if ptr == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping { ptr, len })
+    }
+
+    // SAFETY: `offset` must come from the kernel's ring offsets for this mapping
+    unsafe fn at<T>(&self, offset: u32) -> *mut T {
+        self.ptr.add(offset as usize) as *mut T
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: unmapping what `new()` mapped, once
+        unsafe {
+            munmap(self.ptr, self.len);
+        }
+    }
+}
+
+pub struct Ring {
+    fd: i32,
+    _sq: Mapping,
+    _cq: Mapping,
+    sqes: Mapping,
+    sq_head: *const AtomicU32,
+    sq_tail: *const AtomicU32,
+    sq_mask: u32,
+    sq_array: *mut u32,
+    cq_head: *const AtomicU32,
+    cq_tail: *const AtomicU32,
+    cq_mask: u32,
+    cqes: *const Cqe,
+}
+
+impl Ring {
+    pub fn new() -> io::Result<Ring> {
+        let mut p = Params::default();
+        // SAFETY: io_uring_setup only writes to `p`
+        let fd = unsafe { syscall(SYS_IO_URING_SETUP, RING_ENTRIES, &mut p as *mut Params) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = fd as i32;
+
+        let mapped = (|| {
+            let sq_len = (p.sq_off.array + p.sq_entries * 4) as usize;
+            let cq_len =
+                p.cq_off.cqes as usize + p.cq_entries as usize * std::mem::size_of::<Cqe>();
+            let sqes_len = p.sq_entries as usize * std::mem::size_of::<Sqe>();
+            Ok::<_, io::Error>((
+                Mapping::new(fd, sq_len, IORING_OFF_SQ_RING)?,
+                Mapping::new(fd, cq_len, IORING_OFF_CQ_RING)?,
+                Mapping::new(fd, sqes_len, IORING_OFF_SQES)?,
+            ))
+        })();
+        let (sq, cq, sqes) = match mapped {
+            Ok(m) => m,
+            Err(e) => {
+                // SAFETY: the descriptor is ours, and nothing else refers to it
+                unsafe { close(fd) };
+                return Err(e);
+            }
+        };
+
+        // SAFETY: all offsets are the kernel's, into the mappings it just sized
+        unsafe {
+            Ok(Ring {
+                fd,
+                sq_head: sq.at(p.sq_off.head),
+                sq_tail: sq.at(p.sq_off.tail),
+                sq_mask: *sq.at::<u32>(p.sq_off.ring_mask),
+                sq_array: sq.at(p.sq_off.array),
+                cq_head: cq.at(p.cq_off.head),
+                cq_tail: cq.at(p.cq_off.tail),
+                cq_mask: *cq.at::<u32>(p.cq_off.ring_mask),
+                cqes: cq.at(p.cq_off.cqes),
+                _sq: sq,
+                _cq: cq,
+                sqes,
+            })
+        }
+    }
+
+    // Queue a request, returning false if the submission queue is full.
+    fn push(&mut self, sqe: Sqe) -> bool {
+        // SAFETY: the ring pointers are valid for the life of `self`, and only this
+        // thread produces submissions
+        unsafe {
+            let head = (*self.sq_head).load(Ordering::Acquire);
+            let tail = (*self.sq_tail).load(Ordering::Relaxed);
+            if tail.wrapping_sub(head) > self.sq_mask {
+                return false;
+            }
+            let index = tail & self.sq_mask;
+            self.sqes.at::<Sqe>(0).add(index as usize).write(sqe);
+            *self.sq_array.add(index as usize) = index;
+            (*self.sq_tail).store(tail.wrapping_add(1), Ordering::Release);
+        }
+        true
+    }
+
+    fn submit_and_wait(&mut self, to_submit: u32, wait_for: u32) -> io::Result<()> {
+        loop {
+            // SAFETY: no signal mask is passed
+            let n = unsafe {
+                syscall(
+                    SYS_IO_URING_ENTER,
+                    self.fd,
+                    to_submit,
+                    wait_for,
+                    IORING_ENTER_GETEVENTS,
+                    std::ptr::null::<u8>(),
+                    0usize,
+                )
+            };
+            if n >= 0 {
+                return Ok(());
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+
+    // `submit_and_wait()` with requests into a caller's buffer outstanding. If the
+    // kernel can't be asked about them, it may still write into the buffer after
+    // it's released, and there's no safe way back from that.
+    fn submit_and_wait_or_abort(&mut self, to_submit: u32, wait_for: u32) {
+        if self.submit_and_wait(to_submit, wait_for).is_err() {
+            std::process::abort();
+        }
+    }
+
+    fn pop(&mut self) -> Option<(u64, i32)> {
+        // SAFETY: as in push(), with this thread the only consumer
+        unsafe {
+            let head = (*self.cq_head).load(Ordering::Relaxed);
+            let tail = (*self.cq_tail).load(Ordering::Acquire);
+            if head == tail {
+                return None;
+            }
+            let cqe = &*self.cqes.add((head & self.cq_mask) as usize);
+            let done = (cqe.user_data, cqe.res);
+            (*self.cq_head).store(head.wrapping_add(1), Ordering::Release);
+            Some(done)
+        }
+    }
+
+    // Read or write all of `buf` at `offset`, as a batch of requests.
+    fn transfer(&mut self, op: u8, fd: i32, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        // what's left of each chunk: (position in buf, length)
+        let mut chunks: Vec<(usize, usize)> = (0..buf.len())
+            .step_by(CHUNK)
+            .map(|at| (at, CHUNK.min(buf.len() - at)))
+            .collect();
+        let mut queued = vec![false; chunks.len()];
+        let mut remaining = chunks.len();
+        let mut in_flight = 0;
+
+        while remaining > 0 {
+            let mut submitted = 0;
+            for (i, &(at, len)) in chunks.iter().enumerate() {
+                if queued[i] || len == 0 {
+                    continue;
+                }
+                let sqe = Sqe {
+                    opcode: op,
+                    fd,
+                    off: offset + at as u64,
+                    addr: buf[at..].as_mut_ptr() as u64,
+                    len: len as u32,
+                    user_data: i as u64,
+                    ..Default::default()
+                };
+                if !self.push(sqe) {
+                    break;
+                }
+                queued[i] = true;
+                submitted += 1;
+            }
+            in_flight += submitted;
+
+            self.submit_and_wait_or_abort(submitted, 1);
+
+            let mut failed = None;
+            while let Some((i, res)) = self.pop() {
+                let i = i as usize;
+                in_flight -= 1;
+                queued[i] = false;
+                match res {
+                    ..=-1 => {
+                        failed.get_or_insert(io::Error::from_raw_os_error(-res));
+                    }
+                    0 => {
+                        failed.get_or_insert(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    _ => {
+                        // a short transfer leaves the rest of the chunk to go again
+                        let (at, len) = chunks[i];
+                        chunks[i] = (at + res as usize, len - res as usize);
+                        if chunks[i].1 == 0 {
+                            remaining -= 1;
+                        }
+                    }
+                }
+            }
+
+            if let Some(e) = failed {
+                // let everything else land before `buf` is released
+                while in_flight > 0 {
+                    self.submit_and_wait_or_abort(0, 1);
+                    while self.pop().is_some() {
+                        in_flight -= 1;
+                    }
+                }
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        // SAFETY: the mappings are dropped after this, which the kernel allows
+        unsafe {
+            close(self.fd);
+        }
+    }
+}
+
+// Extract a file entry to `path`, batching its reads, from the file `as_file` finds
+// beneath its reader, and then its writes. Returns false, having done nothing, for an
+// entry too large to hold in memory.
+pub fn extract_entry<R: Read + Seek>(
+    ring: &mut Ring,
+    entry: &Entry<'_, R>,
+    as_file: fn(&R) -> &File,
+    path: &Path,
+) -> Result<bool, MuError> {
+    check_flags(&entry.header)?;
+    if entry
+        .header
+        .compressed_size
+        .max(entry.header.uncompressed_size)
+        > MAX_IN_MEMORY
+    {
+        return Ok(false);
+    }
+
+    #[cfg(feature = "metrics")]
+    let start = std::time::Instant::now();
+
+    let mut compressed = vec![0; entry.header.compressed_size as usize];
+    {
+        let file = entry.file.borrow();
+        ring.transfer(
+            IORING_OP_READ,
+            as_file(&file).as_raw_fd(),
+            entry.header.offset,
+            &mut compressed,
+        )?;
+    }
+
+    let mut data = decompress(entry.header.compression_method, compressed)?;
+
+    let out = File::create(path)?;
+    ring.transfer(IORING_OP_WRITE, out.as_raw_fd(), 0, &mut data)?;
+
+    #[cfg(feature = "metrics")]
+    entry
+        .metrics
+        .borrow_mut()
+        .record(&entry.filename, &entry.header, data.len() as u64, start);
+
+    Ok(true)
+}
@@ -0,0 +1,93 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::crc32::CrcReader;
+use crate::shared::*;
+use crate::types::*;
+use crate::write::WrittenEntry;
+
+// Read back the archive just written to `out`, and check it holds what was meant to
+// be written: `central`, the central directory and end record, byte for byte from
+// `cd_offset`, and for each entry, a local header that agrees with it and data that
+// has its size and CRC-32.
+pub(crate) fn validate<R: Read + Seek>(
+    out: &mut R,
+    entries: &[WrittenEntry],
+    central: &[u8],
+    cd_offset: u64,
+) -> Result<(), MuError> {
+    let mut written = vec![0; central.len()];
+    out.seek(SeekFrom::Start(cd_offset))?;
+    out.read_exact(&mut written)?;
+    if written != central {
+        return Err(invalid("the central directory reads back differently"));
+    }
+
+    for entry in entries {
+        validate_entry(out, entry)?;
+    }
+
+    Ok(())
+}
+
+fn validate_entry<R: Read + Seek>(out: &mut R, entry: &WrittenEntry) -> Result<(), MuError> {
+    let name = &entry.name;
+    let mut buf = [0; LOCAL_FILE_HEADER_SIZE];
+    out.seek(SeekFrom::Start(entry.local_header_offset as u64))?;
+    out.read_exact(&mut buf)?;
+    let lfh = get_internal_file_header(&buf)
+        .map_err(|e| invalid(&format!("'{name}' has a bad local header: {e}")))?;
+
+    let mut name_raw = vec![0; lfh.file_name_length as usize];
+    out.read_exact(&mut name_raw)?;
+    let descriptor = entry.flags & FLAG_DATA_DESCRIPTOR != 0 && lfh.crc32 == 0;
+    let (crc32, compressed_size, uncompressed_size) =
+        (lfh.crc32, lfh.compressed_size, lfh.uncompressed_size);
+    if name_raw != name.as_bytes()
+        || lfh.compression_method != entry.method
+        || !descriptor
+            && (crc32 != entry.crc32
+                || compressed_size != entry.compressed_size
+                || uncompressed_size != entry.uncompressed_size)
+    {
+        return Err(invalid(&format!(
+            "'{name}''s local header disagrees with its central directory record"
+        )));
+    }
+
+    // without the password, encrypted data copied from another archive can't be read
+    if entry.flags & FLAG_ENCRYPTED != 0 {
+        return Ok(());
+    }
+
+    out.seek(SeekFrom::Current(lfh.extra_field_length as i64))?;
+    let data = out.take(entry.compressed_size as u64);
+    let mut reader = match entry.method {
+        0 => CrcReader::new(Box::new(data) as Box<dyn Read + '_>),
+        #[cfg(feature = "deflate")]
+        8 => CrcReader::new(Box::new(inflate::DeflateDecoder::new(data)) as Box<dyn Read + '_>),
+        // the data can't be read back without a decoder, so only its headers are
+        // checked
+        _ => return Ok(()),
+    };
+    std::io::copy(&mut reader, &mut std::io::sink())
+        .map_err(|e| invalid(&format!("'{name}' reads back corrupt: {e}")))?;
+
+    if reader.bytes_read() != entry.uncompressed_size as u64 || reader.crc32() != entry.crc32 {
+        return Err(invalid(&format!(
+            "'{name}' reads back as {} bytes with CRC-32 {:08x}, but {} bytes with {:08x} were written",
+            reader.bytes_read(),
+            reader.crc32(),
+            entry.uncompressed_size,
+            entry.crc32
+        )));
+    }
+
+    Ok(())
+}
+
+fn invalid(message: &str) -> MuError {
+    MuError::new(
+        ErrorKind::Format,
+        format!("validating the written archive: {message}"),
+    )
+}
@@ -0,0 +1,1120 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::crc32::Crc32;
+use crate::deflate::Deflater;
+use crate::iterable::Entry;
+use crate::shared::*;
+use crate::types::*;
+
+/// The smallest segment size `ZipWriter::split()` accepts.
+pub const MIN_SEGMENT_SIZE: u64 = 65536;
+
+/// How `ZipWriter` compresses an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionMethod {
+    /// Stored as is
+    Store,
+    /// DEFLATE, at a level from 1 (fastest) to 9 (smallest)
+    Deflate(u8),
+    /// DEFLATE at level 6
+    #[default]
+    DeflateDefault,
+}
+
+/// Settings for an entry added to a `ZipWriter`.
+#[derive(Debug, Clone, Default)]
+pub struct EntryOptions {
+    pub(crate) method: CompressionMethod,
+    pub(crate) modified: Option<SystemTime>,
+    pub(crate) unix_mode: Option<u32>,
+    pub(crate) unix_owner: Option<(u32, u32)>,
+    pub(crate) dos_attributes: u8,
+    pub(crate) alignment: u16,
+}
+
+impl EntryOptions {
+    /// Creates `EntryOptions` with every setting at its default.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the compression method. Defaults to `CompressionMethod::DeflateDefault`.
+    /// Directories are always stored.
+    pub fn method(mut self, method: CompressionMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Sets the modification time, written as both a DOS time and an extended
+    /// timestamp field. Without one, entries are dated 1980-01-01 so that output
+    /// is reproducible.
+    pub fn modified(mut self, time: SystemTime) -> Self {
+        self.modified = Some(time);
+        self
+    }
+
+    /// Sets the Unix permission and file type bits (as in `st_mode`), and marks
+    /// the entry as made on Unix. A mode with no write permission also sets the
+    /// DOS readonly bit, for Windows tools.
+    pub fn unix_mode(mut self, mode: u32) -> Self {
+        self.unix_mode = Some(mode);
+        self
+    }
+
+    /// Sets the DOS attribute bits: `0x01` readonly, `0x02` hidden, `0x04` system
+    /// and `0x20` archive. The directory bit is set for directories regardless.
+    /// Without a `unix_mode()`, the entry is marked as made on MS-DOS, so these
+    /// are all its attributes. Defaults to none.
+    pub fn dos_attributes(mut self, attributes: u8) -> Self {
+        self.dos_attributes = attributes & !(DOS_DIRECTORY as u8);
+        self
+    }
+
+    /// Sets the Unix owner, written as an Info-ZIP Unix extra field.
+    pub fn unix_owner(mut self, uid: u32, gid: u32) -> Self {
+        self.unix_owner = Some((uid, gid));
+        self
+    }
+
+    /// Pads the local header so a stored entry's data starts at a multiple of
+    /// `alignment` bytes, which must be a power of two. Use 4 for Android's
+    /// `zipalign`, or 4096 to `mmap` entries a page at a time. The padding goes in
+    /// a `zipalign` style extra field (0xD935). Deflated entries aren't padded.
+    pub fn alignment(mut self, alignment: u16) -> Self {
+        self.alignment = alignment;
+        self
+    }
+}
+
+pub(crate) struct WrittenEntry {
+    pub(crate) name: String,
+    version_made_by: u16,
+    version_needed: u16,
+    pub(crate) flags: u16,
+    pub(crate) method: u16,
+    time: u16,
+    date: u16,
+    pub(crate) crc32: u32,
+    pub(crate) compressed_size: u32,
+    pub(crate) uncompressed_size: u32,
+    central_extra: Vec<u8>,
+    external_attributes: u32,
+    disk: u32,
+    pub(crate) local_header_offset: u32,
+}
+
+// In a split archive, opens the segment for a disk, creating it if it's new.
+type OpenSegment<W> = Box<dyn FnMut(u32, bool) -> std::io::Result<W>>;
+// Renames the last segment once it's known to be the last, and reopens it.
+type FinishSegments<W> = Box<dyn FnMut(u32) -> std::io::Result<W>>;
+// Reads back what `finish()` wrote, for `set_validate_after_write()`.
+type Validator<W> = fn(&mut W, &[WrittenEntry], &[u8], u64) -> Result<(), MuError>;
+
+struct Split<W> {
+    segment_size: u64,
+    open: OpenSegment<W>,
+    finish: FinishSegments<W>,
+}
+
+/// Writes a zip archive. Entries are streamed in as they're added, and the central
+/// directory is written by `finish()`.
+///
+/// # Examples
+///
+/// ``` no_run
+/// let out = std::fs::File::create("archive.zip").unwrap();
+/// let mut zw = munzip::ZipWriter::new(out);
+///
+/// let options = munzip::EntryOptions::new();
+/// zw.add_directory("docs", &options).unwrap();
+/// zw.add_file("docs/hello.txt", &b"hello"[..], &options).unwrap();
+///
+/// zw.finish().unwrap();
+/// ```
+pub struct ZipWriter<W: Write + Seek> {
+    out: W,
+    entries: Vec<WrittenEntry>,
+    comment: Vec<u8>,
+    // the disk `out` is, when writing a split archive
+    disk: u32,
+    split: Option<Split<W>>,
+    buffer_sizes: BufferSizes,
+    validate: Option<Validator<W>>,
+    // whether entries' CRCs and sizes follow their data, as `out` can't be seeked
+    // back to fill them in
+    descriptors: bool,
+}
+
+// An entry's data, compressed by `compress()` ahead of being written.
+pub(crate) struct Compressed {
+    data: Vec<u8>,
+    crc: Crc32,
+    uncompressed: u64,
+}
+
+// Compress all of `data` the way `add_file()` would for `options`, but into memory,
+// so it can be done away from the `ZipWriter`, such as on another thread.
+pub(crate) fn compress<R: Read>(
+    mut data: R,
+    options: &EntryOptions,
+) -> Result<Compressed, MuError> {
+    let (method, level) = method_and_level(options);
+    let mut deflater = (method == 8).then(|| Deflater::new(level));
+    let mut compressed = Compressed {
+        data: Vec::new(),
+        crc: Crc32::new(),
+        uncompressed: 0,
+    };
+
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = match data.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        compressed.crc.update(&buf[..n]);
+        compressed.uncompressed += n as u64;
+        match deflater.as_mut() {
+            Some(d) => {
+                d.write(&buf[..n]);
+                compressed.data.extend(d.take_output());
+            }
+            None => compressed.data.extend_from_slice(&buf[..n]),
+        }
+    }
+    if let Some(d) = deflater {
+        compressed.data.extend(d.finish());
+    }
+
+    Ok(compressed)
+}
+
+// The method field and deflate level `options` ask for.
+fn method_and_level(options: &EntryOptions) -> (u16, u8) {
+    match options.method {
+        CompressionMethod::Store => (0, 0),
+        CompressionMethod::Deflate(level) => (8, level.clamp(1, 9)),
+        CompressionMethod::DeflateDefault => (8, 6),
+    }
+}
+
+// A file `ZipWriter::begin_file()` has started, and how much has been written to it.
+pub(crate) struct OpenFile {
+    entry: WrittenEntry,
+    crc: Crc32,
+    compressed: u64,
+    uncompressed: u64,
+    deflater: Option<Deflater>,
+}
+
+/// A `Write` that can't seek, wrapped so a `ZipWriter` can write to it, from
+/// `ZipWriter::streaming()`. It counts what's written, to answer where it's up to,
+/// but any other seek is an error.
+pub struct Unseekable<W: Write> {
+    inner: W,
+    position: u64,
+}
+
+impl<W: Write> Unseekable<W> {
+    /// Returns the wrapped writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Returns a reference to the wrapped writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+}
+
+impl<W: Write> Write for Unseekable<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Seek for Unseekable<W> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match pos {
+            SeekFrom::Current(0) => Ok(self.position),
+            SeekFrom::Start(at) if at == self.position => Ok(at),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "the archive's output can't be seeked",
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "stream")]
+impl ZipWriter<Unseekable<Vec<u8>>> {
+    // Take what's been written since last time.
+    pub(crate) fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.out.inner)
+    }
+}
+
+impl<W: Write> ZipWriter<Unseekable<W>> {
+    /// Creates a `ZipWriter` that writes to `out` front to back, never seeking, so it
+    /// can be a pipe, stdout or a socket, and an archive can be sent as it's made.
+    /// Each file's CRC-32 and sizes go in a data descriptor after its data, rather
+    /// than being filled in to its local header, and the central directory is kept
+    /// in memory until `finish()`, which returns `out` wrapped in an `Unseekable`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// // e.g. the body of an HTTP response
+    /// let mut zw = munzip::ZipWriter::streaming(std::io::stdout().lock());
+    ///
+    /// let options = munzip::EntryOptions::new();
+    /// for name in ["report.csv", "summary.txt"] {
+    ///     let file = std::fs::File::open(name).unwrap();
+    ///     zw.add_file(name, file, &options).unwrap();
+    /// }
+    /// zw.finish().unwrap().into_inner();
+    /// ```
+    pub fn streaming(out: W) -> Self {
+        let mut zw = ZipWriter::new(Unseekable {
+            inner: out,
+            position: 0,
+        });
+        zw.descriptors = true;
+        zw
+    }
+}
+
+impl ZipWriter<File> {
+    /// Creates a `ZipWriter` that splits the archive into segments of at most
+    /// `segment_size` bytes. For a `path` of `big.zip`, the segments are named
+    /// `big.z01`, `big.z02` and so on, with the last one named `big.zip`. Headers
+    /// are never split across segments, so some may come up a little short.
+    ///
+    /// If everything fits in the first segment, the result is a plain `big.zip`
+    /// that any reader can open.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// let mut zw = munzip::ZipWriter::split("release.zip", 100 * 1024 * 1024).unwrap();
+    ///
+    /// let image = std::fs::File::open("disk.img").unwrap();
+    /// zw.add_file("disk.img", image, &munzip::EntryOptions::new()).unwrap();
+    ///
+    /// zw.finish().unwrap();
+    /// ```
+    pub fn split(path: impl AsRef<Path>, segment_size: u64) -> Result<Self, MuError> {
+        if segment_size < MIN_SEGMENT_SIZE {
+            return Err(MuError::new(
+                ErrorKind::Other,
+                format!("segments must be at least {MIN_SEGMENT_SIZE} bytes"),
+            ));
+        }
+
+        let path = path.as_ref().to_path_buf();
+        let segment =
+            move |path: &PathBuf, disk: u32| path.with_extension(format!("z{:02}", disk + 1));
+
+        let segment_path = path.clone();
+        let mut open: OpenSegment<File> = Box::new(move |disk, create| {
+            let p = segment(&segment_path, disk);
+            if create {
+                File::create(p)
+            } else {
+                OpenOptions::new().write(true).open(p)
+            }
+        });
+        let finish: FinishSegments<File> = Box::new(move |disk| {
+            std::fs::rename(segment(&path, disk), &path)?;
+            OpenOptions::new().read(true).write(true).open(&path)
+        });
+
+        let out = open(0, true)?;
+        let mut zw = ZipWriter::new(out);
+        zw.split = Some(Split {
+            segment_size,
+            open,
+            finish,
+        });
+        zw.emit(&SPLIT_SIGNATURE.to_le_bytes(), true)?;
+
+        Ok(zw)
+    }
+}
+
+impl<W: Write + Seek> ZipWriter<W> {
+    /// Creates a `ZipWriter` that writes to `out`, starting at its current position.
+    pub fn new(out: W) -> Self {
+        ZipWriter {
+            out,
+            entries: Vec::new(),
+            comment: Vec::new(),
+            disk: 0,
+            split: None,
+            buffer_sizes: BufferSizes::default(),
+            validate: None,
+            descriptors: false,
+        }
+    }
+
+    /// Sets the smallest and largest buffer data is read through when adding an
+    /// entry. `add_file()` can't know how much it'll read, so it starts with the
+    /// smallest and doubles it each time a read fills it, up to the largest;
+    /// `copy_entry()` gets one the size of the entry's data. Defaults to 4 KiB and
+    /// 1 MiB.
+    pub fn set_buffer_sizes(&mut self, min: usize, max: usize) {
+        self.buffer_sizes = BufferSizes::new(min, max);
+    }
+
+    /// Sets whether `finish()` reads back the archive it wrote, and checks it against
+    /// what was meant to be written: the central directory and end record byte for
+    /// byte, and each entry's local header, and its data's size and CRC-32, by
+    /// decompressing it. It's an error if anything differs, catching corruption below
+    /// `out`, such as a flaky disk or network filesystem. Deflated data is only
+    /// checked with the `deflate` feature, and encrypted data copied in by
+    /// `copy_entry()` isn't. Defaults to `false`.
+    ///
+    /// A split archive can't be read back this way, so this is an error for one.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// let out = std::fs::OpenOptions::new()
+    ///     .read(true)
+    ///     .write(true)
+    ///     .create(true)
+    ///     .truncate(true)
+    ///     .open("artifacts.zip")
+    ///     .unwrap();
+    /// let mut zw = munzip::ZipWriter::new(out);
+    /// zw.set_validate_after_write(true).unwrap();
+    ///
+    /// let report = std::fs::File::open("target/report.html").unwrap();
+    /// zw.add_file("report.html", report, &munzip::EntryOptions::new()).unwrap();
+    /// zw.finish().expect("artifact archive is corrupt");
+    /// ```
+    pub fn set_validate_after_write(&mut self, validate: bool) -> Result<(), MuError>
+    where
+        W: Read,
+    {
+        if self.split.is_some() {
+            return Err(MuError::new(
+                ErrorKind::Other,
+                "a split archive can't be validated after it's written",
+            ));
+        }
+        self.validate = match validate {
+            true => Some(crate::validate::validate::<W>),
+            false => None,
+        };
+        Ok(())
+    }
+
+    /// Sets the archive comment, written after the end record.
+    pub fn set_comment(&mut self, comment: &[u8]) -> Result<(), MuError> {
+        if comment.len() > u16::MAX as usize {
+            return Err(MuError::new(ErrorKind::Other, "archive comment too long"));
+        }
+        self.comment = comment.to_vec();
+        Ok(())
+    }
+
+    /// Adds a file named `name`, reading its contents from `data` until it's exhausted.
+    pub fn add_file<R: Read>(
+        &mut self,
+        name: &str,
+        mut data: R,
+        options: &EntryOptions,
+    ) -> Result<(), MuError> {
+        let mut file = self.begin_file(name, options)?;
+
+        let mut buf = vec![0; self.buffer_sizes.min];
+        loop {
+            let n = match data.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            self.write_file_data(&mut file, &buf[..n])?;
+
+            // a full read means there's likely more, so the next can be bigger
+            if n == buf.len() && buf.len() < self.buffer_sizes.max {
+                buf.resize((buf.len() * 2).min(self.buffer_sizes.max), 0);
+            }
+        }
+
+        self.end_file(file)
+    }
+
+    // Start a file named `name`, to be written a piece at a time by
+    // `write_file_data()` and closed by `end_file()`.
+    pub(crate) fn begin_file(
+        &mut self,
+        name: &str,
+        options: &EntryOptions,
+    ) -> Result<OpenFile, MuError> {
+        let (method, level) = method_and_level(options);
+
+        Ok(OpenFile {
+            entry: self.start_entry(name, method, options, 0)?,
+            crc: Crc32::new(),
+            compressed: 0,
+            uncompressed: 0,
+            deflater: (method == 8).then(|| Deflater::new(level)),
+        })
+    }
+
+    pub(crate) fn write_file_data(
+        &mut self,
+        file: &mut OpenFile,
+        data: &[u8],
+    ) -> Result<(), MuError> {
+        file.crc.update(data);
+        file.uncompressed += data.len() as u64;
+
+        let out = match file.deflater.as_mut() {
+            Some(d) => {
+                d.write(data);
+                d.take_output()
+            }
+            None => data.to_vec(),
+        };
+        self.emit(&out, false)?;
+        file.compressed += out.len() as u64;
+        Ok(())
+    }
+
+    pub(crate) fn end_file(&mut self, mut file: OpenFile) -> Result<(), MuError> {
+        if let Some(d) = file.deflater.take() {
+            let out = d.finish();
+            self.emit(&out, false)?;
+            file.compressed += out.len() as u64;
+        }
+
+        let mut entry = file.entry;
+        entry.crc32 = file.crc.finish();
+        entry.compressed_size = fit_u32(file.compressed, "entry too large")?;
+        entry.uncompressed_size = fit_u32(file.uncompressed, "entry too large")?;
+
+        let mut sizes = [0u8; 12];
+        sizes[..4].copy_from_slice(&entry.crc32.to_le_bytes());
+        sizes[4..8].copy_from_slice(&entry.compressed_size.to_le_bytes());
+        sizes[8..].copy_from_slice(&entry.uncompressed_size.to_le_bytes());
+        let at = SeekFrom::Start(entry.local_header_offset as u64 + 14);
+
+        if self.descriptors {
+            // there's no going back, so they follow the data
+            self.emit(&DATA_DESCRIPTOR_SIGNATURE.to_le_bytes(), false)?;
+            self.emit(&sizes, false)?;
+        } else if entry.disk == self.disk {
+            // go back and fill in the local header
+            let end = self.out.stream_position()?;
+            self.out.seek(at)?;
+            self.out.write_all(&sizes)?;
+            self.out.seek(SeekFrom::Start(end))?;
+        } else if let Some(split) = self.split.as_mut() {
+            let mut segment = (split.open)(entry.disk, false)?;
+            segment.seek(at)?;
+            segment.write_all(&sizes)?;
+            segment.flush()?;
+        }
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    // Add a file named `name` whose data `compress()` has already compressed, as
+    // for `options`.
+    pub(crate) fn add_compressed(
+        &mut self,
+        name: &str,
+        compressed: Compressed,
+        options: &EntryOptions,
+    ) -> Result<(), MuError> {
+        let mut file = self.begin_file(name, options)?;
+        file.deflater = None;
+        self.emit(&compressed.data, false)?;
+        file.crc = compressed.crc;
+        file.compressed = compressed.data.len() as u64;
+        file.uncompressed = compressed.uncompressed;
+        self.end_file(file)
+    }
+
+    /// Adds a directory named `name`, with no data. A trailing `/` is added if it's
+    /// missing, and a `unix_mode()` with no file type gets the directory type.
+    pub fn add_directory(&mut self, name: &str, options: &EntryOptions) -> Result<(), MuError> {
+        let name = if name.ends_with('/') {
+            name.to_string()
+        } else {
+            format!("{name}/")
+        };
+
+        let options = match options.unix_mode {
+            Some(mode) if mode & S_IFMT == 0 => options.clone().unix_mode(S_IFDIR | mode),
+            _ => options.clone(),
+        };
+        let entry = self.start_entry(&name, 0, &options, DOS_DIRECTORY)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Adds a symlink named `name` pointing at `target`, stored Info-ZIP style: the
+    /// Unix mode has the symlink type, and the target is the entry's contents. The
+    /// permission bits come from `unix_mode()`, or are `0777` without one, and the
+    /// entry is always stored.
+    pub fn add_symlink(
+        &mut self,
+        name: &str,
+        target: &str,
+        options: &EntryOptions,
+    ) -> Result<(), MuError> {
+        let permissions = options.unix_mode.map_or(0o777, |mode| mode & 0o7777);
+        let options = options
+            .clone()
+            .unix_mode(S_IFLNK | permissions)
+            .method(CompressionMethod::Store);
+        self.add_file(name, target.as_bytes(), &options)
+    }
+
+    /// Adds everything beneath the directory `dir`, named by their paths relative to
+    /// it, after `prefix` and a `/` unless `prefix` is empty. Every directory gets an
+    /// entry, so empty ones survive, and symlinks are added as symlinks rather than
+    /// followed. Each entry's modification time comes from the filesystem, as does
+    /// its mode on Unix, or its readonly bit elsewhere; other settings come from
+    /// `options`. Each directory's contents are added in name order, ahead of its
+    /// subdirectories' contents, so the same tree always makes the same archive.
+    /// Anything that isn't a file, directory or symlink is skipped.
+    /// Returns how many entries were added.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// let out = std::fs::File::create("site.zip").unwrap();
+    /// let mut zw = munzip::ZipWriter::new(out);
+    ///
+    /// let added = zw
+    ///     .add_dir_all("public".as_ref(), "site", &munzip::EntryOptions::new())
+    ///     .unwrap();
+    /// println!("{added} entries");
+    /// zw.finish().unwrap();
+    /// ```
+    pub fn add_dir_all(
+        &mut self,
+        dir: &Path,
+        prefix: &str,
+        options: &EntryOptions,
+    ) -> Result<usize, MuError> {
+        let prefix = prefix.trim_end_matches('/');
+        let mut added = 0;
+        if !prefix.is_empty() {
+            let metadata = std::fs::metadata(dir)?;
+            self.add_directory(prefix, &options_from(options, &metadata))?;
+            added += 1;
+        }
+
+        // directories still to be walked, with the names their contents go under
+        let mut pending = vec![(dir.to_path_buf(), prefix.to_string())];
+        while let Some((dir, name)) = pending.pop() {
+            let mut children = std::fs::read_dir(&dir)?.collect::<Result<Vec<_>, _>>()?;
+            children.sort_by_key(|child| child.file_name());
+
+            // walked last in first out, so pushed in reverse to come out in order
+            let mut subdirs = Vec::new();
+            for child in children {
+                let file_name = child.file_name();
+                let Some(file_name) = file_name.to_str() else {
+                    return Err(MuError::new(
+                        ErrorKind::Utf8,
+                        format!("'{}' isn't valid UTF-8", child.path().display()),
+                    ));
+                };
+                let child_name = match name.is_empty() {
+                    true => file_name.to_string(),
+                    false => format!("{name}/{file_name}"),
+                };
+
+                let path = child.path();
+                let metadata = std::fs::symlink_metadata(&path)?;
+                let entry = options_from(options, &metadata);
+                let file_type = metadata.file_type();
+                if file_type.is_symlink() {
+                    let target = std::fs::read_link(&path)?;
+                    let Some(target) = target.to_str() else {
+                        return Err(MuError::new(
+                            ErrorKind::Utf8,
+                            format!("target of '{}' isn't valid UTF-8", path.display()),
+                        ));
+                    };
+                    self.add_symlink(&child_name, &target.replace('\\', "/"), &entry)?;
+                } else if file_type.is_dir() {
+                    self.add_directory(&child_name, &entry)?;
+                    subdirs.push((path, child_name));
+                } else if file_type.is_file() {
+                    self.add_file(&child_name, File::open(&path)?, &entry)?;
+                } else {
+                    continue;
+                }
+                added += 1;
+            }
+            pending.extend(subdirs.into_iter().rev());
+        }
+
+        Ok(added)
+    }
+
+    /// Adds a copy of `entry`, which may come from any archive, named `name`. The
+    /// data is copied exactly as it's stored, compressed or encrypted, so nothing is
+    /// decompressed or recompressed. Times, attributes and extra fields come along
+    /// with it.
+    pub fn copy_entry<R: Read + Seek>(
+        &mut self,
+        entry: &Entry<'_, R>,
+        name: &str,
+    ) -> Result<(), MuError> {
+        if name.len() > u16::MAX as usize {
+            return Err(MuError::new(ErrorKind::Other, "entry name too long"));
+        }
+
+        let header = &entry.header;
+        let mut file = entry.file.borrow_mut();
+        let compressed_size = fit_u32(header.compressed_size, "entry too large")?;
+        let uncompressed_size = fit_u32(header.uncompressed_size, "entry too large")?;
+
+        let (local_extra, central_extra) = split_extra(&local_extra(&mut *file, header)?);
+
+        let flags = match name.is_ascii() {
+            true => header.general_purpose_bit_flag & !FLAG_UTF8,
+            false => header.general_purpose_bit_flag | FLAG_UTF8,
+        };
+        let version_needed = version_needed(header.compression_method, name.ends_with('/'));
+
+        let mut local = Vec::with_capacity(30 + name.len() + local_extra.len());
+        local.extend_from_slice(&LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes());
+        local.extend_from_slice(&version_needed.to_le_bytes());
+        local.extend_from_slice(&flags.to_le_bytes());
+        local.extend_from_slice(&header.compression_method.to_le_bytes());
+        local.extend_from_slice(&header.last_mod_file_time.to_le_bytes());
+        local.extend_from_slice(&header.last_mod_file_date.to_le_bytes());
+        local.extend_from_slice(&header.crc32.to_le_bytes());
+        local.extend_from_slice(&compressed_size.to_le_bytes());
+        local.extend_from_slice(&uncompressed_size.to_le_bytes());
+        local.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        local.extend_from_slice(&(local_extra.len() as u16).to_le_bytes());
+        local.extend_from_slice(name.as_bytes());
+        local.extend_from_slice(&local_extra);
+        let (disk, offset) = self.emit(&local, true)?;
+
+        file.seek(SeekFrom::Start(header.offset))?;
+        let mut data = (&mut *file).take(header.compressed_size);
+        let mut buf = self.buffer_sizes.for_len(header.compressed_size);
+        let mut copied: u64 = 0;
+        loop {
+            let n = match data.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            self.emit(&buf[..n], false)?;
+            copied += n as u64;
+        }
+        if copied < header.compressed_size {
+            return Err(MuError::new(
+                ErrorKind::Format,
+                format!("'{}' is truncated", entry.filename),
+            ));
+        }
+
+        // the flag promises a descriptor, so it gets one, though the header has the sizes
+        if flags & FLAG_DATA_DESCRIPTOR != 0 {
+            let mut descriptor = Vec::with_capacity(16);
+            descriptor.extend_from_slice(&DATA_DESCRIPTOR_SIGNATURE.to_le_bytes());
+            descriptor.extend_from_slice(&header.crc32.to_le_bytes());
+            descriptor.extend_from_slice(&compressed_size.to_le_bytes());
+            descriptor.extend_from_slice(&uncompressed_size.to_le_bytes());
+            self.emit(&descriptor, false)?;
+        }
+
+        self.entries.push(WrittenEntry {
+            name: name.to_string(),
+            version_made_by: header.version_made_by,
+            version_needed,
+            flags,
+            method: header.compression_method,
+            time: header.last_mod_file_time,
+            date: header.last_mod_file_date,
+            crc32: header.crc32,
+            compressed_size,
+            uncompressed_size,
+            central_extra,
+            external_attributes: header.external_file_attributes,
+            disk,
+            local_header_offset: fit_u32(offset, "archive too large")?,
+        });
+        Ok(())
+    }
+
+    /// Writes the central directory and end record, and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, MuError> {
+        let entries = std::mem::take(&mut self.entries);
+        let mut cd_start = None;
+        let mut cd_size: u64 = 0;
+        // how many records are on the disk the latest one went to
+        let mut record_disk = self.disk;
+        let mut on_disk: u64 = 0;
+        // everything from the central directory on, to check it reads back the same
+        let mut central = Vec::new();
+
+        for e in &entries {
+            let mut rec = Vec::with_capacity(46 + e.name.len() + e.central_extra.len());
+            rec.extend_from_slice(&GLOBAL_FILE_HEADER_SIGNATURE.to_le_bytes());
+            rec.extend_from_slice(&e.version_made_by.to_le_bytes());
+            rec.extend_from_slice(&e.version_needed.to_le_bytes());
+            rec.extend_from_slice(&e.flags.to_le_bytes());
+            rec.extend_from_slice(&e.method.to_le_bytes());
+            rec.extend_from_slice(&e.time.to_le_bytes());
+            rec.extend_from_slice(&e.date.to_le_bytes());
+            rec.extend_from_slice(&e.crc32.to_le_bytes());
+            rec.extend_from_slice(&e.compressed_size.to_le_bytes());
+            rec.extend_from_slice(&e.uncompressed_size.to_le_bytes());
+            rec.extend_from_slice(&(e.name.len() as u16).to_le_bytes());
+            rec.extend_from_slice(&(e.central_extra.len() as u16).to_le_bytes());
+            rec.extend_from_slice(&0u16.to_le_bytes()); // comment length
+            rec.extend_from_slice(&(e.disk as u16).to_le_bytes());
+            rec.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+            rec.extend_from_slice(&e.external_attributes.to_le_bytes());
+            rec.extend_from_slice(&e.local_header_offset.to_le_bytes());
+            rec.extend_from_slice(e.name.as_bytes());
+            rec.extend_from_slice(&e.central_extra);
+
+            let (disk, offset) = self.emit(&rec, true)?;
+            if self.validate.is_some() {
+                central.extend_from_slice(&rec);
+            }
+            if disk != record_disk {
+                record_disk = disk;
+                on_disk = 0;
+            }
+            cd_start.get_or_insert((disk, offset));
+            cd_size += rec.len() as u64;
+            on_disk += 1;
+        }
+
+        // the end record has to share a segment with the last of the directory
+        let mut end = Vec::with_capacity(22 + self.comment.len());
+        end.extend_from_slice(&[0; 22]);
+        end.extend_from_slice(&self.comment);
+        let (disk, position) = self.reserve(end.len() as u64)?;
+        let (cd_disk, cd_offset) = cd_start.unwrap_or((disk, position));
+        if disk != record_disk {
+            on_disk = 0;
+        }
+
+        let bad = |what| MuError::new(ErrorKind::Other, format!("too many {what}"));
+        let count = u16::try_from(entries.len()).map_err(|_| bad("entries"))?;
+        let this_disk = u16::try_from(on_disk).map_err(|_| bad("entries"))?;
+        let disk = u16::try_from(disk).map_err(|_| bad("segments"))?;
+        let cd_disk = cd_disk as u16;
+
+        end[..4].copy_from_slice(&END_RECORD_SIGNATURE.to_le_bytes());
+        end[4..6].copy_from_slice(&disk.to_le_bytes());
+        end[6..8].copy_from_slice(&cd_disk.to_le_bytes());
+        end[8..10].copy_from_slice(&this_disk.to_le_bytes());
+        end[10..12].copy_from_slice(&count.to_le_bytes());
+        end[12..16]
+            .copy_from_slice(&fit_u32(cd_size, "central directory too large")?.to_le_bytes());
+        end[16..20].copy_from_slice(&fit_u32(cd_offset, "archive too large")?.to_le_bytes());
+        end[20..22].copy_from_slice(&(self.comment.len() as u16).to_le_bytes());
+        self.emit(&end, true)?;
+        self.out.flush()?;
+
+        if let Some(validate) = self.validate {
+            central.extend_from_slice(&end);
+            validate(&mut self.out, &entries, &central, cd_offset)?;
+        }
+
+        match self.split.as_mut() {
+            None => Ok(self.out),
+            Some(split) => {
+                if self.disk == 0 {
+                    // it all fit, so this isn't a split archive after all
+                    self.out.seek(SeekFrom::Start(0))?;
+                    self.out.write_all(&UNSPLIT_SIGNATURE.to_le_bytes())?;
+                    self.out.flush()?;
+                }
+                drop(self.out);
+                Ok((split.finish)(self.disk)?)
+            }
+        }
+    }
+
+    // Write a local header with the crc and sizes zeroed, to be filled in later.
+    fn start_entry(
+        &mut self,
+        name: &str,
+        method: u16,
+        options: &EntryOptions,
+        dos_attributes: u32,
+    ) -> Result<WrittenEntry, MuError> {
+        if name.len() > u16::MAX as usize {
+            return Err(MuError::new(ErrorKind::Other, "entry name too long"));
+        }
+
+        let (date, time, mtime) = match options.modified {
+            Some(t) => {
+                let secs = match t.duration_since(UNIX_EPOCH) {
+                    Ok(d) => d.as_secs() as i64,
+                    Err(e) => -(e.duration().as_secs() as i64),
+                };
+                let (date, time) = unix_to_dos(secs).unwrap_or((0x21, 0));
+                (date, time, i32::try_from(secs).ok())
+            }
+            None => (0x21, 0, None),
+        };
+
+        let mut local_extra = Vec::new();
+        let mut central_extra = Vec::new();
+        if let Some(mtime) = mtime {
+            for extra in [&mut local_extra, &mut central_extra] {
+                extra.extend_from_slice(&EXTENDED_TIMESTAMP_TAG.to_le_bytes());
+                extra.extend_from_slice(&5u16.to_le_bytes());
+                extra.push(1); // mtime present
+                extra.extend_from_slice(&mtime.to_le_bytes());
+            }
+        }
+        if let Some((uid, gid)) = options.unix_owner {
+            local_extra.extend_from_slice(&INFOZIP_UNIX_TAG.to_le_bytes());
+            local_extra.extend_from_slice(&11u16.to_le_bytes());
+            local_extra.push(1); // version
+            local_extra.push(4);
+            local_extra.extend_from_slice(&uid.to_le_bytes());
+            local_extra.push(4);
+            local_extra.extend_from_slice(&gid.to_le_bytes());
+            // the central copy carries no data
+            central_extra.extend_from_slice(&INFOZIP_UNIX_TAG.to_le_bytes());
+            central_extra.extend_from_slice(&0u16.to_le_bytes());
+        }
+        if method == 0 && dos_attributes & DOS_DIRECTORY == 0 && options.alignment > 1 {
+            let alignment = options.alignment as u64;
+            if !alignment.is_power_of_two() {
+                return Err(MuError::new(
+                    ErrorKind::Other,
+                    "alignment must be a power of two",
+                ));
+            }
+
+            // find where the header lands first, since a split archive may move it
+            let most = 30 + name.len() + local_extra.len() + 6 + alignment as usize;
+            let (_, position) = self.reserve(most as u64)?;
+            let unpadded = position + (30 + name.len() + local_extra.len() + 6) as u64;
+            let padding = (alignment - unpadded % alignment) % alignment;
+
+            local_extra.extend_from_slice(&ALIGNMENT_TAG.to_le_bytes());
+            local_extra.extend_from_slice(&(2 + padding as u16).to_le_bytes());
+            local_extra.extend_from_slice(&options.alignment.to_le_bytes());
+            local_extra.resize(local_extra.len() + padding as usize, 0);
+        }
+
+        let mut dos_attributes = dos_attributes | options.dos_attributes as u32;
+        let (version_made_by, external_attributes) = match options.unix_mode {
+            Some(mode) => {
+                if mode & 0o222 == 0 {
+                    dos_attributes |= DOS_READONLY;
+                }
+                ((HOST_UNIX as u16) << 8 | 20, mode << 16 | dos_attributes)
+            }
+            None => ((HOST_DOS as u16) << 8 | 20, dos_attributes),
+        };
+        let version_needed = version_needed(method, dos_attributes & DOS_DIRECTORY != 0);
+        let mut flags = if name.is_ascii() { 0 } else { FLAG_UTF8 };
+        // a directory has nothing to follow it, so its zeroes are already right
+        if self.descriptors && dos_attributes & DOS_DIRECTORY == 0 {
+            flags |= FLAG_DATA_DESCRIPTOR;
+        }
+
+        let mut header = Vec::with_capacity(30 + name.len() + local_extra.len());
+        header.extend_from_slice(&LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes());
+        header.extend_from_slice(&version_needed.to_le_bytes());
+        header.extend_from_slice(&flags.to_le_bytes());
+        header.extend_from_slice(&method.to_le_bytes());
+        header.extend_from_slice(&time.to_le_bytes());
+        header.extend_from_slice(&date.to_le_bytes());
+        header.extend_from_slice(&[0; 12]); // crc and sizes
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&(local_extra.len() as u16).to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        header.extend_from_slice(&local_extra);
+        let (disk, offset) = self.emit(&header, true)?;
+
+        Ok(WrittenEntry {
+            name: name.to_string(),
+            version_made_by,
+            version_needed,
+            flags,
+            method,
+            time,
+            date,
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            central_extra,
+            external_attributes,
+            disk,
+            local_header_offset: fit_u32(offset, "archive too large")?,
+        })
+    }
+
+    // Moves on to the next segment if `len` bytes won't fit in this one, and returns
+    // where they'd be written.
+    fn reserve(&mut self, len: u64) -> Result<(u32, u64), MuError> {
+        let position = self.out.stream_position()?;
+        let Some(split) = self.split.as_mut() else {
+            return Ok((self.disk, position));
+        };
+
+        if position > 0 && position + len > split.segment_size && len <= split.segment_size {
+            self.next_segment()?;
+            return Ok((self.disk, 0));
+        }
+        Ok((self.disk, position))
+    }
+
+    // Writes `bytes`, spreading them over as many segments as needed, unless they're
+    // a header that has to be kept `whole`. Returns where they start.
+    fn emit(&mut self, bytes: &[u8], whole: bool) -> Result<(u32, u64), MuError> {
+        let start = match whole {
+            true => self.reserve(bytes.len() as u64)?,
+            false => (self.disk, self.out.stream_position()?),
+        };
+
+        let mut rest = bytes;
+        while let Some(split) = self.split.as_ref() {
+            let room = split.segment_size - self.out.stream_position()?.min(split.segment_size);
+            if rest.len() as u64 <= room {
+                break;
+            }
+            let (now, later) = rest.split_at(room as usize);
+            self.out.write_all(now)?;
+            self.next_segment()?;
+            rest = later;
+        }
+        self.out.write_all(rest)?;
+
+        Ok(start)
+    }
+
+    fn next_segment(&mut self) -> Result<(), MuError> {
+        if let Some(split) = self.split.as_mut() {
+            self.out.flush()?;
+            self.disk += 1;
+            self.out = (split.open)(self.disk, true)?;
+        }
+        Ok(())
+    }
+}
+
+// How many bytes `copy_entry()` adds to an archive for `entry` named `name`, central
+// record included.
+pub(crate) fn copied_size<R: Read + Seek>(
+    entry: &Entry<'_, R>,
+    name: &str,
+) -> Result<u64, MuError> {
+    let header = &entry.header;
+    let extra = local_extra(&mut *entry.file.borrow_mut(), header)?;
+    let (local_extra, central_extra) = split_extra(&extra);
+
+    let descriptor = match header.general_purpose_bit_flag & FLAG_DATA_DESCRIPTOR {
+        0 => 0,
+        _ => 16,
+    };
+    let local = 30 + name.len() + local_extra.len();
+    let central = 46 + name.len() + central_extra.len();
+
+    Ok((local + descriptor + central) as u64 + header.compressed_size)
+}
+
+// Read an entry's local extra fields, which hold more than its central ones.
+fn local_extra(file: &mut (impl Read + Seek), header: &InternalHeader) -> Result<Vec<u8>, MuError> {
+    file.seek(SeekFrom::Start(header.local_header_offset + 26))?;
+    let mut lengths = [0; 4];
+    file.read_exact(&mut lengths)?;
+
+    let name_len = u16::from_le_bytes([lengths[0], lengths[1]]) as i64;
+    let mut extra = vec![0; u16::from_le_bytes([lengths[2], lengths[3]]) as usize];
+    file.seek(SeekFrom::Current(name_len))?;
+    file.read_exact(&mut extra)?;
+
+    Ok(extra)
+}
+
+// Sort a copied entry's local extra fields into those for its new local header and
+// those for its central record. Alignment padding is dropped, since the data won't
+// land where it did, and the fields whose central form is shorter are cut down.
+fn split_extra(extra: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut local = Vec::new();
+    let mut central = Vec::new();
+
+    let mut rest = extra;
+    while rest.len() >= 4 {
+        let tag = u16::from_le_bytes([rest[0], rest[1]]);
+        let len = (u16::from_le_bytes([rest[2], rest[3]]) as usize).min(rest.len() - 4);
+        let (field, data) = (&rest[..4 + len], &rest[4..4 + len]);
+        rest = &rest[4 + len..];
+
+        match tag {
+            ALIGNMENT_TAG => continue,
+            // the central copy holds the flags, and the mtime if there is one
+            EXTENDED_TIMESTAMP_TAG => {
+                let central_len = match data.first() {
+                    Some(flags) if flags & 1 != 0 && data.len() >= 5 => 5,
+                    Some(_) => 1,
+                    None => 0,
+                };
+                central.extend_from_slice(&tag.to_le_bytes());
+                central.extend_from_slice(&(central_len as u16).to_le_bytes());
+                central.extend_from_slice(&data[..central_len]);
+            }
+            // the central copy carries no data
+            INFOZIP_UNIX_TAG => {
+                central.extend_from_slice(&tag.to_le_bytes());
+                central.extend_from_slice(&0u16.to_le_bytes());
+            }
+            _ => central.extend_from_slice(field),
+        }
+        local.extend_from_slice(field);
+    }
+
+    (local, central)
+}
+
+fn fit_u32(value: u64, message: &str) -> Result<u32, MuError> {
+    u32::try_from(value).map_err(|_| MuError::new(ErrorKind::Other, message))
+}
+
+// `options`, with the modification time and attributes of what `metadata` describes.
+fn options_from(options: &EntryOptions, metadata: &std::fs::Metadata) -> EntryOptions {
+    let mut options = options.clone();
+    if let Ok(modified) = metadata.modified() {
+        options = options.modified(modified);
+    }
+    match crate::platform::unix_mode(metadata) {
+        Some(mode) => options.unix_mode(mode),
+        None if metadata.permissions().readonly() => {
+            let dos = options.dos_attributes | DOS_READONLY as u8;
+            options.dos_attributes(dos)
+        }
+        None => options,
+    }
+}
@@ -0,0 +1,184 @@
+// External attributes written by `ZipWriter` and read back: Unix modes, DOS
+// attributes and symlinks, and on Unix, the permissions extraction gives files.
+
+#![cfg(feature = "writer")]
+
+mod common;
+
+use std::io::Cursor;
+
+use munzip::*;
+
+use common::Scratch;
+
+// Each entry's name, kind, host system, Unix mode, DOS attributes and whether it's
+// readonly.
+type Attributes = (String, EntryKind, HostSystem, Option<u32>, u8, bool);
+
+fn attributes(bytes: &[u8]) -> Vec<Attributes> {
+    IterableArchive::from_bytes(bytes)
+        .unwrap()
+        .map(|e| {
+            let e = e.unwrap();
+            (
+                e.filename(),
+                e.kind(),
+                e.host_system(),
+                e.unix_mode(),
+                e.dos_attributes(),
+                e.is_readonly(),
+            )
+        })
+        .collect()
+}
+
+fn unix_archive() -> Vec<u8> {
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = EntryOptions::new();
+    zw.add_directory("bin/", &options.clone().unix_mode(0o40750))
+        .unwrap();
+    zw.add_file(
+        "bin/run",
+        &b"#!/bin/sh"[..],
+        &options.clone().unix_mode(0o100755),
+    )
+    .unwrap();
+    zw.add_file(
+        "notes.txt",
+        &b"notes"[..],
+        &options.clone().unix_mode(0o100644),
+    )
+    .unwrap();
+    zw.add_file(
+        "frozen.txt",
+        &b"frozen"[..],
+        &options.clone().unix_mode(0o100444),
+    )
+    .unwrap();
+    zw.finish().unwrap().into_inner()
+}
+
+#[test]
+fn unix_modes_round_trip() {
+    use EntryKind::*;
+    let unix = HostSystem::Unix;
+    assert_eq!(
+        attributes(&unix_archive()),
+        [
+            (
+                "bin/".to_string(),
+                Directory,
+                unix,
+                Some(0o40750),
+                0x10,
+                false
+            ),
+            ("bin/run".to_string(), File, unix, Some(0o100755), 0, false),
+            (
+                "notes.txt".to_string(),
+                File,
+                unix,
+                Some(0o100644),
+                0,
+                false
+            ),
+            (
+                "frozen.txt".to_string(),
+                File,
+                unix,
+                Some(0o100444),
+                0x01,
+                true
+            ),
+        ]
+    );
+}
+
+#[test]
+fn dos_attributes_round_trip() {
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    zw.add_directory("hidden/", &EntryOptions::new().dos_attributes(0x02))
+        .unwrap();
+    zw.add_file(
+        "system.sys",
+        &b"system"[..],
+        &EntryOptions::new().dos_attributes(0x01 | 0x04 | 0x20),
+    )
+    .unwrap();
+    zw.add_file("plain.txt", &b"plain"[..], &EntryOptions::new())
+        .unwrap();
+    // asking for the directory bit on a file doesn't make it one
+    zw.add_file(
+        "odd",
+        &b"odd"[..],
+        &EntryOptions::new().dos_attributes(0x10),
+    )
+    .unwrap();
+    let bytes = zw.finish().unwrap().into_inner();
+
+    use EntryKind::*;
+    let dos = HostSystem::Dos;
+    assert_eq!(
+        attributes(&bytes),
+        [
+            ("hidden/".to_string(), Directory, dos, None, 0x12, false),
+            ("system.sys".to_string(), File, dos, None, 0x25, true),
+            ("plain.txt".to_string(), File, dos, None, 0, false),
+            ("odd".to_string(), File, dos, None, 0, false),
+        ]
+    );
+}
+
+#[test]
+fn symlinks_round_trip() {
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    zw.add_symlink("latest", "releases/1.2/", &EntryOptions::new())
+        .unwrap();
+    let options = EntryOptions::new()
+        .unix_mode(0o755)
+        .method(CompressionMethod::DeflateDefault);
+    zw.add_symlink("bin/tool", "../libexec/tool", &options)
+        .unwrap();
+    let bytes = zw.finish().unwrap().into_inner();
+
+    let expected = [
+        ("latest", 0o120777, "releases/1.2/"),
+        ("bin/tool", 0o120755, "../libexec/tool"),
+    ];
+    let entries = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    for (entry, (name, mode, target)) in entries.zip(expected) {
+        let mut entry = entry.unwrap();
+        assert_eq!(entry.filename(), name);
+        assert_eq!(entry.kind(), EntryKind::Symlink);
+        assert_eq!(entry.unix_mode(), Some(mode));
+        assert_eq!(entry.compression_method(), 0);
+        assert_eq!(entry.buffer().unwrap(), target.as_bytes());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn extraction_applies_unix_modes() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let scratch = Scratch::new("extract");
+    let path = scratch.path("modes.zip");
+    std::fs::write(&path, unix_archive()).unwrap();
+    let dest = scratch.path("out");
+
+    let mut file = std::fs::File::open(&path).unwrap();
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    zi.extract_all(&dest, &ExtractOptions::new().preserve_attributes(true))
+        .unwrap();
+
+    let mode = |name: &str| {
+        std::fs::metadata(dest.join(name))
+            .unwrap()
+            .permissions()
+            .mode()
+    };
+    assert_eq!(mode("bin") & 0o777, 0o750);
+    assert_eq!(mode("bin/run") & 0o777, 0o755);
+    assert_eq!(mode("notes.txt") & 0o777, 0o644);
+    assert_eq!(mode("frozen.txt") & 0o777, 0o444);
+}
@@ -0,0 +1,99 @@
+// `audit_layout()` against an archive whose AppleDouble entry, hidden by default,
+// comes first in the central directory, ahead of two entries sharing their data, and
+// against ZIP64 archives that account for every byte.
+
+#![cfg(feature = "writer")]
+
+mod common;
+
+use std::io::Cursor;
+
+use munzip::*;
+
+use common::{u32_at, zip64_archive, TempArchive};
+
+// `__MACOSX/._a.txt` and `a.txt`, then a copy of the central directory record of
+// `a.txt` under another name, which is read as `a.txt` from its local header.
+fn archive() -> Vec<u8> {
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = EntryOptions::new();
+    zw.add_file("__MACOSX/._a.txt", &b"resource fork"[..], &options)
+        .unwrap();
+    zw.add_file("a.txt", &b"shared data"[..], &options).unwrap();
+    let bytes = zw.finish().unwrap().into_inner();
+
+    let end = bytes.len() - 22;
+    let cd = u32_at(&bytes, end + 16) as usize;
+    let last = cd
+        + bytes[cd..end]
+            .windows(5)
+            .rposition(|w| w == b"a.txt")
+            .unwrap()
+        - 46;
+    let mut copy = bytes[last..end].to_vec();
+    copy[46..51].copy_from_slice(b"b.txt");
+
+    let mut out = bytes[..end].to_vec();
+    out.extend_from_slice(&copy);
+    let mut end_record = bytes[end..].to_vec();
+    end_record[8..10].copy_from_slice(&3u16.to_le_bytes());
+    end_record[10..12].copy_from_slice(&3u16.to_le_bytes());
+    let cd_size = (end + copy.len() - cd) as u32;
+    end_record[12..16].copy_from_slice(&cd_size.to_le_bytes());
+    out.extend_from_slice(&end_record);
+    out
+}
+
+#[test]
+fn regions_hold_central_directory_indexes() {
+    let temp = TempArchive::new("indexes", &archive());
+    let mut file = temp.open();
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    assert_eq!(zi.len(), 2);
+
+    let report = zi.audit_layout().unwrap();
+    let entries: Vec<(&str, usize)> = report
+        .entries
+        .iter()
+        .map(|e| (e.filename.as_str(), e.central_index))
+        .collect();
+    assert_eq!(
+        entries,
+        [("__MACOSX/._a.txt", 0), ("a.txt", 1), ("a.txt", 2)]
+    );
+
+    let findings = report.findings();
+    assert_eq!(findings.len(), 1);
+    assert_eq!(
+        findings[0].kind,
+        FindingKind::Overlap(RegionKind::Entry(1), RegionKind::Entry(2))
+    );
+    assert_eq!(findings[0].entry.as_deref(), Some("a.txt"));
+}
+
+#[test]
+fn zip64_archive_is_clean() {
+    let files: [(&str, &[u8]); 2] = [("a.txt", b"first entry"), ("b.txt", b"and the second")];
+    for descriptors in [false, true] {
+        let bytes = zip64_archive(&files, descriptors);
+        let mut cursor = Cursor::new(&bytes[..]);
+        let zi = SearchableArchive::from_reader(&mut cursor).unwrap();
+
+        let report = zi.audit_layout().unwrap();
+        assert!(
+            report.is_clean(),
+            "with descriptors: {descriptors}: {report:?}"
+        );
+        let kinds: Vec<&RegionKind> = report.regions.iter().map(|r| &r.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                &RegionKind::Entry(0),
+                &RegionKind::Entry(1),
+                &RegionKind::CentralDirectory,
+                &RegionKind::Zip64EndRecord,
+                &RegionKind::EndRecord,
+            ]
+        );
+    }
+}
@@ -0,0 +1,211 @@
+// Fixtures shared by the integration suites, each of which declares `mod common;`
+// and uses what it needs of them.
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+// The little-endian `u32` at `at`.
+pub fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+// Bytes no match can be found in, so DEFLATE writes blocks of them stored.
+pub fn noise(len: usize) -> Vec<u8> {
+    let mut state = 0x9e3779b97f4a7c15u64;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 32) as u8
+        })
+        .collect()
+}
+
+// Lines that repeat with small changes, some of them further back than a match can
+// reach, which compress best with dynamic codes.
+pub fn text(len: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut line = 0u32;
+    while out.len() < len {
+        line = line.wrapping_mul(2654435761).wrapping_add(1);
+        out.extend(format!("line {} of the log, status {}\n", line % 5000, line % 7).bytes());
+    }
+    out.truncate(len);
+    out
+}
+
+// Writes `bytes` to a file of its own, for the entry points that only open files,
+// removing it when dropped. It's named after the suite and the test, so suites
+// running at once don't share one.
+pub struct TempArchive(pub PathBuf);
+
+impl TempArchive {
+    pub fn new(test: &str, bytes: &[u8]) -> TempArchive {
+        let temp = TempArchive::empty(test);
+        std::fs::write(&temp.0, bytes).unwrap();
+        temp
+    }
+
+    // Only the path, for a test to write to.
+    pub fn empty(test: &str) -> TempArchive {
+        TempArchive(std::env::temp_dir().join(format!(
+            "munzip-{}-{}-{test}.zip",
+            env!("CARGO_CRATE_NAME"),
+            std::process::id()
+        )))
+    }
+
+    pub fn open(&self) -> std::fs::File {
+        std::fs::File::open(&self.0).unwrap()
+    }
+
+    pub fn open_rw(&self) -> std::fs::File {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.0)
+            .unwrap()
+    }
+}
+
+impl Drop for TempArchive {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+// A directory of its own for each test, removed once it's done.
+pub struct Scratch(pub PathBuf);
+
+impl Scratch {
+    pub fn new(test: &str) -> Scratch {
+        let dir = std::env::temp_dir().join(format!(
+            "munzip-{}-{}-{test}",
+            env!("CARGO_CRATE_NAME"),
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Scratch(dir)
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// Stored entries written as ZIP64 throughout, as Python's `zipfile` writes them when
+// told to: sizes of all ones with a ZIP64 field holding the real ones, in both
+// headers, then a ZIP64 end record and locator before an end record of placeholders.
+// With `descriptors`, the local ZIP64 fields hold zeros and each entry's data is
+// followed by a signed descriptor with 8-byte sizes.
+pub fn zip64_archive(files: &[(&str, &[u8])], descriptors: bool) -> Vec<u8> {
+    fn zip64_field(uncompressed: u64, compressed: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(1u16.to_le_bytes());
+        out.extend(16u16.to_le_bytes());
+        out.extend(uncompressed.to_le_bytes());
+        out.extend(compressed.to_le_bytes());
+        out
+    }
+
+    let flags: u16 = match descriptors {
+        true => 1 << 3,
+        false => 0,
+    };
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in files {
+        let crc = munzip::Crc32::checksum(data);
+        let size = data.len() as u64;
+        let offset = out.len() as u32;
+
+        let local_size = if descriptors { 0 } else { size };
+        let local_extra = zip64_field(local_size, local_size);
+        out.extend(0x04034b50u32.to_le_bytes());
+        out.extend(45u16.to_le_bytes());
+        out.extend(flags.to_le_bytes());
+        out.extend(0u16.to_le_bytes()); // method
+        out.extend(0u16.to_le_bytes()); // time
+        out.extend(0x21u16.to_le_bytes()); // date, 1980-01-01
+        out.extend(if descriptors { 0 } else { crc }.to_le_bytes());
+        out.extend(u32::MAX.to_le_bytes());
+        out.extend(u32::MAX.to_le_bytes());
+        out.extend((name.len() as u16).to_le_bytes());
+        out.extend((local_extra.len() as u16).to_le_bytes());
+        out.extend(name.as_bytes());
+        out.extend(&local_extra);
+        out.extend(*data);
+        if descriptors {
+            out.extend(0x08074b50u32.to_le_bytes());
+            out.extend(crc.to_le_bytes());
+            out.extend(size.to_le_bytes());
+            out.extend(size.to_le_bytes());
+        }
+
+        let central_extra = zip64_field(size, size);
+        central.extend(0x02014b50u32.to_le_bytes());
+        central.extend(45u16.to_le_bytes()); // made by
+        central.extend(45u16.to_le_bytes()); // needed
+        central.extend(flags.to_le_bytes());
+        central.extend(0u16.to_le_bytes()); // method
+        central.extend(0u16.to_le_bytes()); // time
+        central.extend(0x21u16.to_le_bytes()); // date
+        central.extend(crc.to_le_bytes());
+        central.extend(u32::MAX.to_le_bytes());
+        central.extend(u32::MAX.to_le_bytes());
+        central.extend((name.len() as u16).to_le_bytes());
+        central.extend((central_extra.len() as u16).to_le_bytes());
+        central.extend(0u16.to_le_bytes()); // comment
+        central.extend(0u16.to_le_bytes()); // disk
+        central.extend(0u16.to_le_bytes()); // internal attributes
+        central.extend(0u32.to_le_bytes()); // external attributes
+        central.extend(offset.to_le_bytes());
+        central.extend(name.as_bytes());
+        central.extend(&central_extra);
+    }
+
+    let cd_offset = out.len() as u64;
+    out.extend(&central);
+    let record_at = out.len() as u64;
+    out.extend(0x06064b50u32.to_le_bytes());
+    out.extend(44u64.to_le_bytes());
+    out.extend(45u16.to_le_bytes());
+    out.extend(45u16.to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend((files.len() as u64).to_le_bytes());
+    out.extend((files.len() as u64).to_le_bytes());
+    out.extend((central.len() as u64).to_le_bytes());
+    out.extend(cd_offset.to_le_bytes());
+
+    out.extend(0x07064b50u32.to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend(record_at.to_le_bytes());
+    out.extend(1u32.to_le_bytes());
+
+    out.extend(0x06054b50u32.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out.extend(u16::MAX.to_le_bytes());
+    out.extend(u16::MAX.to_le_bytes());
+    out.extend(u32::MAX.to_le_bytes());
+    out.extend(u32::MAX.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out
+}
+
+// Where the central directory of a `zip64_archive()` starts, from its ZIP64 end
+// record.
+pub fn zip64_central_directory(bytes: &[u8]) -> usize {
+    let record = bytes.len() - 22 - 20 - 56;
+    u64::from_le_bytes(bytes[record + 48..record + 56].try_into().unwrap()) as usize
+}
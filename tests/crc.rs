@@ -0,0 +1,67 @@
+// CRC-32 checking of entry data, against an archive with one byte of its data
+// changed after it was written.
+
+#![cfg(feature = "writer")]
+
+use std::io::{Cursor, Read};
+
+use munzip::*;
+
+const DATA: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+// A stored entry holding `DATA`, with one byte of its data flipped.
+fn corrupt() -> Vec<u8> {
+    let options = EntryOptions::new().method(CompressionMethod::Store);
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    zw.add_file("fox.txt", DATA, &options).unwrap();
+    let mut bytes = zw.finish().unwrap().into_inner();
+
+    let at = bytes.windows(DATA.len()).position(|w| w == DATA).unwrap();
+    bytes[at] ^= 0x20;
+    bytes
+}
+
+#[test]
+fn verified_by_default() {
+    let bytes = corrupt();
+    let expected = Crc32::checksum(DATA);
+    let actual = Crc32::checksum(&[b"T".as_slice(), &DATA[1..]].concat());
+
+    let mut zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    let err = zi.next().unwrap().unwrap().buffer().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::CrcMismatch { expected, actual });
+
+    let mut zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    let mut reader = zi.next().unwrap().unwrap().reader().unwrap();
+    let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(
+        MuError::from(err).kind(),
+        ErrorKind::CrcMismatch { expected, actual }
+    );
+}
+
+#[test]
+fn temp_file_verified_and_removed() {
+    let bytes = corrupt();
+    let mut zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    let err = zi.next().unwrap().unwrap().to_temp_file().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::CrcMismatch { .. }));
+
+    let prefix = format!(".munzip-{}-", std::process::id());
+    let left = std::fs::read_dir(std::env::temp_dir())
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .any(|e| e.file_name().to_string_lossy().starts_with(&prefix));
+    assert!(!left);
+}
+
+#[test]
+fn skipped_when_trusted() {
+    let bytes = corrupt();
+    let options = ArchiveOptions::new().crc_policy(CrcPolicy::Trust);
+
+    let mut zi = IterableArchive::from_bytes_with_options(&bytes[..], &options).unwrap();
+    let data = zi.next().unwrap().unwrap().buffer().unwrap();
+    assert_eq!(&data[1..], &DATA[1..]);
+    assert_ne!(data[0], DATA[0]);
+}
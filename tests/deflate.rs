@@ -0,0 +1,107 @@
+// munzip's DEFLATE encoder, through `ZipWriter`, at every level, with the streams
+// it writes inflated by the `inflate` crate.
+
+#![cfg(all(feature = "deflate", feature = "writer"))]
+
+mod common;
+
+use std::io::{self, Cursor, Read};
+
+use munzip::*;
+
+use common::{noise, text};
+
+fn inputs() -> Vec<(&'static str, Vec<u8>)> {
+    let mut runs = vec![0; 300_000];
+    runs.extend([b'x'; 1000]);
+    vec![
+        ("empty", Vec::new()),
+        ("one byte", b"z".to_vec()),
+        ("text", text(250_000)),
+        ("incompressible", noise(200_000)),
+        ("runs", runs),
+    ]
+}
+
+// A reader that hands out at most `chunk` bytes at a time.
+struct Chunked<'d> {
+    data: &'d [u8],
+    chunk: usize,
+}
+
+impl Read for Chunked<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.chunk).min(self.data.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        Ok(len)
+    }
+}
+
+// The raw DEFLATE stream `data` is written as at `level`, read `chunk` bytes at a
+// time, and what munzip reads back of it.
+fn deflate(data: &[u8], level: u8, chunk: usize) -> (Vec<u8>, Vec<u8>) {
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = EntryOptions::new().method(CompressionMethod::Deflate(level));
+    zw.add_file("data.bin", Chunked { data, chunk }, &options)
+        .unwrap();
+    let bytes = zw.finish().unwrap().into_inner();
+
+    let mut cursor = Cursor::new(bytes.clone());
+    let zi = SearchableArchive::from_reader(&mut cursor).unwrap();
+    let mut entry = zi.entry("data.bin").unwrap();
+    assert_eq!(entry.compression_method(), 8);
+    let start = entry.data_offset() as usize;
+    let raw = bytes[start..start + entry.compressed_size() as usize].to_vec();
+    (raw, entry.buffer().unwrap())
+}
+
+#[test]
+fn every_level_round_trips() {
+    for (name, data) in inputs() {
+        for level in 1..=9 {
+            let (raw, read_back) = deflate(&data, level, 1 << 16);
+            assert_eq!(
+                inflate::inflate_bytes(&raw).unwrap(),
+                data,
+                "{name} at {level}"
+            );
+            assert_eq!(read_back, data, "{name} at {level}");
+        }
+    }
+}
+
+#[test]
+fn incompressible_input_barely_grows() {
+    let data = noise(200_000);
+    for level in 1..=9 {
+        let (raw, _) = deflate(&data, level, 1 << 16);
+        // a block is written every 16384 symbols, and stored costs five bytes more
+        assert!(
+            raw.len() <= data.len() + 5 * data.len().div_ceil(16384),
+            "at {level}"
+        );
+    }
+}
+
+#[test]
+fn compressible_input_shrinks() {
+    let data = text(250_000);
+    for level in 1..=9 {
+        let (raw, _) = deflate(&data, level, 1 << 16);
+        assert!(raw.len() < data.len() / 3, "at {level}");
+    }
+}
+
+#[test]
+fn input_in_small_pieces_round_trips() {
+    let data = text(100_000);
+    for chunk in [1, 7, 4096] {
+        let (raw, _) = deflate(&data, 6, chunk);
+        assert_eq!(
+            inflate::inflate_bytes(&raw).unwrap(),
+            data,
+            "{chunk} at a time"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+// `ZipWriter::add_dir_all()` over a small tree written at test time, read back by
+// name, contents and, on Unix, mode.
+
+#![cfg(feature = "writer")]
+
+mod common;
+
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use munzip::*;
+
+use common::Scratch;
+
+impl Scratch {
+    // Writes out `tree/` with a file, a subdirectory holding another, an empty
+    // directory and on Unix a symlink, and returns its path.
+    fn tree(&self) -> PathBuf {
+        let tree = self.path("tree");
+        std::fs::create_dir_all(tree.join("bin")).unwrap();
+        std::fs::create_dir_all(tree.join("empty")).unwrap();
+        std::fs::write(tree.join("readme.txt"), "read me").unwrap();
+        std::fs::write(tree.join("bin/run"), "#!/bin/sh").unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = |path: &str, mode| {
+                std::fs::set_permissions(tree.join(path), std::fs::Permissions::from_mode(mode))
+                    .unwrap()
+            };
+            mode("readme.txt", 0o644);
+            mode("bin/run", 0o755);
+            mode("empty", 0o750);
+            std::os::unix::fs::symlink("readme.txt", tree.join("latest")).unwrap();
+        }
+
+        tree
+    }
+}
+
+// Each entry's name, kind, Unix mode and contents.
+fn read_back(bytes: &[u8]) -> Vec<(String, EntryKind, Option<u32>, Vec<u8>)> {
+    IterableArchive::from_bytes(bytes)
+        .unwrap()
+        .map(|e| {
+            let mut e = e.unwrap();
+            let data = e.buffer().unwrap();
+            (e.filename(), e.kind(), e.unix_mode(), data)
+        })
+        .collect()
+}
+
+fn add_dir_all(tree: &std::path::Path, prefix: &str) -> (usize, Vec<u8>) {
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    let added = zw.add_dir_all(tree, prefix, &EntryOptions::new()).unwrap();
+    (added, zw.finish().unwrap().into_inner())
+}
+
+#[test]
+fn tree_read_back_in_order() {
+    let scratch = Scratch::new("order");
+    let tree = scratch.tree();
+    let (added, bytes) = add_dir_all(&tree, "site/");
+    let entries = read_back(&bytes);
+    assert_eq!(added, entries.len());
+
+    // each directory's contents, in name order, before its subdirectories'
+    let names: Vec<&str> = entries.iter().map(|e| e.0.as_str()).collect();
+    let mut expected = vec!["site/", "site/bin/", "site/empty/"];
+    #[cfg(unix)]
+    expected.push("site/latest");
+    expected.extend(["site/readme.txt", "site/bin/run"]);
+    assert_eq!(names, expected);
+
+    let find = |name: &str| entries.iter().find(|e| e.0 == name).unwrap();
+    assert_eq!(find("site/readme.txt").3, b"read me");
+    assert_eq!(find("site/bin/run").3, b"#!/bin/sh");
+    assert_eq!(find("site/empty/").1, EntryKind::Directory);
+    assert!(find("site/empty/").3.is_empty());
+
+    let mut file = Cursor::new(bytes);
+    let zi = SearchableArchive::from_reader(&mut file).unwrap();
+    let on_disk = std::fs::metadata(tree.join("readme.txt"))
+        .unwrap()
+        .modified()
+        .unwrap();
+    let recorded = zi.entry("site/readme.txt").unwrap().modified().unwrap();
+    let apart = on_disk
+        .duration_since(recorded)
+        .or_else(|_| recorded.duration_since(on_disk))
+        .unwrap();
+    // DOS times only go to two seconds
+    assert!(apart <= Duration::from_secs(2));
+}
+
+#[cfg(unix)]
+#[test]
+fn modes_and_symlinks_kept() {
+    let scratch = Scratch::new("modes");
+    let (_, bytes) = add_dir_all(&scratch.tree(), "");
+    let entries = read_back(&bytes);
+    let find = |name: &str| entries.iter().find(|e| e.0 == name).unwrap();
+
+    assert_eq!(find("readme.txt").2, Some(0o100644));
+    assert_eq!(find("bin/run").2, Some(0o100755));
+    assert_eq!(find("empty/").2, Some(0o40750));
+
+    let latest = find("latest");
+    assert_eq!(latest.1, EntryKind::Symlink);
+    assert_eq!(latest.2.map(|mode| mode & 0o170000), Some(0o120000));
+    assert_eq!(latest.3, b"readme.txt");
+}
+
+#[test]
+fn no_prefix_has_no_root_entry() {
+    let scratch = Scratch::new("unprefixed");
+    let (_, bytes) = add_dir_all(&scratch.tree(), "");
+    let names: Vec<String> = read_back(&bytes).into_iter().map(|e| e.0).collect();
+    assert_eq!(names[0], "bin/");
+    assert!(names.iter().all(|name| !name.starts_with('/')));
+}
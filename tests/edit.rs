@@ -0,0 +1,117 @@
+// `ArchiveEditor` against extra fields that don't hold what their headers say, or
+// have no room for an extended timestamp, and the exact second it writes being read
+// back.
+
+#![cfg(feature = "writer")]
+
+mod common;
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use munzip::*;
+
+use common::TempArchive;
+
+// An extended timestamp field claiming an mtime, cut off after its flags byte.
+const TRUNCATED_UT: &[u8] = &[0x55, 0x54, 9, 0, 1, 0, 0];
+
+// An archive of one stored entry, with `extra` as both its local and central extra
+// fields.
+fn with_extra(extra: &[u8]) -> Vec<u8> {
+    let data = b"hello";
+    let fields = |out: &mut Vec<u8>| {
+        out.extend(20u16.to_le_bytes()); // version needed
+        out.extend(0u16.to_le_bytes()); // flags
+        out.extend(0u16.to_le_bytes()); // method
+        out.extend(0u16.to_le_bytes()); // time
+        out.extend(0x21u16.to_le_bytes()); // date
+        out.extend(Crc32::checksum(data).to_le_bytes());
+        out.extend((data.len() as u32).to_le_bytes());
+        out.extend((data.len() as u32).to_le_bytes());
+        out.extend(5u16.to_le_bytes()); // name length
+        out.extend((extra.len() as u16).to_le_bytes());
+    };
+
+    let mut out = Vec::new();
+    out.extend(0x04034b50u32.to_le_bytes());
+    fields(&mut out);
+    out.extend(b"a.txt");
+    out.extend(extra);
+    out.extend(data);
+
+    let cd_offset = out.len() as u32;
+    out.extend(0x02014b50u32.to_le_bytes());
+    out.extend(20u16.to_le_bytes()); // made by
+    fields(&mut out);
+    out.extend([0; 10]); // comment length, disk, attributes
+    out.extend(0u32.to_le_bytes()); // local header offset
+    out.extend(b"a.txt");
+    out.extend(extra);
+    let cd_size = out.len() as u32 - cd_offset;
+
+    out.extend(0x06054b50u32.to_le_bytes());
+    out.extend([0; 4]);
+    out.extend(1u16.to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    out.extend(cd_size.to_le_bytes());
+    out.extend(cd_offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out
+}
+
+#[test]
+fn truncated_extended_timestamp() {
+    let temp = TempArchive::new("truncated-ut", &with_extra(TRUNCATED_UT));
+    let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+    let mut file = temp.open_rw();
+    let mut editor = ArchiveEditor::new(&mut file).unwrap();
+    editor.patch_local_headers(true);
+    assert!(editor
+        .patch("a.txt", &MetadataPatch::new().modified(time))
+        .unwrap());
+    editor.commit().unwrap();
+
+    // the broken field is left alone, and a whole one added after it
+    let mut file = temp.open_rw();
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    assert_eq!(zi.entry("a.txt").unwrap().buffer().unwrap(), b"hello");
+}
+
+#[test]
+fn exact_second_read_back() {
+    let temp = TempArchive::new("exact-second", &with_extra(&[]));
+    // odd, so the DOS time can't hold it
+    let time = UNIX_EPOCH + Duration::from_secs(1_700_000_001);
+
+    for patch_local in [false, true] {
+        let mut file = temp.open_rw();
+        let mut editor = ArchiveEditor::new(&mut file).unwrap();
+        editor.patch_local_headers(patch_local);
+        editor
+            .patch("a.txt", &MetadataPatch::new().modified(time))
+            .unwrap();
+        editor.commit().unwrap();
+
+        let mut file = temp.open_rw();
+        let zi = SearchableArchive::new(&mut file).unwrap();
+        assert_eq!(zi.entry("a.txt").unwrap().modified(), Some(time));
+    }
+}
+
+#[test]
+fn full_central_extra_field() {
+    // an unknown field taking up all but 5 bytes of the room there is
+    let mut extra = vec![0xAA, 0xAA];
+    extra.extend((u16::MAX - 9).to_le_bytes());
+    extra.resize(u16::MAX as usize - 5, 0);
+    let temp = TempArchive::new("full-extra", &with_extra(&extra));
+    let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+    let mut file = temp.open_rw();
+    let mut editor = ArchiveEditor::new(&mut file).unwrap();
+    let err = editor
+        .patch("a.txt", &MetadataPatch::new().modified(time))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Other);
+}
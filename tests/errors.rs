@@ -0,0 +1,71 @@
+// `ErrorMode` against an archive of three entries, the middle one of which has had
+// its local header signature wiped.
+
+#![cfg(feature = "writer")]
+
+use std::io::Cursor;
+
+use munzip::*;
+
+fn archive() -> Vec<u8> {
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data) in [("a.txt", "first"), ("b.txt", "second"), ("c.txt", "third")] {
+        zw.add_file(name, data.as_bytes(), &EntryOptions::new())
+            .unwrap();
+    }
+    let mut bytes = zw.finish().unwrap().into_inner();
+
+    // the first "b.txt" is the name in its local header, which starts 30 bytes before
+    let name = bytes.windows(5).position(|w| w == b"b.txt").unwrap();
+    bytes[name - 30..name - 26].fill(0);
+    bytes
+}
+
+// Each item iteration yields, as the entry's name or the name of the entry an error
+// is about.
+fn iterate(mode: ErrorMode) -> Vec<Result<String, String>> {
+    let bytes = archive();
+    let options = ArchiveOptions::new().error_mode(mode);
+    IterableArchive::from_bytes_with_options(&bytes[..], &options)
+        .unwrap()
+        .map(|item| match item {
+            Ok(entry) => Ok(entry.filename()),
+            Err(e) => Err(e.entry().unwrap().filename().to_string()),
+        })
+        .collect()
+}
+
+#[test]
+fn stop_ends_at_the_bad_entry() {
+    assert_eq!(
+        iterate(ErrorMode::Stop),
+        [Ok("a.txt".to_string()), Err("b.txt".to_string())]
+    );
+
+    let mut cursor = Cursor::new(archive());
+    let options = ArchiveOptions::new().error_mode(ErrorMode::Stop);
+    let err = SearchableArchive::from_reader_with_options(&mut cursor, &options)
+        .err()
+        .unwrap();
+    assert_eq!(err.entry().unwrap().filename(), "b.txt");
+}
+
+#[test]
+fn continue_carries_on_past_the_bad_entry() {
+    assert_eq!(
+        iterate(ErrorMode::Continue),
+        [
+            Ok("a.txt".to_string()),
+            Err("b.txt".to_string()),
+            Ok("c.txt".to_string())
+        ]
+    );
+
+    let mut cursor = Cursor::new(archive());
+    let options = ArchiveOptions::new().error_mode(ErrorMode::Continue);
+    let zi = SearchableArchive::from_reader_with_options(&mut cursor, &options).unwrap();
+    let names: Vec<String> = zi.entries().map(|e| e.filename()).collect();
+    assert_eq!(names, ["a.txt", "c.txt"]);
+    assert_eq!(zi.entry("c.txt").unwrap().buffer().unwrap(), b"third");
+    assert!(zi.entry("b.txt").is_none());
+}
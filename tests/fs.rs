@@ -0,0 +1,78 @@
+// `ArchiveFs` over an archive whose directories are partly listed and partly only
+// implied by the names inside them, opened with and without a prefix index.
+
+#![cfg(feature = "writer")]
+
+mod common;
+
+use std::io::{Cursor, ErrorKind};
+
+use munzip::*;
+
+use common::TempArchive;
+
+fn archive() -> Vec<u8> {
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = EntryOptions::new();
+    zw.add_directory("docs/", &options.clone().unix_mode(0o750))
+        .unwrap();
+    zw.add_file("docs/guide.md", &b"# guide"[..], &options)
+        .unwrap();
+    zw.add_file("src/lib/mod.rs", &b"pub mod a;"[..], &options)
+        .unwrap();
+    zw.add_file("README", &b"read me"[..], &options).unwrap();
+    zw.finish().unwrap().into_inner()
+}
+
+// Each path under `dir`, depth first, with whether it's a directory and its length.
+fn walk(fs: &ArchiveFs, dir: &str, out: &mut Vec<(String, bool, u64)>) {
+    for entry in fs.read_dir(dir).unwrap() {
+        let entry = entry.unwrap();
+        let path = entry.path().to_str().unwrap().to_string();
+        let metadata = entry.metadata().unwrap();
+        out.push((path.clone(), metadata.is_dir(), metadata.len()));
+        if metadata.is_dir() {
+            walk(fs, &path, out);
+        }
+    }
+}
+
+#[test]
+fn same_tree_with_or_without_a_prefix_index() {
+    let temp = TempArchive::new("tree", &archive());
+    let expected = [
+        ("README", false, 7),
+        ("docs", true, 0),
+        ("docs/guide.md", false, 7),
+        ("src", true, 0),
+        ("src/lib", true, 0),
+        ("src/lib/mod.rs", false, 10),
+    ];
+
+    for prefix_index in [false, true] {
+        let mut file = temp.open();
+        let options = ArchiveOptions::new().prefix_index(prefix_index);
+        let zi = SearchableArchive::with_options(&mut file, &options).unwrap();
+        let fs = ArchiveFs::new(&zi);
+
+        let mut tree = Vec::new();
+        walk(&fs, "/", &mut tree);
+        let tree: Vec<(&str, bool, u64)> = tree
+            .iter()
+            .map(|(path, is_dir, len)| (path.as_str(), *is_dir, *len))
+            .collect();
+        assert_eq!(tree, expected);
+
+        assert_eq!(
+            fs.metadata("docs").unwrap().permissions().mode(),
+            Some(0o40750)
+        );
+        assert_eq!(fs.read_to_string("/src/lib/mod.rs").unwrap(), "pub mod a;");
+        assert_eq!(fs.read("src").unwrap_err().kind(), ErrorKind::IsADirectory);
+        assert_eq!(
+            fs.read_dir("README").err().unwrap().kind(),
+            ErrorKind::NotADirectory
+        );
+        assert!(!fs.exists("src/lib/missing.rs"));
+    }
+}
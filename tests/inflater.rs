@@ -0,0 +1,132 @@
+// munzip's own DEFLATE decoder, behind `RawInflater` and `Entry::inflate_index()`,
+// against streams from its encoder, checked by the `inflate` crate as well.
+
+#![cfg(all(feature = "deflate", feature = "writer"))]
+
+mod common;
+
+use std::io::{Cursor, Read};
+
+use munzip::*;
+
+use common::{noise, text};
+
+fn zip(data: &[u8]) -> Vec<u8> {
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = EntryOptions::new().method(CompressionMethod::Deflate(6));
+    zw.add_file("data.bin", data, &options).unwrap();
+    zw.finish().unwrap().into_inner()
+}
+
+// The raw DEFLATE stream of the only entry of `bytes`.
+fn raw(bytes: &[u8]) -> Vec<u8> {
+    let mut cursor = Cursor::new(bytes.to_vec());
+    let zi = SearchableArchive::from_reader(&mut cursor).unwrap();
+    let entry = zi.entry("data.bin").unwrap();
+    let start = entry.data_offset() as usize;
+    bytes[start..start + entry.compressed_size() as usize].to_vec()
+}
+
+// The type of a stream's first block: 0 stored, 1 fixed codes, 2 dynamic codes.
+fn first_block_type(raw: &[u8]) -> u8 {
+    (raw[0] >> 1) & 3
+}
+
+fn round_trip(data: &[u8], block_type: u8) {
+    let raw = raw(&zip(data));
+    assert_eq!(first_block_type(&raw), block_type);
+
+    let mut out = Vec::new();
+    RawInflater::new(&raw[..]).read_to_end(&mut out).unwrap();
+    assert_eq!(out, data);
+    assert_eq!(inflate::inflate_bytes(&raw).unwrap(), data);
+}
+
+#[test]
+fn stored_blocks_round_trip() {
+    round_trip(&noise(100_000), 0);
+}
+
+#[test]
+fn fixed_blocks_round_trip() {
+    round_trip(b"hello hello", 1);
+}
+
+#[test]
+fn dynamic_blocks_round_trip() {
+    round_trip(&text(200_000), 2);
+}
+
+#[test]
+fn resumes_from_checkpoints_at_block_boundaries() {
+    let mut data = text(150_000);
+    data.extend(noise(70_000));
+    data.extend(text(150_000));
+    let bytes = zip(&data);
+
+    // a read stops at the end of each block, so the lengths read add up to where
+    // the blocks end
+    let mut boundaries = Vec::new();
+    let mut inflater = RawInflater::new(Cursor::new(raw(&bytes)));
+    let mut buf = vec![0; data.len()];
+    let mut output = 0;
+    loop {
+        let n = inflater.read(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        output += n;
+        boundaries.push(output);
+    }
+    assert_eq!(output, data.len());
+    boundaries.pop();
+    assert!(boundaries.len() >= 3);
+
+    let mut cursor = Cursor::new(bytes);
+    let zi = SearchableArchive::from_reader(&mut cursor).unwrap();
+    let entry = zi.entry("data.bin").unwrap();
+    let index = entry.inflate_index(1).unwrap();
+    assert_eq!(index.len(), boundaries.len());
+
+    let mut saved = Vec::new();
+    index.write_to(&mut saved).unwrap();
+    let loaded = InflateIndex::read_from(&saved[..]).unwrap();
+
+    for index in [&index, &loaded] {
+        for &boundary in &boundaries {
+            for start in [boundary - 1, boundary, boundary + 1] {
+                let end = (start + 40_000).min(data.len());
+                let mut out = Vec::new();
+                entry
+                    .indexed_range_reader(index, start as u64..end as u64)
+                    .unwrap()
+                    .read_to_end(&mut out)
+                    .unwrap();
+                assert_eq!(out, data[start..end], "from {start}");
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    let data = text(300_000);
+    let mut cursor = Cursor::new(zip(&data));
+    let zi = SearchableArchive::from_reader(&mut cursor).unwrap();
+    let entry = zi.entry("data.bin").unwrap();
+    let index = entry.inflate_index(64 << 10).unwrap();
+    assert!(!index.is_empty());
+
+    let json = serde_json::to_string(&index).unwrap();
+    let loaded: InflateIndex = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.len(), index.len());
+
+    let mut out = Vec::new();
+    entry
+        .indexed_range_reader(&loaded, 250_000..260_000)
+        .unwrap()
+        .read_to_end(&mut out)
+        .unwrap();
+    assert_eq!(out, data[250_000..260_000]);
+}
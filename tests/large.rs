@@ -0,0 +1,148 @@
+// Archives past 4 GiB without ZIP64, whose entries all start below 4 GiB but whose
+// central directory doesn't, so the end record can only hold its offset modulo
+// 4 GiB. Each is a sparse file, with a gap made by `set_len()`, so next to nothing
+// is written to disk.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use munzip::*;
+
+const FOUR_GIB: u64 = 1 << 32;
+
+// The fields local headers and central directory records share.
+fn fields(out: &mut Vec<u8>, name: &[u8], data: &[u8]) {
+    out.extend(10u16.to_le_bytes()); // version needed
+    out.extend(0u16.to_le_bytes()); // flags
+    out.extend(0u16.to_le_bytes()); // method
+    out.extend(0u16.to_le_bytes()); // time
+    out.extend(0x21u16.to_le_bytes()); // date
+    out.extend(Crc32::checksum(data).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out.extend((name.len() as u16).to_le_bytes());
+    out.extend(0u16.to_le_bytes()); // extra field
+}
+
+fn local(name: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut out = 0x04034b50u32.to_le_bytes().to_vec();
+    fields(&mut out, name, data);
+    out.extend(name);
+    out.extend(data);
+    out
+}
+
+fn central(name: &[u8], data: &[u8], offset: u32) -> Vec<u8> {
+    let mut out = 0x02014b50u32.to_le_bytes().to_vec();
+    out.extend(10u16.to_le_bytes()); // made by
+    fields(&mut out, name, data);
+    out.extend([0; 10]); // comment length, disk, attributes
+    out.extend(offset.to_le_bytes());
+    out.extend(name);
+    out
+}
+
+fn end_record(entries: u16, cd_size: u32, cd_offset: u32) -> Vec<u8> {
+    let mut out = 0x06054b50u32.to_le_bytes().to_vec();
+    out.extend([0; 4]);
+    out.extend(entries.to_le_bytes());
+    out.extend(entries.to_le_bytes());
+    out.extend(cd_size.to_le_bytes());
+    out.extend(cd_offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out
+}
+
+struct Sparse(PathBuf);
+
+impl Sparse {
+    fn new(test: &str) -> Sparse {
+        Sparse(std::env::temp_dir().join(format!("munzip-large-{}-{test}.zip", std::process::id())))
+    }
+
+    // Writes `entries` at their offsets, then the central directory for them from
+    // `cd_at`, recording its offset as `recorded`, or as it would wrap if `None`.
+    fn write(&self, entries: &[(&[u8], &[u8], u32)], cd_at: u64, recorded: Option<u32>) {
+        let mut file = File::create(&self.0).unwrap();
+        for (name, data, offset) in entries {
+            file.seek(SeekFrom::Start(*offset as u64)).unwrap();
+            file.write_all(&local(name, data)).unwrap();
+        }
+        file.set_len(cd_at).unwrap();
+
+        let mut cd = Vec::new();
+        for (name, data, offset) in entries {
+            cd.extend(central(name, data, *offset));
+        }
+        let recorded = recorded.unwrap_or(cd_at as u32);
+        file.seek(SeekFrom::Start(cd_at)).unwrap();
+        file.write_all(&cd).unwrap();
+        file.write_all(&end_record(entries.len() as u16, cd.len() as u32, recorded))
+            .unwrap();
+    }
+
+    fn open(&self) -> File {
+        OpenOptions::new().read(true).open(&self.0).unwrap()
+    }
+}
+
+impl Drop for Sparse {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn central_directory_past_4gib() {
+    let sparse = Sparse::new("past");
+    let cd_at = FOUR_GIB + 100;
+    sparse.write(&[(b"a.txt", b"hello", 0)], cd_at, None);
+
+    let mut file = sparse.open();
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    assert_eq!(zi.entry("a.txt").unwrap().buffer().unwrap(), b"hello");
+
+    let peek = peek(&sparse.0).unwrap();
+    assert_eq!(peek.central_directory_offset, cd_at);
+    assert!(!peek.zip64);
+    assert!(peek.file_size > FOUR_GIB);
+}
+
+#[test]
+fn entry_straddling_4gib() {
+    let sparse = Sparse::new("straddling");
+    let data = [0x5a; 64];
+    let offset = (FOUR_GIB - 40) as u32;
+    sparse.write(
+        &[(b"a.txt", b"first", 0), (b"b.bin", &data, offset)],
+        FOUR_GIB + 4096,
+        None,
+    );
+
+    let mut file = sparse.open();
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    assert_eq!(zi.entry("a.txt").unwrap().buffer().unwrap(), b"first");
+    let mut b = zi.entry("b.bin").unwrap();
+    assert_eq!(b.local_header_offset(), offset as u64);
+    assert!(b.data_offset() < FOUR_GIB && b.data_offset() + 64 > FOUR_GIB);
+    assert_eq!(b.buffer().unwrap(), data);
+
+    let mut file = sparse.open();
+    let names: Vec<String> = IterableArchive::new(&mut file)
+        .unwrap()
+        .map(|e| e.unwrap().filename())
+        .collect();
+    assert_eq!(names, ["a.txt", "b.bin"]);
+}
+
+#[test]
+fn wrapped_offset_has_to_agree() {
+    let sparse = Sparse::new("disagrees");
+    let cd_at = FOUR_GIB + 100;
+    sparse.write(&[(b"a.txt", b"hello", 0)], cd_at, Some(99));
+
+    let mut file = sparse.open();
+    let err = SearchableArchive::new(&mut file).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Format);
+}
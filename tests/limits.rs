@@ -0,0 +1,306 @@
+// Each limit munzip puts on an archive, tripped by a small bomb made at test time.
+// An overlapping bomb, whose entries share their data, is stopped by
+// `max_entries()` or `max_total_size()`, or found beforehand by `audit_layout()`.
+
+#![cfg(all(feature = "writer", feature = "deflate"))]
+
+mod common;
+
+use std::io::{Cursor, Write};
+use std::path::Path;
+
+use munzip::*;
+
+use common::{u32_at, Scratch};
+
+impl Scratch {
+    // Writes `bytes` out as an archive, and opens it.
+    fn archive(&self, bytes: &[u8]) -> std::fs::File {
+        let path = self.path("bomb.zip");
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(bytes)
+            .unwrap();
+        std::fs::File::open(path).unwrap()
+    }
+}
+
+fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let options = EntryOptions::new().method(CompressionMethod::Deflate(9));
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data) in files {
+        zw.add_file(name, *data, &options).unwrap();
+    }
+    zw.finish().unwrap().into_inner()
+}
+
+// Where the central directory starts, from the end record of an archive without a
+// comment.
+fn central_directory(bytes: &[u8]) -> usize {
+    u32_at(bytes, bytes.len() - 22 + 16) as usize
+}
+
+// Records `size` as the uncompressed size of an archive's only entry, in both its
+// local header and its central directory record.
+fn claim_size(bytes: &mut [u8], size: u32) {
+    let cd = central_directory(bytes);
+    bytes[22..26].copy_from_slice(&size.to_le_bytes());
+    bytes[cd + 24..cd + 28].copy_from_slice(&size.to_le_bytes());
+}
+
+// Repeats the central directory record of an archive's only entry `copies` times, so
+// every entry is the same data.
+fn overlap(bytes: &[u8], copies: u16) -> Vec<u8> {
+    let cd = central_directory(bytes);
+    let end = bytes.len() - 22;
+    let record = &bytes[cd..end];
+
+    let mut out = bytes[..cd].to_vec();
+    for _ in 0..copies {
+        out.extend_from_slice(record);
+    }
+    let mut end_record = bytes[end..].to_vec();
+    end_record[8..10].copy_from_slice(&copies.to_le_bytes());
+    end_record[10..12].copy_from_slice(&copies.to_le_bytes());
+    end_record[12..16].copy_from_slice(&((record.len() * copies as usize) as u32).to_le_bytes());
+    out.extend_from_slice(&end_record);
+    out
+}
+
+fn quota(result: Result<impl std::fmt::Debug, MuError>) -> Quota {
+    match result.unwrap_err().kind() {
+        ErrorKind::QuotaExceeded(quota) => quota,
+        kind => panic!("expected a quota to be exceeded, not {kind:?}"),
+    }
+}
+
+fn is_empty(dir: &Path) -> bool {
+    !dir.exists() || std::fs::read_dir(dir).unwrap().next().is_none()
+}
+
+#[test]
+fn entry_count_is_checked_on_open() {
+    let names: Vec<String> = (0..100).map(|i| format!("{i}.txt")).collect();
+    let files: Vec<(&str, &[u8])> = names.iter().map(|n| (n.as_str(), &b"x"[..])).collect();
+    let bytes = zip(&files);
+
+    let options = ArchiveOptions::new().max_entries(99);
+    let opened = IterableArchive::from_bytes_with_options(&bytes[..], &options).map(|_| ());
+    assert_eq!(quota(opened), Quota::Entries);
+
+    let options = ArchiveOptions::new().max_entries(100);
+    let zi = IterableArchive::from_bytes_with_options(&bytes[..], &options).unwrap();
+    assert_eq!(zi.count(), 100);
+}
+
+#[test]
+fn entry_count_is_checked_before_extracting() {
+    let scratch = Scratch::new("entries");
+    let mut file = scratch.archive(&zip(&[("a", b"a"), ("b", b"b"), ("c", b"c")]));
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    let dest = scratch.path("out");
+
+    let options = ExtractOptions::new().max_entries(2);
+    assert_eq!(quota(zi.extract_all(&dest, &options)), Quota::Entries);
+    assert!(is_empty(&dest));
+}
+
+#[test]
+fn central_directory_size_is_checked_on_open() {
+    let bytes = overlap(&zip(&[("bomb", &[0; 1024])]), 1000);
+    let cd_size = u32_at(&bytes, bytes.len() - 22 + 12) as u64;
+
+    let options = ArchiveOptions::new().max_central_directory_size(cd_size - 1);
+    let opened = IterableArchive::from_bytes_with_options(&bytes[..], &options).map(|_| ());
+    assert_eq!(quota(opened), Quota::CentralDirectorySize);
+}
+
+#[test]
+fn depth_is_checked_before_extracting() {
+    let scratch = Scratch::new("depth");
+    let deep = format!("{}deep.txt", "d/".repeat(64));
+    let mut file = scratch.archive(&zip(&[("shallow.txt", b"x"), (&deep, b"x")]));
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    let dest = scratch.path("out");
+
+    let options = ExtractOptions::new().max_depth(64);
+    assert_eq!(quota(zi.extract_all(&dest, &options)), Quota::Depth);
+    assert!(is_empty(&dest));
+
+    let options = ExtractOptions::new().max_depth(65);
+    zi.extract_all(&dest, &options).unwrap();
+    assert!(dest.join(&deep).exists());
+}
+
+#[test]
+fn path_length_is_checked_before_extracting() {
+    let scratch = Scratch::new("path-length");
+    let long = format!("{}.txt", "n".repeat(200));
+    let mut file = scratch.archive(&zip(&[(&long, b"x")]));
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    let dest = scratch.path("out");
+
+    let options = ExtractOptions::new().max_path_length(203);
+    assert_eq!(quota(zi.extract_all(&dest, &options)), Quota::PathLength);
+    assert!(is_empty(&dest));
+}
+
+#[test]
+fn high_ratio_entry_is_stopped_by_its_recorded_size() {
+    let scratch = Scratch::new("ratio");
+    let zeros = vec![0; 16 * 1024 * 1024];
+    let bytes = zip(&[("zeros", &zeros)]);
+    assert!(bytes.len() * 500 < zeros.len());
+
+    let mut file = scratch.archive(&bytes);
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    let dest = scratch.path("out");
+
+    let options = ExtractOptions::new().max_file_size(1024 * 1024);
+    assert_eq!(quota(zi.extract_all(&dest, &options)), Quota::FileSize);
+    assert!(is_empty(&dest));
+}
+
+#[test]
+fn entry_lying_about_its_size_is_cut_off() {
+    let scratch = Scratch::new("lying");
+    let mut bytes = zip(&[("zeros", &vec![0; 16 * 1024 * 1024])]);
+    claim_size(&mut bytes, 1000);
+
+    let mut file = scratch.archive(&bytes);
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    assert_eq!(zi.entries().next().unwrap().uncompressed_size(), 1000);
+    let dest = scratch.path("out");
+
+    // the recorded size is under the limit, so it's only caught as it's inflated
+    let options = ExtractOptions::new().max_file_size(64 * 1024);
+    assert_eq!(quota(zi.extract_all(&dest, &options)), Quota::FileSize);
+    assert!(!dest.join("zeros").exists());
+
+    // and an atomic extraction leaves nothing at all
+    let dest = scratch.path("atomic");
+    let options = options.atomic(true);
+    assert_eq!(quota(zi.extract_all(&dest, &options)), Quota::FileSize);
+    assert!(!dest.exists());
+}
+
+#[test]
+fn total_size_is_checked_before_extracting() {
+    let scratch = Scratch::new("total");
+    let kilobyte = [b'x'; 1000];
+    let mut file = scratch.archive(&zip(&[
+        ("a", &kilobyte),
+        ("b", &kilobyte),
+        ("c", &kilobyte),
+    ]));
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    let dest = scratch.path("out");
+
+    let options = ExtractOptions::new().max_total_size(2999);
+    assert_eq!(quota(zi.extract_all(&dest, &options)), Quota::TotalSize);
+    assert!(is_empty(&dest));
+
+    let options = ExtractOptions::new().max_total_size(3000);
+    assert_eq!(zi.extract_all(&dest, &options).unwrap().created, 3);
+}
+
+#[test]
+fn total_size_of_entries_lying_about_their_size_is_cut_off() {
+    let scratch = Scratch::new("total-lying");
+    let mut bytes = zip(&[("zeros", &vec![0; 16 * 1024 * 1024])]);
+    claim_size(&mut bytes, 1000);
+
+    let mut file = scratch.archive(&bytes);
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    let dest = scratch.path("out");
+
+    let options = ExtractOptions::new().max_total_size(64 * 1024);
+    assert_eq!(quota(zi.extract_all(&dest, &options)), Quota::TotalSize);
+    assert!(!dest.join("zeros").exists());
+}
+
+#[test]
+fn compression_ratio_is_checked_before_extracting() {
+    let scratch = Scratch::new("compression-ratio");
+    let zeros = vec![0; 16 * 1024 * 1024];
+    let bytes = zip(&[("text", &b"not much to it"[..]), ("zeros", &zeros)]);
+
+    let mut file = scratch.archive(&bytes);
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    let dest = scratch.path("out");
+
+    // the whole file is well under the limit on its own
+    let options = ExtractOptions::new()
+        .max_file_size(64 * 1024 * 1024)
+        .max_compression_ratio(100);
+    assert_eq!(quota(zi.extract_all(&dest, &options)), Quota::Ratio);
+    assert!(is_empty(&dest));
+}
+
+#[test]
+fn compression_ratio_of_an_entry_lying_about_its_size_is_cut_off() {
+    let scratch = Scratch::new("ratio-lying");
+    let mut bytes = zip(&[("zeros", &vec![0; 16 * 1024 * 1024])]);
+    claim_size(&mut bytes, 1000);
+
+    let mut file = scratch.archive(&bytes);
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    let dest = scratch.path("out");
+
+    let options = ExtractOptions::new().max_compression_ratio(100);
+    assert_eq!(quota(zi.extract_all(&dest, &options)), Quota::Ratio);
+    assert!(!dest.join("zeros").exists());
+}
+
+#[test]
+fn overlapping_entries_are_found_and_counted() {
+    let scratch = Scratch::new("overlap");
+    let bytes = overlap(&zip(&[("bomb", &vec![0; 1024 * 1024])]), 1000);
+    let mut file = scratch.archive(&bytes);
+    let zi = SearchableArchive::new(&mut file).unwrap();
+
+    let findings = zi.audit_layout().unwrap().findings();
+    let overlaps = findings
+        .iter()
+        .filter(|f| matches!(f.kind, FindingKind::Overlap(..)))
+        .count();
+    assert!(overlaps >= 999);
+    assert!(findings.iter().all(|f| f.severity() == Severity::Error));
+
+    // each takes up a megabyte once extracted, a thousandfold what's stored
+    let dest = scratch.path("out");
+    let options = ExtractOptions::new().max_entries(100);
+    assert_eq!(quota(zi.extract_all(&dest, &options)), Quota::Entries);
+    assert!(is_empty(&dest));
+    let options = ExtractOptions::new().max_total_size(100 * 1024 * 1024);
+    assert_eq!(quota(zi.extract_all(&dest, &options)), Quota::TotalSize);
+    assert!(is_empty(&dest));
+}
+
+#[test]
+fn nested_archive_is_not_expanded() {
+    let scratch = Scratch::new("nested");
+    let inner = zip(&[("zeros", &vec![0; 16 * 1024 * 1024])]);
+    let outer = {
+        let options = EntryOptions::new().method(CompressionMethod::Store);
+        let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+        zw.add_file("inner.zip", &inner[..], &options).unwrap();
+        zw.finish().unwrap().into_inner()
+    };
+
+    let mut file = scratch.archive(&outer);
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    let dest = scratch.path("out");
+
+    // the inner archive is extracted as it's stored, and limited in turn once opened
+    let options = ExtractOptions::new().max_file_size(1024 * 1024);
+    zi.extract_all(&dest, &options).unwrap();
+    let extracted = std::fs::read(dest.join("inner.zip")).unwrap();
+    assert_eq!(extracted, inner);
+
+    let mut file = std::fs::File::open(dest.join("inner.zip")).unwrap();
+    let inner = SearchableArchive::new(&mut file).unwrap();
+    let dest = scratch.path("inner");
+    assert_eq!(quota(inner.extract_all(&dest, &options)), Quota::FileSize);
+    assert!(is_empty(&dest));
+}
@@ -0,0 +1,88 @@
+// `Entries::modified_after()` at the edges of a DOS time's two second precision.
+
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use munzip::*;
+
+// 2020-01-01 00:00:10 UTC
+const DOS_SECS: u64 = 1_577_836_810;
+
+// An archive of one empty stored entry dated `DOS_SECS`, as a DOS time alone.
+fn dos_dated() -> Vec<u8> {
+    let date: u16 = (2020 - 1980) << 9 | 1 << 5 | 1;
+    let time: u16 = 10 / 2;
+    let fields = |out: &mut Vec<u8>| {
+        out.extend(20u16.to_le_bytes()); // version needed
+        out.extend(0u16.to_le_bytes()); // flags
+        out.extend(0u16.to_le_bytes()); // method
+        out.extend(time.to_le_bytes());
+        out.extend(date.to_le_bytes());
+        out.extend(0u32.to_le_bytes()); // crc
+        out.extend(0u32.to_le_bytes()); // compressed size
+        out.extend(0u32.to_le_bytes()); // uncompressed size
+        out.extend(5u16.to_le_bytes()); // name length
+        out.extend(0u16.to_le_bytes()); // extra field
+    };
+
+    let mut out = Vec::new();
+    out.extend(0x04034b50u32.to_le_bytes());
+    fields(&mut out);
+    out.extend(b"a.txt");
+
+    let cd_offset = out.len() as u32;
+    out.extend(0x02014b50u32.to_le_bytes());
+    out.extend(20u16.to_le_bytes()); // made by
+    fields(&mut out);
+    out.extend([0; 10]); // comment length, disk, attributes
+    out.extend(0u32.to_le_bytes()); // local header offset
+    out.extend(b"a.txt");
+    let cd_size = out.len() as u32 - cd_offset;
+
+    out.extend(0x06054b50u32.to_le_bytes());
+    out.extend([0; 4]);
+    out.extend(1u16.to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    out.extend(cd_size.to_le_bytes());
+    out.extend(cd_offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out
+}
+
+// Whether the entry is yielded as modified after `DOS_SECS` plus `millis`.
+fn kept(test: &str, millis: i64) -> bool {
+    let path =
+        std::env::temp_dir().join(format!("munzip-modified-{}-{test}.zip", std::process::id()));
+    std::fs::File::create(&path)
+        .unwrap()
+        .write_all(&dos_dated())
+        .unwrap();
+
+    let base = UNIX_EPOCH + Duration::from_secs(DOS_SECS);
+    let time: SystemTime = match millis >= 0 {
+        true => base + Duration::from_millis(millis as u64),
+        false => base - Duration::from_millis(millis.unsigned_abs()),
+    };
+
+    let mut file = std::fs::File::open(&path).unwrap();
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    let kept = zi.entries().modified_after(time).count() == 1;
+    drop(zi);
+    let _ = std::fs::remove_file(&path);
+    kept
+}
+
+#[test]
+fn kept_within_two_seconds() {
+    assert!(kept("before", -1000));
+    assert!(kept("same", 0));
+    assert!(kept("one", 1000));
+    assert!(kept("one-and-a-half", 1500));
+    assert!(kept("nearly-two", 1999));
+}
+
+#[test]
+fn dropped_from_two_seconds() {
+    assert!(!kept("two", 2000));
+    assert!(!kept("three", 3000));
+}
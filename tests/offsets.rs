@@ -0,0 +1,87 @@
+// `OffsetCache` entries read against the archive they were made from, and against
+// one that's changed since, and caches saved and loaded.
+
+#![cfg(feature = "writer")]
+
+use std::io::Cursor;
+
+use munzip::*;
+
+fn zip(data: &[u8]) -> Vec<u8> {
+    let options = EntryOptions::new().method(CompressionMethod::Store);
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    zw.add_file("notes.txt", data, &options).unwrap();
+    zw.finish().unwrap().into_inner()
+}
+
+fn cached(bytes: &[u8]) -> CachedEntry {
+    let mut cursor = Cursor::new(bytes.to_vec());
+    let zi = SearchableArchive::from_reader(&mut cursor).unwrap();
+    *zi.offset_cache().get("notes.txt").unwrap()
+}
+
+#[test]
+fn stale_cache_is_caught() {
+    let bytes = zip(b"first draft");
+    let entry = cached(&bytes);
+    assert_eq!(
+        entry.read(&mut Cursor::new(&bytes)).unwrap(),
+        b"first draft"
+    );
+
+    // the same length, so it's only the CRC-32 that tells
+    let changed = zip(b"final draft");
+    let err = entry.read(&mut Cursor::new(&changed)).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::CrcMismatch { .. }));
+
+    let wrong_size = CachedEntry {
+        uncompressed_size: 12,
+        ..entry
+    };
+    let err = wrong_size.read(&mut Cursor::new(&bytes)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Format);
+}
+
+#[test]
+fn name_too_long_to_write() {
+    // 0xFF decodes to two or three bytes, taking the name past a u16 length
+    let long = "n".repeat(40_000);
+    let options = EntryOptions::new().method(CompressionMethod::Store);
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    zw.add_file(&long, &b"data"[..], &options).unwrap();
+    let mut bytes = zw.finish().unwrap().into_inner();
+    let mut at = 0;
+    while let Some(found) = bytes[at..]
+        .windows(long.len())
+        .position(|w| w == long.as_bytes())
+    {
+        at += found;
+        bytes[at..at + long.len()].fill(0xFF);
+    }
+
+    let mut cursor = Cursor::new(bytes);
+    let zi = SearchableArchive::from_reader(&mut cursor).unwrap();
+    let cache = zi.offset_cache();
+    assert!(cache.iter().all(|(name, _)| name.len() > u16::MAX as usize));
+
+    let mut out = Vec::new();
+    let err = cache.write_to(&mut out).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Other);
+    assert!(out.is_empty());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    let bytes = zip(b"first draft");
+    let mut cursor = Cursor::new(bytes.clone());
+    let zi = SearchableArchive::from_reader(&mut cursor).unwrap();
+
+    let json = serde_json::to_string(&zi.offset_cache()).unwrap();
+    let cache: OffsetCache = serde_json::from_str(&json).unwrap();
+    let entry = cache.get("notes.txt").unwrap();
+    assert_eq!(
+        entry.read(&mut Cursor::new(&bytes)).unwrap(),
+        b"first draft"
+    );
+}
@@ -0,0 +1,130 @@
+// Password recovery against an archive encrypted at test time, as munzip has no
+// way to write encrypted entries itself.
+
+#![cfg(feature = "crypto")]
+
+mod common;
+
+use munzip::*;
+
+use common::TempArchive;
+
+// The three keys of traditional PKWARE encryption.
+struct Keys([u32; 3]);
+
+impl Keys {
+    fn new(password: &[u8]) -> Keys {
+        let mut keys = Keys([0x12345678, 0x23456789, 0x34567890]);
+        for &b in password {
+            keys.update(b);
+        }
+        keys
+    }
+
+    fn update(&mut self, plain: u8) {
+        self.0[0] = crc32_byte(self.0[0], plain);
+        self.0[1] = self.0[1]
+            .wrapping_add(self.0[0] & 0xFF)
+            .wrapping_mul(134775813)
+            .wrapping_add(1);
+        self.0[2] = crc32_byte(self.0[2], (self.0[1] >> 24) as u8);
+    }
+
+    fn encrypt(&mut self, plain: u8) -> u8 {
+        let temp = (self.0[2] | 2) & 0xFFFF;
+        let cipher = plain ^ ((temp * (temp ^ 1)) >> 8) as u8;
+        self.update(plain);
+        cipher
+    }
+}
+
+fn crc32_byte(crc: u32, byte: u8) -> u32 {
+    let mut crc = crc ^ byte as u32;
+    for _ in 0..8 {
+        crc = match crc & 1 {
+            1 => (crc >> 1) ^ 0xEDB88320,
+            _ => crc >> 1,
+        };
+    }
+    crc
+}
+
+// An archive of one stored entry holding `data`, encrypted with `password`.
+fn encrypted(name: &[u8], data: &[u8], password: &[u8]) -> Vec<u8> {
+    let crc = Crc32::checksum(data);
+    let mut keys = Keys::new(password);
+    let mut stored: Vec<u8> = (0..11u8)
+        .map(|i| keys.encrypt(i.wrapping_mul(37)))
+        .collect();
+    stored.push(keys.encrypt((crc >> 24) as u8));
+    stored.extend(data.iter().map(|&b| keys.encrypt(b)));
+
+    let fields = |out: &mut Vec<u8>| {
+        out.extend(20u16.to_le_bytes()); // version needed
+        out.extend(1u16.to_le_bytes()); // flags: encrypted
+        out.extend(0u16.to_le_bytes()); // method
+        out.extend(0u16.to_le_bytes()); // time
+        out.extend(0x21u16.to_le_bytes()); // date
+        out.extend(crc.to_le_bytes());
+        out.extend((stored.len() as u32).to_le_bytes());
+        out.extend((data.len() as u32).to_le_bytes());
+        out.extend((name.len() as u16).to_le_bytes());
+        out.extend(0u16.to_le_bytes()); // extra field
+    };
+
+    let mut out = Vec::new();
+    out.extend(0x04034b50u32.to_le_bytes());
+    fields(&mut out);
+    out.extend(name);
+    out.extend(&stored);
+
+    let cd_offset = out.len() as u32;
+    out.extend(0x02014b50u32.to_le_bytes());
+    out.extend(20u16.to_le_bytes()); // made by
+    fields(&mut out);
+    out.extend([0; 10]); // comment length, disk, attributes
+    out.extend(0u32.to_le_bytes()); // local header offset
+    out.extend(name);
+    let cd_size = out.len() as u32 - cd_offset;
+
+    out.extend(0x06054b50u32.to_le_bytes());
+    out.extend([0; 4]);
+    out.extend(1u16.to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    out.extend(cd_size.to_le_bytes());
+    out.extend(cd_offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out
+}
+
+#[test]
+fn wrong_passwords_passing_the_check_byte_are_not_credited() {
+    let data = b"the combination is 12345".repeat(20);
+    let temp = TempArchive::new("dictionary", &encrypted(b"safe.txt", &data, b"swordfish"));
+    let mut file = temp.open();
+    let zi = SearchableArchive::new(&mut file).unwrap();
+
+    // enough wrong passwords that some are all but sure to pass the check byte
+    let mut words: Vec<String> = (0..5000).map(|i| format!("word{i}")).collect();
+    words.push("swordfish".to_string());
+    let search = zi.try_passwords(words).unwrap();
+
+    assert_eq!(search.found.len(), 1);
+    assert_eq!(search.found[0].password.as_bytes(), b"swordfish");
+    assert_eq!(search.found[0].entries, ["safe.txt"]);
+    assert!(search.locked.is_empty());
+    assert_eq!(search.tried, 5001);
+}
+
+#[test]
+fn no_candidate_unlocks() {
+    let temp = TempArchive::new("none", &encrypted(b"safe.txt", b"secret", b"swordfish"));
+    let mut file = temp.open();
+    let zi = SearchableArchive::new(&mut file).unwrap();
+
+    let search = zi
+        .try_passwords((0..1000).map(|i| format!("word{i}")))
+        .unwrap();
+    assert!(search.found.is_empty());
+    assert_eq!(search.locked, ["safe.txt"]);
+}
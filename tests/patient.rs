@@ -0,0 +1,152 @@
+// `PatientReader` over a reader that dribbles out a few bytes at a time and fails
+// with `WouldBlock` in between, as a slow network mount can.
+
+#![cfg(feature = "writer")]
+
+use std::cell::RefCell;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use munzip::*;
+
+const FILES: &[(&str, &[u8])] = &[
+    ("a.txt", b"the quick brown fox"),
+    ("b/c.txt", b"jumps over the lazy dog"),
+];
+
+fn archive() -> Vec<u8> {
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data) in FILES {
+        zw.add_file(name, *data, &EntryOptions::new()).unwrap();
+    }
+    zw.finish().unwrap().into_inner()
+}
+
+// A reader that fails every other read and seek with `WouldBlock`, returns at most
+// three bytes from any read that succeeds, and never reads past `stall_at`.
+struct Flaky {
+    data: Cursor<Vec<u8>>,
+    reads: u64,
+    seeks: u64,
+    stall_at: u64,
+}
+
+impl Flaky {
+    fn new(data: Vec<u8>) -> Flaky {
+        Flaky {
+            data: Cursor::new(data),
+            reads: 0,
+            seeks: 0,
+            stall_at: u64::MAX,
+        }
+    }
+
+    fn stalling_at(data: Vec<u8>, stall_at: u64) -> Flaky {
+        Flaky {
+            stall_at,
+            ..Flaky::new(data)
+        }
+    }
+}
+
+impl Read for Flaky {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reads += 1;
+        let left = self.stall_at.saturating_sub(self.data.position());
+        if self.reads % 2 == 1 || left == 0 {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let len = buf.len().min(3).min(left as usize);
+        self.data.read(&mut buf[..len])
+    }
+}
+
+impl Seek for Flaky {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.seeks += 1;
+        match self.seeks % 2 {
+            0 => Err(io::ErrorKind::WouldBlock.into()),
+            _ => self.data.seek(pos),
+        }
+    }
+}
+
+#[test]
+fn short_reads_and_would_block_are_retried() {
+    let progress = Rc::new(RefCell::new(Vec::new()));
+    let seen = Rc::clone(&progress);
+    // a single retry is enough, as every read that gets data starts them over
+    let mut reader = PatientReader::new(Flaky::new(archive()))
+        .retries(1)
+        .retry_delay(Duration::ZERO)
+        .on_progress(move |read| seen.borrow_mut().push(read));
+
+    {
+        let zi = SearchableArchive::from_reader(&mut reader).unwrap();
+        assert_eq!(zi.len(), FILES.len());
+        for (mut entry, (name, data)) in zi.entries().zip(FILES) {
+            assert_eq!(entry.filename(), *name);
+            assert_eq!(entry.buffer().unwrap(), *data);
+        }
+    }
+
+    assert!(reader.retried() > 0);
+    let progress = progress.borrow();
+    assert!(progress.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(progress.last().copied(), Some(reader.bytes_read()));
+}
+
+#[test]
+fn iterable_archive_reads_through_it() {
+    let mut reader = PatientReader::new(Flaky::new(archive()))
+        .retries(1)
+        .retry_delay(Duration::ZERO);
+
+    let zi = IterableArchive::new(&mut reader).unwrap();
+    let names: Vec<String> = zi.map(|e| e.unwrap().filename()).collect();
+    assert_eq!(names, ["a.txt", "b/c.txt"]);
+}
+
+#[test]
+fn gives_up_after_its_retries() {
+    let mut reader = PatientReader::new(Flaky::stalling_at(archive(), 0))
+        .retries(3)
+        .retry_delay(Duration::ZERO);
+
+    let err = SearchableArchive::from_reader(&mut reader).err().unwrap();
+    assert!(err.to_string().contains("no progress after 3 retries"));
+    assert_eq!(reader.bytes_read(), 0);
+
+    // the first read stalls, after each blocked seek before it was retried once
+    let retried = reader.retried();
+    let flaky = reader.into_inner();
+    assert_eq!(retried, 3 + flaky.seeks / 2);
+}
+
+#[test]
+fn gives_up_at_its_timeout() {
+    let timeout = Duration::from_millis(50);
+    let mut reader = PatientReader::new(Flaky::stalling_at(archive(), 0))
+        .retries(u32::MAX)
+        .retry_delay(Duration::from_millis(1))
+        .timeout(timeout);
+
+    let started = Instant::now();
+    let err = reader.read(&mut [0; 16]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(started.elapsed() >= timeout);
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn read_exact_says_how_much_it_got() {
+    let mut reader = PatientReader::new(Flaky::stalling_at(archive(), 5))
+        .retries(2)
+        .retry_delay(Duration::ZERO);
+
+    let err = reader.read_exact(&mut [0; 10]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(err.to_string().starts_with("got 5 of 10 bytes"));
+    assert_eq!(reader.bytes_read(), 5);
+}
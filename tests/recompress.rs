@@ -0,0 +1,132 @@
+// `recompress()` against a small archive written at test time, one entry of which
+// claims a method munzip can't decompress.
+
+#![cfg(feature = "writer")]
+
+mod common;
+
+use std::io::Cursor;
+
+use munzip::*;
+
+use common::TempArchive;
+
+const FILES: &[(&str, &[u8])] = &[
+    ("a.txt", b"alpha alpha alpha alpha"),
+    ("b/c.txt", b"charlie"),
+    ("b/d.bin", &[7; 5000]),
+    ("e.txt", b""),
+];
+
+// An archive of `FILES` and the directory `b/`, plus `odd.bin` if `odd`, claiming
+// bzip2 in both its headers though it's stored.
+fn source(odd: bool) -> Vec<u8> {
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = EntryOptions::new().method(CompressionMethod::Store);
+    zw.add_directory("b/", &stored.clone().unix_mode(0o750))
+        .unwrap();
+    for (name, data) in FILES {
+        zw.add_file(name, *data, &stored.clone().unix_mode(0o640))
+            .unwrap();
+    }
+    if odd {
+        zw.add_file("odd.bin", &b"not bzip2"[..], &stored).unwrap();
+    }
+    let mut bytes = zw.finish().unwrap().into_inner();
+
+    let mut at = 0;
+    while let Some(found) = bytes[at..].windows(7).position(|w| w == b"odd.bin") {
+        let name = at + found;
+        let method = match &bytes[name - 30..name - 26] {
+            [0x50, 0x4b, 3, 4] => name - 22,
+            _ => name - 36,
+        };
+        bytes[method..method + 2].copy_from_slice(&12u16.to_le_bytes());
+        at = name + 7;
+    }
+    bytes
+}
+
+// Each entry's name, method, Unix mode and data, left empty where it can't be
+// decompressed.
+fn contents(temp: &TempArchive) -> Vec<(String, u16, Option<u32>, Vec<u8>)> {
+    let bytes = std::fs::read(&temp.0).unwrap();
+    IterableArchive::from_bytes(&bytes[..])
+        .unwrap()
+        .map(|e| {
+            let mut e = e.unwrap();
+            let data = e.buffer().unwrap_or_default();
+            (e.filename(), e.compression_method(), e.unix_mode(), data)
+        })
+        .collect()
+}
+
+#[test]
+fn every_entry_rewritten() {
+    let src = TempArchive::new("all-src", &source(false));
+    let dst = TempArchive::empty("all-dst");
+
+    let report = recompress(&src.0, &dst.0, CompressionMethod::Deflate(9)).unwrap();
+    assert_eq!(
+        report.recompressed,
+        ["b/", "a.txt", "b/c.txt", "b/d.bin", "e.txt"]
+    );
+    assert!(report.copied.is_empty());
+
+    let contents = contents(&dst);
+    assert_eq!(contents[0].0, "b/");
+    assert_eq!(contents[0].2, Some(0o40750));
+    for ((name, method, mode, data), (expected_name, expected)) in contents[1..].iter().zip(FILES) {
+        assert_eq!(name, expected_name);
+        assert_eq!(*method, 8);
+        assert_eq!(*mode, Some(0o640));
+        assert_eq!(data, expected);
+    }
+}
+
+#[test]
+fn undecodable_entry_fails_by_default() {
+    let src = TempArchive::new("fails-src", &source(true));
+    let dst = TempArchive::empty("fails-dst");
+
+    assert!(recompress(&src.0, &dst.0, CompressionMethod::Store).is_err());
+    assert!(!dst.0.exists());
+}
+
+#[test]
+fn undecodable_entry_copied_when_asked() {
+    let src = TempArchive::new("copied-src", &source(true));
+    let dst = TempArchive::empty("copied-dst");
+
+    let options = RecompressOptions::new()
+        .method(CompressionMethod::Store)
+        .copy_undecodable(true);
+    let report = recompress_with_options(&src.0, &dst.0, &options).unwrap();
+    assert_eq!(report.copied.len(), 1);
+    assert_eq!(report.copied[0].0, "odd.bin");
+
+    let odd = contents(&dst).pop().unwrap();
+    assert_eq!((odd.0.as_str(), odd.1), ("odd.bin", 12));
+    let bytes = std::fs::read(&dst.0).unwrap();
+    assert!(bytes.windows(9).any(|w| w == b"not bzip2"));
+}
+
+#[test]
+fn threads_write_the_same_archive() {
+    let src = TempArchive::new("threads-src", &source(true));
+    let one = TempArchive::empty("threads-one");
+    let three = TempArchive::empty("threads-three");
+
+    let options = RecompressOptions::new()
+        .method(CompressionMethod::Deflate(6))
+        .copy_undecodable(true);
+    let sequential = recompress_with_options(&src.0, &one.0, &options).unwrap();
+    let threaded = recompress_with_options(&src.0, &three.0, &options.threads(3)).unwrap();
+
+    assert_eq!(sequential.recompressed, threaded.recompressed);
+    assert_eq!(threaded.copied.len(), 1);
+    assert_eq!(
+        std::fs::read(&one.0).unwrap(),
+        std::fs::read(&three.0).unwrap()
+    );
+}
@@ -0,0 +1,39 @@
+// `repair()` falling back to the local headers of ZIP64 archives cut off after their
+// entries, with and without data descriptors.
+
+#![cfg(feature = "writer")]
+
+mod common;
+
+use munzip::*;
+
+use common::{zip64_archive, zip64_central_directory, Scratch, TempArchive};
+
+const FILES: [(&str, &[u8]); 2] = [("a.txt", b"first entry"), ("b.txt", b"and the second")];
+
+#[test]
+fn zip64_archive_cut_after_its_entries() {
+    for descriptors in [false, true] {
+        let bytes = zip64_archive(&FILES, descriptors);
+        let cut = &bytes[..zip64_central_directory(&bytes)];
+        let test = format!("zip64-cut-{descriptors}");
+        let damaged = TempArchive::new(&test, cut);
+        let scratch = Scratch::new(&test);
+        let repaired = scratch.path("repaired.zip");
+
+        let report = repair(&damaged.0, &repaired).unwrap();
+        assert!(report.from_local_headers);
+        assert_eq!(report.recovered, ["a.txt", "b.txt"]);
+        assert!(
+            report.corrected.is_empty(),
+            "with descriptors: {descriptors}"
+        );
+        assert!(report.skipped.is_empty());
+
+        let mut file = std::fs::File::open(&repaired).unwrap();
+        let zi = SearchableArchive::new(&mut file).unwrap();
+        for (name, data) in FILES {
+            assert_eq!(zi.entry(name).unwrap().buffer().unwrap(), data);
+        }
+    }
+}
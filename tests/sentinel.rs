@@ -0,0 +1,83 @@
+// `Compatibility::recover_placeholder_sizes()` against entries whose sizes are ZIP64
+// placeholders with no ZIP64 field, followed by data descriptors with 8-byte sizes.
+
+use std::io::Cursor;
+
+use munzip::*;
+
+const FILES: [(&str, &[u8]); 2] = [("a.txt", b"first entry"), ("b.txt", b"and the second")];
+
+// Stored entries with placeholder sizes in both headers and no extra fields, each
+// followed by a signed ZIP64 descriptor, then a classic end record.
+fn archive() -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in FILES {
+        let crc = Crc32::checksum(data);
+        let offset = out.len() as u32;
+
+        out.extend(0x04034b50u32.to_le_bytes());
+        out.extend(45u16.to_le_bytes());
+        out.extend((1u16 << 3).to_le_bytes());
+        out.extend(0u16.to_le_bytes()); // method
+        out.extend(0u16.to_le_bytes()); // time
+        out.extend(0x21u16.to_le_bytes()); // date, 1980-01-01
+        out.extend(0u32.to_le_bytes());
+        out.extend(u32::MAX.to_le_bytes());
+        out.extend(u32::MAX.to_le_bytes());
+        out.extend((name.len() as u16).to_le_bytes());
+        out.extend(0u16.to_le_bytes());
+        out.extend(name.as_bytes());
+        out.extend(data);
+        out.extend(0x08074b50u32.to_le_bytes());
+        out.extend(crc.to_le_bytes());
+        out.extend((data.len() as u64).to_le_bytes());
+        out.extend((data.len() as u64).to_le_bytes());
+
+        central.extend(0x02014b50u32.to_le_bytes());
+        central.extend(45u16.to_le_bytes()); // made by
+        central.extend(45u16.to_le_bytes()); // needed
+        central.extend((1u16 << 3).to_le_bytes());
+        central.extend(0u16.to_le_bytes()); // method
+        central.extend(0u16.to_le_bytes()); // time
+        central.extend(0x21u16.to_le_bytes()); // date
+        central.extend(crc.to_le_bytes());
+        central.extend(u32::MAX.to_le_bytes());
+        central.extend(u32::MAX.to_le_bytes());
+        central.extend((name.len() as u16).to_le_bytes());
+        central.extend(0u16.to_le_bytes()); // extra
+        central.extend(0u16.to_le_bytes()); // comment
+        central.extend(0u16.to_le_bytes()); // disk
+        central.extend(0u16.to_le_bytes()); // internal attributes
+        central.extend(0u32.to_le_bytes()); // external attributes
+        central.extend(offset.to_le_bytes());
+        central.extend(name.as_bytes());
+    }
+
+    let cd_offset = out.len() as u32;
+    out.extend(&central);
+    out.extend(0x06054b50u32.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out.extend((FILES.len() as u16).to_le_bytes());
+    out.extend((FILES.len() as u16).to_le_bytes());
+    out.extend((central.len() as u32).to_le_bytes());
+    out.extend(cd_offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out
+}
+
+#[test]
+fn sizes_recovered_from_zip64_descriptors() {
+    let bytes = archive();
+    let options =
+        ArchiveOptions::new().compatibility(Compatibility::new().recover_placeholder_sizes(true));
+    let mut cursor = Cursor::new(&bytes[..]);
+    let zi = SearchableArchive::from_reader_with_options(&mut cursor, &options).unwrap();
+
+    for (name, data) in FILES {
+        let mut entry = zi.entry(name).unwrap();
+        assert_eq!(entry.uncompressed_size(), data.len() as u64);
+        assert_eq!(entry.buffer().unwrap(), data);
+    }
+}
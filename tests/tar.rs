@@ -0,0 +1,215 @@
+// `ZipWriter::add_tar()` against a tar stream with pax and GNU long names,
+// directories, symlinks and a hard link, checking what each entry keeps, and
+// `SearchableArchive::write_tar()` round-tripped back through it.
+
+#![cfg(feature = "writer")]
+
+use std::io::Cursor;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use munzip::*;
+
+const BLOCK: usize = 512;
+
+// A ustar member: its header, then `data` padded to a whole block.
+fn member(
+    out: &mut Vec<u8>,
+    name: &str,
+    typeflag: u8,
+    mode: u32,
+    mtime: u64,
+    linkname: &str,
+    data: &[u8],
+) {
+    let mut b = [0u8; BLOCK];
+    b[..name.len()].copy_from_slice(name.as_bytes());
+    b[100..108].copy_from_slice(format!("{mode:07o}\0").as_bytes());
+    b[108..116].copy_from_slice(b"0001750\0"); // uid 1000
+    b[116..124].copy_from_slice(b"0000144\0"); // gid 100
+    b[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+    b[136..148].copy_from_slice(format!("{mtime:011o}\0").as_bytes());
+    b[156] = typeflag;
+    b[157..157 + linkname.len()].copy_from_slice(linkname.as_bytes());
+    b[257..263].copy_from_slice(b"ustar\0");
+    b[263..265].copy_from_slice(b"00");
+
+    b[148..156].fill(b' ');
+    let sum: u32 = b.iter().map(|&x| x as u32).sum();
+    b[148..156].copy_from_slice(format!("{sum:06o}\0 ").as_bytes());
+
+    out.extend(b);
+    out.extend(data);
+    out.resize(out.len().next_multiple_of(BLOCK), 0);
+}
+
+// A pax record, whose length counts itself.
+fn pax_record(key: &str, value: &str) -> String {
+    let rest = format!(" {key}={value}\n");
+    let mut len = rest.len() + 1;
+    while (len.to_string().len() + rest.len()) != len {
+        len += 1;
+    }
+    format!("{len}{rest}")
+}
+
+fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[test]
+fn add_tar_keeps_names_kinds_modes_and_times() {
+    let pax_name = format!("deep/{}/pax.txt", "p".repeat(120));
+    let gnu_name = format!("deep/{}/gnu.txt", "g".repeat(180));
+
+    let mut tar = Vec::new();
+    member(&mut tar, "./docs/", b'5', 0o750, 1_600_000_000, "", b"");
+    let readme = b"hello tar";
+    member(
+        &mut tar,
+        "docs/readme.txt",
+        b'0',
+        0o640,
+        1_600_000_100,
+        "",
+        readme,
+    );
+    member(
+        &mut tar,
+        "latest",
+        b'2',
+        0o777,
+        1_600_000_200,
+        "docs/readme.txt",
+        b"",
+    );
+
+    // the pax mtime, fractional seconds and all, wins over the header's
+    let pax = pax_record("path", &pax_name) + &pax_record("mtime", "1600000300.75");
+    member(
+        &mut tar,
+        "././@PaxHeader",
+        b'x',
+        0o644,
+        0,
+        "",
+        pax.as_bytes(),
+    );
+    member(&mut tar, "truncated", b'0', 0o600, 1, "", b"from pax");
+
+    let mut long = gnu_name.clone().into_bytes();
+    long.push(0);
+    member(&mut tar, "././@LongLink", b'L', 0o644, 0, "", &long);
+    member(
+        &mut tar,
+        &gnu_name[..100],
+        b'0',
+        0o755,
+        1_600_000_400,
+        "",
+        b"from gnu",
+    );
+
+    member(&mut tar, "hard", b'1', 0o644, 0, "docs/readme.txt", b"");
+    tar.extend([0; BLOCK * 2]);
+
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    let ingest = zw.add_tar(&tar[..], &EntryOptions::new()).unwrap();
+    assert_eq!(ingest.added, 5);
+    assert_eq!(ingest.skipped, ["hard"]);
+    let mut cursor = Cursor::new(zw.finish().unwrap().into_inner());
+    let zi = SearchableArchive::from_reader(&mut cursor).unwrap();
+
+    let expected: [(&str, EntryKind, u32, u64, &[u8]); 5] = [
+        ("docs/", EntryKind::Directory, 0o40750, 1_600_000_000, b""),
+        (
+            "docs/readme.txt",
+            EntryKind::File,
+            0o100640,
+            1_600_000_100,
+            readme,
+        ),
+        (
+            "latest",
+            EntryKind::Symlink,
+            0o120777,
+            1_600_000_200,
+            b"docs/readme.txt",
+        ),
+        (
+            &pax_name,
+            EntryKind::File,
+            0o100600,
+            1_600_000_300,
+            b"from pax",
+        ),
+        (
+            &gnu_name,
+            EntryKind::File,
+            0o100755,
+            1_600_000_400,
+            b"from gnu",
+        ),
+    ];
+    assert_eq!(zi.len(), expected.len());
+    for (name, kind, mode, mtime, data) in expected {
+        let mut entry = zi.entry(name).unwrap();
+        assert_eq!(entry.kind(), kind, "{name}");
+        assert_eq!(entry.unix_mode(), Some(mode), "{name}");
+        assert_eq!(entry.modified(), Some(at(mtime)), "{name}");
+        assert_eq!(entry.buffer().unwrap(), data, "{name}");
+    }
+}
+
+#[test]
+fn write_tar_round_trips_through_add_tar() {
+    // one long name fits the ustar prefix field, the other needs a pax header
+    let prefixed = format!("{}/prefixed.txt", "p".repeat(120));
+    let pax_name = format!("deep/{}.txt", "x".repeat(120));
+    let options = |mode: u32, secs: u64| EntryOptions::new().unix_mode(mode).modified(at(secs));
+
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    zw.add_directory("docs/", &options(0o40750, 1_600_000_000))
+        .unwrap();
+    zw.add_file(
+        "docs/readme.txt",
+        &b"hello tar"[..],
+        &options(0o100640, 1_600_000_100),
+    )
+    .unwrap();
+    zw.add_symlink("latest", "docs/readme.txt", &options(0o700, 1_600_000_200))
+        .unwrap();
+    zw.add_file(&prefixed, &b"prefix"[..], &options(0o100600, 1_600_000_300))
+        .unwrap();
+    zw.add_file(&pax_name, &b"pax"[..], &options(0o100755, 1_600_000_400))
+        .unwrap();
+    let mut cursor = Cursor::new(zw.finish().unwrap().into_inner());
+    let zi = SearchableArchive::from_reader(&mut cursor).unwrap();
+
+    let tar = zi.write_tar(Vec::new()).unwrap();
+    let headers: Vec<&[u8]> = tar
+        .chunks(BLOCK)
+        .filter(|b| &b[257..262] == b"ustar")
+        .collect();
+    assert_eq!(headers.iter().filter(|b| b[156] == b'x').count(), 1);
+    assert!(headers
+        .iter()
+        .any(|b| b[345..].starts_with(&prefixed.as_bytes()[..120])));
+
+    let mut zw = ZipWriter::new(Cursor::new(Vec::new()));
+    let ingest = zw.add_tar(&tar[..], &EntryOptions::new()).unwrap();
+    assert_eq!(ingest.added, 5);
+    assert!(ingest.skipped.is_empty());
+    let mut cursor = Cursor::new(zw.finish().unwrap().into_inner());
+    let back = SearchableArchive::from_reader(&mut cursor).unwrap();
+
+    assert_eq!(back.len(), zi.len());
+    for mut original in zi.entries() {
+        let name = original.filename();
+        let mut copy = back.entry(&name).unwrap();
+        assert_eq!(copy.kind(), original.kind(), "{name}");
+        assert_eq!(copy.unix_mode(), original.unix_mode(), "{name}");
+        assert_eq!(copy.modified(), original.modified(), "{name}");
+        assert_eq!(copy.buffer().unwrap(), original.buffer().unwrap(), "{name}");
+    }
+    assert_eq!(back.entry("latest").unwrap().unix_mode(), Some(0o120700));
+}
@@ -0,0 +1,42 @@
+// `diagnose_truncation()` against ZIP64 archives, whole and cut off after their
+// entries, with and without data descriptors.
+
+mod common;
+
+use std::io::Cursor;
+
+use munzip::*;
+
+use common::{zip64_archive, zip64_central_directory};
+
+const FILES: [(&str, &[u8]); 2] = [("a.txt", b"first entry"), ("b.txt", b"and the second")];
+
+#[test]
+fn whole_zip64_archive_is_complete() {
+    for descriptors in [false, true] {
+        let bytes = zip64_archive(&FILES, descriptors);
+        let mut cursor = Cursor::new(&bytes[..]);
+        let zi = SearchableArchive::from_reader(&mut cursor).unwrap();
+        assert_eq!(zi.entry("b.txt").unwrap().buffer().unwrap(), FILES[1].1);
+
+        let report = diagnose_truncation(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(report.declared_entries, Some(2));
+        assert_eq!(report.intact_entries, 2);
+        assert_eq!(report.central_records, 2);
+        assert!(report.is_complete(), "with descriptors: {descriptors}");
+    }
+}
+
+#[test]
+fn zip64_archive_cut_after_its_entries() {
+    for descriptors in [false, true] {
+        let bytes = zip64_archive(&FILES, descriptors);
+        let cut = zip64_central_directory(&bytes);
+
+        let report = diagnose_truncation(&mut Cursor::new(&bytes[..cut])).unwrap();
+        assert!(!report.end_record_found);
+        assert_eq!(report.intact_entries, 2, "with descriptors: {descriptors}");
+        assert_eq!(report.last_intact_entry.as_deref(), Some("b.txt"));
+        assert_eq!(report.intact_bytes, cut as u64);
+    }
+}
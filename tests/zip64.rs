@@ -0,0 +1,364 @@
+// ZIP64 archives put together byte by byte, for the placeholders and extra fields
+// no writer munzip has would produce, and for sizes no archive could really have.
+
+mod common;
+
+use munzip::*;
+
+use common::TempArchive;
+
+const ZIP64_TAG: u16 = 0x0001;
+const PLACEHOLDER: u32 = u32::MAX;
+
+// A single stored entry, with every field that ZIP64 can stand in for set by hand.
+struct Crafted {
+    name: Vec<u8>,
+    data: Vec<u8>,
+    // uncompressed then compressed, as the headers hold them
+    local_sizes: [u32; 2],
+    local_extra: Vec<u8>,
+    central_sizes: [u32; 2],
+    central_offset: u32,
+    central_extra: Vec<u8>,
+}
+
+impl Crafted {
+    // An entry with no ZIP64 about it.
+    fn new(data: &[u8]) -> Crafted {
+        let len = data.len() as u32;
+        Crafted {
+            name: b"a.txt".to_vec(),
+            data: data.to_vec(),
+            local_sizes: [len; 2],
+            local_extra: Vec::new(),
+            central_sizes: [len; 2],
+            central_offset: 0,
+            central_extra: Vec::new(),
+        }
+    }
+
+    fn local(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(0x04034b50u32.to_le_bytes());
+        out.extend(45u16.to_le_bytes());
+        out.extend(0u16.to_le_bytes()); // flags
+        out.extend(0u16.to_le_bytes()); // method
+        out.extend(0u16.to_le_bytes()); // time
+        out.extend(0x21u16.to_le_bytes()); // date, 1980-01-01
+        out.extend(Crc32::checksum(&self.data).to_le_bytes());
+        out.extend(self.local_sizes[1].to_le_bytes());
+        out.extend(self.local_sizes[0].to_le_bytes());
+        out.extend((self.name.len() as u16).to_le_bytes());
+        out.extend((self.local_extra.len() as u16).to_le_bytes());
+        out.extend(&self.name);
+        out.extend(&self.local_extra);
+        out.extend(&self.data);
+        out
+    }
+
+    fn central(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(0x02014b50u32.to_le_bytes());
+        out.extend(45u16.to_le_bytes()); // made by
+        out.extend(45u16.to_le_bytes()); // needed
+        out.extend(0u16.to_le_bytes()); // flags
+        out.extend(0u16.to_le_bytes()); // method
+        out.extend(0u16.to_le_bytes()); // time
+        out.extend(0x21u16.to_le_bytes()); // date
+        out.extend(Crc32::checksum(&self.data).to_le_bytes());
+        out.extend(self.central_sizes[1].to_le_bytes());
+        out.extend(self.central_sizes[0].to_le_bytes());
+        out.extend((self.name.len() as u16).to_le_bytes());
+        out.extend((self.central_extra.len() as u16).to_le_bytes());
+        out.extend(0u16.to_le_bytes()); // comment
+        out.extend(0u16.to_le_bytes()); // disk
+        out.extend(0u16.to_le_bytes()); // internal attributes
+        out.extend(0u32.to_le_bytes()); // external attributes
+        out.extend(self.central_offset.to_le_bytes());
+        out.extend(&self.name);
+        out.extend(&self.central_extra);
+        out
+    }
+
+    // The whole archive, with a classic end record.
+    fn archive(&self) -> Vec<u8> {
+        let mut out = self.local();
+        let cd_offset = out.len() as u32;
+        let central = self.central();
+        out.extend(&central);
+        out.extend(end_record(1, central.len() as u32, cd_offset));
+        out
+    }
+}
+
+fn end_record(entries: u16, cd_size: u32, cd_offset: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend(0x06054b50u32.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out.extend(entries.to_le_bytes());
+    out.extend(entries.to_le_bytes());
+    out.extend(cd_size.to_le_bytes());
+    out.extend(cd_offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out
+}
+
+// A ZIP64 extra field holding `values`.
+fn zip64_field(values: &[u64]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend(ZIP64_TAG.to_le_bytes());
+    out.extend((values.len() as u16 * 8).to_le_bytes());
+    for value in values {
+        out.extend(value.to_le_bytes());
+    }
+    out
+}
+
+// An entry whose ZIP64 field claims `size` for both of its sizes.
+fn claiming(size: u64) -> Vec<u8> {
+    let mut crafted = Crafted::new(b"hello");
+    crafted.local_sizes = [PLACEHOLDER; 2];
+    crafted.local_extra = zip64_field(&[size, size]);
+    crafted.central_sizes = [PLACEHOLDER; 2];
+    crafted.central_extra = zip64_field(&[size, size]);
+    crafted.archive()
+}
+
+fn assert_format_error<T>(result: Result<T, MuError>) {
+    match result {
+        Ok(_) => panic!("expected an error"),
+        Err(e) => assert_eq!(e.kind(), ErrorKind::Format, "{e}"),
+    }
+}
+
+#[test]
+fn size_past_the_end_of_the_file() {
+    let bytes = claiming(1 << 62);
+
+    let mut zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    assert_format_error(zi.next().unwrap());
+
+    let temp = TempArchive::new("past-end", &bytes);
+    assert_format_error(SearchableArchive::new(&mut temp.open()));
+}
+
+#[test]
+fn size_overflowing_the_data_offset() {
+    let bytes = claiming(u64::MAX - 5);
+
+    let mut zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    assert_format_error(zi.next().unwrap());
+
+    let temp = TempArchive::new("overflow", &bytes);
+    assert_format_error(SearchableArchive::new(&mut temp.open()));
+}
+
+// `count` empty entries, with a ZIP64 end record holding the count, which the
+// classic one leaves as a placeholder.
+fn many(count: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for i in 0..count {
+        let mut crafted = Crafted::new(b"");
+        crafted.name = format!("{i:05}").into_bytes();
+        crafted.central_offset = out.len() as u32;
+        out.extend(crafted.local());
+        central.extend(crafted.central());
+    }
+
+    let cd_offset = out.len() as u64;
+    out.extend(&central);
+    let record_at = out.len() as u64;
+    out.extend(zip64_end_record(
+        count as u64,
+        central.len() as u64,
+        cd_offset,
+    ));
+    out.extend(zip64_end_locator(record_at));
+    out.extend(end_record(u16::MAX, central.len() as u32, cd_offset as u32));
+    out
+}
+
+fn zip64_end_record(entries: u64, cd_size: u64, cd_offset: u64) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend(0x06064b50u32.to_le_bytes());
+    out.extend(44u64.to_le_bytes());
+    out.extend(45u16.to_le_bytes());
+    out.extend(45u16.to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend(entries.to_le_bytes());
+    out.extend(entries.to_le_bytes());
+    out.extend(cd_size.to_le_bytes());
+    out.extend(cd_offset.to_le_bytes());
+    out
+}
+
+fn zip64_end_locator(record_at: u64) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend(0x07064b50u32.to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend(record_at.to_le_bytes());
+    out.extend(1u32.to_le_bytes());
+    out
+}
+
+#[cfg(feature = "writer")]
+#[test]
+fn editing_keeps_the_zip64_end_record() {
+    let temp = TempArchive::new("edit", &many(70_000));
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&temp.0)
+        .unwrap();
+    let mut editor = ArchiveEditor::new(&mut file).unwrap();
+    assert!(editor
+        .patch("69999", &MetadataPatch::new().unix_mode(0o100600))
+        .unwrap());
+    editor.commit().unwrap();
+
+    let mut file = temp.open();
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    assert_eq!(zi.len(), 70_000);
+    assert_eq!(zi.entry("69999").unwrap().unix_mode(), Some(0o100600));
+}
+
+#[test]
+fn more_entries_than_the_end_record_holds() {
+    let bytes = many(70_000);
+
+    let zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    let names: Vec<String> = zi.map(|entry| entry.unwrap().filename()).collect();
+    assert_eq!(names.len(), 70_000);
+    assert_eq!(names[69_999], "69999");
+
+    let temp = TempArchive::new("many", &bytes);
+    let mut file = temp.open();
+    assert_eq!(SearchableArchive::new(&mut file).unwrap().len(), 70_000);
+}
+
+#[test]
+fn placeholder_sizes_resolved_from_each_headers_own_field() {
+    let mut crafted = Crafted::new(b"hello");
+    crafted.local_sizes = [PLACEHOLDER; 2];
+    crafted.local_extra = zip64_field(&[5, 5]);
+    crafted.central_sizes = [PLACEHOLDER; 2];
+    crafted.central_extra = zip64_field(&[5, 5]);
+    let bytes = crafted.archive();
+
+    let mut zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    let mut entry = zi.next().unwrap().unwrap();
+    assert_eq!(entry.uncompressed_size(), 5);
+    assert_eq!(
+        entry.provenance().uncompressed_size,
+        FieldSource::LocalZip64
+    );
+    assert_eq!(entry.provenance().compressed_size, FieldSource::LocalZip64);
+    assert_eq!(entry.buffer().unwrap(), b"hello");
+
+    let zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    let meta = zi.metadata_only().next().unwrap().unwrap();
+    assert_eq!(meta.uncompressed_size(), 5);
+    assert_eq!(
+        meta.provenance().uncompressed_size,
+        FieldSource::CentralZip64
+    );
+    assert_eq!(meta.provenance().compressed_size, FieldSource::CentralZip64);
+}
+
+#[test]
+fn placeholder_sizes_in_the_central_directory_only() {
+    let mut crafted = Crafted::new(b"hello");
+    crafted.central_sizes = [PLACEHOLDER; 2];
+    crafted.central_extra = zip64_field(&[5, 5]);
+    let bytes = crafted.archive();
+
+    let mut zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    let mut entry = zi.next().unwrap().unwrap();
+    assert_eq!(
+        entry.provenance().uncompressed_size,
+        FieldSource::LocalHeader
+    );
+    assert_eq!(entry.buffer().unwrap(), b"hello");
+}
+
+#[test]
+fn placeholder_local_header_offset() {
+    let mut crafted = Crafted::new(b"hello");
+    crafted.central_offset = PLACEHOLDER;
+    crafted.central_extra = zip64_field(&[0]);
+    let bytes = crafted.archive();
+
+    let mut zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    let mut entry = zi.next().unwrap().unwrap();
+    assert_eq!(
+        entry.provenance().local_header_offset,
+        FieldSource::CentralZip64
+    );
+    assert_eq!(entry.buffer().unwrap(), b"hello");
+
+    let temp = TempArchive::new("offset", &bytes);
+    let mut file = temp.open();
+    let zi = SearchableArchive::new(&mut file).unwrap();
+    assert_eq!(zi.entry("a.txt").unwrap().buffer().unwrap(), b"hello");
+}
+
+#[test]
+fn zip64_field_too_short_for_its_placeholders() {
+    // the central record's field, holding one of the two sizes
+    let mut crafted = Crafted::new(b"hello");
+    crafted.central_sizes = [PLACEHOLDER; 2];
+    crafted.central_extra = zip64_field(&[5]);
+    let bytes = crafted.archive();
+
+    let mut zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    assert_format_error(zi.next().unwrap());
+    let zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    assert_format_error(zi.metadata_only().next().unwrap());
+    let temp = TempArchive::new("short-central", &bytes);
+    assert_format_error(SearchableArchive::new(&mut temp.open()));
+
+    // the local header's, holding none of them
+    let mut crafted = Crafted::new(b"hello");
+    crafted.local_sizes = [PLACEHOLDER; 2];
+    crafted.local_extra = zip64_field(&[]);
+    let bytes = crafted.archive();
+
+    let mut zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    assert_format_error(zi.next().unwrap());
+}
+
+#[test]
+fn central_metadata_has_no_data_offset() {
+    let bytes = Crafted::new(b"hello").archive();
+
+    let zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    let meta = zi.metadata_only().next().unwrap().unwrap();
+    assert_eq!(meta.data_offset(), None);
+    assert_eq!(meta.compressed_range(), None);
+
+    let mut zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    let meta = zi.next().unwrap().unwrap().meta();
+    assert_eq!(meta.data_offset(), Some(30 + 5));
+    assert_eq!(meta.compressed_range(), Some(35..40));
+}
+
+#[test]
+fn error_keeps_its_entry_when_the_central_extra_field_is_unreadable() {
+    // placeholder sizes with a ZIP64 field too short for them, which the local
+    // header fails on, and the record's own metadata can't be made from either
+    let mut crafted = Crafted::new(b"hello");
+    crafted.central_sizes = [PLACEHOLDER; 2];
+    crafted.central_extra = zip64_field(&[5]);
+    let bytes = crafted.archive();
+
+    let mut zi = IterableArchive::from_bytes(&bytes[..]).unwrap();
+    let Some(Err(err)) = zi.next() else {
+        panic!("expected an error");
+    };
+    let entry = err.entry().expect("the error's entry");
+    assert_eq!(entry.central_directory_index(), 0);
+    assert_eq!(entry.data_offset(), None);
+}